@@ -0,0 +1,279 @@
+//! Compares `ansi-to-html` with other ways of converting terminal output to
+//! HTML. Run with
+//!
+//! ```sh
+//! cargo bench -p ansi-to-html --features competitors --bench competitors
+//! ```
+//!
+//! All competitors convert the same terminal session as the `convert`
+//! benchmark. Before the benchmarks, a table with the size of each output is
+//! printed, and whether its text (without tags) is the same as ours. This is
+//! only a spot check: styles aren't compared, and competitors that drop some
+//! escape sequences may still produce the same text.
+//!
+//! To add a competitor, write a function converting a `&str` to HTML and add it
+//! to [`COMPETITORS`]. Its dependencies are optional dependencies of this crate
+//! enabled by the `competitors` feature, so they aren't built otherwise.
+//!
+//! Baseline on a single-core Linux x86_64 VM, to notice regressions (median of
+//! two runs, for the 139 KB session):
+//!
+//! | competitor     | throughput | output | same text |
+//! |----------------|-----------:|-------:|-----------|
+//! | ansi-to-html   |    50 MB/s | 165 KB | yes       |
+//! | anstyle-parse  |    89 MB/s | 259 KB | no        |
+//! | strip + escape |    69 MB/s |  75 KB | no        |
+//!
+//! The text differs because ansi-to-html keeps the `=` and `>` of the keypad
+//! mode sequences `ESC =` and `ESC >`, which the others remove.
+
+use std::{fmt, hint::black_box, io::Read, sync::LazyLock, time::Duration};
+
+use divan::{bench, counter::BytesCount, Bencher, Divan};
+use flate2::bufread::GzDecoder;
+
+fn main() {
+    print_summary();
+    Divan::default()
+        .min_time(Duration::from_millis(500))
+        .config_with_args()
+        .main();
+}
+
+static COMPRESSED_TERMINAL_SESSION: &[u8] = include_bytes!("../assets/terminal_session.gz");
+
+static TERMINAL_SESSION: LazyLock<String> = LazyLock::new(|| {
+    let mut decoder = GzDecoder::new(COMPRESSED_TERMINAL_SESSION);
+    let mut terminal_session = String::new();
+    decoder.read_to_string(&mut terminal_session).unwrap();
+    terminal_session
+});
+
+struct Competitor {
+    name: &'static str,
+    convert: fn(&str) -> String,
+}
+
+impl fmt::Display for Competitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// The converters that are compared. The first one is the reference for the
+/// text comparison.
+const COMPETITORS: &[Competitor] = &[
+    Competitor {
+        name: "ansi-to-html",
+        convert: |input| ansi_to_html::convert(input).unwrap(),
+    },
+    Competitor {
+        name: "anstyle-parse",
+        convert: anstyle_parse_html::convert,
+    },
+    Competitor {
+        name: "strip + escape",
+        convert: |input| {
+            let text = strip_ansi_escapes::strip_str(input);
+            html_escape::encode_text(&text).into_owned()
+        },
+    },
+];
+
+#[bench(args = COMPETITORS)]
+fn convert(bencher: Bencher, competitor: &Competitor) {
+    let input = &*TERMINAL_SESSION;
+    bencher
+        .counter(BytesCount::of_str(input))
+        .bench(|| (competitor.convert)(black_box(input)));
+}
+
+fn print_summary() {
+    let input = &*TERMINAL_SESSION;
+    let reference = text(&(COMPETITORS[0].convert)(input));
+
+    println!("input: {} KB\n", input.len() / 1000);
+    println!(
+        "{:<16} {:>10} {:>9}  same text",
+        "competitor", "output", "ratio"
+    );
+    for competitor in COMPETITORS {
+        let html = (competitor.convert)(input);
+        println!(
+            "{:<16} {:>7} KB {:>9.2}  {}",
+            competitor.name,
+            html.len() / 1000,
+            html.len() as f64 / input.len() as f64,
+            if text(&html) == reference {
+                "yes"
+            } else {
+                "no"
+            },
+        );
+    }
+    println!();
+}
+
+/// Returns the text of the HTML, without tags and with entities decoded.
+/// Control characters except line breaks and tabs are removed, since some
+/// converters keep them and others don't.
+fn text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |i| start + i + 1);
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+    html_escape::decode_html_entities(&text)
+        .chars()
+        .filter(|&c| !c.is_control() || c == '\n' || c == '\t')
+        .collect()
+}
+
+/// A straightforward converter built on the `anstyle-parse` state machine,
+/// which opens a new `<span>` with all active styles whenever they change
+mod anstyle_parse_html {
+    use std::fmt::Write;
+
+    use anstyle_parse::{DefaultCharAccumulator, Params, Parser, Perform};
+
+    pub(crate) fn convert(input: &str) -> String {
+        let mut parser = Parser::<DefaultCharAccumulator>::new();
+        let mut performer = Html::default();
+        for &byte in input.as_bytes() {
+            parser.advance(&mut performer, byte);
+        }
+        if performer.span_open {
+            performer.html.push_str("</span>");
+        }
+        performer.html
+    }
+
+    #[derive(Default)]
+    struct Html {
+        html: String,
+        span_open: bool,
+        bold: bool,
+        italic: bool,
+        underline: bool,
+        fg: Option<String>,
+        bg: Option<String>,
+    }
+
+    impl Perform for Html {
+        fn print(&mut self, c: char) {
+            match c {
+                '<' => self.html.push_str("&lt;"),
+                '>' => self.html.push_str("&gt;"),
+                '&' => self.html.push_str("&amp;"),
+                _ => self.html.push(c),
+            }
+        }
+
+        fn execute(&mut self, byte: u8) {
+            if let b'\n' | b'\t' = byte {
+                self.html.push(byte as char);
+            }
+        }
+
+        fn csi_dispatch(&mut self, params: &Params, _: &[u8], _: bool, action: u8) {
+            if action != b'm' {
+                return;
+            }
+            let codes: Vec<u16> = params.iter().flatten().copied().collect();
+            let mut codes = codes.iter().copied();
+            while let Some(code) = codes.next() {
+                match code {
+                    0 => {
+                        let html = std::mem::take(&mut self.html);
+                        *self = Html {
+                            html,
+                            span_open: self.span_open,
+                            ..Html::default()
+                        };
+                    }
+                    1 => self.bold = true,
+                    3 => self.italic = true,
+                    4 => self.underline = true,
+                    22 => self.bold = false,
+                    23 => self.italic = false,
+                    24 => self.underline = false,
+                    30..=37 => self.fg = Some(palette(code - 30)),
+                    90..=97 => self.fg = Some(palette(code - 90 + 8)),
+                    40..=47 => self.bg = Some(palette(code - 40)),
+                    100..=107 => self.bg = Some(palette(code - 100 + 8)),
+                    38 => self.fg = extended_color(&mut codes),
+                    48 => self.bg = extended_color(&mut codes),
+                    39 => self.fg = None,
+                    49 => self.bg = None,
+                    _ => {}
+                }
+            }
+            self.open_span();
+        }
+    }
+
+    impl Html {
+        fn open_span(&mut self) {
+            if self.span_open {
+                self.html.push_str("</span>");
+            }
+            let mut style = String::new();
+            if self.bold {
+                style.push_str("font-weight:bold;");
+            }
+            if self.italic {
+                style.push_str("font-style:italic;");
+            }
+            if self.underline {
+                style.push_str("text-decoration:underline;");
+            }
+            if let Some(fg) = &self.fg {
+                let _ = write!(style, "color:{fg};");
+            }
+            if let Some(bg) = &self.bg {
+                let _ = write!(style, "background-color:{bg};");
+            }
+            self.span_open = !style.is_empty();
+            if self.span_open {
+                let _ = write!(self.html, "<span style='{style}'>");
+            }
+        }
+    }
+
+    fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<String> {
+        match codes.next()? {
+            5 => codes.next().map(palette),
+            2 => {
+                let (r, g, b) = (codes.next()?, codes.next()?, codes.next()?);
+                Some(format!("#{r:02x}{g:02x}{b:02x}"))
+            }
+            _ => None,
+        }
+    }
+
+    /// The color with the given index in the 256-color palette
+    fn palette(index: u16) -> String {
+        const BASIC: [&str; 16] = [
+            "#000", "#a00", "#0a0", "#a60", "#00a", "#a0a", "#0aa", "#aaa", "#555", "#f55", "#5f5",
+            "#ff5", "#55f", "#f5f", "#5ff", "#fff",
+        ];
+        match index {
+            0..=15 => BASIC[index as usize].to_string(),
+            16..=231 => {
+                let level = |i: u16| if i == 0 { 0 } else { 55 + i * 40 };
+                let i = index - 16;
+                let (r, g, b) = (level(i / 36), level(i / 6 % 6), level(i % 6));
+                format!("#{r:02x}{g:02x}{b:02x}")
+            }
+            _ => {
+                let gray = 8 + (index.min(255) - 232) * 10;
+                format!("#{gray:02x}{gray:02x}{gray:02x}")
+            }
+        }
+    }
+}
@@ -23,3 +23,126 @@ fn convert(bencher: Bencher) {
         .counter(bytes_counter)
         .bench(|| ansi_to_html::convert(black_box(&terminal_session)).unwrap());
 }
+
+/// With the `tracing` feature but without a subscriber, the spans should have
+/// no measurable overhead. Compare with `convert` of a build without the
+/// feature: `cargo bench --bench convert -- convert` and
+/// `cargo bench --bench convert --features tracing -- convert`
+#[cfg(feature = "tracing")]
+#[bench]
+fn convert_tracing_without_subscriber(bencher: Bencher) {
+    let mut decoder = GzDecoder::new(COMPRESSED_TERMINAL_SESSION);
+    let mut terminal_session = String::new();
+    decoder.read_to_string(&mut terminal_session).unwrap();
+
+    let bytes_counter = BytesCount::of_str(&terminal_session);
+    let no_subscriber = tracing::subscriber::NoSubscriber::default();
+    tracing::subscriber::with_default(no_subscriber, || {
+        bencher
+            .counter(bytes_counter)
+            .bench(|| ansi_to_html::convert(black_box(&terminal_session)).unwrap());
+    });
+}
+
+#[bench]
+fn strip_ansi(bencher: Bencher) {
+    let mut decoder = GzDecoder::new(COMPRESSED_TERMINAL_SESSION);
+    let mut terminal_session = String::new();
+    decoder.read_to_string(&mut terminal_session).unwrap();
+
+    let bytes_counter = BytesCount::of_str(&terminal_session);
+    bencher
+        .counter(bytes_counter)
+        .bench(|| ansi_to_html::strip_ansi(black_box(&terminal_session)));
+}
+
+/// 80-character lines with a few colors each, as emitted by a typical logger
+fn colored_lines() -> Vec<String> {
+    (0..1000)
+        .map(|i| {
+            format!(
+                "\x1b[2m2024-01-01T00:00:{:02}Z\x1b[0m \x1b[1;32mINFO\x1b[0m \x1b[36mserver\x1b[0m: \
+                 request {i:>5} handled in {:>3}ms",
+                i % 60,
+                i % 1000,
+            )
+        })
+        .collect()
+}
+
+#[bench]
+fn convert_lines(bencher: Bencher) {
+    let lines = colored_lines();
+    let converter = ansi_to_html::Converter::new();
+
+    bencher
+        .counter(BytesCount::of_iter(lines.iter().map(|l| l.len())))
+        .bench(|| {
+            for line in &lines {
+                black_box(converter.convert(black_box(line)).unwrap());
+            }
+        });
+}
+
+#[bench]
+fn convert_line(bencher: Bencher) {
+    let lines = colored_lines();
+    let converter = ansi_to_html::Converter::new();
+    let mut out = String::new();
+
+    bencher
+        .counter(BytesCount::of_iter(lines.iter().map(|l| l.len())))
+        .bench_local(|| {
+            for line in &lines {
+                out.clear();
+                converter.convert_line(black_box(line), &mut out).unwrap();
+                black_box(&out);
+            }
+        });
+}
+
+#[bench]
+fn convert_line_prepared(bencher: Bencher) {
+    let lines = colored_lines();
+    let converter = ansi_to_html::Converter::new()
+        .four_bit_var_prefix(Some("term-".into()))
+        .build()
+        .unwrap();
+    let mut out = String::new();
+
+    bencher
+        .counter(BytesCount::of_iter(lines.iter().map(|l| l.len())))
+        .bench_local(|| {
+            for line in &lines {
+                out.clear();
+                converter.convert_line(black_box(line), &mut out).unwrap();
+                black_box(&out);
+            }
+        });
+}
+
+/// Log lines without colors or characters that have to be escaped
+fn plain_lines() -> Vec<String> {
+    (0..1000)
+        .map(|i| {
+            format!(
+                "2024-01-01T00:00:{:02}Z INFO server: request {i:>5} handled",
+                i % 60
+            )
+        })
+        .collect()
+}
+
+#[bench]
+fn convert_plain_lines(bencher: Bencher) {
+    let lines = plain_lines();
+    let converter = ansi_to_html::Converter::new();
+
+    bencher
+        .counter(BytesCount::of_iter(lines.iter().map(|l| l.len())))
+        .bench(|| {
+            for line in &lines {
+                black_box(converter.convert_cow(black_box(line)).unwrap());
+            }
+        });
+}
@@ -0,0 +1,38 @@
+//! Simulates a log file that grows over time, and converts only the appended
+//! part whenever new content arrives.
+//!
+//! Run with `cargo run --example append_log`
+
+use ansi_to_html::{Converter, StyleState};
+
+fn main() {
+    // Chunks as they might be read from a growing log file. Note that escape
+    // sequences and styles can span multiple chunks.
+    let chunks = [
+        "\x1b[1m[build]\x1b[0m compiling foo\n",
+        "\x1b[1m[build]\x1b[0m compiling bar\n\x1b[3",
+        "3mwarning:\x1b[0m unused variable `x`\n\x1b[31m",
+        "error:\x1b[0m could not compile bar\n",
+    ];
+
+    let converter = Converter::new();
+    let mut state = StyleState::new();
+    let mut html = String::new();
+
+    for chunk in chunks {
+        // Snapshot the state, so we know if the cached HTML depends on it
+        let before = state.clone();
+        let len_before = html.len();
+        converter
+            .convert_append(&mut state, chunk, &mut html)
+            .unwrap();
+
+        println!("--- appended {} bytes of HTML", html.len() - len_before);
+        println!("{}", &html[len_before..]);
+        if before.is_default() {
+            println!("(this chunk doesn't depend on previous chunks)");
+        }
+    }
+
+    println!("--- full HTML:\n{html}");
+}
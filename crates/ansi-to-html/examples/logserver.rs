@@ -0,0 +1,225 @@
+//! Serves a log file as a self-refreshing HTML page. Whenever the page is
+//! requested, the content that was appended to the file since the last request
+//! is converted with [`Converter::convert_append`].
+//!
+//! Run with `cargo run --example logserver -- <FILE> [ADDRESS]`, then open
+//! `http://127.0.0.1:8080/` or `http://127.0.0.1:8080/?theme=light`.
+//!
+//! The colors are declared as CSS variables in `/colors.css`.
+
+use std::{
+    env,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+};
+
+use ansi_to_html::{Converter, Esc, StyleState, VarNames};
+
+fn main() -> io::Result<()> {
+    let mut args = env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage: logserver <FILE> [ADDRESS]");
+        std::process::exit(1);
+    };
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:8080".into());
+
+    let converter = Converter::new();
+    let mut tail = Tail::new(path.into());
+
+    let listener = TcpListener::bind(&address)?;
+    println!("serving on http://{address}/");
+    for stream in listener.incoming() {
+        if let Err(e) = handle(stream?, &converter, &mut tail) {
+            eprintln!("error: {e}");
+        }
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, converter: &Converter, tail: &mut Tail) -> io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let target = request_line.split(' ').nth(1).unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (content_type, body) = match path {
+        "/" => {
+            tail.poll(converter)?;
+            let theme = query
+                .split('&')
+                .find_map(|param| param.strip_prefix("theme="))
+                .map_or(Theme::Dark, Theme::from_name);
+            ("text/html", page(&tail.html, theme))
+        }
+        "/colors.css" => {
+            let names = VarNames::new(|name| name.to_string()).unwrap();
+            ("text/css", names.stylesheet())
+        }
+        _ => {
+            let response = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+            return stream.write_all(response.as_bytes());
+        }
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {content_type}; charset=utf-8\r\n\
+         Content-Length: {}\r\n\r\n{body}",
+        body.len(),
+    )
+}
+
+#[derive(Clone, Copy)]
+enum Theme {
+    Dark,
+    Light,
+}
+
+impl Theme {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "light" => Theme::Light,
+            _ => Theme::Dark,
+        }
+    }
+
+    /// Returns the page style. The 4-bit colors are overridden for light
+    /// backgrounds, where the defaults for white and yellow are hard to read.
+    fn css(self) -> &'static str {
+        match self {
+            Theme::Dark => "body { background: #141414; color: #fff; }",
+            Theme::Light => {
+                "body { background: #fff; color: #000; }
+:root { --white: #777; --bright-white: #555; --yellow: #860; --bright-yellow: #a80; }"
+            }
+        }
+    }
+}
+
+fn page(html: &str, theme: Theme) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta http-equiv=\"refresh\" content=\"2\">
+<title>Log</title>
+<link rel=\"stylesheet\" href=\"/colors.css\">
+<style>{}</style>
+</head>
+<body>
+<pre>{html}</pre>
+</body>
+</html>
+",
+        theme.css(),
+    )
+}
+
+/// Follows a file that is appended to, and converts the new content
+struct Tail {
+    path: PathBuf,
+    /// The number of bytes that have been read
+    offset: u64,
+    /// Bytes of an incomplete UTF-8 character at the end of the last read
+    incomplete: Vec<u8>,
+    state: StyleState,
+    html: String,
+}
+
+impl Tail {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            offset: 0,
+            incomplete: Vec::new(),
+            state: StyleState::new(),
+            html: String::new(),
+        }
+    }
+
+    /// Reads and converts the content that was appended since the last call.
+    /// Escape sequences that are cut off are completed in the next call.
+    fn poll(&mut self, converter: &Converter) -> io::Result<()> {
+        let mut file = File::open(&self.path)?;
+        if file.metadata()?.len() < self.offset {
+            // the file was truncated, e.g. by log rotation
+            *self = Self::new(self.path.clone());
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+
+        let mut bytes = std::mem::take(&mut self.incomplete);
+        let read = file.read_to_end(&mut bytes)?;
+        self.offset += read as u64;
+
+        let text = match std::str::from_utf8(&bytes) {
+            Ok(text) => text,
+            Err(e) if e.error_len().is_none() => {
+                // keep the start of a character that is cut off
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                self.incomplete = rest.to_vec();
+                std::str::from_utf8(valid).unwrap()
+            }
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+
+        let result = converter.convert_append(&mut self.state, text, &mut self.html);
+        if result.is_err() {
+            // show invalid escape sequences as text instead of giving up
+            self.state = StyleState::new();
+            self.html.push_str(&Esc(text).to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, io::Write};
+
+    use super::{Converter, Tail};
+
+    #[test]
+    fn tail_and_convert() {
+        let path = std::env::temp_dir().join(format!("logserver-{}.log", std::process::id()));
+        let append = |s: &[u8]| {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .unwrap();
+            file.write_all(s).unwrap();
+        };
+
+        let converter = Converter::new();
+        append(b"");
+        let mut tail = Tail::new(path.clone());
+
+        // An escape sequence and a character split across reads
+        append(b"\x1b[1mstart\x1b[3");
+        tail.poll(&converter).unwrap();
+        append(b"1mred \xc3");
+        tail.poll(&converter).unwrap();
+        append(b"\xa4\x1b[0m plain\n");
+        tail.poll(&converter).unwrap();
+        assert_eq!(
+            tail.html,
+            "<b>start</b><b><span style='color:var(--red,#a00)'>red</span></b> \
+             <b><span style='color:var(--red,#a00)'>ä</span></b> plain\n",
+        );
+        assert!(tail.state.is_default());
+
+        // Nothing new
+        tail.poll(&converter).unwrap();
+        assert!(tail.html.ends_with(" plain\n"));
+
+        // The file is truncated
+        std::fs::write(&path, "new").unwrap();
+        tail.poll(&converter).unwrap();
+        assert_eq!(tail.html, "new");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../utils/no_panic.rs"]
+mod no_panic;
+
+// A panic aborts the fuzzer (the panic hook installed by `fuzz_target!` calls
+// `process::abort`), so every panic is reported, even if it would be caught by
+// `catch_unwind` in the application. The first three bytes select the options,
+// so all combinations are covered.
+fuzz_target!(|data: &[u8]| {
+    no_panic::check(data);
+});
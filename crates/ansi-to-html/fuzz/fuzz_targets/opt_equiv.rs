@@ -1,21 +1,24 @@
 #![no_main]
 
-use ansi_to_html::{convert, Converter};
-use html_interpreter::{interpret_html, StylizedText};
+use ansi_to_html::Converter;
+use html_interpreter::{interpret_html, ComputedStyle, StylizedText};
 use libfuzzer_sys::fuzz_target;
 
 fuzz_target!(|ansi_text: &str| {
-    assert_opt_equiv_to_no_opt(ansi_text);
+    assert_opt_equiv_to_no_opt(ansi_text, Converter::new());
+    assert_opt_equiv_to_no_opt(ansi_text, Converter::new().bidi_isolate(true));
+    assert_flat_equiv_to_nested(ansi_text, Converter::new());
+    assert_flat_equiv_to_nested(ansi_text, Converter::new().render_blink(true));
 });
 
 /// Ensures that our optimized HTML output is semantically equivalent to the unoptimized output
 /// (along with verifying some other properties like our HTML being reasonably well-formed)
-pub fn assert_opt_equiv_to_no_opt(ansi_text: &str) {
-    let Ok(htmlified) = Converter::new().skip_optimize(true).convert(ansi_text) else {
+pub fn assert_opt_equiv_to_no_opt(ansi_text: &str, converter: Converter) {
+    let Ok(htmlified) = converter.clone().skip_optimize(true).convert(ansi_text) else {
         return;
     };
     let full_text = normalize_output(interpret_html(&htmlified));
-    let opt_text = normalize_output(interpret_html(&convert(ansi_text).unwrap()));
+    let opt_text = normalize_output(interpret_html(&converter.convert(ansi_text).unwrap()));
 
     assert_eq!(
         full_text, opt_text,
@@ -37,3 +40,33 @@ fn normalize_output(texts: Vec<StylizedText>) -> Vec<StylizedText> {
             acc
         })
 }
+
+/// Ensures that the single spans with combined styles of [`Converter::flat_spans`] are
+/// semantically equivalent to the nested elements
+pub fn assert_flat_equiv_to_nested(ansi_text: &str, converter: Converter) {
+    let Ok(nested) = converter.convert(ansi_text) else {
+        return;
+    };
+    let flat = converter.flat_spans(true).convert(ansi_text).unwrap();
+
+    assert_eq!(
+        computed_output(interpret_html(&nested)),
+        computed_output(interpret_html(&flat)),
+        "Flat spans should be semantically equivalent"
+    );
+}
+
+/// Returns the runs of text with their computed style, like [`normalize_output`]
+fn computed_output(texts: Vec<StylizedText>) -> Vec<(ComputedStyle, String)> {
+    texts
+        .into_iter()
+        .filter(|t| !t.text.is_empty())
+        .fold(Vec::new(), |mut acc, text| {
+            let computed = text.styles.computed();
+            match acc.last_mut() {
+                Some((top, top_text)) if *top == computed => top_text.push_str(&text.text),
+                _ => acc.push((computed, text.text)),
+            }
+            acc
+        })
+}
@@ -0,0 +1,33 @@
+#![no_main]
+
+use ansi_to_html::Converter;
+use libfuzzer_sys::fuzz_target;
+
+/// The maximum number of output bytes per input byte. The worst known inputs are around 17:
+/// Changing the outermost of many nested styles closes and reopens all of them. Escaping a
+/// character like `'` takes 5 bytes.
+const BYTES_PER_INPUT_BYTE: usize = 64;
+
+/// The output bytes allowed in addition, e.g. for closing the styles that are open at the end
+const EXTRA_BYTES: usize = 1024;
+
+fuzz_target!(|ansi_text: &str| {
+    assert_output_linearly_bounded(ansi_text);
+});
+
+/// Ensures that the size of the HTML grows at most linearly with the size of the input, so no
+/// input can cause quadratic output or run time
+pub fn assert_output_linearly_bounded(ansi_text: &str) {
+    let limit = BYTES_PER_INPUT_BYTE * ansi_text.len() + EXTRA_BYTES;
+    for converter in [Converter::new(), Converter::new().skip_optimize(true)] {
+        let Ok(html) = converter.convert(ansi_text) else {
+            return;
+        };
+        assert!(
+            html.len() <= limit,
+            "{} bytes of HTML for {} bytes of input: {ansi_text:?}",
+            html.len(),
+            ansi_text.len(),
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use ansi_to_html::Converter;
+use html_interpreter::interpret_html;
+
+/// Hebrew and Arabic messages mixed with paths and numbers, as printed by a localized program
+static MIXED: &str = "\x1b[1;31mשגיאה:\x1b[0m /var/log/app.log \x1b[33mלא נמצא\x1b[0m (42)\n\
+    \x1b[32m✓\x1b[0m تم الحفظ في \x1b[4m/home/user/نتائج.txt\x1b[0m\n\
+    mixed \x1b[3mעברית and English\x1b[0m in one line\n";
+
+/// With BiDi isolation, every run of text is isolated, and the text is in the same logical
+/// order as in the input
+#[test]
+fn runs_isolated_in_logical_order() {
+    let html = Converter::new().bidi_isolate(true).convert(MIXED).unwrap();
+    let texts = interpret_html(&html);
+
+    let text: String = texts.iter().map(|t| t.text.as_str()).collect();
+    let expected = MIXED
+        .split('\x1b')
+        .enumerate()
+        .map(|(i, part)| {
+            if i == 0 {
+                part
+            } else {
+                &part[part.find('m').unwrap() + 1..]
+            }
+        })
+        .collect::<String>();
+    assert_eq!(text, expected);
+
+    for t in &texts {
+        assert!(
+            t.styles.is_isolated() || t.text.chars().all(|c| c == '\n'),
+            "{t:?} is not isolated"
+        );
+    }
+    // 4 + 3 + 3 runs, line breaks are outside of the runs
+    assert_eq!(html.matches("<bdi>").count(), 10);
+}
@@ -0,0 +1,164 @@
+//! Model-based test of the interaction between colors, reverse video and resets. Every sequence of
+//! up to 4 tokens from a small alphabet is converted with and without optimization, and both
+//! results are compared with a reference model that only tracks the effective colors.
+
+use std::collections::BTreeSet;
+
+use ansi_to_html::Converter;
+use html_interpreter::interpret_html;
+
+const RED: &str = "color:var(--red,#a00)";
+const BLUE_BG: &str = "background:var(--blue,#00a)";
+// the colors swapped by reverse video, where unset colors are replaced by the default colors
+const BLUE: &str = "color:var(--blue,#00a)";
+const RED_BG: &str = "background:var(--red,#a00)";
+const DEFAULT_FG: &str = "color:#000000";
+const DEFAULT_BG: &str = "background:#aaaaaa";
+const BACKGROUNDS: [&str; 3] = [BLUE_BG, RED_BG, DEFAULT_BG];
+
+#[derive(Clone, Copy, Debug)]
+enum Token {
+    RedFg,
+    BlueBg,
+    /// Reverse video, which swaps the colors
+    Invert,
+    InvertOff,
+    DefaultFg,
+    DefaultBg,
+    Reset,
+    Text,
+}
+
+const ALPHABET: [Token; 8] = [
+    Token::RedFg,
+    Token::BlueBg,
+    Token::Invert,
+    Token::InvertOff,
+    Token::DefaultFg,
+    Token::DefaultBg,
+    Token::Reset,
+    Token::Text,
+];
+
+/// The effective colors, as the reference for the HTML
+#[derive(Default)]
+struct Model {
+    fg: bool,
+    bg: bool,
+    invert: bool,
+}
+
+impl Model {
+    fn apply(&mut self, token: Token) {
+        match token {
+            Token::RedFg => self.fg = true,
+            Token::BlueBg => self.bg = true,
+            Token::Invert => self.invert = true,
+            Token::InvertOff => self.invert = false,
+            Token::Text => {}
+            Token::DefaultFg => self.fg = false,
+            Token::DefaultBg => self.bg = false,
+            Token::Reset => *self = Self::default(),
+        }
+    }
+
+    fn styles(&self) -> BTreeSet<&'static str> {
+        if self.invert {
+            let fg = if self.bg { BLUE } else { DEFAULT_FG };
+            let bg = if self.fg { RED_BG } else { DEFAULT_BG };
+            return BTreeSet::from([fg, bg]);
+        }
+        let fg = self.fg.then_some(RED);
+        let bg = self.bg.then_some(BLUE_BG);
+        fg.into_iter().chain(bg).collect()
+    }
+}
+
+/// Returns the ANSI input, where each text token is a different letter, and the styles expected
+/// for each letter
+fn input(tokens: &[Token]) -> (String, Vec<(char, BTreeSet<&'static str>)>) {
+    let mut input = String::new();
+    let mut expected = Vec::new();
+    let mut model = Model::default();
+    for &token in tokens {
+        model.apply(token);
+        match token {
+            Token::RedFg => input.push_str("\x1b[31m"),
+            Token::BlueBg => input.push_str("\x1b[44m"),
+            Token::Invert => input.push_str("\x1b[7m"),
+            Token::InvertOff => input.push_str("\x1b[27m"),
+            Token::DefaultFg => input.push_str("\x1b[39m"),
+            Token::DefaultBg => input.push_str("\x1b[49m"),
+            Token::Reset => input.push_str("\x1b[0m"),
+            Token::Text => {
+                let c = char::from(b'a' + expected.len() as u8);
+                input.push(c);
+                expected.push((c, model.styles()));
+            }
+        }
+    }
+    (input, expected)
+}
+
+/// Returns the styles of each character of the HTML
+fn interpret(html: &str) -> Vec<(char, BTreeSet<&'static str>)> {
+    let mut chars = Vec::new();
+    for text in interpret_html(html) {
+        assert!(!text.styles.has_tags(), "unexpected tags in {html:?}");
+        let styles = (text.styles.span_styles().into_iter())
+            .map(|style| match style {
+                RED => RED,
+                BLUE_BG => BLUE_BG,
+                BLUE => BLUE,
+                RED_BG => RED_BG,
+                DEFAULT_FG => DEFAULT_FG,
+                DEFAULT_BG => DEFAULT_BG,
+                _ => panic!("unexpected style {style:?} in {html:?}"),
+            })
+            .collect::<BTreeSet<_>>();
+        chars.extend(text.text.chars().map(|c| (c, styles.clone())));
+    }
+    chars
+}
+
+/// Returns all sequences of the given length
+fn sequences(len: usize) -> impl Iterator<Item = Vec<Token>> {
+    (0..ALPHABET.len().pow(len as u32)).map(move |mut n| {
+        (0..len)
+            .map(|_| {
+                let token = ALPHABET[n % ALPHABET.len()];
+                n /= ALPHABET.len();
+                token
+            })
+            .collect()
+    })
+}
+
+/// The sequences are checked from shortest to longest, so the first divergence is a minimal
+/// reproducer
+#[test]
+fn colors_match_model() {
+    let converter = Converter::new();
+    let raw = converter.clone().skip_optimize(true);
+    for len in 1..=4 {
+        for tokens in sequences(len) {
+            let (input, expected) = input(&tokens);
+            for (name, converter) in [("optimized", &converter), ("unoptimized", &raw)] {
+                let html = converter.convert(&input).unwrap();
+                assert_eq!(
+                    interpret(&html),
+                    expected,
+                    "{name} output diverges from the model\n  tokens: {tokens:?}\n  \
+                     input: {input:?}\n  html: {html:?}"
+                );
+                // the background span is always nested in the foreground span
+                assert!(
+                    !(BACKGROUNDS.iter())
+                        .any(|bg| html.contains(&format!("{bg}'><span style='color:"))),
+                    "{name} output nests the colors wrongly\n  tokens: {tokens:?}\n  \
+                     html: {html:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use ansi_to_html::{Converter, StyleState};
+use html_interpreter::{interpret_html, visible_runs};
+
+static LOG: &str = "\x1b[1m[build]\x1b[0m compiling foo\n\
+    \x1b[1m[build] \x1b[33mwarning:\x1b[22m unused variable\n\
+    \x1b[4munderlined \x1b[41mred background\x1b[24m\n\
+    \x1b[39;38;5;200mpink\x1b[0m plain \x1b[1;3mbold italic\n\x1b[0m";
+
+/// The HTML converted in chunks should look the same as the HTML converted at once
+#[test]
+fn append_equivalent_to_full_conversion() {
+    let converter = Converter::new();
+    let full = visible_runs(interpret_html(&converter.convert(LOG).unwrap()));
+
+    for chunk_size in [1, 2, 3, 5, 8, 13, 21] {
+        let mut state = StyleState::new();
+        let mut html = String::new();
+        for chunk in LOG.as_bytes().chunks(chunk_size) {
+            let chunk = std::str::from_utf8(chunk).unwrap();
+            converter
+                .convert_append(&mut state, chunk, &mut html)
+                .unwrap();
+        }
+        assert!(state.is_default());
+        assert_eq!(
+            visible_runs(interpret_html(&html)),
+            full,
+            "chunk size {chunk_size}"
+        );
+    }
+}
+
+/// The HTML converted by a stream should look the same as the HTML converted at once, and the
+/// tags are only closed at the end
+#[test]
+fn stream_equivalent_to_full_conversion() {
+    let converter = Converter::new();
+    let full = visible_runs(interpret_html(&converter.convert(LOG).unwrap()));
+
+    for chunk_size in [1, 2, 3, 5, 8, 13, 21] {
+        let mut stream = converter.begin();
+        let mut html = String::new();
+        for chunk in LOG.as_bytes().chunks(chunk_size) {
+            let chunk = std::str::from_utf8(chunk).unwrap();
+            html.push_str(&stream.feed(chunk).unwrap());
+        }
+        html.push_str(&stream.finish());
+        assert_eq!(
+            visible_runs(interpret_html(&html)),
+            full,
+            "chunk size {chunk_size}"
+        );
+        assert!(html.len() <= converter.convert(LOG).unwrap().len());
+    }
+}
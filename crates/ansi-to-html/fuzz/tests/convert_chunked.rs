@@ -0,0 +1,54 @@
+use ansi_to_html::Converter;
+use html_interpreter::{interpret_html, visible_runs};
+
+static LOG: &str = "\x1b[1m[build]\x1b[0m compiling foo\n\
+    \x1b[1m[build] \x1b[33mwarning:\x1b[22m unused variable\n\
+    \x1b[4munderlined \x1b[41mred background\x1b[24m\n\
+    \x1b[39;38;5;200mpink\x1b[0m plain \x1b[1;3mbold italic\n\x1b[0m\
+    \x1b[2mfaint \x1b[9mcrossed out\x1b[0m <tag> & \x1b[7;32mmany\x1b[35m colors\x1b[36m in\x1b[34m \
+    one\x1b[31m line\x1b[0m\n";
+
+/// The concatenated chunks should look the same as the HTML converted at once
+#[test]
+fn chunks_equivalent_to_full_conversion() {
+    let converter = Converter::new();
+    let full = visible_runs(interpret_html(&converter.convert(LOG).unwrap()));
+
+    for max_elements in [0, 1, 2, 3, 5, 8, 13, 1000] {
+        let chunks = converter.convert_chunked(LOG, max_elements).unwrap();
+        assert_eq!(
+            visible_runs(interpret_html(&chunks.concat())),
+            full,
+            "max_elements {max_elements}"
+        );
+        // every chunk is valid on its own
+        for chunk in &chunks {
+            assert_eq!(
+                chunk.matches("<span").count(),
+                chunk.matches("</span>").count()
+            );
+        }
+    }
+}
+
+/// No chunk has more elements than allowed, unless the styles that are active at its start
+/// already need more
+#[test]
+fn chunks_respect_element_limit() {
+    let converter = Converter::new();
+    for max_elements in [3, 4, 5, 8, 13] {
+        let chunks = converter.convert_chunked(LOG, max_elements).unwrap();
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            let elements = chunk.matches("<b>").count()
+                + chunk.matches("<i>").count()
+                + chunk.matches("<u>").count()
+                + chunk.matches("<s>").count()
+                + chunk.matches("<span").count();
+            assert!(
+                elements <= max_elements,
+                "{elements} elements in {chunk:?} (max_elements {max_elements})"
+            );
+        }
+    }
+}
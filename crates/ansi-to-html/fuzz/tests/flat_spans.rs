@@ -0,0 +1,83 @@
+use std::fs;
+
+use ansi_to_html::{Converter, FaintBlend};
+use html_interpreter::{interpret_html, ComputedStyle, StylizedText};
+
+static LOG: &str = "\x1b[1m[build]\x1b[0m compiling foo\n\
+    \x1b[1m[build] \x1b[33mwarning:\x1b[22m unused variable\n\
+    \x1b[4munderlined \x1b[41mred background\x1b[24m\n\
+    \x1b[39;38;5;200mpink\x1b[0m plain \x1b[1;3mbold italic\n\x1b[0m\
+    \x1b[2mfaint \x1b[9mcrossed out\x1b[53m and overlined\x1b[0m <tag> & \x1b[5;32mblinking\
+    \x1b[35m colors\x1b[8m concealed\x1b[28;36m in\x1b[34m one\x1b[31m line\x1b[0m\n";
+
+fn converters() -> [Converter; 4] {
+    [
+        Converter::new(),
+        Converter::new().render_blink(true),
+        Converter::new().faint_blend(Some(FaintBlend::new([255; 3], [0; 3]))),
+        Converter::new()
+            .four_bit_classes(Some("t-".into()))
+            .grayscale_levels(Some(4)),
+    ]
+}
+
+/// The spans with combined styles look the same as the nested elements, as checked by the
+/// `opt_equiv` fuzz target
+#[test]
+fn flat_equivalent_to_nested() {
+    for converter in converters() {
+        assert_flat_equiv_to_nested(LOG, &converter);
+    }
+}
+
+/// The inputs in the corpora of the fuzz targets are equivalent with flat spans, even without
+/// running the fuzzer
+#[test]
+fn corpus_flat_equivalent_to_nested() {
+    for corpus in ["opt_equiv", "no_panic"] {
+        let dir = format!("{}/corpus/{corpus}", env!("CARGO_MANIFEST_DIR"));
+        for entry in fs::read_dir(dir).unwrap() {
+            let Ok(input) = fs::read_to_string(entry.unwrap().path()) else {
+                continue;
+            };
+            for converter in converters() {
+                assert_flat_equiv_to_nested(&input, &converter);
+            }
+        }
+    }
+}
+
+/// There are fewer elements than nested elements, since every run of text is in at most one
+#[test]
+fn flat_has_fewer_elements() {
+    let nested = Converter::new().convert(LOG).unwrap();
+    let flat = Converter::new().flat_spans(true).convert(LOG).unwrap();
+    assert_eq!(flat.matches('<').count(), 2 * flat.matches("<span").count());
+    assert!(nested.matches('<').count() > flat.matches('<').count());
+}
+
+fn assert_flat_equiv_to_nested(input: &str, converter: &Converter) {
+    let Ok(nested) = converter.convert(input) else {
+        return;
+    };
+    let flat = converter.clone().flat_spans(true).convert(input).unwrap();
+    assert_eq!(
+        computed(interpret_html(&flat)),
+        computed(interpret_html(&nested)),
+        "{input:?}"
+    );
+}
+
+fn computed(texts: Vec<StylizedText>) -> Vec<(ComputedStyle, String)> {
+    texts
+        .into_iter()
+        .filter(|t| !t.text.is_empty())
+        .fold(Vec::new(), |mut acc, text| {
+            let computed = text.styles.computed();
+            match acc.last_mut() {
+                Some((top, top_text)) if *top == computed => top_text.push_str(&text.text),
+                _ => acc.push((computed, text.text)),
+            }
+            acc
+        })
+}
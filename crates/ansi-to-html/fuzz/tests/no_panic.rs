@@ -0,0 +1,18 @@
+use std::fs;
+
+#[path = "../utils/no_panic.rs"]
+mod no_panic;
+
+/// The inputs in the corpus of the `no_panic` fuzz target, including inputs that caused panics
+/// in the past, are converted without panicking, even without running the fuzzer
+#[test]
+fn corpus_converted_without_panic() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/no_panic");
+    let mut seeds = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let data = fs::read(entry.unwrap().path()).unwrap();
+        no_panic::check(&data);
+        seeds += 1;
+    }
+    assert!(seeds >= 20);
+}
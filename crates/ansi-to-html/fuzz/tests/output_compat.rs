@@ -0,0 +1,38 @@
+use std::fs;
+
+use ansi_to_html::{Converter, OutputCompat};
+use html_interpreter::{interpret_html, visible_runs};
+
+fn converters() -> [Converter; 4] {
+    [
+        Converter::new(),
+        Converter::new().skip_optimize(true),
+        Converter::new().render_blink(true),
+        Converter::new().four_bit_classes(Some("t-".into())),
+    ]
+}
+
+/// The output of the current version looks the same as the output of the 0.x versions, so
+/// only snapshots that compare the HTML byte for byte have to be updated
+#[test]
+fn current_output_equivalent_to_v0() {
+    for corpus in ["opt_equiv", "no_panic", "output_size"] {
+        let dir = format!("{}/corpus/{corpus}", env!("CARGO_MANIFEST_DIR"));
+        for entry in fs::read_dir(dir).unwrap() {
+            let Ok(input) = fs::read_to_string(entry.unwrap().path()) else {
+                continue;
+            };
+            for converter in converters() {
+                let Ok(current) = converter.convert(&input) else {
+                    continue;
+                };
+                let v0 = converter.output_compat(OutputCompat::V0);
+                assert_eq!(
+                    visible_runs(interpret_html(&v0.convert(&input).unwrap())),
+                    visible_runs(interpret_html(&current)),
+                    "{input:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use std::fs;
+
+use ansi_to_html::Converter;
+
+/// The bound checked by the `output_size` fuzz target, see `fuzz_targets/output_size.rs`
+const BYTES_PER_INPUT_BYTE: usize = 64;
+const EXTRA_BYTES: usize = 1024;
+
+/// The pathological inputs in the corpus of the `output_size` fuzz target stay within the bound,
+/// even without running the fuzzer
+#[test]
+fn corpus_output_linearly_bounded() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/output_size");
+    let mut seeds = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let Ok(input) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let html = Converter::new().convert(&input).unwrap();
+        assert!(
+            html.len() <= BYTES_PER_INPUT_BYTE * input.len() + EXTRA_BYTES,
+            "{}: {} bytes of HTML for {} bytes of input",
+            path.display(),
+            html.len(),
+            input.len(),
+        );
+        seeds += 1;
+    }
+    assert!(seeds >= 4);
+}
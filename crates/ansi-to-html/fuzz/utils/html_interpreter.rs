@@ -6,7 +6,12 @@ use html5ever::{
     Attribute, QualName,
 };
 
-use std::{cell::RefCell, collections::BTreeSet, mem, str::FromStr};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    mem,
+    str::FromStr,
+};
 
 /// Convert HTML to runs of stylized text
 pub fn interpret_html(text: &str) -> Vec<StylizedText> {
@@ -20,6 +25,23 @@ pub fn interpret_html(text: &str) -> Vec<StylizedText> {
     tokenizer.sink.finish()
 }
 
+/// Returns the runs of text with the styles that are visible on them, which are the same for HTML
+/// that renders the same, e.g. if tags are closed before whitespace instead of after it
+pub fn visible_runs(texts: Vec<StylizedText>) -> Vec<(ComputedStyle, String)> {
+    let chars = texts.into_iter().flat_map(|text| {
+        let computed = text.styles.computed();
+        let chars: Vec<_> = text.text.chars().collect();
+        chars.into_iter().map(move |c| (computed.visible_on(c), c))
+    });
+    chars.fold(Vec::new(), |mut acc, (style, c)| {
+        match acc.last_mut() {
+            Some((top, text)) if *top == style => text.push(c),
+            _ => acc.push((style, c.to_string())),
+        }
+        acc
+    })
+}
+
 #[derive(Default)]
 struct HtmlInterpreter(RefCell<Inner>);
 
@@ -138,6 +160,8 @@ enum RawStyle {
     Italic,
     Underlined,
     CrossedOut,
+    /// A `<bdi>` element isolating the text for the bidirectional algorithm
+    Isolate,
     Span(Vec<Attr>),
 }
 
@@ -157,6 +181,7 @@ impl RawStyle {
             &local_name!("i") => Self::Italic,
             &local_name!("u") => Self::Underlined,
             &local_name!("s") => Self::CrossedOut,
+            &local_name!("bdi") => Self::Isolate,
             &local_name!("span") => Self::Span(attrs.iter().map(Attr::new).collect()),
             unknown => panic!("Unexpected HTML tag kind: {unknown}"),
         };
@@ -192,7 +217,9 @@ pub struct Styles {
     italic: bool,
     underlined: bool,
     crossed_out: bool,
-    spans: BTreeSet<Vec<Attr>>,
+    isolated: bool,
+    /// The attributes of the spans containing the text, from the outermost to the innermost
+    spans: Vec<Vec<Attr>>,
 }
 
 impl Styles {
@@ -203,6 +230,58 @@ impl Styles {
             .fold(Styles::default(), |styles, s| styles.apply(s))
     }
 
+    /// Returns whether the text is in a `<bdi>` element
+    pub fn is_isolated(&self) -> bool {
+        self.isolated
+    }
+
+    /// Returns whether the text is in a `<b>`, `<i>`, `<u>` or `<s>` element
+    pub fn has_tags(&self) -> bool {
+        self.bold || self.italic || self.underlined || self.crossed_out
+    }
+
+    /// Returns the `style` attributes of the spans containing the text
+    pub fn span_styles(&self) -> BTreeSet<&str> {
+        (self.spans.iter().flatten())
+            .filter(|attr| attr.name == "style")
+            .map(|attr| attr.value.as_str())
+            .collect()
+    }
+
+    /// Returns the CSS that applies to the text, which is the same for equivalent HTML, e.g. for
+    /// nested elements and a single span with the combined styles
+    pub fn computed(&self) -> ComputedStyle {
+        let mut computed = ComputedStyle {
+            isolated: self.isolated,
+            ..Default::default()
+        };
+        if self.bold {
+            computed.set("font-weight", "bold");
+        }
+        if self.italic {
+            computed.set("font-style", "italic");
+        }
+        if self.underlined {
+            computed.set("text-decoration", "underline");
+        }
+        if self.crossed_out {
+            computed.set("text-decoration", "line-through");
+        }
+        for attr in self.spans.iter().flatten() {
+            match attr.name.as_str() {
+                "class" => (computed.classes).extend(attr.value.split(' ').map(str::to_owned)),
+                "style" => {
+                    for declaration in attr.value.split(';') {
+                        let (property, value) = declaration.split_once(':').unwrap();
+                        computed.set(property, value);
+                    }
+                }
+                unknown => panic!("Unexpected span attribute: {unknown}"),
+            }
+        }
+        computed
+    }
+
     #[must_use]
     fn apply(mut self, raw_style: RawStyle) -> Self {
         match raw_style {
@@ -210,12 +289,65 @@ impl Styles {
             RawStyle::Italic => self.italic = true,
             RawStyle::Underlined => self.underlined = true,
             RawStyle::CrossedOut => self.crossed_out = true,
-            RawStyle::Span(span) => _ = self.spans.insert(span),
+            RawStyle::Isolate => self.isolated = true,
+            RawStyle::Span(span) => self.spans.push(span),
         }
         self
     }
 }
 
+/// The CSS that applies to a text, see [`Styles::computed`]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ComputedStyle {
+    /// The CSS properties, where the value of an inner element overrides an outer one
+    properties: BTreeMap<String, String>,
+    /// The lines of `text-decoration`, which are drawn for all elements containing the text
+    lines: BTreeSet<String>,
+    classes: BTreeSet<String>,
+    isolated: bool,
+}
+
+impl ComputedStyle {
+    fn set(&mut self, property: &str, value: &str) {
+        if property == "text-decoration" {
+            self.lines.extend(value.split(' ').map(str::to_owned));
+        } else {
+            self.properties
+                .insert(property.to_owned(), value.to_owned());
+        }
+    }
+
+    /// Returns the style without the CSS that isn't visible on the character: Line breaks show
+    /// no style, and spaces only their background and lines
+    pub fn visible_on(&self, c: char) -> Self {
+        let isolated = self.isolated;
+        match c {
+            '\r' | '\n' => Self {
+                isolated,
+                ..Default::default()
+            },
+            ' ' => Self {
+                properties: (self.properties.iter())
+                    .filter(|(property, _)| *property == "background")
+                    .map(|(property, value)| (property.clone(), value.clone()))
+                    .collect(),
+                lines: self.lines.clone(),
+                classes: (self.classes.iter())
+                    .filter(|class| class.contains("bg-"))
+                    .cloned()
+                    .collect(),
+                isolated,
+            },
+            _ => Self {
+                properties: self.properties.clone(),
+                lines: self.lines.clone(),
+                classes: self.classes.clone(),
+                isolated,
+            },
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Attr {
     name: String,
@@ -253,7 +385,8 @@ mod tests {
                     italic: false,
                     underlined: false,
                     crossed_out: false,
-                    spans: {},
+                    isolated: false,
+                    spans: [],
                 },
                 text: "Bold",
             },
@@ -263,14 +396,15 @@ mod tests {
                     italic: false,
                     underlined: false,
                     crossed_out: false,
-                    spans: {
+                    isolated: false,
+                    spans: [
                         [
                             Attr {
                                 name: "style",
                                 value: "color:var(--red,#a00)",
                             },
                         ],
-                    },
+                    ],
                 },
                 text: "Red and Bold",
             },
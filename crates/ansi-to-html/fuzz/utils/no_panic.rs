@@ -0,0 +1,112 @@
+//! The conversions run by the `no_panic` fuzz target, see `fuzz_targets/no_panic.rs`. This is
+//! shared with `tests/no_panic.rs`, which runs the corpus without the fuzzer.
+
+use ansi_to_html::{Converter, FaintBlend, LineWrapper, Newlines, StyleState};
+
+/// Converts the input with the options selected by the first three bytes of `data`. Errors are
+/// fine, panics are not. `convert_to_writer` must write the same HTML as `convert` returns.
+/// Invalid UTF-8 is only passed to `convert_bytes`.
+pub fn check(data: &[u8]) {
+    let [a, b, c, rest @ ..] = data else {
+        return;
+    };
+    let converter = converter(u32::from_le_bytes([*a, *b, *c, 0]));
+    let bytes_html = converter.convert_bytes(rest);
+    let Ok(input) = std::str::from_utf8(rest) else {
+        return;
+    };
+    assert_eq!(bytes_html.ok(), converter.convert(input).ok());
+    convert_all_ways(input, &converter, usize::from(*b >> 6));
+}
+
+/// Returns a converter with the options selected by bits 0 to 19
+fn converter(flags: u32) -> Converter {
+    let flag = |i: u32| flags & (1 << i) != 0;
+    let newlines = match flags >> 12 & 3 {
+        0 => Newlines::Keep,
+        1 => Newlines::Lf,
+        _ => Newlines::CrLfToLf,
+    };
+    Converter::new()
+        .skip_escape(flag(0))
+        .sanitize(flag(1))
+        .skip_optimize(flag(2))
+        .pretty(flag(3))
+        .bidi_isolate(flag(4))
+        .normalize_trailing_whitespace(flag(5))
+        .render_links(flag(6))
+        .lossy(flag(7))
+        .skip_css_vars(flag(8))
+        .grayscale_levels(flag(9).then_some(2))
+        .max_sequence_len(if flag(10) { 8 } else { 64 })
+        .faint_blend(flag(11).then(|| FaintBlend::new([0xff; 3], [0; 3])))
+        .newline_normalization(newlines)
+        .carriage_return_overwrites(flag(14))
+        .detect_double_escape(flag(15))
+        .emulate_cursor_movement(flag(16))
+        .line_safe(flag(17))
+        .line_wrapper(flag(18).then(|| LineWrapper::new("div", Some("line"), true).unwrap()))
+        .elide_default_colors(flag(19))
+}
+
+/// Calls every conversion method. With `convert_append` and streams, the input is split into
+/// pieces of `split + 1` characters. A session converts the input, its lines and the input again.
+fn convert_all_ways(input: &str, converter: &Converter, split: usize) {
+    let html = converter.convert(input);
+    let mut written = String::new();
+    let result = converter.convert_to_writer(input, &mut written);
+    assert_eq!(result.is_ok(), html.is_ok());
+    if let Ok(html) = html {
+        assert_eq!(written, html);
+    }
+    // a session must not carry anything over from one conversion to the next
+    let mut session = converter.session();
+    for text in std::iter::once(input).chain(input.lines()).chain([input]) {
+        let session_html = session.convert(text).map(str::to_owned);
+        assert_eq!(session_html.ok(), converter.convert(text).ok());
+    }
+    let _ = converter.convert_chunked(input, split);
+    let _ = converter.convert_lines(input);
+    if let Ok(segments) = converter.to_segments(input) {
+        assert!(segments.iter().all(|s| !s.text.is_empty()));
+    }
+    let _ = converter.convert_line(input, &mut String::new());
+    for line in input.lines() {
+        let _ = converter.convert_line(line, &mut String::new());
+    }
+    let _ = ansi_to_html::text_fragments(input).count();
+    let mut parsed = String::new();
+    for fragment in ansi_to_html::AnsiParser::new(input) {
+        parsed.push_str(fragment.as_str());
+        fragment.sgr_codes().into_iter().flatten().for_each(drop);
+    }
+    assert_eq!(parsed, input);
+    assert_eq!(
+        ansi_to_html::strip_ansi(input),
+        ansi_to_html::text_fragments(input).collect::<String>()
+    );
+
+    let mut state = StyleState::new();
+    let mut html = String::new();
+    let mut stream = converter.begin();
+    for piece in pieces(input, split + 1) {
+        let _ = converter.convert_append(&mut state, piece, &mut html);
+        let _ = stream.feed(piece);
+    }
+    let _ = stream.finish();
+}
+
+/// Splits the input into pieces of `len` characters, which may end in the middle of escape
+/// sequences and entities
+fn pieces(input: &str, len: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (i, (pos, _)) in input.char_indices().enumerate() {
+        if i > 0 && i % len == 0 {
+            pieces.push(&input[start..pos]);
+            start = pos;
+        }
+    }
+    pieces.push(&input[start..]);
+    pieces
+}
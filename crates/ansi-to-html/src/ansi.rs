@@ -1,7 +1,139 @@
-use std::num::ParseIntError;
+use std::{num::ParseIntError, ops::Range};
 
 use crate::{Color, Error};
 
+/// Returns the range of the first escape sequence in the input that is
+/// converted or stripped: CSI sequences such as `ESC[1;31m`, `ESC(B`, OSC
+/// sequences terminated by `BEL` or `ESC \`, and sequences that are aborted by
+/// a control character (see [`is_aborted`]).
+pub(crate) fn find_sequence(input: &str) -> Option<Range<usize>> {
+    let mut start = 0;
+    while let Some(i) = input[start..].find('\x1b') {
+        let i = start + i;
+        if let Some(len) = sequence_len(&input.as_bytes()[i + 1..]) {
+            // sequences end with an ASCII character, so the input can be sliced at the end
+            debug_assert!(input.is_char_boundary(i + 1 + len));
+            return Some(i..i + 1 + len);
+        }
+        start = i + 1;
+    }
+    None
+}
+
+/// Returns an iterator over the text between the escape sequences of the
+/// input, i.e. the text that is shown by a terminal. Empty fragments are
+/// skipped.
+///
+/// Only sequences that [`Converter`](crate::Converter) recognizes are
+/// removed; an `ESC` that doesn't start a valid sequence is part of the text.
+/// A sequence that is interrupted by a control character other than `ESC`,
+/// e.g. `ESC[3` in `ESC[3\x001m`, is removed together with the control
+/// character. Line breaks and tabs are kept.
+///
+/// ## Example
+///
+/// ```
+/// let fragments: Vec<&str> =
+///     ansi_to_html::text_fragments("\x1b[1mbold\x1b[0m plain\x1b[K").collect();
+/// assert_eq!(fragments, ["bold", " plain"]);
+/// ```
+pub fn text_fragments(input: &str) -> TextFragments<'_> {
+    TextFragments { rest: input }
+}
+
+/// The iterator returned by [`text_fragments`]
+#[derive(Clone, Debug)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct TextFragments<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for TextFragments<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        while !self.rest.is_empty() {
+            let (text, rest) = match find_sequence(self.rest) {
+                Some(range) => (&self.rest[..range.start], &self.rest[range.end..]),
+                None => (self.rest, ""),
+            };
+            self.rest = rest;
+            if !text.is_empty() {
+                return Some(text);
+            }
+        }
+        None
+    }
+}
+
+/// Returns the length of the sequence after the `ESC`, if it's a valid sequence
+fn sequence_len(rest: &[u8]) -> Option<usize> {
+    match rest.first()? {
+        b'[' => {
+            let params = rest[1..]
+                .iter()
+                .take_while(|&&b| b.is_ascii_digit() || b == b';' || b == b'?')
+                .count();
+            let end = *rest.get(1 + params)?;
+            if let Some(len) = aborted_len(1 + params, end) {
+                return Some(len);
+            }
+            let is_final = matches!(
+                end,
+                b'A'..=b'H'
+                    | b'J'
+                    | b'K'
+                    | b'S'
+                    | b'T'
+                    | b'f'
+                    | b'h'
+                    | b'i'
+                    | b'l'
+                    | b'm'
+                    | b'n'
+                    | b's'
+                    | b'u'
+            );
+            is_final.then_some(2 + params)
+        }
+        b'(' => match *rest.get(1)? {
+            b'B' => Some(2),
+            control => aborted_len(1, control),
+        },
+        b']' => {
+            let payload = rest[1..]
+                .iter()
+                .position(|&b| b == b'\x07' || b == b'\x1b')?;
+            match &rest[1 + payload..] {
+                [b'\x07', ..] => Some(2 + payload),
+                [b'\x1b', b'\\', ..] => Some(3 + payload),
+                _ => None,
+            }
+        }
+        &control => aborted_len(0, control),
+    }
+}
+
+/// Returns the length of a sequence whose first `len` bytes after the `ESC` are
+/// followed by a control character other than `ESC`, which aborts it. The
+/// control character is removed with the sequence, except for line breaks and
+/// tabs, which stay in the text.
+fn aborted_len(len: usize, control: u8) -> Option<usize> {
+    match control {
+        b'\n' | b'\r' | b'\t' => Some(len),
+        b'\x1b' => None,
+        0..=0x1f => Some(len + 1),
+        _ => None,
+    }
+}
+
+/// Returns whether a sequence returned by [`find_sequence`] was aborted by a
+/// control character, e.g. `ESC[3\x00`. It isn't converted and is removed from
+/// the output.
+pub(crate) fn is_aborted(sequence: &str) -> bool {
+    !sequence.starts_with("\x1b]") && !sequence.ends_with(|c: char| c.is_ascii_alphabetic())
+}
+
 /// Iterator that consumes a sequence of numbers and emits ANSI escape sequences.
 #[must_use = "iterators are lazy and do nothing unless consumed"]
 pub(crate) struct AnsiIter<T> {
@@ -32,7 +164,7 @@ where
     }
 }
 
-fn iter_next<I>(code: u8, iter: I) -> Result<Ansi, Error>
+pub(crate) fn iter_next<I>(code: u8, iter: I) -> Result<Ansi, Error>
 where
     I: Iterator<Item = Result<u8, ParseIntError>>,
 {
@@ -42,7 +174,10 @@ where
         2 => Ansi::Faint,
         3 => Ansi::Italic,
         4 => Ansi::Underline,
-        5..=8 => Ansi::Noop,
+        5 => Ansi::Blink,
+        6 => Ansi::RapidBlink,
+        7 => Ansi::Reverse,
+        8 => Ansi::Conceal,
         9 => Ansi::CrossedOut,
         10..=19 => Ansi::Noop,
         20 => Ansi::Noop,
@@ -50,7 +185,10 @@ where
         22 => Ansi::BoldAndFaintOff,
         23 => Ansi::ItalicOff,
         24 => Ansi::UnderlineOff,
-        25..=28 => Ansi::Noop,
+        25 => Ansi::BlinkOff,
+        26 => Ansi::Noop,
+        27 => Ansi::ReverseOff,
+        28 => Ansi::ConcealOff,
         29 => Ansi::CrossedOutOff,
         30..=37 => Ansi::ForgroundColor(Color::parse_4bit(code - 30)?),
         38 => Ansi::ForgroundColor(Color::parse_8bit_or_rgb(iter)?),
@@ -58,17 +196,16 @@ where
         40..=47 => Ansi::BackgroundColor(Color::parse_4bit(code - 40)?),
         48 => Ansi::BackgroundColor(Color::parse_8bit_or_rgb(iter)?),
         49 => Ansi::DefaultBackgroundColor,
-        50..=55 => Ansi::Noop,
+        50..=52 => Ansi::Noop,
+        53 => Ansi::Overline,
+        54 => Ansi::Noop,
+        55 => Ansi::OverlineOff,
         58..=59 => Ansi::Noop,
         60..=65 => Ansi::Noop,
         73..=74 => Ansi::Noop,
         90..=97 => Ansi::ForgroundColor(Color::parse_4bit_bright(code - 90)?),
         100..=107 => Ansi::BackgroundColor(Color::parse_4bit_bright(code - 100)?),
-        _ => {
-            return Err(Error::InvalidAnsi {
-                msg: format!("Unexpected code {}", code),
-            })
-        }
+        _ => return Err(Error::invalid(format!("Unexpected code {}", code))),
     })
 }
 
@@ -85,10 +222,11 @@ pub(crate) enum Ansi {
     Faint,
     Italic,
     Underline,
-    // SlowBlink,
-    // RapidBlink,
-    // ReverseVideo,
-    // Conceal,
+    /// Slow blink
+    Blink,
+    RapidBlink,
+    Reverse,
+    Conceal,
     CrossedOut,
     // DefaultFont,
     // AlternateFont,
@@ -97,9 +235,9 @@ pub(crate) enum Ansi {
     BoldAndFaintOff,
     ItalicOff,
     UnderlineOff,
-    // BlinkOff,
-    // InverseOff,
-    // ConcealOff,
+    BlinkOff,
+    ReverseOff,
+    ConcealOff,
     CrossedOutOff,
     ForgroundColor(Color),
     DefaultForegroundColor,
@@ -107,9 +245,9 @@ pub(crate) enum Ansi {
     DefaultBackgroundColor,
     // Framed,
     // Encircled,
-    // Overlined,
+    Overline,
     // FramedAndEncircledOff,
-    // OverlinedOff,
+    OverlineOff,
     // IdeogramUnderline,
     // IdeogramDoubleUnderline,
     // IdeogramOverline,
@@ -117,3 +255,11 @@ pub(crate) enum Ansi {
     // IdeogramStressMarking,
     // IdeogramAttributesOff,
 }
+
+impl Ansi {
+    /// Returns whether the code starts or ends blinking text, which is only
+    /// rendered with [`Converter::render_blink`](crate::Converter::render_blink)
+    pub(crate) fn is_blink(self) -> bool {
+        matches!(self, Ansi::Blink | Ansi::RapidBlink | Ansi::BlinkOff)
+    }
+}
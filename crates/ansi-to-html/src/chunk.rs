@@ -0,0 +1,114 @@
+//! Splitting the output into chunks with a limited number of elements, see
+//! [`Converter::convert_chunked`](crate::Converter::convert_chunked).
+//!
+//! The input is divided into pieces, which start at an escape sequence or
+//! after a line break. A chunk contains as many pieces as possible without
+//! exceeding the limit, but ends after the last line break if there is one.
+//!
+//! The elements of a piece are counted by converting it on its own. This
+//! reopens all active styles at its start, so the tags that are already open
+//! when the pieces are converted together are subtracted. The wrapper of a line
+//! (see [`Converter::line_wrapper`]) is counted in every piece of the line, so a
+//! chunk may have fewer elements than allowed.
+
+use std::ops::Range;
+
+use crate::{
+    ansi::find_sequence,
+    html,
+    html::{CurrentStyling, Position},
+    Converter, Error,
+};
+
+/// Converts the (already escaped) input to HTML chunks with at most
+/// `max_elements` elements each, unless a single piece has more elements.
+pub(crate) fn convert_chunked(
+    input: &str,
+    converter: &Converter,
+    max_elements: usize,
+) -> Result<Vec<String>, Error> {
+    let pieces = pieces(input);
+    let mut chunks = Vec::new();
+    let mut position = Position::default();
+    let mut start = 0;
+
+    while start < pieces.len() {
+        let mut end = start;
+        let mut line_end = None;
+        let mut elements = 0;
+        let mut piece_position = position.clone();
+        // the styling of the tags that are open after the previous piece
+        let mut open = CurrentStyling::default();
+        while end < pieces.len() {
+            let piece = &input[pieces[end].clone()];
+            let (html, next) = html::ansi_to_html_from(piece, converter, piece_position)?;
+            let applied = applies_styling(piece, converter.max_sequence_len);
+            let added = if applied {
+                let common = html::common_styles(&open, &next.styling, converter);
+                count_elements(&html).saturating_sub(common)
+            } else {
+                0
+            };
+            if end > start && added > 0 && elements + added > max_elements {
+                break;
+            }
+            elements += added;
+            if applied {
+                open = next.styling.clone();
+            }
+            piece_position = next;
+            end += 1;
+            if piece.ends_with('\n') {
+                line_end = Some(end);
+            }
+        }
+        if end < pieces.len() {
+            end = line_end.unwrap_or(end);
+        }
+
+        let range = pieces[start].start..pieces[end - 1].end;
+        let (html, next) = html::ansi_to_html_from(&input[range], converter, position)?;
+        chunks.push(html);
+        position = next;
+        start = end;
+    }
+    Ok(chunks)
+}
+
+/// Splits the input before every escape sequence and after every line break
+fn pieces(input: &str) -> Vec<Range<usize>> {
+    let mut boundaries = vec![0];
+    let mut pos = 0;
+    while pos < input.len() {
+        let (text_end, next) = match find_sequence(&input[pos..]) {
+            Some(range) => (pos + range.start, pos + range.end),
+            None => (input.len(), input.len()),
+        };
+        let text = &input[pos..text_end];
+        boundaries.extend(text.match_indices('\n').map(|(i, _)| pos + i + 1));
+        boundaries.push(text_end);
+        pos = next;
+    }
+    boundaries.push(input.len());
+    boundaries.dedup();
+
+    boundaries.windows(2).map(|w| w[0]..w[1]).collect()
+}
+
+/// Returns whether the styling is applied in the piece, i.e. whether it isn't
+/// just an escape sequence. Otherwise the styling is applied in the next piece.
+fn applies_styling(piece: &str, max_sequence_len: usize) -> bool {
+    match find_sequence(piece) {
+        Some(range) if range.start == 0 && range.len() <= max_sequence_len => {
+            range.end < piece.len()
+        }
+        _ => true,
+    }
+}
+
+/// Counts the opening tags in the HTML
+fn count_elements(html: &str) -> usize {
+    html.match_indices('<')
+        .filter(|&(i, _)| html[i + 1..].starts_with(|c: char| c.is_ascii_alphabetic()))
+        .count()
+}
@@ -1,6 +1,6 @@
 use std::{fmt, num::ParseIntError};
 
-use crate::Error;
+use crate::{Error, EscAttr, SgrColor, VarNames};
 
 /// An ANSI color.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -22,9 +22,10 @@ impl Color {
             6 => FourBitColor::Cyan,
             7 => FourBitColor::White,
             _ => {
-                return Err(Error::InvalidAnsi {
-                    msg: format!("unexpected integer {} parsing 4-bit color", code),
-                })
+                return Err(Error::invalid(format!(
+                    "unexpected integer {} parsing 4-bit color",
+                    code
+                )))
             }
         }))
     }
@@ -40,9 +41,10 @@ impl Color {
             6 => FourBitColor::BrightCyan,
             7 => FourBitColor::BrightWhite,
             _ => {
-                return Err(Error::InvalidAnsi {
-                    msg: format!("unexpected integer {} parsing bright 4-bit color", code),
-                })
+                return Err(Error::invalid(format!(
+                    "unexpected integer {} parsing bright 4-bit color",
+                    code
+                )))
             }
         }))
     }
@@ -74,31 +76,457 @@ impl Color {
 
                 Color::Rgb(RgbColor { r, g, b })
             }
-            _ => {
-                return Err(Error::InvalidAnsi {
-                    msg: format!("Expected 2 or 5, got {}", code),
-                })
+            _ => return Err(Error::invalid(format!("Expected 2 or 5, got {}", code))),
+        })
+    }
+
+    pub(crate) fn into_opening_fg_span(
+        self,
+        vars: Option<CssVars<'_>>,
+        themes: Option<Themes<'_>>,
+    ) -> String {
+        self.into_opening_span(vars, themes, true)
+    }
+
+    pub(crate) fn into_opening_bg_span(
+        self,
+        vars: Option<CssVars<'_>>,
+        themes: Option<Themes<'_>>,
+    ) -> String {
+        self.into_opening_span(vars, themes, false)
+    }
+
+    /// Returns the opening span for the color. With `vars` set to `None`, the color
+    /// is hardcoded even if it is a 4-bit color. With `themes`, the colors of the
+    /// palette depend on the color scheme.
+    pub(crate) fn into_opening_span(
+        self,
+        vars: Option<CssVars<'_>>,
+        themes: Option<Themes<'_>>,
+        is_fg: bool,
+    ) -> String {
+        let fg_vs_bg = if is_fg { "color" } else { "background" };
+        let value = match themes {
+            Some(themes) => themes.resolve(self, is_fg),
+            None => ThemedColor::same(self),
+        };
+        // the stylesheet defining the variables is expected to adapt them to
+        // the color scheme, so only the dark color is used as fallback
+        let fallback = value.dark;
+        match (self, vars) {
+            (Self::FourBit(four_bit), Some(CssVars::Prefix(prefix))) => {
+                format!("<span style='{fg_vs_bg}:var(--{prefix}{four_bit},{fallback})'>")
+            }
+            (Self::FourBit(four_bit), Some(CssVars::Names(names))) => {
+                let name = names.name(four_bit);
+                format!("<span style='{fg_vs_bg}:var(--{name},{fallback})'>")
             }
+            (Self::FourBit(four_bit), Some(CssVars::Classes(prefix))) => {
+                let bg = if is_fg { "" } else { "bg-" };
+                format!("<span class='{}{bg}{four_bit}'>", EscAttr(prefix))
+            }
+            _ => format!("<span style='{fg_vs_bg}:{value}'>"),
+        }
+    }
+
+    /// Returns the index of the color in the 16-color palette of the terminal
+    fn palette_index(self) -> Option<usize> {
+        match self {
+            Color::FourBit(color) => Some(color as usize),
+            Color::EightBit(EightBitColor { code: code @ 0..16 }) => Some(code as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns the red, green and blue components of the color
+    pub(crate) fn to_rgb(self) -> [u8; 3] {
+        match self {
+            Color::FourBit(color) => parse_hex(EIGHT_BIT_COLORS[color as usize]),
+            Color::EightBit(EightBitColor { code }) => parse_hex(EIGHT_BIT_COLORS[code as usize]),
+            Color::Rgb(RgbColor { r, g, b }) => [r, g, b],
+        }
+    }
+
+    /// Converts the color to the public type returned by
+    /// [`AnsiFragment::sgr_codes`](crate::AnsiFragment::sgr_codes)
+    pub(crate) fn to_sgr_color(self) -> SgrColor {
+        match self {
+            Color::FourBit(color) => SgrColor::Basic(color as u8),
+            Color::EightBit(EightBitColor { code }) => SgrColor::Indexed(code),
+            Color::Rgb(RgbColor { r, g, b }) => SgrColor::Rgb(r, g, b),
+        }
+    }
+
+    /// Replaces an 8-bit color that isn't one of the 16 colors of the palette
+    /// with its color in `eight_bit`
+    pub(crate) fn with_eight_bit_palette(self, eight_bit: &[[u8; 3]; 256]) -> Self {
+        match self {
+            Color::EightBit(EightBitColor { code: code @ 16.. }) => {
+                Color::Rgb(RgbColor::from(eight_bit[code as usize]))
+            }
+            _ => self,
+        }
+    }
+
+    /// Maps the grays of the 8-bit colors (the grayscale ramp 232-255 and the
+    /// grays of the color cube) to the nearest of `levels` evenly spaced grays
+    /// from black to white. Other colors are returned unchanged.
+    pub(crate) fn quantize_gray(self, levels: u8) -> Self {
+        let Color::EightBit(EightBitColor { code: 16.. }) = self else {
+            return self;
+        };
+        let [r, g, b] = self.to_rgb();
+        if r != g || g != b {
+            return self;
+        }
+
+        let steps = levels.max(2) as u32 - 1;
+        let level = (r as u32 * steps + 127) / 255;
+        let v = ((level * 255 + steps / 2) / steps) as u8;
+        Color::Rgb(RgbColor { r: v, g: v, b: v })
+    }
+}
+
+/// Parses a color like `#a0f` or `#aa00ff`
+fn parse_hex(hex: &str) -> [u8; 3] {
+    let hex = hex.trim_start_matches('#');
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap_or_default();
+    if hex.len() == 3 {
+        [digit(0) * 17, digit(1) * 17, digit(2) * 17]
+    } else {
+        [
+            digit(0) * 16 + digit(1),
+            digit(2) * 16 + digit(3),
+            digit(4) * 16 + digit(5),
+        ]
+    }
+}
+
+/// The opening spans of the 16 4-bit colors, computed once by
+/// [`Converter::build`](crate::Converter::build) instead of for every color
+/// change
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Palette {
+    fg: [String; 16],
+    bg: [String; 16],
+}
+
+impl Palette {
+    pub(crate) fn new(vars: Option<CssVars<'_>>, themes: Option<Themes<'_>>) -> Self {
+        let span = |i: usize, is_fg| {
+            Color::FourBit(FourBitColor::ALL[i]).into_opening_span(vars, themes, is_fg)
+        };
+        Self {
+            fg: std::array::from_fn(|i| span(i, true)),
+            bg: std::array::from_fn(|i| span(i, false)),
+        }
+    }
+
+    /// Returns the opening span if it is a 4-bit color
+    pub(crate) fn span(&self, color: Color, is_fg: bool) -> Option<&str> {
+        let Color::FourBit(color) = color else {
+            return None;
+        };
+        let spans = if is_fg { &self.fg } else { &self.bg };
+        Some(&spans[color as usize])
+    }
+}
+
+/// How the CSS variables of the 4-bit colors are named
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum CssVars<'a> {
+    /// The default names with a prefix, e.g. `--prefix-red`
+    Prefix(&'a str),
+    Names(&'a VarNames),
+    /// Classes instead of variables, e.g. `prefix-red` and `prefix-bg-red`
+    Classes(&'a str),
+}
+
+/// The colors of a terminal theme, used by
+/// [`Converter::palette`](crate::Converter::palette) and
+/// [`Converter::dual_theme`](crate::Converter::dual_theme).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct ThemePalette {
+    /// The default text color, as `[red, green, blue]`
+    pub foreground: [u8; 3],
+    /// The background color, as `[red, green, blue]`
+    pub background: [u8; 3],
+    /// The 16 colors of the palette, as `[red, green, blue]`: black, red,
+    /// green, yellow, blue, magenta, cyan and white, followed by their bright
+    /// variants
+    pub colors: [[u8; 3]; 16],
+}
+
+impl ThemePalette {
+    /// Creates a palette from the default text color, the background color and
+    /// the 16 colors of the palette, each as `[red, green, blue]`
+    pub const fn new(foreground: [u8; 3], background: [u8; 3], colors: [[u8; 3]; 16]) -> Self {
+        Self {
+            foreground,
+            background,
+            colors,
+        }
+    }
+
+    /// The default colors, with light gray text on black. The 16 colors are
+    /// the ones used without a palette, e.g. `#a00` for red.
+    pub const fn default_dark() -> Self {
+        Self::new([0xaa; 3], [0; 3], DEFAULT_COLORS)
+    }
+
+    /// The default colors with black text on white. The bright colors that are
+    /// hard to read on white (green, yellow, cyan and white) are darker than
+    /// in [`default_dark`](ThemePalette::default_dark).
+    pub const fn default_light() -> Self {
+        let mut colors = DEFAULT_COLORS;
+        colors[10] = [0x00, 0xaa, 0x00];
+        colors[11] = [0xaa, 0x88, 0x00];
+        colors[14] = [0x00, 0x99, 0x99];
+        colors[15] = [0x77, 0x77, 0x77];
+        Self::new([0; 3], [0xff; 3], colors)
+    }
+
+    /// The dark variant of [Solarized](https://ethanschoonover.com/solarized/)
+    pub const fn solarized_dark() -> Self {
+        Self::new(SOLARIZED_BASE0, SOLARIZED_BASE03, SOLARIZED_COLORS)
+    }
+
+    /// The light variant of [Solarized](https://ethanschoonover.com/solarized/)
+    pub const fn solarized_light() -> Self {
+        Self::new(SOLARIZED_BASE00, SOLARIZED_BASE3, SOLARIZED_COLORS)
+    }
+
+    /// Returns the color in this theme. Only the 16 colors of the palette
+    /// depend on the theme, unless other colors are adapted to it.
+    fn resolve(&self, color: Color, adapt: bool) -> Color {
+        match color.palette_index() {
+            Some(i) => Color::Rgb(RgbColor::from(self.colors[i])),
+            None if adapt => self.adapt(color),
+            None => color,
+        }
+    }
+
+    /// Darkens the color on a light background, or lightens it on a dark
+    /// background, until its contrast to the background is at least
+    /// [`MIN_CONTRAST`]. See
+    /// [`Converter::adapt_to_theme`](crate::Converter::adapt_to_theme).
+    fn adapt(&self, color: Color) -> Color {
+        let rgb = color.to_rgb();
+        let bg = self.background;
+        let target = match contrast(bg, [0; 3]) > contrast(bg, [0xff; 3]) {
+            true => [0; 3],
+            false => [0xff; 3],
+        };
+        let mix = |step: u32| {
+            std::array::from_fn(|i| {
+                let mixed = rgb[i] as u32 * (ADAPT_STEPS - step) + target[i] as u32 * step;
+                ((mixed + ADAPT_STEPS / 2) / ADAPT_STEPS) as u8
+            })
+        };
+        match (0..=ADAPT_STEPS)
+            .map(mix)
+            .find(|&c| contrast(c, bg) >= MIN_CONTRAST)
+        {
+            Some(readable) if readable == rgb => color,
+            Some(readable) => Color::Rgb(RgbColor::from(readable)),
+            None => Color::Rgb(RgbColor::from(target)),
+        }
+    }
+
+    fn faint_blend(&self) -> FaintBlend {
+        FaintBlend::new(self.foreground, self.background)
+    }
+}
+
+/// The minimum contrast ratio of text colors adapted to a theme, which is the
+/// minimum for normal text in WCAG 2
+const MIN_CONTRAST: f64 = 4.5;
+
+/// The number of steps in which a color is moved towards black or white when
+/// it is adapted to a theme
+const ADAPT_STEPS: u32 = 32;
+
+/// Returns the contrast ratio of two colors, from 1 to 21, as defined by WCAG 2
+fn contrast(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Returns the relative luminance of the color, as defined by WCAG 2
+fn luminance(rgb: [u8; 3]) -> f64 {
+    let [r, g, b] = rgb.map(|c| {
+        let c = c as f64 / 255.0;
+        match c <= 0.04045 {
+            true => c / 12.92,
+            false => ((c + 0.055) / 1.055).powf(2.4),
+        }
+    });
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// The colors of [`EIGHT_BIT_COLORS`] that are used for the 16 colors of the palette
+const DEFAULT_COLORS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0xaa, 0x00, 0x00],
+    [0x00, 0xaa, 0x00],
+    [0xaa, 0x66, 0x00],
+    [0x00, 0x00, 0xaa],
+    [0xaa, 0x00, 0xaa],
+    [0x00, 0xaa, 0xaa],
+    [0xaa, 0xaa, 0xaa],
+    [0x55, 0x55, 0x55],
+    [0xff, 0x55, 0x55],
+    [0x55, 0xff, 0x55],
+    [0xff, 0xff, 0x55],
+    [0x55, 0x55, 0xff],
+    [0xff, 0x55, 0xff],
+    [0x55, 0xff, 0xff],
+    [0xff, 0xff, 0xff],
+];
+
+const SOLARIZED_BASE03: [u8; 3] = [0x00, 0x2b, 0x36];
+const SOLARIZED_BASE00: [u8; 3] = [0x65, 0x7b, 0x83];
+const SOLARIZED_BASE0: [u8; 3] = [0x83, 0x94, 0x96];
+const SOLARIZED_BASE3: [u8; 3] = [0xfd, 0xf6, 0xe3];
+
+/// The 16 colors of Solarized, which are the same in the light and dark variant. The bright colors
+/// are mostly the gray base colors, like in the terminal themes published by its author.
+const SOLARIZED_COLORS: [[u8; 3]; 16] = [
+    [0x07, 0x36, 0x42],
+    [0xdc, 0x32, 0x2f],
+    [0x85, 0x99, 0x00],
+    [0xb5, 0x89, 0x00],
+    [0x26, 0x8b, 0xd2],
+    [0xd3, 0x36, 0x82],
+    [0x2a, 0xa1, 0x98],
+    [0xee, 0xe8, 0xd5],
+    SOLARIZED_BASE03,
+    [0xcb, 0x4b, 0x16],
+    [0x58, 0x6e, 0x75],
+    SOLARIZED_BASE00,
+    SOLARIZED_BASE0,
+    [0x6c, 0x71, 0xc4],
+    [0x93, 0xa1, 0xa1],
+    SOLARIZED_BASE3,
+];
+
+/// The light and dark theme set with
+/// [`Converter::dual_theme`](crate::Converter::dual_theme)
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Themes<'a> {
+    pub(crate) light: &'a ThemePalette,
+    pub(crate) dark: &'a ThemePalette,
+    /// Whether `light-dark()` is used; otherwise, only the dark theme is
+    pub(crate) light_dark: bool,
+    /// Whether text colors are adapted to the themes, see
+    /// [`Converter::adapt_to_theme`](crate::Converter::adapt_to_theme)
+    pub(crate) adapt: bool,
+    /// The default colors that replace the ones of the themes, see
+    /// [`Converter::default_colors`](crate::Converter::default_colors)
+    pub(crate) default_colors: Option<[[u8; 3]; 2]>,
+}
+
+impl Themes<'_> {
+    fn resolve(self, color: Color, is_fg: bool) -> ThemedColor {
+        self.themed(|theme| theme.resolve(color, self.adapt && is_fg))
+    }
+
+    /// Returns whether the color is displayed like the default text color (or
+    /// the background color) in every theme that is used
+    pub(crate) fn is_default(self, color: Color, is_fg: bool) -> bool {
+        let is_default = |theme: &ThemePalette| {
+            let [fg, bg] = (self.default_colors).unwrap_or([theme.foreground, theme.background]);
+            let default = if is_fg { fg } else { bg };
+            theme.resolve(color, self.adapt && is_fg).to_rgb() == default
+        };
+        is_default(self.dark) && (!self.light_dark || is_default(self.light))
+    }
+
+    /// Returns the color of faint text, blended with the background of each
+    /// theme. `fg` is the text color, if it isn't the default.
+    pub(crate) fn blend_faint(self, fg: Option<Color>) -> ThemedColor {
+        self.themed(|theme| {
+            let fg = fg.map_or(theme.foreground, |fg| {
+                theme.resolve(fg, self.adapt).to_rgb()
+            });
+            Color::Rgb(theme.faint_blend().blend(fg))
         })
     }
 
-    pub(crate) fn into_opening_fg_span(self, var_prefix: Option<&str>) -> String {
-        self.into_opening_span(var_prefix, true)
+    fn themed(self, color: impl Fn(&ThemePalette) -> Color) -> ThemedColor {
+        ThemedColor {
+            light: color(self.light),
+            dark: color(self.dark),
+            light_dark: self.light_dark,
+        }
     }
+}
 
-    pub(crate) fn into_opening_bg_span(self, var_prefix: Option<&str>) -> String {
-        self.into_opening_span(var_prefix, false)
+/// A color that can differ between the light and dark color scheme. It is
+/// displayed as CSS `light-dark()` function if it does.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ThemedColor {
+    light: Color,
+    dark: Color,
+    light_dark: bool,
+}
+
+impl ThemedColor {
+    fn same(color: Color) -> Self {
+        Self {
+            light: color,
+            dark: color,
+            light_dark: false,
+        }
     }
 
-    pub(crate) fn into_opening_span(self, var_prefix: Option<&str>, is_fg: bool) -> String {
-        if let Self::FourBit(four_bit) = self {
-            let fg_vs_bg = if is_fg { "color" } else { "background" };
-            let prefix = var_prefix.unwrap_or_default();
-            format!("<span style='{fg_vs_bg}:var(--{prefix}{four_bit},{self})'>")
-        } else if is_fg {
-            format!("<span style='color:{self}'>")
+    pub(crate) fn into_opening_fg_span(self) -> String {
+        format!("<span style='color:{self}'>")
+    }
+}
+
+impl fmt::Display for ThemedColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.light_dark && self.light != self.dark {
+            write!(f, "light-dark({},{})", self.light, self.dark)
         } else {
-            format!("<span style='background:{self}'>")
+            fmt::Display::fmt(&self.dark, f)
+        }
+    }
+}
+
+/// The colors used to render faint text with a concrete color instead of
+/// `opacity`, see [`Converter::faint_blend`](crate::Converter::faint_blend).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct FaintBlend {
+    /// The default text color, as `[red, green, blue]`
+    pub foreground: [u8; 3],
+    /// The background color, as `[red, green, blue]`
+    pub background: [u8; 3],
+}
+
+impl FaintBlend {
+    /// Creates the blend colors from the default text color and the
+    /// background color, each as `[red, green, blue]`
+    pub const fn new(foreground: [u8; 3], background: [u8; 3]) -> Self {
+        Self {
+            foreground,
+            background,
+        }
+    }
+
+    /// Blends the color with the background, so it looks like it has an opacity of 67%
+    pub(crate) fn blend(&self, color: [u8; 3]) -> RgbColor {
+        let mix = |i: usize| {
+            let c = color[i] as u32 * 67 + self.background[i] as u32 * 33;
+            ((c + 50) / 100) as u8
+        };
+        RgbColor {
+            r: mix(0),
+            g: mix(1),
+            b: mix(2),
         }
     }
 }
@@ -154,6 +582,25 @@ impl fmt::Display for FourBitColor {
 }
 
 impl FourBitColor {
+    pub(crate) const ALL: [FourBitColor; 16] = [
+        Self::Black,
+        Self::Red,
+        Self::Green,
+        Self::Yellow,
+        Self::Blue,
+        Self::Magenta,
+        Self::Cyan,
+        Self::White,
+        Self::BrightBlack,
+        Self::BrightRed,
+        Self::BrightGreen,
+        Self::BrightYellow,
+        Self::BrightBlue,
+        Self::BrightMagenta,
+        Self::BrightCyan,
+        Self::BrightWhite,
+    ];
+
     pub(crate) fn is_bright(self) -> bool {
         matches!(
             self,
@@ -182,47 +629,54 @@ impl EightBitColor {
 
 impl fmt::Display for EightBitColor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        const COLORS: [&str; 256] = [
-            "#000", "#a00", "#0a0", "#a60", "#00a", "#a0a", "#0aa", "#aaa", "#555", "#f55", "#5f5",
-            "#ff5", "#55f", "#f5f", "#5ff", "#fff", "#000", "#00005f", "#000087", "#0000af",
-            "#0000d7", "#00f", "#005f00", "#005f5f", "#005f87", "#005faf", "#005fd7", "#005fff",
-            "#008700", "#00875f", "#008787", "#0087af", "#0087d7", "#0087ff", "#00af00", "#00af5f",
-            "#00af87", "#00afaf", "#00afd7", "#00afff", "#00d700", "#00d75f", "#00d787", "#00d7af",
-            "#00d7d7", "#00d7ff", "#0f0", "#00ff5f", "#00ff87", "#00ffaf", "#00ffd7", "#0ff",
-            "#5f0000", "#5f005f", "#5f0087", "#5f00af", "#5f00d7", "#5f00ff", "#5f5f00", "#5f5f5f",
-            "#5f5f87", "#5f5faf", "#5f5fd7", "#5f5fff", "#5f8700", "#5f875f", "#5f8787", "#5f87af",
-            "#5f87d7", "#5f87ff", "#5faf00", "#5faf5f", "#5faf87", "#5fafaf", "#5fafd7", "#5fafff",
-            "#5fd700", "#5fd75f", "#5fd787", "#5fd7af", "#5fd7d7", "#5fd7ff", "#5fff00", "#5fff5f",
-            "#5fff87", "#5fffaf", "#5fffd7", "#5fffff", "#870000", "#87005f", "#870087", "#8700af",
-            "#8700d7", "#8700ff", "#875f00", "#875f5f", "#875f87", "#875faf", "#875fd7", "#875fff",
-            "#878700", "#87875f", "#878787", "#8787af", "#8787d7", "#8787ff", "#87af00", "#87af5f",
-            "#87af87", "#87afaf", "#87afd7", "#87afff", "#87d700", "#87d75f", "#87d787", "#87d7af",
-            "#87d7d7", "#87d7ff", "#87ff00", "#87ff5f", "#87ff87", "#87ffaf", "#87ffd7", "#87ffff",
-            "#af0000", "#af005f", "#af0087", "#af00af", "#af00d7", "#af00ff", "#af5f00", "#af5f5f",
-            "#af5f87", "#af5faf", "#af5fd7", "#af5fff", "#af8700", "#af875f", "#af8787", "#af87af",
-            "#af87d7", "#af87ff", "#afaf00", "#afaf5f", "#afaf87", "#afafaf", "#afafd7", "#afafff",
-            "#afd700", "#afd75f", "#afd787", "#afd7af", "#afd7d7", "#afd7ff", "#afff00", "#afff5f",
-            "#afff87", "#afffaf", "#afffd7", "#afffff", "#d70000", "#d7005f", "#d70087", "#d700af",
-            "#d700d7", "#d700ff", "#d75f00", "#d75f5f", "#d75f87", "#d75faf", "#d75fd7", "#d75fff",
-            "#d78700", "#d7875f", "#d78787", "#d787af", "#d787d7", "#d787ff", "#d7af00", "#d7af5f",
-            "#d7af87", "#d7afaf", "#d7afd7", "#d7afff", "#d7d700", "#d7d75f", "#d7d787", "#d7d7af",
-            "#d7d7d7", "#d7d7ff", "#d7ff00", "#d7ff5f", "#d7ff87", "#d7ffaf", "#d7ffd7", "#d7ffff",
-            "#f00", "#ff005f", "#ff0087", "#ff00af", "#ff00d7", "#f0f", "#ff5f00", "#ff5f5f",
-            "#ff5f87", "#ff5faf", "#ff5fd7", "#ff5fff", "#ff8700", "#ff875f", "#ff8787", "#ff87af",
-            "#ff87d7", "#ff87ff", "#ffaf00", "#ffaf5f", "#ffaf87", "#ffafaf", "#ffafd7", "#ffafff",
-            "#ffd700", "#ffd75f", "#ffd787", "#ffd7af", "#ffd7d7", "#ffd7ff", "#ff0", "#ffff5f",
-            "#ffff87", "#ffffaf", "#ffffd7", "#fff", "#080808", "#121212", "#1c1c1c", "#262626",
-            "#303030", "#3a3a3a", "#444", "#4e4e4e", "#585858", "#626262", "#6c6c6c", "#767676",
-            "#808080", "#8a8a8a", "#949494", "#9e9e9e", "#a8a8a8", "#b2b2b2", "#bcbcbc", "#c6c6c6",
-            "#d0d0d0", "#dadada", "#e4e4e4", "#eee",
-        ];
-        f.write_str(COLORS[self.code as usize])
+        f.write_str(EIGHT_BIT_COLORS[self.code as usize])
     }
 }
 
+/// The default hex values of the 256 8-bit colors. The first 16 entries are the 4-bit colors.
+const EIGHT_BIT_COLORS: [&str; 256] = [
+    "#000", "#a00", "#0a0", "#a60", "#00a", "#a0a", "#0aa", "#aaa", "#555", "#f55", "#5f5", "#ff5",
+    "#55f", "#f5f", "#5ff", "#fff", "#000", "#00005f", "#000087", "#0000af", "#0000d7", "#00f",
+    "#005f00", "#005f5f", "#005f87", "#005faf", "#005fd7", "#005fff", "#008700", "#00875f",
+    "#008787", "#0087af", "#0087d7", "#0087ff", "#00af00", "#00af5f", "#00af87", "#00afaf",
+    "#00afd7", "#00afff", "#00d700", "#00d75f", "#00d787", "#00d7af", "#00d7d7", "#00d7ff", "#0f0",
+    "#00ff5f", "#00ff87", "#00ffaf", "#00ffd7", "#0ff", "#5f0000", "#5f005f", "#5f0087", "#5f00af",
+    "#5f00d7", "#5f00ff", "#5f5f00", "#5f5f5f", "#5f5f87", "#5f5faf", "#5f5fd7", "#5f5fff",
+    "#5f8700", "#5f875f", "#5f8787", "#5f87af", "#5f87d7", "#5f87ff", "#5faf00", "#5faf5f",
+    "#5faf87", "#5fafaf", "#5fafd7", "#5fafff", "#5fd700", "#5fd75f", "#5fd787", "#5fd7af",
+    "#5fd7d7", "#5fd7ff", "#5fff00", "#5fff5f", "#5fff87", "#5fffaf", "#5fffd7", "#5fffff",
+    "#870000", "#87005f", "#870087", "#8700af", "#8700d7", "#8700ff", "#875f00", "#875f5f",
+    "#875f87", "#875faf", "#875fd7", "#875fff", "#878700", "#87875f", "#878787", "#8787af",
+    "#8787d7", "#8787ff", "#87af00", "#87af5f", "#87af87", "#87afaf", "#87afd7", "#87afff",
+    "#87d700", "#87d75f", "#87d787", "#87d7af", "#87d7d7", "#87d7ff", "#87ff00", "#87ff5f",
+    "#87ff87", "#87ffaf", "#87ffd7", "#87ffff", "#af0000", "#af005f", "#af0087", "#af00af",
+    "#af00d7", "#af00ff", "#af5f00", "#af5f5f", "#af5f87", "#af5faf", "#af5fd7", "#af5fff",
+    "#af8700", "#af875f", "#af8787", "#af87af", "#af87d7", "#af87ff", "#afaf00", "#afaf5f",
+    "#afaf87", "#afafaf", "#afafd7", "#afafff", "#afd700", "#afd75f", "#afd787", "#afd7af",
+    "#afd7d7", "#afd7ff", "#afff00", "#afff5f", "#afff87", "#afffaf", "#afffd7", "#afffff",
+    "#d70000", "#d7005f", "#d70087", "#d700af", "#d700d7", "#d700ff", "#d75f00", "#d75f5f",
+    "#d75f87", "#d75faf", "#d75fd7", "#d75fff", "#d78700", "#d7875f", "#d78787", "#d787af",
+    "#d787d7", "#d787ff", "#d7af00", "#d7af5f", "#d7af87", "#d7afaf", "#d7afd7", "#d7afff",
+    "#d7d700", "#d7d75f", "#d7d787", "#d7d7af", "#d7d7d7", "#d7d7ff", "#d7ff00", "#d7ff5f",
+    "#d7ff87", "#d7ffaf", "#d7ffd7", "#d7ffff", "#f00", "#ff005f", "#ff0087", "#ff00af", "#ff00d7",
+    "#f0f", "#ff5f00", "#ff5f5f", "#ff5f87", "#ff5faf", "#ff5fd7", "#ff5fff", "#ff8700", "#ff875f",
+    "#ff8787", "#ff87af", "#ff87d7", "#ff87ff", "#ffaf00", "#ffaf5f", "#ffaf87", "#ffafaf",
+    "#ffafd7", "#ffafff", "#ffd700", "#ffd75f", "#ffd787", "#ffd7af", "#ffd7d7", "#ffd7ff", "#ff0",
+    "#ffff5f", "#ffff87", "#ffffaf", "#ffffd7", "#fff", "#080808", "#121212", "#1c1c1c", "#262626",
+    "#303030", "#3a3a3a", "#444", "#4e4e4e", "#585858", "#626262", "#6c6c6c", "#767676", "#808080",
+    "#8a8a8a", "#949494", "#9e9e9e", "#a8a8a8", "#b2b2b2", "#bcbcbc", "#c6c6c6", "#d0d0d0",
+    "#dadada", "#e4e4e4", "#eee",
+];
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub(crate) struct RgbColor {
     r: u8,
     g: u8,
     b: u8,
 }
+
+impl From<[u8; 3]> for RgbColor {
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Self { r, g, b }
+    }
+}
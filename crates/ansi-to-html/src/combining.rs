@@ -0,0 +1,60 @@
+//! Characters that extend the preceding character, see
+//! [`Converter::attach_combining`](crate::Converter::attach_combining).
+//!
+//! This is not a full implementation of grapheme cluster boundaries. Only the
+//! characters that commonly appear in terminal output are included: the
+//! blocks of combining diacritical marks, zero-width joiners, variation
+//! selectors, emoji modifiers and tags. A character after a zero-width joiner
+//! is joined to the preceding character as well, as in emoji ZWJ sequences.
+
+const ZWJ: char = '\u{200D}';
+
+/// Ranges of characters that extend the preceding character, sorted
+const EXTENDING: &[(char, char)] = &[
+    // combining diacritical marks
+    ('\u{0300}', '\u{036F}'),
+    // Cyrillic combining marks
+    ('\u{0483}', '\u{0489}'),
+    // combining diacritical marks extended
+    ('\u{1AB0}', '\u{1AFF}'),
+    // combining diacritical marks supplement
+    ('\u{1DC0}', '\u{1DFF}'),
+    // zero-width non-joiner and zero-width joiner
+    ('\u{200C}', '\u{200D}'),
+    // combining marks for symbols, e.g. the enclosing keycap
+    ('\u{20D0}', '\u{20FF}'),
+    // variation selectors
+    ('\u{FE00}', '\u{FE0F}'),
+    // combining half marks
+    ('\u{FE20}', '\u{FE2F}'),
+    // emoji skin tone modifiers
+    ('\u{1F3FB}', '\u{1F3FF}'),
+    // tags, used in the flags of subdivisions
+    ('\u{E0020}', '\u{E007F}'),
+    // variation selectors supplement
+    ('\u{E0100}', '\u{E01EF}'),
+];
+
+/// Returns whether `c` belongs to the same character as `prev`, which is
+/// displayed before it. Line breaks are never joined.
+pub(crate) fn extends(prev: char, c: char) -> bool {
+    if prev == '\n' || c == '\n' {
+        return false;
+    }
+    prev == ZWJ || is_extending(c)
+}
+
+fn is_extending(c: char) -> bool {
+    c >= EXTENDING[0].0
+        && EXTENDING
+            .binary_search_by(|&(start, end)| {
+                if end < c {
+                    std::cmp::Ordering::Less
+                } else if start > c {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+}
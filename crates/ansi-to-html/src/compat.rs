@@ -0,0 +1,140 @@
+use crate::{trace::phase, Converter};
+
+/// Which version's output to reproduce byte for byte, see
+/// [`Converter::output_compat`](crate::Converter::output_compat).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputCompat {
+    /// The output of the current version, which may change in a new version
+    /// as long as it renders the same
+    #[default]
+    Current,
+    /// The output of the 0.x versions. This will be deprecated in 1.1 and
+    /// removed in 2.0.
+    V0,
+}
+
+/// Returns whether the HTML is optimized in a second pass like in the 0.x
+/// versions, see [`optimize_v0`]
+pub(crate) fn optimizes_v0(converter: &Converter) -> bool {
+    cfg!(feature = "optimize")
+        && converter.output_compat == OutputCompat::V0
+        && !converter.skip_optimize
+}
+
+/// Removes empty elements and then merges adjacent elements with the same
+/// tag, like the second pass of the 0.x versions, if it is enabled. Unlike the
+/// optimization while converting, this changes tags in the input as well (see
+/// [`Converter::skip_escape`]). The wrappers of empty lines are kept, see
+/// [`Converter::line_wrapper`].
+pub(crate) fn optimize_v0(html: String, converter: &Converter) -> String {
+    if !optimizes_v0(converter) {
+        return html;
+    }
+    let phase = phase!("optimize", html.len());
+    let style = converter.tag_style;
+    let tags = [
+        style.bold(),
+        style.italic(),
+        ("<u>", "</u>"),
+        style.crossed_out(),
+    ];
+    let empty_line = (converter.line_wrapper.as_ref())
+        .and_then(|wrapper| wrapper.empty_element(converter.attribute_quotes));
+
+    let html = remove_matches(&html, |rest| {
+        let len = empty_span_len(rest).or_else(|| {
+            (tags.iter())
+                .find(|(open, close)| {
+                    (rest.strip_prefix(open)).is_some_and(|rest| rest.starts_with(close))
+                })
+                .map(|(open, close)| open.len() + close.len())
+        })?;
+        Some((len, empty_line.as_deref() == Some(&rest[..len])))
+    });
+    let html = remove_matches(&html, |rest| {
+        (tags.iter())
+            .find(|(open, close)| {
+                (rest.strip_prefix(close)).is_some_and(|rest| rest.starts_with(open))
+            })
+            .map(|(open, close)| (open.len() + close.len(), false))
+    });
+
+    phase.output_len(html.len());
+    html
+}
+
+/// Removes the matches at the start of a `<`. `find` returns the length of the
+/// match at the start of the string, and whether it is kept. Like with a regex,
+/// the search continues after the end of a match, so matches don't overlap.
+fn remove_matches(html: &str, find: impl Fn(&str) -> Option<(usize, bool)>) -> String {
+    let mut out = String::with_capacity(html.len());
+    // the end of the HTML that was copied or removed
+    let mut copied = 0;
+    let mut search = 0;
+    while let Some(i) = html[search..].find('<') {
+        let start = search + i;
+        match find(&html[start..]) {
+            Some((len, keep)) => {
+                let end = if keep { start + len } else { start };
+                out.push_str(&html[copied..end]);
+                copied = start + len;
+                search = copied;
+            }
+            None => search = start + 1,
+        }
+    }
+    out.push_str(&html[copied..]);
+    out
+}
+
+/// Returns the length of an empty `<span>` with a single attribute at the
+/// start of the HTML, like `<span \w+=(?:'[^']*'|"[^"]*")></span>`. Word
+/// characters are alphanumeric characters and `_`, which differs from the
+/// `\w` of the 0.x versions only for rare characters in attribute names, e.g.
+/// combining marks.
+fn empty_span_len(html: &str) -> Option<usize> {
+    let rest = html.strip_prefix("<span ")?;
+    let name_len = rest
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(rest.len());
+    let rest = rest[name_len..]
+        .strip_prefix('=')
+        .filter(|_| name_len > 0)?;
+    let quote = rest.chars().next().filter(|&c| c == '\'' || c == '"')?;
+    let value_len = rest[1..].find(quote)?;
+    let rest = rest[value_len + 2..].strip_prefix("></span>")?;
+    Some(html.len() - rest.len())
+}
+
+/// Returns the length of the start of the HTML that can be optimized on its
+/// own, i.e. `optimize_v0(html)` equals `optimize_v0(&html[..len]) +
+/// optimize_v0(&html[len..])`.
+///
+/// The start ends after a line break that can't be part of a match, so it is
+/// kept by the first pass and the second pass can't match across it. Line
+/// breaks can only be matched in the attribute of a `<span>`: Then the last
+/// `'` or the last `"` before the line break follows `<span \w+=`.
+pub(crate) fn optimizable_len(html: &str) -> usize {
+    let mut end = html.len();
+    while let Some(i) = html[..end].rfind('\n') {
+        let opening_quote = ['\'', '"']
+            .into_iter()
+            .filter_map(|quote| html[..i].rfind(quote))
+            .filter(|&quote| opens_span_attribute(&html[..quote]))
+            .min();
+        match opening_quote {
+            Some(quote) => end = quote,
+            None => return i + 1,
+        }
+    }
+    0
+}
+
+/// Returns whether the HTML ends with `<span \w+=`. More characters than
+/// `\w` are accepted, since a false positive only holds back more HTML.
+fn opens_span_attribute(html: &str) -> bool {
+    html.strip_suffix('=').is_some_and(|html| {
+        html.trim_end_matches(|c: char| !c.is_whitespace() && !"<>='\"".contains(c))
+            .ends_with("<span ")
+    })
+}
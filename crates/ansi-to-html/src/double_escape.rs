@@ -0,0 +1,55 @@
+//! Detects input that is already HTML, see
+//! [`Converter::detect_double_escape`](crate::Converter::detect_double_escape).
+
+use crate::entity::MAX_ENTITY_LEN;
+
+/// The minimum number of entities in text that looks like HTML
+const MIN_ENTITIES: usize = 2;
+
+/// The maximum number of bytes per entity in text that looks like HTML
+const MAX_BYTES_PER_ENTITY: usize = 64;
+
+/// Returns whether the text looks like HTML, e.g. the output of a previous
+/// conversion, rather than terminal output.
+///
+/// This is a heuristic: The text must not contain escape sequences, every `&`
+/// must start an entity such as `&lt;` or `&#39;`, and there must be at least
+/// 2 entities and one entity per 64 bytes. Plain text with only a few entities,
+/// or with a `&` that isn't an entity, doesn't look like HTML.
+///
+/// ## Example
+///
+/// ```
+/// use ansi_to_html::looks_like_html;
+///
+/// let html = ansi_to_html::convert("\x1b[1m<b> & </b>").unwrap();
+/// assert_eq!(html, "<b>&lt;b&gt; &amp; &lt;/b&gt;</b>");
+/// assert!(looks_like_html(&html));
+///
+/// assert!(!looks_like_html("\x1b[1m&lt;b&gt; &amp; &lt;/b&gt;"));
+/// assert!(!looks_like_html("Tom &amp; Jerry & friends &lt;3"));
+/// ```
+#[must_use]
+pub fn looks_like_html(text: &str) -> bool {
+    if text.contains('\x1b') {
+        return false;
+    }
+    let mut entities = 0;
+    for (i, _) in text.match_indices('&') {
+        if !is_entity(&text[i + 1..]) {
+            return false;
+        }
+        entities += 1;
+    }
+    entities >= MIN_ENTITIES && text.len() <= entities * MAX_BYTES_PER_ENTITY
+}
+
+/// Returns whether the text after a `&` is the rest of an entity
+fn is_entity(rest: &str) -> bool {
+    let name_len = rest
+        .bytes()
+        .take(MAX_ENTITY_LEN)
+        .take_while(|&b| b.is_ascii_alphanumeric() || b == b'#')
+        .count();
+    name_len > 0 && rest.as_bytes().get(name_len) == Some(&b';')
+}
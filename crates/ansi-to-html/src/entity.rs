@@ -0,0 +1,97 @@
+//! HTML entities in input that is already HTML, see
+//! [`Converter::skip_escape`](crate::Converter::skip_escape).
+//!
+//! An escape sequence in the middle of an entity, e.g. `&l\x1b[31mt;`, would
+//! insert a tag into the entity, so it's no longer recognized by browsers.
+//! Such sequences are moved after the `;` that ends the entity.
+
+use std::borrow::Cow;
+
+use crate::ansi::find_sequence;
+
+/// The maximum number of characters and escape sequences in an entity after
+/// the `&`. The longest named entity, `&CounterClockwiseContourIntegral;`, has
+/// 31 characters.
+pub(crate) const MAX_ENTITY_LEN: usize = 32;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Entity {
+    /// An entity containing escape sequences, with the length including the `;`
+    Split(usize),
+    /// The input ends before the entity is complete
+    Incomplete,
+    /// Not an entity, or an entity without escape sequences
+    Other,
+}
+
+/// Scans the entity at the start of the input, which starts with `&`
+fn scan(input: &str) -> Entity {
+    let mut i = 1;
+    let mut parts = 0;
+    let mut name_len = 0;
+    let mut has_sequence = false;
+    loop {
+        let rest = &input[i..];
+        if parts == MAX_ENTITY_LEN {
+            return Entity::Other;
+        }
+        parts += 1;
+
+        if rest.starts_with('\x1b') {
+            if let Some(range) = find_sequence(rest).filter(|r| r.start == 0) {
+                has_sequence = true;
+                i += range.end;
+                continue;
+            }
+        }
+        match rest.bytes().next() {
+            None => return Entity::Incomplete,
+            Some(b) if b.is_ascii_alphanumeric() || b == b'#' => {
+                name_len += 1;
+                i += 1;
+            }
+            Some(b';') if name_len > 0 && has_sequence => return Entity::Split(i + 1),
+            Some(_) => return Entity::Other,
+        }
+    }
+}
+
+/// Moves escape sequences in the middle of entities after the entities.
+pub(crate) fn move_sequences_out(input: &str) -> Cow<'_, str> {
+    if !input.contains('&') || !input.contains('\x1b') {
+        return Cow::Borrowed(input);
+    }
+
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let Entity::Split(len) = scan(rest) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let (mut entity, after) = rest.split_at(len);
+        let mut sequences = String::new();
+        while let Some(range) = find_sequence(entity) {
+            out.push_str(&entity[..range.start]);
+            sequences.push_str(&entity[range.clone()]);
+            entity = &entity[range.end..];
+        }
+        out.push_str(entity);
+        out.push_str(&sequences);
+        rest = after;
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Returns the start of an entity that is cut off at the end of the input, so
+/// it can be completed by the next call of
+/// [`convert_append`](crate::Converter::convert_append).
+pub(crate) fn incomplete_start(input: &str) -> Option<usize> {
+    let start = input.rfind('&')?;
+    (scan(&input[start..]) == Entity::Incomplete).then_some(start)
+}
@@ -1,34 +1,305 @@
-use std::{fmt, num::ParseIntError};
+use std::{fmt, num::ParseIntError, ops::Range};
+
+use crate::ansi::find_sequence;
 
 /// Errors that can occur when converting an ANSI string to HTML
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Parsing a number was unsuccessful
-    ParseInt(ParseIntError),
+    ParseInt {
+        err: ParseIntError,
+        /// The escape sequence containing the number
+        sequence: String,
+        /// The byte range of the sequence in the input, see [`Error::span`]
+        span: Option<Range<usize>>,
+    },
 
     /// The ANSI escape code is invalid
-    InvalidAnsi { msg: String },
+    InvalidAnsi {
+        msg: String,
+        /// The escape sequence containing the code
+        sequence: String,
+        /// The byte range of the sequence in the input, see [`Error::span`]
+        span: Option<Range<usize>>,
+    },
+
+    /// A name passed to [`VarNames::new`](crate::VarNames::new) isn't a valid
+    /// CSS variable name
+    InvalidVarName { name: String },
+
+    /// A tag or class name passed to
+    /// [`LineWrapper::new`](crate::LineWrapper::new) isn't valid
+    InvalidLineWrapper { name: String },
+
+    /// Writing the HTML failed, see
+    /// [`Converter::convert_to_writer`](crate::Converter::convert_to_writer)
+    Write { err: fmt::Error },
 }
 
 impl From<ParseIntError> for Error {
     fn from(err: ParseIntError) -> Self {
-        Self::ParseInt(err)
+        Self::ParseInt {
+            err,
+            sequence: String::new(),
+            span: None,
+        }
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::ParseInt(err) => write!(f, "{}", err),
-            Self::InvalidAnsi { msg } => write!(f, "Invalid ANSI: {}", msg),
+            Self::ParseInt { err, .. } => write!(f, "{}", err)?,
+            Self::InvalidAnsi { msg, .. } => write!(f, "Invalid ANSI: {}", msg)?,
+            Self::InvalidVarName { name } => {
+                return write!(f, "Invalid CSS variable name: {:?}", name)
+            }
+            Self::InvalidLineWrapper { name } => {
+                return write!(
+                    f,
+                    "Invalid tag or class name of the line wrapper: {:?}",
+                    name
+                )
+            }
+            Self::Write { err } => return write!(f, "Writing the HTML failed: {}", err),
+        }
+        if let Some(sequence) = self.sequence() {
+            write!(f, " in {:?}", sequence)?;
+        }
+        if let Some(span) = self.span() {
+            write!(f, " at bytes {}..{}", span.start, span.end)?;
         }
+        Ok(())
     }
 }
 
 impl std::error::Error for Error {}
 
+/// Errors returned by [`Converter::build`](crate::Converter::build) for
+/// options that are invalid or conflict with each other
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The prefix passed to
+    /// [`four_bit_var_prefix`](crate::Converter::four_bit_var_prefix) may only
+    /// contain the characters allowed in [`VarNames`](crate::VarNames)
+    InvalidVarPrefix { prefix: String },
+
+    /// [`four_bit_var_prefix`](crate::Converter::four_bit_var_prefix) is
+    /// ignored because [`var_names`](crate::Converter::var_names),
+    /// [`skip_css_vars`](crate::Converter::skip_css_vars) or
+    /// [`four_bit_classes`](crate::Converter::four_bit_classes) is set
+    IgnoredVarPrefix,
+
+    /// [`var_names`](crate::Converter::var_names) is ignored because
+    /// [`skip_css_vars`](crate::Converter::skip_css_vars) or
+    /// [`four_bit_classes`](crate::Converter::four_bit_classes) is set
+    IgnoredVarNames,
+
+    /// The prefix passed to
+    /// [`four_bit_classes`](crate::Converter::four_bit_classes) may only
+    /// contain letters, digits, `-` and `_`
+    InvalidClassPrefix { prefix: String },
+
+    /// [`skip_css_vars`](crate::Converter::skip_css_vars) is ignored because
+    /// [`four_bit_classes`](crate::Converter::four_bit_classes) is set
+    IgnoredSkipCssVars,
+
+    /// [`sanitize`](crate::Converter::sanitize) has no effect without
+    /// [`skip_escape`](crate::Converter::skip_escape)
+    SanitizeWithoutSkipEscape,
+
+    /// [`allowed_tags`](crate::Converter::allowed_tags) has no effect without
+    /// [`sanitize`](crate::Converter::sanitize)
+    AllowedTagsWithoutSanitize,
+
+    /// [`link_schemes`](crate::Converter::link_schemes) has no effect without
+    /// [`render_links`](crate::Converter::render_links)
+    LinkSchemesWithoutRenderLinks,
+
+    /// [`skip_light_dark`](crate::Converter::skip_light_dark) has no effect
+    /// without [`dual_theme`](crate::Converter::dual_theme)
+    SkipLightDarkWithoutDualTheme,
+
+    /// [`adapt_to_theme`](crate::Converter::adapt_to_theme) has no effect
+    /// without [`palette`](crate::Converter::palette) or
+    /// [`dual_theme`](crate::Converter::dual_theme)
+    AdaptWithoutTheme,
+
+    /// The [`palette`](crate::Converter::palette) is ignored because
+    /// [`dual_theme`](crate::Converter::dual_theme) is set
+    IgnoredPalette,
+
+    /// The [`tag_style`](crate::Converter::tag_style) is ignored because
+    /// [`flat_spans`](crate::Converter::flat_spans) is enabled
+    IgnoredTagStyle,
+
+    /// [`detect_double_escape`](crate::Converter::detect_double_escape) has no
+    /// effect with [`skip_escape`](crate::Converter::skip_escape)
+    DetectDoubleEscapeWithSkipEscape,
+
+    /// [`line_safe`](crate::Converter::line_safe) can't be combined with
+    /// [`pretty`](crate::Converter::pretty), which adds line breaks inside of
+    /// comments
+    LineSafeWithPretty,
+
+    /// A [`line_wrapper`](crate::Converter::line_wrapper) can't be combined
+    /// with [`pretty`](crate::Converter::pretty), which adds line breaks
+    /// inside of comments
+    LineWrapperWithPretty,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidVarPrefix { prefix } => {
+                write!(f, "Invalid CSS variable prefix: {:?}", prefix)
+            }
+            Self::IgnoredVarPrefix => f.write_str(
+                "The `four_bit_var_prefix` is ignored with `var_names`, `skip_css_vars` or \
+                `four_bit_classes`",
+            ),
+            Self::IgnoredVarNames => f.write_str(
+                "The `var_names` are ignored with `skip_css_vars` or `four_bit_classes`",
+            ),
+            Self::InvalidClassPrefix { prefix } => {
+                write!(f, "Invalid CSS class prefix: {:?}", prefix)
+            }
+            Self::IgnoredSkipCssVars => {
+                f.write_str("The `skip_css_vars` option is ignored with `four_bit_classes`")
+            }
+            Self::SanitizeWithoutSkipEscape => {
+                f.write_str("The `sanitize` option has no effect without `skip_escape`")
+            }
+            Self::AllowedTagsWithoutSanitize => {
+                f.write_str("The `allowed_tags` have no effect without `sanitize`")
+            }
+            Self::LinkSchemesWithoutRenderLinks => {
+                f.write_str("The `link_schemes` have no effect without `render_links`")
+            }
+            Self::SkipLightDarkWithoutDualTheme => {
+                f.write_str("The `skip_light_dark` option has no effect without `dual_theme`")
+            }
+            Self::AdaptWithoutTheme => f.write_str(
+                "The `adapt_to_theme` option has no effect without `palette` or `dual_theme`",
+            ),
+            Self::IgnoredPalette => f.write_str("The `palette` is ignored with `dual_theme`"),
+            Self::IgnoredTagStyle => f.write_str("The `tag_style` is ignored with `flat_spans`"),
+            Self::DetectDoubleEscapeWithSkipEscape => {
+                f.write_str("The `detect_double_escape` option has no effect with `skip_escape`")
+            }
+            Self::LineSafeWithPretty => {
+                f.write_str("The `line_safe` option can't be combined with `pretty`")
+            }
+            Self::LineWrapperWithPretty => {
+                f.write_str("The `line_wrapper` can't be combined with `pretty`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 impl Error {
+    /// Returns the byte range of the invalid escape sequence in the input.
+    ///
+    /// With [`Converter::convert_append`](crate::Converter::convert_append),
+    /// the range is relative to the `new_input`. If the sequence started in the
+    /// previous input, the range starts at 0.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let error = ansi_to_html::convert("ok \x1b[1;38;7mbroken").unwrap_err();
+    /// assert_eq!(error.span(), Some(3..12));
+    /// assert_eq!(error.sequence(), Some("\x1b[1;38;7m"));
+    /// ```
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Self::ParseInt { span, .. } | Self::InvalidAnsi { span, .. } => span.clone(),
+            Self::InvalidVarName { .. } | Self::InvalidLineWrapper { .. } | Self::Write { .. } => {
+                None
+            }
+        }
+    }
+
+    /// Returns the invalid escape sequence, e.g. `"\x1b[38;7m"`
+    pub fn sequence(&self) -> Option<&str> {
+        match self {
+            Self::ParseInt { sequence, .. } | Self::InvalidAnsi { sequence, .. } => {
+                Some(sequence).filter(|s| !s.is_empty()).map(String::as_str)
+            }
+            Self::InvalidVarName { .. } | Self::InvalidLineWrapper { .. } | Self::Write { .. } => {
+                None
+            }
+        }
+    }
+
+    pub(crate) fn invalid(msg: String) -> Self {
+        Error::InvalidAnsi {
+            msg,
+            sequence: String::new(),
+            span: None,
+        }
+    }
+
     pub(crate) fn invalid_ansi(s: &'static str) -> impl Fn() -> Self {
-        move || Error::InvalidAnsi { msg: s.to_string() }
+        move || Error::invalid(s.to_string())
+    }
+
+    /// Sets the escape sequence in which the error occurred
+    pub(crate) fn in_sequence(mut self, new: &str) -> Self {
+        if let Self::ParseInt { sequence, .. } | Self::InvalidAnsi { sequence, .. } = &mut self {
+            *sequence = new.to_string();
+        }
+        self
+    }
+
+    /// Converts the span in the input with replaced invalid UTF-8 (see
+    /// [`Converter::convert_bytes`](crate::Converter::convert_bytes)) to the
+    /// span in the bytes. Every invalid sequence of bytes was replaced with
+    /// `U+FFFD`, which is 3 bytes long.
+    pub(crate) fn in_bytes(mut self, input: &[u8]) -> Self {
+        let offset = |pos: usize| {
+            let (mut text_pos, mut byte_pos) = (0, 0);
+            for chunk in input.utf8_chunks() {
+                let valid = chunk.valid().len();
+                if pos <= text_pos + valid {
+                    return byte_pos + pos - text_pos;
+                }
+                text_pos += valid + char::REPLACEMENT_CHARACTER.len_utf8();
+                byte_pos += valid + chunk.invalid().len();
+            }
+            byte_pos
+        };
+        if let Self::ParseInt { span, .. } | Self::InvalidAnsi { span, .. } = &mut self {
+            if let Some(range) = span {
+                *span = Some(offset(range.start)..offset(range.end));
+            }
+        }
+        self
+    }
+
+    /// Sets the span to the first occurrence of the sequence in the input. The
+    /// input may differ from the converted text (e.g. it isn't escaped yet),
+    /// but the escape sequences are the same, and the first occurrence is the
+    /// one that failed. The first `skip` bytes of the input don't count.
+    pub(crate) fn locate(mut self, input: &str, skip: usize) -> Self {
+        if let Self::ParseInt { sequence, span, .. } | Self::InvalidAnsi { sequence, span, .. } =
+            &mut self
+        {
+            let mut pos = 0;
+            while let Some(range) = find_sequence(&input[pos..]) {
+                let range = pos + range.start..pos + range.end;
+                if input[range.clone()] == **sequence {
+                    // the sequence may be part of an entity that was cut off before
+                    *span = Some(range.start.saturating_sub(skip)..range.end.saturating_sub(skip));
+                    break;
+                }
+                pos = range.end;
+            }
+        }
+        self
     }
 }
@@ -45,3 +45,81 @@ impl<T: AsRef<str>> fmt::Display for Esc<T> {
         Ok(())
     }
 }
+
+/// A formatting wrapper for escaping a string used as an HTML attribute value.
+///
+/// This escapes the same characters as [`Esc`]. In addition, control characters
+/// (including `\n` and `\t`) are written as numeric character references, so
+/// they survive the attribute value normalization of XML parsers, which would
+/// otherwise turn them into spaces.
+///
+/// ## Example
+///
+/// ```
+/// # use ansi_to_html::EscAttr;
+/// assert_eq!(&EscAttr("a'b\nc").to_string(), "a&#39;b&#10;c");
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EscAttr<T: AsRef<str>>(pub T);
+
+impl<T: AsRef<str>> fmt::Display for EscAttr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.as_ref().chars() {
+            if c.is_control() {
+                write!(f, "&#{};", c as u32)?;
+            } else {
+                fmt::Display::fmt(&Esc(c.encode_utf8(&mut [0; 4])), f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A formatting wrapper for escaping a URL used as the value of an `href` or
+/// `src` attribute.
+///
+/// The `Display` implementation
+///   - percent-encodes spaces, quotes, `<`, `>`, `` ` `` and control characters
+///   - percent-encodes `%` unless it already starts a percent-escape like `%20`
+///   - replaces `&` with `&amp;`
+///
+/// All other characters, including non-ASCII characters, are kept. The result
+/// can be put in a quoted attribute value. Note that this doesn't check the URL
+/// scheme; use [`UrlSchemes`](crate::UrlSchemes) to reject `javascript:` URLs.
+///
+/// ## Example
+///
+/// ```
+/// # use ansi_to_html::EscUrl;
+/// assert_eq!(
+///     &EscUrl("https://example.com/a b?x='1'&y=%20").to_string(),
+///     "https://example.com/a%20b?x=%271%27&amp;y=%20",
+/// );
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EscUrl<T: AsRef<str>>(pub T);
+
+impl<T: AsRef<str>> fmt::Display for EscUrl<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = self.0.as_ref();
+        for (i, c) in s.char_indices() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '%' if is_percent_escape(&s[i..]) => f.write_str("%")?,
+                '%' | ' ' | '"' | '\'' | '<' | '>' | '`' => write!(f, "%{:02X}", c as u32)?,
+                c if c.is_control() => {
+                    for b in c.encode_utf8(&mut [0; 4]).bytes() {
+                        write!(f, "%{:02X}", b)?;
+                    }
+                }
+                c => fmt::Display::fmt(&c, f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_percent_escape(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() >= 3 && b[1].is_ascii_hexdigit() && b[2].is_ascii_hexdigit()
+}
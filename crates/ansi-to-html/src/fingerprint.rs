@@ -0,0 +1,68 @@
+//! A hash of the styled text, see
+//! [`Converter::fingerprint`](crate::Converter::fingerprint).
+
+use crate::{SgrColor, StyledSegment};
+
+/// The 64-bit FNV-1a hash. Unlike `std`'s `DefaultHasher`, its output is
+/// specified, so it's the same on every platform, in every process and with
+/// every Rust version.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    /// Writes the length before the bytes, so the end of a string can't be
+    /// confused with the start of the next value
+    fn write_str(&mut self, s: &str) {
+        self.write(&(s.len() as u64).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+
+    fn write_color(&mut self, color: Option<SgrColor>) {
+        match color {
+            None => self.write(&[0]),
+            Some(SgrColor::Basic(n)) => self.write(&[1, n]),
+            Some(SgrColor::Indexed(n)) => self.write(&[2, n]),
+            Some(SgrColor::Rgb(r, g, b)) => self.write(&[3, r, g, b]),
+        }
+    }
+}
+
+/// Hashes the segments. Since adjacent text with the same styling is always
+/// in one segment, inputs with the same text and styling have equal segments.
+pub(crate) fn fingerprint(segments: &[StyledSegment]) -> u64 {
+    let mut hasher = Fnv1a(Fnv1a::OFFSET_BASIS);
+    for segment in segments {
+        hasher.write_str(&segment.text);
+        let flags = [
+            segment.bold,
+            segment.faint,
+            segment.italic,
+            segment.underline,
+            segment.overline,
+            segment.crossed_out,
+            segment.blink,
+            segment.reverse,
+            segment.concealed,
+        ];
+        let flags = flags.iter().rev().fold(0u16, |acc, &f| acc << 1 | f as u16);
+        hasher.write(&flags.to_le_bytes());
+        hasher.write_color(segment.foreground);
+        hasher.write_color(segment.background);
+        match &segment.link {
+            Some(link) => {
+                hasher.write(&[1]);
+                hasher.write_str(link);
+            }
+            None => hasher.write(&[0]),
+        }
+    }
+    hasher.0
+}
@@ -0,0 +1,192 @@
+//! A line whose text is overwritten after a carriage return and erased by
+//! `ESC[K`, see
+//! [`Converter::carriage_return_overwrites`](crate::Converter::carriage_return_overwrites).
+
+use std::ops::Range;
+
+use crate::{combining, html::CurrentStyling};
+
+/// A line of styled cells, like a line of a terminal. Every cell contains one
+/// character, with the characters that extend it (e.g. combining accents). An
+/// HTML entity or tag is a single cell as well.
+#[derive(Debug, Default)]
+pub(super) struct Line {
+    /// The text of all cells, including the text that was overwritten
+    text: String,
+    /// The range of every cell in `text`, and the index of its styling
+    cells: Vec<(Range<usize>, usize)>,
+    stylings: Vec<CurrentStyling>,
+    /// The index of the next cell that is written, which may be after the
+    /// end of the line
+    cursor: usize,
+    /// Whether the last character was a `\r`, which only moves the cursor to
+    /// the start of the line if it isn't followed by a line break
+    carriage_return: bool,
+}
+
+impl Line {
+    /// Writes the text at the cursor, overwriting the cells after it
+    pub(super) fn push(&mut self, styling: &CurrentStyling, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.column();
+        if self.cells.len() < self.cursor {
+            let blank = self.blank();
+            self.cells.resize(self.cursor, blank);
+        }
+        if self.stylings.last() != Some(styling) {
+            self.stylings.push(styling.clone());
+        }
+        let styling = self.stylings.len() - 1;
+
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let len = cell_len(rest);
+            let (cell, after) = rest.split_at(len);
+            rest = after;
+
+            let extends = self.cursor > 0 && {
+                let (prev, _) = &self.cells[self.cursor - 1];
+                let prev = self.text[prev.clone()].chars().next_back();
+                prev.is_some_and(|prev| combining::extends(prev, c))
+            };
+            if extends {
+                // the extending characters are appended to the previous cell
+                let (prev, _) = self.cells[self.cursor - 1].clone();
+                if prev.end != self.text.len() {
+                    self.text.extend_from_within(prev.clone());
+                }
+                let start = self.text.len() - prev.len();
+                self.text.push_str(cell);
+                self.cells[self.cursor - 1].0 = start..self.text.len();
+                continue;
+            }
+
+            let start = self.text.len();
+            self.text.push_str(cell);
+            let cell = (start..self.text.len(), styling);
+            match self.cells.get_mut(self.cursor) {
+                Some(old) => *old = cell,
+                None => self.cells.push(cell),
+            }
+            self.cursor += 1;
+        }
+    }
+
+    /// Moves the cursor to the start of the line, unless the next character
+    /// is a line break
+    pub(super) fn carriage_return(&mut self) {
+        self.carriage_return = true;
+    }
+
+    /// Returns whether the last character was a `\r`, and forgets it, so the
+    /// cursor doesn't move
+    pub(super) fn take_carriage_return(&mut self) -> bool {
+        std::mem::take(&mut self.carriage_return)
+    }
+
+    /// Returns the column of the cursor
+    pub(super) fn column(&mut self) -> usize {
+        if self.take_carriage_return() {
+            self.cursor = 0;
+        }
+        self.cursor
+    }
+
+    /// Moves the cursor to the column, which may be after the end of the line
+    pub(super) fn move_to(&mut self, column: usize) {
+        self.carriage_return = false;
+        self.cursor = column;
+    }
+
+    /// Erases a part of the line. The cursor doesn't move, so erased cells
+    /// before it become spaces without styling.
+    pub(super) fn erase(&mut self, erase: Erase) {
+        let cursor = self.column();
+        let blank_until = match erase {
+            Erase::ToEnd => 0,
+            // the cell at the cursor is erased as well
+            Erase::ToCursor => cursor + 1,
+            Erase::All => cursor,
+        };
+        if erase != Erase::ToCursor {
+            self.cells.truncate(cursor);
+        }
+        let blank_until = blank_until.min(self.cells.len());
+        if blank_until == 0 {
+            return;
+        }
+        let blank = self.blank();
+        self.cells[..blank_until].fill(blank);
+    }
+
+    /// Returns a cell containing a space without styling
+    fn blank(&mut self) -> (Range<usize>, usize) {
+        let start = self.text.len();
+        self.text.push(' ');
+        self.stylings.push(CurrentStyling::default());
+        (start..self.text.len(), self.stylings.len() - 1)
+    }
+
+    /// Returns the text of the line in runs with the same styling, and clears
+    /// it. The cursor moves to the start of the line.
+    pub(super) fn take(&mut self) -> Vec<(CurrentStyling, String)> {
+        let mut runs: Vec<(CurrentStyling, String)> = Vec::new();
+        for (range, styling) in self.cells.drain(..) {
+            let styling = &self.stylings[styling];
+            let text = &self.text[range];
+            match runs.last_mut() {
+                Some((last, run)) if last == styling => run.push_str(text),
+                _ => runs.push((styling.clone(), text.to_string())),
+            }
+        }
+        self.text.clear();
+        self.stylings.clear();
+        self.cursor = 0;
+        self.carriage_return = false;
+        runs
+    }
+}
+
+/// The part of the line that is erased by an `ESC[K` sequence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Erase {
+    /// `ESC[K` or `ESC[0K`
+    ToEnd,
+    /// `ESC[1K`
+    ToCursor,
+    /// `ESC[2K`
+    All,
+}
+
+impl Erase {
+    /// Parses an erase-in-line sequence, e.g. `ESC[2K`
+    pub(super) fn parse(sequence: &str) -> Option<Self> {
+        let param = sequence.strip_prefix("\x1b[")?.strip_suffix('K')?;
+        match param {
+            "" | "0" => Some(Erase::ToEnd),
+            "1" => Some(Erase::ToCursor),
+            "2" => Some(Erase::All),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the length of the cell at the start of the text, which isn't empty:
+/// an HTML entity, an HTML tag, or a character
+fn cell_len(text: &str) -> usize {
+    let markup_len = |start: char, end: char, max_len: usize| {
+        let rest = text.strip_prefix(start)?;
+        let len = rest.find(end).filter(|&len| len <= max_len)?;
+        let is_entity = start == '&';
+        let valid = !is_entity
+            || rest[..len]
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'#');
+        (valid && len > 0).then_some(len + 2)
+    };
+    markup_len('&', ';', 32)
+        .or_else(|| markup_len('<', '>', usize::MAX))
+        .unwrap_or_else(|| text.chars().next().map_or(0, char::len_utf8))
+}
@@ -1,26 +1,57 @@
-use crate::{html::AnsiConverter, Ansi, Color};
+use std::sync::Arc;
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-struct CurrentStyling {
+use crate::{
+    color::RgbColor,
+    combining,
+    html::{
+        default_colors, is_default_color,
+        line::Erase,
+        screen::{self, CursorMove, Screen},
+        AnsiConverter, Buffers, Style,
+    },
+    Ansi, Color, Converter, LineWrapper, OutputCompat, StyledSegment,
+};
+
+/// The effective styling at some point in the input
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct CurrentStyling {
+    /// The target of the OSC 8 hyperlink, if links are rendered
+    pub(crate) link: Option<Arc<str>>,
     fg: Option<Color>,
     bg: Option<Color>,
     bold: bool,
     faint: bool,
     italic: bool,
     underline: bool,
+    overline: bool,
     crossed_out: bool,
+    /// Slow and rapid blinking are rendered the same
+    blink: bool,
+    /// Reverse video, which swaps the text and background colors when they are rendered
+    reverse: bool,
+    concealed: bool,
 }
 
 impl CurrentStyling {
-    fn apply(&mut self, ansi: Ansi) {
+    pub(crate) fn apply(&mut self, ansi: Ansi) {
         match ansi {
             Ansi::Noop => {}
-            Ansi::Reset => *self = Self::default(),
+            // hyperlinks aren't SGR attributes, so they aren't reset
+            Ansi::Reset => {
+                *self = Self {
+                    link: self.link.take(),
+                    ..Self::default()
+                }
+            }
             Ansi::Bold => self.bold = true,
             Ansi::Faint => self.faint = true,
             Ansi::Italic => self.italic = true,
             Ansi::Underline => self.underline = true,
+            Ansi::Overline => self.overline = true,
             Ansi::CrossedOut => self.crossed_out = true,
+            Ansi::Blink | Ansi::RapidBlink => self.blink = true,
+            Ansi::Reverse => self.reverse = true,
+            Ansi::Conceal => self.concealed = true,
             Ansi::BoldOff => self.bold = false,
             Ansi::BoldAndFaintOff => {
                 self.bold = false;
@@ -28,13 +59,156 @@ impl CurrentStyling {
             }
             Ansi::ItalicOff => self.italic = false,
             Ansi::UnderlineOff => self.underline = false,
+            Ansi::OverlineOff => self.overline = false,
             Ansi::CrossedOutOff => self.crossed_out = false,
+            Ansi::BlinkOff => self.blink = false,
+            Ansi::ReverseOff => self.reverse = false,
+            Ansi::ConcealOff => self.concealed = false,
             Ansi::ForgroundColor(c) => self.fg = Some(c),
             Ansi::DefaultForegroundColor => self.fg = None,
             Ansi::BackgroundColor(c) => self.bg = Some(c),
             Ansi::DefaultBackgroundColor => self.bg = None,
         }
     }
+
+    /// Returns the ANSI codes that turn the default styling into this styling,
+    /// except for the link
+    pub(crate) fn to_ansi_codes(&self) -> impl Iterator<Item = Ansi> {
+        let flags = [
+            (self.bold, Ansi::Bold),
+            (self.faint, Ansi::Faint),
+            (self.italic, Ansi::Italic),
+            (self.underline, Ansi::Underline),
+            (self.overline, Ansi::Overline),
+            (self.crossed_out, Ansi::CrossedOut),
+            (self.blink, Ansi::Blink),
+            (self.reverse, Ansi::Reverse),
+            (self.concealed, Ansi::Conceal),
+        ];
+        let flags = flags
+            .into_iter()
+            .filter(|&(on, _)| on)
+            .map(|(_, code)| code);
+        let fg = self.fg.map(Ansi::ForgroundColor);
+        let bg = self.bg.map(Ansi::BackgroundColor);
+        flags.chain(fg).chain(bg)
+    }
+
+    /// Returns the text with this styling, see
+    /// [`Converter::to_segments`](crate::Converter::to_segments)
+    pub(crate) fn to_segment(&self, text: String) -> StyledSegment {
+        StyledSegment {
+            text,
+            bold: self.bold,
+            faint: self.faint,
+            italic: self.italic,
+            underline: self.underline,
+            overline: self.overline,
+            crossed_out: self.crossed_out,
+            blink: self.blink,
+            reverse: self.reverse,
+            concealed: self.concealed,
+            foreground: self.fg.map(Color::to_sgr_color),
+            background: self.bg.map(Color::to_sgr_color),
+            link: self.link.as_deref().map(str::to_owned),
+        }
+    }
+
+    /// Returns the styling of spaces and line breaks at the end of a line.
+    /// Unless there is a background color, only the lines (underline, overline
+    /// and crossing out) are visible.
+    fn visible_on_spaces(&self) -> Self {
+        match self.has_background() {
+            true => self.clone(),
+            false => Self {
+                link: self.link.clone(),
+                underline: self.underline,
+                overline: self.overline,
+                crossed_out: self.crossed_out,
+                ..Self::default()
+            },
+        }
+    }
+
+    /// Returns whether a background color is rendered. With reverse video, the text color (or
+    /// the default text color) is the background color.
+    fn has_background(&self) -> bool {
+        self.bg.is_some() || self.reverse
+    }
+
+    /// Returns the active styles, in the order in which their tags are nested:
+    ///
+    /// `<b>`, faint `<span>`, `<i>`, `<u>`, overline `<span>`, `<s>`, blink `<span>`,
+    /// color `<span>`, background `<span>`, concealed `<span>`
+    ///
+    /// The link isn't included; its `<a>` tag is outside of all other tags. Concealed text is
+    /// innermost, so no color of a nested tag makes it visible. With reverse video, the colors
+    /// are swapped, and the [default colors](default_colors) are used for those that aren't set.
+    pub(super) fn styles(&self, converter: &Converter) -> impl Iterator<Item = Style> {
+        let flags = [
+            (self.bold, Style::Bold),
+            (self.faint, Style::Faint),
+            (self.italic, Style::Italic),
+            (self.underline, Style::Underline),
+            (self.overline, Style::Overline),
+            (self.crossed_out, Style::CrossedOut),
+            (self.blink, Style::Blink),
+        ];
+        let flags = flags
+            .into_iter()
+            .filter(|&(on, _)| on)
+            .map(|(_, style)| style);
+        let (fg, bg) = match self.reverse {
+            true => {
+                let [default_fg, default_bg] =
+                    default_colors(converter).map(|c| Color::Rgb(RgbColor::from(c)));
+                (
+                    Some(self.bg.unwrap_or(default_bg)),
+                    Some(self.fg.unwrap_or(default_fg)),
+                )
+            }
+            false => (self.fg, self.bg),
+        };
+        let fg = fg.map(Style::ForegroundColor);
+        let bg = bg.map(Style::BackgroundColor);
+        let concealed = self.concealed.then_some(Style::Concealed);
+        flags.chain(fg).chain(bg).chain(concealed)
+    }
+
+    /// Returns whether the text with this styling is concealed, see
+    /// [`Converter::redact_concealed`]
+    pub(crate) fn is_concealed(&self) -> bool {
+        self.concealed
+    }
+}
+
+/// The state at the end of some converted input, from which the conversion of
+/// the following input starts
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Position {
+    /// The styling at the end of the input
+    pub(crate) styling: CurrentStyling,
+    /// The number of line breaks, if the lines are numbered, see [`Minifier::lines`]
+    pub(crate) lines: usize,
+}
+
+/// The state of a [`ConverterStream`](crate::ConverterStream) between two chunks
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StreamState {
+    /// The styling at the end of the converted input
+    styling: CurrentStyling,
+    /// The styling of the tags that are still open in the output
+    open: CurrentStyling,
+    /// See [`Minifier::lines`]
+    lines: usize,
+    /// Whether the wrapper of the current line is open
+    line_open: bool,
+    /// Spaces that may be trailing, see [`Minifier::pending_spaces`]
+    pending_spaces: Vec<(CurrentStyling, usize)>,
+    /// See [`Minifier::pending_whitespace`]
+    pending_whitespace: Vec<(CurrentStyling, String)>,
+    /// The last character that was written, see [`Minifier::last_char`]
+    last_char: Option<char>,
 }
 
 /// Basic minifier that avoids reapplying the same style to consecutive runs of text
@@ -43,19 +217,125 @@ impl CurrentStyling {
 /// Blue - "foo" - Reset, Blue - "bar" - Reset
 /// becomes
 /// Blue - "foo" - "bar" - Reset
-#[derive(Debug, Default)]
-pub(crate) struct Minifier {
+///
+/// With [`Converter::normalize_trailing_whitespace`], spaces at the end of a text are held back
+/// until the next text shows whether they are at the end of a line.
+///
+/// Spaces and line breaks at the end of a text are held back until the next text shows whether
+/// they are at the end of the input. At the end, the line breaks are written after the tags are
+/// closed, and the spaces only with the styles that are visible on them. This is skipped with
+/// [`OutputCompat::V0`].
+///
+/// With [`Converter::attach_combining`], characters that extend the preceding character (e.g.
+/// combining accents) are written with its styling, so the new styling starts after them.
+///
+/// With [`Converter::carriage_return_overwrites`], the text of a line is held back until the line
+/// ends, since a carriage return moves back to its start and the text after it overwrites it. With
+/// [`Converter::emulate_cursor_movement`], the last lines are held back, since the cursor can move
+/// up to them.
+///
+/// With [`Converter::elide_default_colors`], colors that look like the default colors are treated
+/// like the default colors, so they don't get a tag.
+///
+/// With [`Converter::line_safe`], all tags are closed before a line break. They are opened again
+/// with the next text, like after a change of the styling. With [`Converter::line_wrapper`], the
+/// wrapper of a line is opened before its first text or line break, and closed before the line
+/// break.
+#[derive(Debug)]
+pub(crate) struct Minifier<'a> {
     code_buffer: Vec<Ansi>,
     current_styling: CurrentStyling,
-    converter: AnsiConverter,
+    /// The styling of the tags that are currently open
+    written_styling: CurrentStyling,
+    /// Spaces that may be trailing, with the styling they were printed with
+    pending_spaces: Vec<(CurrentStyling, usize)>,
+    normalize_trailing_whitespace: bool,
+    /// Spaces and line breaks that may be at the end of the input, with the styling they were
+    /// printed with
+    pending_whitespace: Vec<(CurrentStyling, String)>,
+    /// Whether whitespace at the end of the input is held back, i.e. not with [`OutputCompat::V0`]
+    hold_trailing_whitespace: bool,
+    /// The last character that was written, if the next one may be attached to it
+    last_char: Option<char>,
+    attach_combining: bool,
+    line_safe: bool,
+    line_wrapper: Option<&'a LineWrapper>,
+    /// The converter, if colors that look like the default colors are elided
+    elide_default_colors: Option<&'a Converter>,
+    /// Whether reverse video is ignored like in the 0.x versions, see [`OutputCompat::V0`]
+    ignore_reverse: bool,
+    /// The number of line breaks written so far, if the lines are numbered
+    lines: usize,
+    /// Whether the wrapper of the current line is open
+    line_open: bool,
+    /// The lines that may still be overwritten, if carriage returns overwrite text
+    screen: Option<Screen>,
+    converter: AnsiConverter<'a>,
 }
 
-impl Minifier {
-    pub(crate) fn new(var_prefix: Option<String>) -> Self {
+impl<'a> Minifier<'a> {
+    /// Creates a minifier that appends the html to `buf`
+    pub(crate) fn new(converter: &'a Converter, buf: String) -> Self {
+        Self::with_buffers(converter, buf, Buffers::default())
+    }
+
+    /// Like [`Minifier::new`], but reuses the allocations of a previous conversion
+    pub(crate) fn with_buffers(
+        converter: &'a Converter,
+        buf: String,
+        mut buffers: Buffers,
+    ) -> Self {
+        buffers.clear();
         Self {
-            converter: AnsiConverter::new(var_prefix),
-            ..Self::default()
+            code_buffer: std::mem::take(&mut buffers.code_buffer),
+            current_styling: CurrentStyling::default(),
+            written_styling: CurrentStyling::default(),
+            pending_spaces: Vec::new(),
+            normalize_trailing_whitespace: converter.normalize_trailing_whitespace,
+            pending_whitespace: Vec::new(),
+            hold_trailing_whitespace: converter.output_compat != OutputCompat::V0,
+            last_char: None,
+            attach_combining: converter.attach_combining,
+            line_safe: converter.line_safe || converter.line_wrapper.is_some(),
+            line_wrapper: converter.line_wrapper.as_ref(),
+            elide_default_colors: converter.elide_default_colors.then_some(converter),
+            ignore_reverse: converter.output_compat == OutputCompat::V0,
+            lines: 0,
+            line_open: false,
+            screen: match converter.emulate_cursor_movement {
+                true => Some(Screen::new(screen::ROWS)),
+                false => converter.carriage_return_overwrites.then(|| Screen::new(1)),
+            },
+            converter: AnsiConverter::new(converter, buf, buffers),
+        }
+    }
+
+    /// Creates a minifier that continues a stream, whose open tags were written before
+    pub(crate) fn resume(converter: &'a Converter, buf: String, state: StreamState) -> Self {
+        let mut minifier = Self::new(converter, buf);
+        minifier.converter.assume_open(&state.open);
+        minifier.current_styling = state.styling;
+        minifier.written_styling = state.open;
+        minifier.pending_spaces = state.pending_spaces;
+        minifier.pending_whitespace = state.pending_whitespace;
+        minifier.last_char = state.last_char;
+        minifier.lines = state.lines;
+        minifier.line_open = state.line_open;
+        minifier
+    }
+
+    /// Starts the conversion at the given position, i.e. with its styling and line number
+    pub(crate) fn start_at(&mut self, position: Position) {
+        for ansi in position.styling.to_ansi_codes() {
+            self.push_ansi_code(ansi);
         }
+        self.set_link(position.styling.link);
+        self.lines = position.lines;
+    }
+
+    /// Returns the number of line breaks written so far, if the lines are numbered
+    pub(crate) fn lines(&self) -> usize {
+        self.lines
     }
 
     pub fn clear_styles(&mut self) {
@@ -63,30 +343,393 @@ impl Minifier {
     }
 
     pub fn push_ansi_code(&mut self, ansi: Ansi) {
+        if self.ignore_reverse && matches!(ansi, Ansi::Reverse | Ansi::ReverseOff) {
+            return;
+        }
+        let ansi = match (ansi, self.elide_default_colors) {
+            (Ansi::ForgroundColor(c), Some(converter)) if is_default_color(converter, c, true) => {
+                Ansi::DefaultForegroundColor
+            }
+            (Ansi::BackgroundColor(c), Some(converter))
+                if is_default_color(converter, c, false) =>
+            {
+                Ansi::DefaultBackgroundColor
+            }
+            _ => ansi,
+        };
         self.code_buffer.push(ansi);
     }
 
-    /// Apply buffered ansi codes while ignoring ansi codes that repeat the previously used style
+    /// Apply buffered ansi codes to the current styling
     fn apply_ansi_codes(&mut self) {
-        let prev_styling = self.current_styling;
         for &code in &self.code_buffer {
             self.current_styling.apply(code);
         }
-        if prev_styling != self.current_styling {
-            for &code in &self.code_buffer {
-                self.converter.consume_ansi_code(code);
-            }
-        }
         self.code_buffer.clear();
     }
 
+    /// Returns the styling after applying all buffered ansi codes
+    pub fn styling(&self) -> CurrentStyling {
+        let mut styling = self.current_styling.clone();
+        for &code in &self.code_buffer {
+            styling.apply(code);
+        }
+        styling
+    }
+
     pub fn push_str(&mut self, text: &str) {
         self.apply_ansi_codes();
-        self.converter.push_str(text);
+        let Some(screen) = &mut self.screen else {
+            self.push_styled(text);
+            return;
+        };
+        let mut rest = text;
+        while let Some(i) = rest.find(['\r', '\n']) {
+            screen.push(&self.current_styling, &rest[..i]);
+            if rest.as_bytes()[i] == b'\r' {
+                screen.carriage_return();
+            } else {
+                screen.line_break(&self.current_styling);
+            }
+            rest = &rest[i + 1..];
+        }
+        screen.push(&self.current_styling, rest);
+        self.flush_scrolled();
     }
 
-    pub fn into_html(mut self) -> String {
+    /// Erases a part of the current line, see [`Line::erase`](super::line::Line::erase)
+    pub fn erase_line(&mut self, erase: Erase) {
+        if let Some(screen) = &mut self.screen {
+            screen.erase(erase);
+        }
+    }
+
+    /// Moves the cursor, see [`Screen::move_cursor`]
+    pub fn move_cursor(&mut self, movement: CursorMove) {
+        if let Some(screen) = &mut self.screen {
+            screen.move_cursor(movement);
+        }
+    }
+
+    /// Writes the lines that can no longer be overwritten, see [`Screen::pop_scrolled`]
+    fn flush_scrolled(&mut self) {
+        while let Some((runs, line_break)) = self.screen.as_mut().and_then(Screen::pop_scrolled) {
+            self.push_runs(
+                runs.into_iter()
+                    .chain([(line_break.0, line_break.1.into())]),
+            );
+        }
+    }
+
+    /// Writes the text of all lines that may still be overwritten, see [`Screen`]
+    fn flush_screen(&mut self) {
+        if let Some(screen) = &mut self.screen {
+            let runs = screen.take();
+            self.push_runs(runs);
+        }
+    }
+
+    /// Writes runs of text with their styling
+    fn push_runs(&mut self, runs: impl IntoIterator<Item = (CurrentStyling, String)>) {
+        for (styling, text) in runs {
+            let current = std::mem::replace(&mut self.current_styling, styling);
+            self.push_styled(&text);
+            self.current_styling = current;
+        }
+    }
+
+    /// Writes text with the current styling
+    fn push_styled(&mut self, text: &str) {
+        let text = self.push_attached(text);
+        if !self.normalize_trailing_whitespace {
+            self.write(self.current_styling.clone(), text);
+            return;
+        }
+
+        let mut rest = text;
+        while let Some(i) = rest.find('\n') {
+            self.push_line_part(&rest[..i]);
+            self.flush_spaces(true);
+            self.write(self.current_styling.visible_on_spaces(), "\n");
+            rest = &rest[i + 1..];
+        }
+        self.push_line_part(rest);
+    }
+
+    /// Writes the characters at the start of the text that extend the last written character with
+    /// its styling, if the styling changed. Returns the rest of the text.
+    fn push_attached<'t>(&mut self, text: &'t str) -> &'t str {
+        // held back whitespace was printed with its styling
+        let last_styling =
+            (self.pending_whitespace.last()).map_or(&self.written_styling, |(styling, _)| styling);
+        if !self.attach_combining || *last_styling == self.current_styling {
+            return text;
+        }
+        let Some(first) = text.chars().next() else {
+            return text;
+        };
+        // held back spaces were printed after the last written character
+        let last_char = match self.pending_spaces.is_empty() {
+            true => self.last_char,
+            false => Some(' '),
+        };
+        let Some(mut prev) = last_char.filter(|&prev| combining::extends(prev, first)) else {
+            return text;
+        };
+        // the spaces are followed by the attached characters, so they aren't trailing
+        self.flush_whitespace();
+        self.flush_spaces(false);
+        let len = text
+            .char_indices()
+            .find(|&(_, c)| !combining::extends(std::mem::replace(&mut prev, c), c))
+            .map_or(text.len(), |(i, _)| i);
+        if len > 0 {
+            self.write(self.written_styling.clone(), &text[..len]);
+        }
+        &text[len..]
+    }
+
+    /// Pushes text that doesn't contain a line break. Spaces at its end are held back.
+    fn push_line_part(&mut self, text: &str) {
+        let content = text.trim_end_matches(' ');
+        if !content.is_empty() {
+            self.flush_spaces(false);
+            self.write(self.current_styling.clone(), content);
+        }
+
+        let spaces = text.len() - content.len();
+        if spaces == 0 {
+            return;
+        }
+        if self.current_styling.has_background() {
+            // the background of trailing spaces is visible
+            self.flush_spaces(false);
+            self.write(self.current_styling.clone(), &text[content.len()..]);
+        } else {
+            match self.pending_spaces.last_mut() {
+                Some((styling, n)) if *styling == self.current_styling => *n += spaces,
+                _ => (self.pending_spaces).push((self.current_styling.clone(), spaces)),
+            }
+        }
+    }
+
+    /// Writes the spaces that were held back. If they are at the end of a line, only the styles
+    /// that are visible on spaces are applied.
+    fn flush_spaces(&mut self, trailing: bool) {
+        let mut pending = std::mem::take(&mut self.pending_spaces);
+        for (styling, n) in pending.drain(..) {
+            let styling = if trailing {
+                styling.visible_on_spaces()
+            } else {
+                styling
+            };
+            self.write(styling, &" ".repeat(n));
+        }
+        // keep the allocation
+        self.pending_spaces = pending;
+    }
+
+    /// Writes text with the given styling. Spaces and line breaks at its end may be held back, see
+    /// [`Minifier::flush_whitespace`].
+    fn write(&mut self, styling: CurrentStyling, text: &str) {
+        if let Some(c) = text.chars().next_back() {
+            self.last_char = Some(c);
+        }
+        // whitespace without styles is written the same at the end, unless it is a `\r` that may
+        // be followed by a `\n` with other styles
+        let unstyled = styling == CurrentStyling::default()
+            && self.pending_whitespace.is_empty()
+            && !text.ends_with('\r');
+        if !self.hold_trailing_whitespace || unstyled {
+            self.write_now(styling, text);
+            return;
+        }
+        let content = text.trim_end_matches([' ', '\r', '\n']);
+        let whitespace = &text[content.len()..];
+        if !content.is_empty() {
+            self.flush_whitespace();
+            self.write_now(styling.clone(), content);
+        } else if text.is_empty() && self.pending_whitespace.is_empty() {
+            self.write_now(styling.clone(), text);
+        }
+        if !whitespace.is_empty() {
+            // they aren't merged, since a `\r` and a `\n` written separately aren't a `\r\n`
+            (self.pending_whitespace).push((styling, whitespace.to_owned()));
+        }
+    }
+
+    /// Writes the whitespace that was held back with the styling it was printed with
+    fn flush_whitespace(&mut self) {
+        let mut pending = std::mem::take(&mut self.pending_whitespace);
+        for (styling, whitespace) in pending.drain(..) {
+            self.write_now(styling, &whitespace);
+        }
+        // keep the allocation
+        self.pending_whitespace = pending;
+    }
+
+    /// Writes the whitespace that was held back at the end of the input. The line breaks are
+    /// written with the current styling, and the spaces only with the styles that are visible on
+    /// them.
+    fn flush_trailing_whitespace(&mut self) {
+        let mut pending = std::mem::take(&mut self.pending_whitespace);
+        // the runs of spaces or line breaks, with the styling they are written with
+        let mut runs: Vec<(CurrentStyling, &str)> = Vec::new();
+        let mut printed: Option<&CurrentStyling> = None;
+        for (styling, whitespace) in &pending {
+            let mut rest = whitespace.as_str();
+            while !rest.is_empty() {
+                let spaces = rest.len() - rest.trim_start_matches(' ').len();
+                let (mut written, len) = match spaces {
+                    0 => {
+                        let line_breaks = rest.trim_start_matches(['\r', '\n']);
+                        (self.current_styling.clone(), rest.len() - line_breaks.len())
+                    }
+                    n => (styling.visible_on_spaces(), n),
+                };
+                // a `\r` and a `\n` printed with different styling are two line breaks, so
+                // they stay separated by a tag
+                let prev = runs.last_mut().filter(|(prev, text)| {
+                    *prev == written && text.ends_with('\r') && rest.starts_with('\n')
+                });
+                if let Some((prev, _)) = prev.filter(|_| printed != Some(styling)) {
+                    match written == *styling {
+                        true => *prev = printed.unwrap().clone(),
+                        false => written = styling.clone(),
+                    }
+                }
+                runs.push((written, &rest[..len]));
+                printed = Some(styling);
+                rest = &rest[len..];
+            }
+        }
+        for (styling, text) in runs {
+            self.write_now(styling, text);
+        }
+        pending.clear();
+        // keep the allocation
+        self.pending_whitespace = pending;
+    }
+
+    /// Writes text with the given styling, changing the open tags only if necessary
+    fn write_now(&mut self, styling: CurrentStyling, text: &str) {
+        let mut rest = text;
+        if self.line_safe {
+            while let Some(i) = rest.find('\n') {
+                // a `\r` before the line break is written after the tags as well
+                let end = rest[..i].strip_suffix('\r').map_or(i, str::len);
+                // an empty line is wrapped as well
+                self.open_line();
+                if end > 0 {
+                    self.set_styling(styling.clone());
+                    self.converter.push_str(&rest[..end]);
+                }
+                self.set_styling(CurrentStyling::default());
+                self.close_line();
+                if self.line_wrapper.is_some_and(LineWrapper::data_line) {
+                    self.lines += 1;
+                }
+                self.converter.push_raw(&rest[end..=i]);
+                rest = &rest[i + 1..];
+            }
+            // the tags are opened again with the next text
+            if rest.is_empty() {
+                return;
+            }
+            self.open_line();
+        }
+        self.set_styling(styling);
+        self.converter.push_str(rest);
+    }
+
+    /// Opens the wrapper of the current line, unless it is open already
+    fn open_line(&mut self) {
+        if let Some(wrapper) = self.line_wrapper.filter(|_| !self.line_open) {
+            self.converter
+                .push_tags(&wrapper.opening_tag(self.lines + 1));
+            self.line_open = true;
+        }
+    }
+
+    /// Closes the wrapper of the current line. The tags in it must be closed already.
+    fn close_line(&mut self) {
+        if let Some(wrapper) = self.line_wrapper.filter(|_| self.line_open) {
+            self.converter.push_tags(&wrapper.closing_tag());
+            self.line_open = false;
+        }
+    }
+
+    fn set_styling(&mut self, styling: CurrentStyling) {
+        if self.written_styling != styling {
+            self.converter.set_styling(&styling);
+            self.written_styling = styling;
+        }
+    }
+
+    /// Sets the target of the current hyperlink, or ends it with `None`
+    pub fn set_link(&mut self, link: Option<Arc<str>>) {
+        self.current_styling.link = link;
+    }
+
+    pub fn push_sequence(&mut self, sequence: &str) {
         self.apply_ansi_codes();
+        self.flush_screen();
+        self.flush_whitespace();
+        self.flush_spaces(false);
+        self.open_line();
+        match self.line_safe && sequence.contains('\n') {
+            true => self.set_styling(CurrentStyling::default()),
+            false => self.set_styling(self.current_styling.clone()),
+        }
+        self.converter.push_raw(sequence);
+        self.last_char = None;
+    }
+
+    /// Takes the html written so far, so it can be passed on while the conversion continues
+    pub(crate) fn take_html(&mut self) -> String {
+        self.converter.take_result()
+    }
+
+    /// Returns the html without closing the open tags, and the state to resume from
+    pub(crate) fn suspend(mut self) -> (String, StreamState) {
+        self.apply_ansi_codes();
+        self.flush_screen();
+        let state = StreamState {
+            styling: self.current_styling,
+            open: self.written_styling,
+            pending_spaces: self.pending_spaces,
+            pending_whitespace: self.pending_whitespace,
+            last_char: self.last_char,
+            lines: self.lines,
+            line_open: self.line_open,
+        };
+        (self.converter.result(), state)
+    }
+
+    /// Writes the text that was held back. The tags that are open are closed if the styling is
+    /// the default, e.g. after a reset, and so is the wrapper of the line. Whitespace at the end
+    /// is written with this styling, see [`Minifier::flush_trailing_whitespace`].
+    pub(crate) fn close(&mut self) {
+        self.apply_ansi_codes();
+        self.flush_screen();
+        self.flush_spaces(true);
+        self.flush_trailing_whitespace();
+        self.set_styling(self.current_styling.clone());
+        if self.written_styling == CurrentStyling::default() {
+            self.close_line();
+        }
+    }
+
+    pub fn into_html(mut self) -> String {
+        self.close();
         self.converter.result()
     }
+
+    /// Like [`Minifier::into_html`], but also returns the allocations that can be reused
+    pub(crate) fn into_parts(mut self) -> (String, Buffers) {
+        self.close();
+        let (html, mut buffers) = self.converter.into_parts();
+        buffers.code_buffer = self.code_buffer;
+        (html, buffers)
+    }
 }
@@ -1,8 +1,17 @@
-use regex::Regex;
+use std::{fmt, sync::Arc};
 
-use crate::{Ansi, AnsiIter, Color, Error};
+use crate::{
+    ansi::{find_sequence, is_aborted},
+    color::{CssVars, Themes},
+    transform::unescape,
+    Ansi, AnsiIter, Color, Converter, Error, EscAttr, EscUrl, OutputCompat, ThemePalette,
+};
 
+mod line;
 mod minifier;
+mod screen;
+
+pub(crate) use minifier::{CurrentStyling, Position, StreamState};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum Style {
@@ -10,162 +19,863 @@ enum Style {
     Faint,
     Italic,
     Underline,
+    Overline,
     CrossedOut,
+    Blink,
     ForegroundColor(Color),
     BackgroundColor(Color),
+    Concealed,
+}
+
+/// Returns how the CSS variables of the 4-bit colors are named, or `None` if
+/// the colors are hardcoded
+pub(crate) fn css_vars(converter: &Converter) -> Option<CssVars<'_>> {
+    if let Some(prefix) = &converter.four_bit_classes {
+        return Some(CssVars::Classes(prefix));
+    }
+    match (&converter.var_names, converter.skip_css_vars) {
+        (_, true) => None,
+        (Some(names), false) => Some(CssVars::Names(names)),
+        (None, false) => Some(CssVars::Prefix(
+            converter.four_bit_var_prefix.as_deref().unwrap_or_default(),
+        )),
+    }
+}
+
+/// Returns the light and dark theme, if the colors of the palette depend on the
+/// color scheme
+pub(crate) fn themes(converter: &Converter) -> Option<Themes<'_>> {
+    match (&converter.dual_theme, &converter.theme) {
+        (Some((light, dark)), _) => Some(Themes {
+            light,
+            dark,
+            light_dark: !converter.skip_light_dark,
+            adapt: converter.adapt_to_theme,
+            default_colors: converter.default_colors,
+        }),
+        // a single palette is a dual theme with the same colors in both themes
+        (None, Some(theme)) => Some(Themes {
+            light: theme,
+            dark: theme,
+            light_dark: false,
+            adapt: converter.adapt_to_theme,
+            default_colors: converter.default_colors,
+        }),
+        (None, None) => None,
+    }
+}
+
+/// Returns the color that is displayed, with the grays quantized and the 8-bit palette applied
+fn map_color(converter: &Converter, c: Color) -> Color {
+    let c = match converter.grayscale_levels {
+        Some(levels) => c.quantize_gray(levels),
+        None => c,
+    };
+    match &converter.eight_bit_palette {
+        Some(eight_bit) => c.with_eight_bit_palette(eight_bit),
+        None => c,
+    }
+}
+
+/// Returns the default text and background colors, see [`Converter::default_colors`]
+pub(crate) fn default_colors(converter: &Converter) -> [[u8; 3]; 2] {
+    if let Some(colors) = converter.default_colors {
+        return colors;
+    }
+    let theme = match &converter.dual_theme {
+        Some((_, dark)) => Some(dark),
+        None => converter.theme.as_ref(),
+    };
+    match (theme, converter.faint_blend) {
+        (Some(theme), _) => [theme.foreground, theme.background],
+        (None, Some(blend)) => [blend.foreground, blend.background],
+        (None, None) => {
+            let dark = ThemePalette::default_dark();
+            [dark.foreground, dark.background]
+        }
+    }
+}
+
+/// Returns whether the color is displayed like the default text color (or the background color),
+/// see [`Converter::elide_default_colors`]
+pub(crate) fn is_default_color(converter: &Converter, c: Color, is_fg: bool) -> bool {
+    let c = map_color(converter, c);
+    if let Some(themes) = themes(converter) {
+        return themes.is_default(c, is_fg);
+    }
+    let [fg, bg] = default_colors(converter);
+    c.to_rgb() == if is_fg { fg } else { bg }
 }
 
 impl Style {
-    fn apply(&self, buf: &mut String, var_prefix: Option<&str>) {
+    /// Pushes the opening tag. `active` are the styles that are already active.
+    fn apply(&self, buf: &mut String, converter: &Converter, active: &[Style]) {
+        let vars = css_vars(converter);
+        let themes = themes(converter);
+        let faint_blend = converter
+            .faint_blend
+            .filter(|_| matches!(self, Style::Faint) || active.contains(&Style::Faint));
+
         let s;
-        buf.push_str(match self {
-            Style::Bold => "<b>",
-            Style::Faint => "<span style='opacity:0.67'>",
-            Style::Italic => "<i>",
-            Style::Underline => "<u>",
-            Style::CrossedOut => "<s>",
-            Style::ForegroundColor(c) => {
-                s = c.into_opening_fg_span(var_prefix);
+        buf.push_str(match (self, faint_blend) {
+            (Style::Bold, _) => converter.tag_style.bold().0,
+            (Style::Faint, None) => "<span style='opacity:0.67'>",
+            (Style::Faint, Some(blend)) => {
+                let fg = active.iter().rev().find_map(|s| match s {
+                    Style::ForegroundColor(c) => Some(map_color(converter, *c)),
+                    _ => None,
+                });
+                s = match themes {
+                    Some(themes) => themes.blend_faint(fg).into_opening_fg_span(),
+                    None => {
+                        let fg = fg.map_or(blend.foreground, Color::to_rgb);
+                        Color::Rgb(blend.blend(fg)).into_opening_fg_span(vars, None)
+                    }
+                };
+                &s
+            }
+            (Style::Italic, _) => converter.tag_style.italic().0,
+            (Style::Underline, _) => "<u>",
+            // nested in `<u>`, so both lines are drawn
+            (Style::Overline, _) => "<span style='text-decoration:overline'>",
+            (Style::CrossedOut, _) => converter.tag_style.crossed_out().0,
+            (Style::Blink, _) => {
+                let prefix = converter.four_bit_var_prefix.as_deref().unwrap_or_default();
+                s = format!("<span class='{}blink'>", EscAttr(prefix));
                 &s
             }
-            Style::BackgroundColor(c) => {
-                s = c.into_opening_bg_span(var_prefix);
+            (Style::ForegroundColor(c), Some(blend)) => {
+                let c = map_color(converter, *c);
+                s = match themes {
+                    Some(themes) => themes.blend_faint(Some(c)).into_opening_fg_span(),
+                    None => Color::Rgb(blend.blend(c.to_rgb())).into_opening_fg_span(vars, None),
+                };
                 &s
             }
+            (Style::ForegroundColor(c), None) => {
+                let c = map_color(converter, *c);
+                match converter.palette.as_ref().and_then(|p| p.span(c, true)) {
+                    Some(span) => span,
+                    None => {
+                        s = c.into_opening_fg_span(vars, themes);
+                        &s
+                    }
+                }
+            }
+            (Style::Concealed, _) => "<span style='color:transparent'>",
+            (Style::BackgroundColor(c), _) => {
+                let c = map_color(converter, *c);
+                match converter.palette.as_ref().and_then(|p| p.span(c, false)) {
+                    Some(span) => span,
+                    None => {
+                        s = c.into_opening_bg_span(vars, themes);
+                        &s
+                    }
+                }
+            }
         });
     }
 
-    fn clear(&self, buf: &mut String) {
-        buf.push_str(match self {
-            Style::Bold => "</b>",
+    /// Returns the closing tag
+    fn closing_tag(&self, converter: &Converter) -> &'static str {
+        match self {
+            Style::Bold => converter.tag_style.bold().1,
             Style::Faint => "</span>",
-            Style::Italic => "</i>",
+            Style::Italic => converter.tag_style.italic().1,
             Style::Underline => "</u>",
-            Style::CrossedOut => "</s>",
+            Style::Overline => "</span>",
+            Style::CrossedOut => converter.tag_style.crossed_out().1,
+            Style::Blink => "</span>",
             Style::ForegroundColor(_) => "</span>",
             Style::BackgroundColor(_) => "</span>",
-        })
+            Style::Concealed => "</span>",
+        }
     }
 }
 
-/// Convert ANSI sequences to html. This does NOT escape html characters such as `<` and `&`.
-pub fn ansi_to_html(
-    mut input: &str,
-    ansi_regex: &Regex,
-    four_bit_var_prefix: Option<String>,
-) -> Result<String, Error> {
-    let mut minifier = minifier::Minifier::new(four_bit_var_prefix);
+/// Pushes a single opening `<span>` with all styles, see [`Converter::flat_spans`]. The styles
+/// are in the order of [`CurrentStyling::styles`], so a color that is applied later overrides an
+/// earlier one, like the color of a nested element. Overridden declarations are omitted.
+fn apply_flat(styles: &[Style], buf: &mut String, converter: &Converter) {
+    let mut declarations: Vec<String> = Vec::new();
+    let mut classes = Vec::new();
+    let mut lines = Vec::new();
+    let mut tag = String::new();
+    for (i, style) in styles.iter().enumerate() {
+        match style {
+            Style::Bold => declarations.push("font-weight:bold".to_string()),
+            Style::Italic => declarations.push("font-style:italic".to_string()),
+            Style::Underline => lines.push("underline"),
+            Style::Overline => lines.push("overline"),
+            Style::CrossedOut => lines.push("line-through"),
+            _ => {
+                // the other styles are a `<span>` with a single attribute
+                tag.clear();
+                style.apply(&mut tag, converter, &styles[..i]);
+                let tag = tag.strip_suffix("'>").unwrap_or_default();
+                if let Some(declaration) = tag.strip_prefix("<span style='") {
+                    let property = declaration.split(':').next();
+                    declarations.retain(|d| d.split(':').next() != property);
+                    declarations.push(declaration.to_string());
+                } else if let Some(class) = tag.strip_prefix("<span class='") {
+                    classes.push(class.to_string());
+                }
+            }
+        }
+    }
+    // the lines of nested elements are all drawn, so they're combined
+    if !lines.is_empty() {
+        declarations.push(format!("text-decoration:{}", lines.join(" ")));
+    }
+
+    buf.push_str("<span");
+    if !classes.is_empty() {
+        buf.push_str(" class='");
+        buf.push_str(&classes.join(" "));
+        buf.push('\'');
+    }
+    if !declarations.is_empty() {
+        buf.push_str(" style='");
+        buf.push_str(&declarations.join(";"));
+        buf.push('\'');
+    }
+    buf.push('>');
+}
+
+/// Convert ANSI sequences to html, starting at the given position. This does NOT escape html
+/// characters such as `<` and `&`.
+///
+/// Returns the html and the position at the end of the input. All tags are closed at the end.
+pub fn ansi_to_html_from(
+    input: &str,
+    converter: &Converter,
+    start: Position,
+) -> Result<(String, Position), Error> {
+    let mut html = String::new();
+    let end = ansi_to_html_into(input, converter, start, &mut html)?;
+    Ok((html, end))
+}
+
+/// Like [`ansi_to_html_from`], but appends the html to `out` instead of allocating a new string.
+///
+/// If an error is returned, `out` is unchanged.
+pub fn ansi_to_html_into(
+    input: &str,
+    converter: &Converter,
+    start: Position,
+    out: &mut String,
+) -> Result<Position, Error> {
+    let len = out.len();
+    let mut minifier = minifier::Minifier::new(converter, std::mem::take(out));
+    minifier.start_at(start);
+
+    let result = push_input(&mut minifier, input, converter);
+    let styling = minifier.styling();
+    // make sure all tags are closed
+    minifier.push_ansi_code(Ansi::Reset);
+    minifier.set_link(None);
+    minifier.close();
+    let lines = minifier.lines();
+
+    *out = minifier.into_html();
+    if result.is_err() {
+        out.truncate(len);
+    }
+    result.map(|()| Position { styling, lines })
+}
+
+/// Like [`ansi_to_html_into`] with the default styling, but the conversion reuses the allocations
+/// in `buffers` and leaves its own allocations there, see
+/// [`ConversionSession`](crate::ConversionSession).
+///
+/// If an error is returned, `out` is unchanged.
+pub(crate) fn ansi_to_html_reusing(
+    input: &str,
+    converter: &Converter,
+    out: &mut String,
+    buffers: &mut Buffers,
+) -> Result<(), Error> {
+    let len = out.len();
+    let buf = std::mem::take(out);
+    let mut minifier = minifier::Minifier::with_buffers(converter, buf, std::mem::take(buffers));
+
+    let result = push_input(&mut minifier, input, converter);
+    minifier.push_ansi_code(Ansi::Reset);
+    minifier.set_link(None);
+    (*out, *buffers) = minifier.into_parts();
+    if result.is_err() {
+        out.truncate(len);
+    }
+    result
+}
+
+/// The minimum length of the pieces of input in [`ansi_to_html_with`]. It is tiny when fuzzing,
+/// so the boundaries between pieces are tested with short inputs.
+const PIECE_LEN: usize = if cfg!(fuzzing) { 16 } else { 1 << 13 };
+
+/// Like [`ansi_to_html_from`] with the default styling, but passes the html to `write` in pieces
+/// while converting, instead of building the whole string. The input is converted in pieces of
+/// about [`PIECE_LEN`] bytes.
+///
+/// If an error is returned, the html of the input before the error may have been written.
+pub(crate) fn ansi_to_html_with(
+    input: &str,
+    converter: &Converter,
+    mut write: impl FnMut(&str) -> fmt::Result,
+) -> Result<(), Error> {
+    let phase = crate::trace::phase!("parse", input.len());
+    let mut written = 0;
+    let mut write = |html: &str| {
+        written += html.len();
+        write(html).map_err(|err| Error::Write { err })
+    };
+    let mut minifier = minifier::Minifier::new(converter, String::new());
+    for piece in pieces(input, PIECE_LEN) {
+        push_input(&mut minifier, piece, converter)?;
+        write(&minifier.take_html())?;
+    }
+    minifier.push_ansi_code(Ansi::Reset);
+    minifier.set_link(None);
+    write(&minifier.into_html())?;
+    phase.output_len(written);
+    Ok(())
+}
 
+/// Splits the input into pieces of at least `min_len` bytes, which end after a line break that
+/// isn't part of an escape sequence. Converting the pieces one after the other is the same as
+/// converting the whole input.
+fn pieces(input: &str, min_len: usize) -> impl Iterator<Item = &str> {
+    let mut pos = 0;
+    // the range of the next escape sequence, which is searched only once
+    let mut sequence = None;
+    std::iter::from_fn(move || {
+        if pos >= input.len() {
+            return None;
+        }
+        let start = pos;
+        let mut text_start = pos;
+        pos = loop {
+            let (text_end, next) = match sequence {
+                Some((start, end)) if start >= text_start => (start, end),
+                _ => {
+                    let range = find_sequence(&input[text_start..])
+                        .map_or((input.len(), input.len()), |range| {
+                            (text_start + range.start, text_start + range.end)
+                        });
+                    sequence = Some(range);
+                    range
+                }
+            };
+            let from = (start + min_len).clamp(text_start, text_end);
+            let line_break = input.as_bytes()[from..text_end]
+                .iter()
+                .position(|&b| b == b'\n');
+            if let Some(i) = line_break {
+                break from + i + 1;
+            }
+            if next >= input.len() {
+                break input.len();
+            }
+            text_start = next;
+        };
+        Some(&input[start..pos])
+    })
+}
+
+/// Converts a chunk of a stream, see [`ConverterStream`](crate::ConverterStream). Unlike
+/// [`ansi_to_html_into`], the tags that are open at the end of the input aren't closed.
+///
+/// If an error is returned, `out` is unchanged.
+pub(crate) fn stream_chunk(
+    input: &str,
+    converter: &Converter,
+    state: StreamState,
+    out: &mut String,
+) -> Result<StreamState, Error> {
+    let len = out.len();
+    let mut minifier = minifier::Minifier::resume(converter, std::mem::take(out), state);
+    let result = push_input(&mut minifier, input, converter);
+    let (html, state) = minifier.suspend();
+
+    *out = html;
+    if result.is_err() {
+        out.truncate(len);
+    }
+    result.map(|()| state)
+}
+
+/// Closes the tags that are open at the end of a stream
+pub(crate) fn stream_end(converter: &Converter, state: StreamState, out: &mut String) {
+    let mut minifier = minifier::Minifier::resume(converter, std::mem::take(out), state);
+    minifier.push_ansi_code(Ansi::Reset);
+    minifier.set_link(None);
+    *out = minifier.into_html();
+}
+
+/// Returns the number of tags that stay open when the styling changes from `from` to `to`.
+pub(crate) fn common_styles(
+    from: &CurrentStyling,
+    to: &CurrentStyling,
+    converter: &Converter,
+) -> usize {
+    if from.link != to.link {
+        return 0;
+    }
+    let link = usize::from(from.link.is_some());
+    let styles = if converter.flat_spans {
+        usize::from(
+            from.styles(converter).next().is_some()
+                && from.styles(converter).eq(to.styles(converter)),
+        )
+    } else {
+        (from.styles(converter))
+            .zip(to.styles(converter))
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+    link + styles
+}
+
+fn push_input(
+    minifier: &mut minifier::Minifier<'_>,
+    mut input: &str,
+    converter: &Converter,
+) -> Result<(), Error> {
+    let max_sequence_len = converter.max_sequence_len;
     loop {
-        match ansi_regex.find(input) {
-            Some(m) => {
-                if m.start() > 0 {
-                    let (before, after) = input.split_at(m.start());
+        match find_sequence(input) {
+            Some(range) => {
+                if range.start > 0 {
+                    let (before, after) = input.split_at(range.start);
                     minifier.push_str(before);
                     input = after;
                 }
 
-                let len = m.range().len();
-                input = &input[len..];
+                let len = range.len();
+                let (sequence, after) = input.split_at(len);
+                input = after;
 
-                if !m.as_str().ends_with('m') {
+                if is_aborted(sequence) {
                     continue;
                 }
 
-                if len == 3 {
+                if len > max_sequence_len {
+                    minifier.push_sequence(sequence);
+                    continue;
+                }
+
+                if converter.render_links {
+                    if let Some(link) = hyperlink(sequence) {
+                        minifier.set_link(link_target(link, converter));
+                        continue;
+                    }
+                }
+
+                if converter.overwrites() {
+                    if let Some(erase) = line::Erase::parse(sequence) {
+                        minifier.erase_line(erase);
+                        continue;
+                    }
+                }
+
+                if converter.emulate_cursor_movement {
+                    if let Some(movement) = screen::CursorMove::parse(sequence) {
+                        minifier.move_cursor(movement);
+                        continue;
+                    }
+                }
+
+                // SGR sequences, e.g. `ESC[1;31m`; other sequences are removed
+                let sgr = sequence
+                    .strip_prefix("\x1b[")
+                    .and_then(|s| s.strip_suffix('m'));
+                let Some(nums) = sgr else {
+                    continue;
+                };
+
+                if nums.is_empty() {
                     minifier.clear_styles();
                     continue;
                 }
 
-                let nums = &m.as_str()[2..len - 1];
                 let norm_nums = nums.strip_suffix(';').unwrap_or(nums);
                 let norm_nums = norm_nums.split(';').map(|n| n.parse::<u8>());
 
                 for ansi in AnsiIter::new(norm_nums) {
-                    minifier.push_ansi_code(ansi?);
+                    match ansi {
+                        Ok(ansi) if ansi.is_blink() && !converter.render_blink => {}
+                        Ok(ansi) => minifier.push_ansi_code(ansi),
+                        Err(_) if converter.lossy => {}
+                        Err(e) => return Err(e.in_sequence(sequence)),
+                    }
                 }
             }
             None => {
                 minifier.push_str(input);
-                break;
+                return Ok(());
             }
         }
     }
-    minifier.push_ansi_code(Ansi::Reset); // make sure all tags are closed
+}
+
+/// Replaces every character except line breaks with a space, see
+/// [`Converter::redact_concealed`]
+pub(crate) fn redact(text: &str) -> String {
+    text.chars()
+        .map(|c| if c == '\n' { c } else { ' ' })
+        .collect()
+}
+
+/// Returns the URI of an OSC 8 hyperlink sequence, e.g. `ESC]8;id=1;https://example.com ESC\`.
+/// The URI is empty at the end of a link.
+pub(crate) fn hyperlink(sequence: &str) -> Option<&str> {
+    let payload = sequence.strip_prefix("\x1b]8;")?;
+    let payload = (payload.strip_suffix('\x07')).or_else(|| payload.strip_suffix("\x1b\\"))?;
+    // the parameters, e.g. `id=1`, are separated by `:`, so the first `;` ends them
+    let (_params, uri) = payload.split_once(';')?;
+    Some(uri)
+}
+
+/// Returns the target of a link with the given (escaped) URI, if it should be rendered
+fn link_target(uri: &str, converter: &Converter) -> Option<Arc<str>> {
+    let uri = if converter.skip_escape {
+        uri.to_string()
+    } else {
+        unescape(uri)
+    };
+    (!uri.is_empty() && converter.link_schemes.allows(&uri)).then(|| uri.into())
+}
+
+/// An element written by [`AnsiConverter::set_styling`]
+#[derive(Debug, PartialEq)]
+enum Element {
+    Link(Arc<str>),
+    Style(Style),
+    /// The single `<span>` of [`Converter::flat_spans`]
+    Flat(Vec<Style>),
+}
 
-    Ok(minifier.into_html())
+/// The closing tag of an element that may be removed again, see [`AnsiConverter::closed`]
+#[derive(Debug)]
+struct Closed {
+    element: Element,
+    /// The range of the closing tag in the result
+    start: usize,
+    end: usize,
 }
 
+/// The allocations of a conversion that can be reused by the next one. They are cleared before
+/// they are reused, so no state is carried over.
 #[derive(Debug, Default)]
-struct AnsiConverter {
+pub(crate) struct Buffers {
+    code_buffer: Vec<Ansi>,
+    styles: Vec<Style>,
+    target: Vec<Style>,
+    tags: String,
+    run: String,
+    empty: Vec<usize>,
+    closed: Vec<Closed>,
+}
+
+impl Buffers {
+    fn clear(&mut self) {
+        self.code_buffer.clear();
+        self.styles.clear();
+        self.target.clear();
+        self.tags.clear();
+        self.run.clear();
+        self.empty.clear();
+        self.closed.clear();
+    }
+}
+
+#[derive(Debug)]
+struct AnsiConverter<'a> {
     styles: Vec<Style>,
-    styles_to_apply: Vec<Style>,
+    target: Vec<Style>,
+    /// The target of the `<a>` tag that is open
+    link: Option<Arc<str>>,
     result: String,
-    four_bit_var_prefix: Option<String>,
+    /// Buffer for an opening tag, before its quotes are changed
+    tags: String,
+    /// Text with the current styling, if a text transform is set or BiDi isolation is enabled
+    run: String,
+    /// Whether empty elements are removed, and elements that are closed and opened again with
+    /// the same tag are kept open instead, see [`Converter::skip_optimize`]
+    optimize: bool,
+    /// The positions of the opening tags of the innermost open elements that are still empty,
+    /// from the outermost one. Closing such an element removes it.
+    empty: Vec<usize>,
+    /// The elements that were closed since the last text, from the innermost one. Opening an
+    /// element with the same opening tag right after the closing tag removes the closing tag
+    /// instead.
+    closed: Vec<Closed>,
+    converter: &'a Converter,
 }
 
-impl AnsiConverter {
-    fn new(four_bit_var_prefix: Option<String>) -> Self {
+impl<'a> AnsiConverter<'a> {
+    /// Creates a converter that appends the html to `result`, with the allocations of `buffers`,
+    /// which must be cleared
+    fn new(converter: &'a Converter, result: String, buffers: Buffers) -> Self {
+        let Buffers {
+            code_buffer: _,
+            styles,
+            target,
+            tags,
+            run,
+            empty,
+            closed,
+        } = buffers;
         Self {
-            four_bit_var_prefix,
-            ..Self::default()
+            styles,
+            target,
+            link: None,
+            result,
+            tags,
+            run,
+            optimize: !converter.skip_optimize && converter.output_compat != OutputCompat::V0,
+            empty,
+            closed,
+            converter,
         }
     }
 
-    fn consume_ansi_code(&mut self, ansi: Ansi) {
-        match ansi {
-            Ansi::Noop => {}
-            Ansi::Reset => self.clear_style(|_| true),
-            Ansi::Bold => self.set_style(Style::Bold),
-            Ansi::Faint => self.set_style(Style::Faint),
-            Ansi::Italic => self.set_style(Style::Italic),
-            Ansi::Underline => self.set_style(Style::Underline),
-            Ansi::CrossedOut => self.set_style(Style::CrossedOut),
-            Ansi::BoldOff => self.clear_style(|&s| s == Style::Bold),
-            Ansi::BoldAndFaintOff => self.clear_style(|&s| s == Style::Bold || s == Style::Faint),
-            Ansi::ItalicOff => self.clear_style(|&s| s == Style::Italic),
-            Ansi::UnderlineOff => self.clear_style(|&s| s == Style::Underline),
-            Ansi::CrossedOutOff => self.clear_style(|&s| s == Style::CrossedOut),
-            Ansi::ForgroundColor(c) => self.set_style(Style::ForegroundColor(c)),
-            Ansi::DefaultForegroundColor => {
-                self.clear_style(|&s| matches!(s, Style::ForegroundColor(_)))
+    /// Treats the tags of `styling` as open, because they were written before.
+    fn assume_open(&mut self, styling: &CurrentStyling) {
+        self.styles.clear();
+        self.styles.extend(styling.styles(self.converter));
+        self.link = styling.link.clone();
+        self.content_written();
+    }
+
+    /// Closes and opens tags so that exactly the styles of `styling` are active.
+    ///
+    /// The tags are always nested in the same order (see [`CurrentStyling::styles`]), regardless
+    /// of the order of the ANSI codes. Tags that are already open in the right position are kept.
+    /// A link is outside of all other tags, so they're closed when it starts or ends.
+    fn set_styling(&mut self, styling: &CurrentStyling) {
+        self.flush_run();
+        self.target.clear();
+        self.target.extend(styling.styles(self.converter));
+
+        let same_link = self.link == styling.link;
+        let common = if self.converter.flat_spans {
+            // the span is kept only if all styles stay the same
+            match same_link && self.styles == self.target {
+                true => self.styles.len(),
+                false => 0,
+            }
+        } else {
+            (self.styles.iter())
+                .zip(&self.target)
+                .take_while(|(a, b)| same_link && a == b)
+                .count()
+        };
+        if self.converter.flat_spans && self.styles.len() > common {
+            let styles = std::mem::take(&mut self.styles);
+            self.close_tag("</span>", Element::Flat(styles));
+        }
+        while self.styles.len() > common {
+            let style = self.styles.pop().unwrap();
+            self.close_tag(style.closing_tag(self.converter), Element::Style(style));
+        }
+        if !same_link {
+            if let Some(link) = self.link.take() {
+                self.close_tag("</a>", Element::Link(link));
             }
-            Ansi::BackgroundColor(c) => self.set_style(Style::BackgroundColor(c)),
-            Ansi::DefaultBackgroundColor => {
-                self.clear_style(|&s| matches!(s, Style::BackgroundColor(_)))
+            if let Some(link) = &styling.link {
+                let tag = format!("<a href='{}'>", EscUrl(&**link));
+                self.open_tag(&tag, Element::Link(link.clone()));
             }
+            self.link = styling.link.clone();
         }
+        let mut tag = std::mem::take(&mut self.tags);
+        if self.converter.flat_spans {
+            if self.target.len() > common {
+                tag.clear();
+                apply_flat(&self.target, &mut tag, self.converter);
+                self.open_tag(&tag, Element::Flat(self.target.clone()));
+                self.styles.clone_from(&self.target);
+            }
+        } else {
+            for i in common..self.target.len() {
+                let style = self.target[i];
+                tag.clear();
+                style.apply(&mut tag, self.converter, &self.styles);
+                self.open_tag(&tag, Element::Style(style));
+                self.styles.push(style);
+            }
+        }
+        self.tags = tag;
     }
 
-    fn set_style(&mut self, s: Style) {
-        if !self.styles.contains(&s) {
-            s.apply(&mut self.result, self.four_bit_var_prefix.as_deref());
-            self.styles.push(s);
+    /// Writes the opening tag of an element, with single quotes. If an element with the same
+    /// opening tag was closed right before, e.g. a color that is written the same as the previous
+    /// one, its closing tag is removed instead.
+    fn open_tag(&mut self, tag: &str, element: Element) {
+        if self.optimize {
+            let len = self.result.len();
+            let reopened = self.closed.last().is_some_and(|closed| {
+                closed.end == len
+                    && (closed.element == element || self.opening_tag(&closed.element) == tag)
+            });
+            if reopened {
+                let closed = self.closed.pop().unwrap();
+                self.result.truncate(closed.start);
+                return;
+            }
+            self.empty.push(len);
         }
+        (self.converter.attribute_quotes).push_tags(tag, &mut self.result);
     }
 
-    fn clear_style(&mut self, cond: impl Fn(&Style) -> bool) {
-        if let Some((i, _)) = self.styles.iter().enumerate().find(|&(_, s)| cond(s)) {
-            while self.styles.len() > i {
-                let style = self.styles.pop().unwrap();
-                style.clear(&mut self.result);
-                if !cond(&style) {
-                    self.styles_to_apply.push(style);
-                }
-            }
+    /// Returns the opening tag of an element in the current position, before its quotes are
+    /// changed. Its closing tag must have been written right before, so the styles that are
+    /// active around it are the same as when it was opened.
+    fn opening_tag(&self, element: &Element) -> String {
+        let mut tag = String::new();
+        match element {
+            Element::Link(link) => tag = format!("<a href='{}'>", EscUrl(&**link)),
+            Element::Style(style) => style.apply(&mut tag, self.converter, &self.styles),
+            Element::Flat(styles) => apply_flat(styles, &mut tag, self.converter),
         }
-        for &style in &self.styles_to_apply {
-            style.apply(&mut self.result, self.four_bit_var_prefix.as_deref());
-            self.styles.push(style);
+        tag
+    }
+
+    /// Writes the closing tag of the innermost open element. If the element is empty, it is
+    /// removed instead.
+    fn close_tag(&mut self, tag: &str, element: Element) {
+        if !self.optimize {
+            self.result.push_str(tag);
+            return;
+        }
+        if let Some(start) = self.empty.pop() {
+            self.result.truncate(start);
+            return;
         }
-        self.styles_to_apply.clear();
+        let start = self.result.len();
+        self.result.push_str(tag);
+        self.closed.push(Closed {
+            element,
+            start,
+            end: self.result.len(),
+        });
+    }
+
+    /// Forgets the empty and closed elements after text or other content was written, since
+    /// they can no longer be removed
+    fn content_written(&mut self) {
+        self.empty.clear();
+        self.closed.clear();
     }
 
     fn push_str(&mut self, s: &str) {
+        if !s.is_empty() {
+            self.content_written();
+        }
+        let redacted;
+        let s = if self.converter.redact_concealed && self.styles.contains(&Style::Concealed) {
+            // the text is escaped, so entities must be replaced with a single space
+            let text = match self.converter.skip_escape {
+                true => s.into(),
+                false => unescape(s),
+            };
+            redacted = redact(&text);
+            &redacted
+        } else {
+            s
+        };
+        if self.converter.text_transform.is_some() || self.converter.bidi_isolate {
+            self.run.push_str(s);
+        } else {
+            self.result.push_str(s);
+        }
+    }
+
+    /// Pushes an escape sequence that is kept as is, or a line break after the closed tags of a
+    /// line, so it isn't passed to the text transform.
+    fn push_raw(&mut self, s: &str) {
+        self.flush_run();
+        if !s.is_empty() {
+            self.content_written();
+        }
         self.result.push_str(s);
     }
 
+    /// Pushes tags that aren't related to the styling, e.g. the wrapper of a line, with single
+    /// quotes
+    fn push_tags(&mut self, tags: &str) {
+        self.flush_run();
+        self.content_written();
+        (self.converter.attribute_quotes).push_tags(tags, &mut self.result);
+    }
+
+    /// Writes the buffered text run. With BiDi isolation, every line of the run is wrapped in a
+    /// `<bdi>` element and passed to the text transform separately.
+    fn flush_run(&mut self) {
+        if self.run.is_empty() {
+            return;
+        }
+        let run = std::mem::take(&mut self.run);
+        if self.converter.bidi_isolate {
+            for (i, line) in run.split('\n').enumerate() {
+                if i > 0 {
+                    self.result.push('\n');
+                }
+                if !line.is_empty() {
+                    self.result.push_str("<bdi>");
+                    self.push_text(line);
+                    self.result.push_str("</bdi>");
+                }
+            }
+        } else {
+            self.push_text(&run);
+        }
+        // keep the allocation
+        self.run = run;
+        self.run.clear();
+    }
+
+    fn push_text(&mut self, text: &str) {
+        match &self.converter.text_transform {
+            Some(transform) => transform.apply(text, self.converter.skip_escape, &mut self.result),
+            None => self.result.push_str(text),
+        }
+    }
+
+    /// Takes the html written so far. The text run isn't written until the styling changes, and
+    /// the tags that may still be removed are kept.
+    fn take_result(&mut self) -> String {
+        let keep = (self.closed.first().map(|closed| closed.start))
+            .or(self.empty.first().copied())
+            .unwrap_or(self.result.len());
+        for start in &mut self.empty {
+            *start -= keep;
+        }
+        for closed in &mut self.closed {
+            closed.start -= keep;
+            closed.end -= keep;
+        }
+        let rest = self.result.split_off(keep);
+        std::mem::replace(&mut self.result, rest)
+    }
+
     fn result(self) -> String {
-        self.result
+        self.into_parts().0
+    }
+
+    /// Returns the html and the allocations that can be reused, see [`Buffers`]
+    fn into_parts(mut self) -> (String, Buffers) {
+        self.flush_run();
+        let buffers = Buffers {
+            code_buffer: Vec::new(),
+            styles: self.styles,
+            target: self.target,
+            tags: self.tags,
+            run: self.run,
+            empty: self.empty,
+            closed: self.closed,
+        };
+        (self.result, buffers)
     }
 }
@@ -0,0 +1,146 @@
+//! The lines that can still be changed by a carriage return or by moving the
+//! cursor, see
+//! [`Converter::emulate_cursor_movement`](crate::Converter::emulate_cursor_movement).
+
+use std::collections::VecDeque;
+
+use crate::html::{
+    line::{Erase, Line},
+    CurrentStyling,
+};
+
+/// The number of lines that the cursor can move between. Lines above them are
+/// written, like lines that scrolled out of a terminal window.
+pub(super) const ROWS: usize = 256;
+
+/// The maximum column that the cursor can move to, which limits the number of
+/// spaces added before the text
+const MAX_COLUMN: usize = 1023;
+
+/// The line break that ends a line, with the styling it was printed with
+pub(super) type LineBreak = (CurrentStyling, &'static str);
+
+/// The last lines of the output, like the lines of a terminal window. Without
+/// cursor movement, only the current line is kept.
+#[derive(Debug)]
+pub(super) struct Screen {
+    /// The lines, and the line breaks that end all but the last one
+    lines: VecDeque<(Line, Option<LineBreak>)>,
+    /// The index of the line containing the cursor
+    row: usize,
+    rows: usize,
+}
+
+impl Screen {
+    /// Creates a screen that keeps at most `rows` lines
+    pub(super) fn new(rows: usize) -> Self {
+        Self {
+            lines: VecDeque::from([(Line::default(), None)]),
+            row: 0,
+            rows,
+        }
+    }
+
+    fn line(&mut self) -> &mut Line {
+        &mut self.lines[self.row].0
+    }
+
+    /// Writes the text, which doesn't contain line breaks, at the cursor
+    pub(super) fn push(&mut self, styling: &CurrentStyling, text: &str) {
+        self.line().push(styling, text);
+    }
+
+    /// See [`Line::carriage_return`]
+    pub(super) fn carriage_return(&mut self) {
+        self.line().carriage_return();
+    }
+
+    /// Moves the cursor to the start of the next line. The line break is
+    /// `\r\n` if it follows a `\r`.
+    pub(super) fn line_break(&mut self, styling: &CurrentStyling) {
+        let crlf = self.line().take_carriage_return();
+        let (_, line_break) = &mut self.lines[self.row];
+        line_break.get_or_insert_with(|| {
+            let line_break = if crlf { "\r\n" } else { "\n" };
+            (styling.clone(), line_break)
+        });
+        self.row += 1;
+        if self.row == self.lines.len() {
+            self.lines.push_back((Line::default(), None));
+        }
+        self.line().move_to(0);
+    }
+
+    /// See [`Line::erase`]
+    pub(super) fn erase(&mut self, erase: Erase) {
+        self.line().erase(erase);
+    }
+
+    /// Moves the cursor. It stops at the first and the last line, since the
+    /// screen doesn't scroll.
+    pub(super) fn move_cursor(&mut self, movement: CursorMove) {
+        let column = self.line().column();
+        let (row, column) = match movement {
+            CursorMove::Up(n) => (self.row.saturating_sub(n), column),
+            CursorMove::Down(n) => (self.row.saturating_add(n), column),
+            CursorMove::Column(column) => (self.row, column),
+        };
+        self.row = row.min(self.lines.len() - 1);
+        self.line().move_to(column.min(MAX_COLUMN));
+    }
+
+    /// Removes the first line if there are more than `rows` lines, and
+    /// returns its text in runs with the same styling and its line break
+    pub(super) fn pop_scrolled(&mut self) -> Option<(Vec<(CurrentStyling, String)>, LineBreak)> {
+        if self.lines.len() <= self.rows {
+            return None;
+        }
+        let (mut line, line_break) = self.lines.pop_front()?;
+        self.row -= 1;
+        Some((line.take(), line_break?))
+    }
+
+    /// Returns the text of all lines in runs with the same styling, with the
+    /// line breaks between them, and clears the screen
+    pub(super) fn take(&mut self) -> Vec<(CurrentStyling, String)> {
+        let mut runs = Vec::new();
+        for (mut line, line_break) in self.lines.drain(..) {
+            runs.extend(line.take());
+            runs.extend(line_break.map(|(styling, text)| (styling, text.to_string())));
+        }
+        self.lines.push_back((Line::default(), None));
+        self.row = 0;
+        runs
+    }
+}
+
+/// A sequence that moves the cursor, e.g. `ESC[2A`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum CursorMove {
+    /// `ESC[<n>A`
+    Up(usize),
+    /// `ESC[<n>B`
+    Down(usize),
+    /// `ESC[<n>G`, with the column starting at 0
+    Column(usize),
+}
+
+impl CursorMove {
+    /// Parses a sequence that moves the cursor. Like in terminals, the number
+    /// defaults to 1, and 0 counts as 1.
+    pub(super) fn parse(sequence: &str) -> Option<Self> {
+        let params = sequence.strip_prefix("\x1b[")?;
+        let command = params.chars().next_back()?;
+        let n = match &params[..params.len() - command.len_utf8()] {
+            "" => 1,
+            n if n.bytes().all(|b| b.is_ascii_digit()) => n.parse::<usize>().ok()?.max(1),
+            _ => return None,
+        };
+        match command {
+            'A' => Some(CursorMove::Up(n)),
+            'B' => Some(CursorMove::Down(n)),
+            'G' => Some(CursorMove::Column(n - 1)),
+            _ => None,
+        }
+    }
+}
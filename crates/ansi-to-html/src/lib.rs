@@ -7,29 +7,56 @@
 //! - bold
 //! - italic
 //! - underlined
+//! - overlined
 //! - crossed out
 //! - faint
 //! - foreground and background colors: 3-bit, 4-bit, 8-bit, truecolor (24-bit)
+//! - reverse video, with configurable
+//!   [`default_colors`](Converter::default_colors)
+//! - slow/rapid blink, as a CSS class (opt-in)
+//! - conceal, as transparent text (or spaces, see
+//!   [`redact_concealed`](Converter::redact_concealed))
 //!
 //! **Not** supported SGR parameters (note that most of these are niche features
 //! and rarely supported by terminals):
 //!
-//! - slow/rapid blink
-//! - reverse video
-//! - conceal
 //! - alternative fonts
 //! - fraktur
 //! - doubly underlined
 //! - proportional spacing
 //! - framed
 //! - encircled
-//! - overlined
 //! - underline color (not in standard)
 //! - ideogram attributes
 //! - superscript, subscript (not in standard)
 //! - bright foreground/background color (not in standard)
 //!
-//! All unsupported ANSI escape codes are stripped from the output.
+//! All unsupported ANSI escape codes are stripped from the output. This includes
+//! OSC sequences, which are terminated either by `BEL` or by `ESC \`. OSC 8
+//! hyperlinks are stripped as well, unless
+//! [`render_links`](Converter::render_links) is enabled.
+//!
+//! The tags are always nested in the same order, regardless of the order of the
+//! ANSI codes: `<b>`, `<span>` for faint text, `<i>`, `<u>`, `<span>` for
+//! overlined text, `<s>`, `<span>` for blinking text, `<span>` for the
+//! foreground color, `<span>` for the background color, `<span>` for concealed
+//! text. Underlined and overlined text has both lines, since
+//! `text-decoration` lines are drawn for nested elements as well. For example, both
+//! `\x1b[1;91m` and `\x1b[91;1m` produce `<b><span style='color:…'>…</span></b>`.
+//!
+//! The foreground and background colors are independent: `39` only ends the
+//! foreground color, `49` only the background color, and `0` both. Reverse video
+//! (`7`) swaps them when they are rendered, using the
+//! [`default_colors`](Converter::default_colors) for those that aren't set, until
+//! `27` or `0`. The HTML is the same with and without
+//! [`skip_optimize`](Converter::skip_optimize), apart from empty and redundant
+//! tags.
+//!
+//! The tags that are open at the end of the input are closed before the spaces
+//! and line breaks at its end, so output that ends with `\x1b[0m\n` and output
+//! that ends with `\n` are converted the same. Trailing spaces keep the styles
+//! that are visible on them, i.e. a background color, underlining and crossing
+//! out. With [`OutputCompat::V0`], the tags are closed after them.
 //!
 //! It should be easy to add support for more styles, if there's a straightforward HTML
 //! representation. If you need a different style (e.g. doubly underlined), file an issue.
@@ -48,23 +75,144 @@
 //! ```
 //!
 //! Use the [`Converter`] builder for customization options.
+//!
+//! ## Features
+//!
+//! | Feature       | Default | Description                                                               |
+//! |---------------|---------|---------------------------------------------------------------------------|
+//! | `optimize`    | yes     | Removes empty and redundant tags in a second pass with `OutputCompat::V0` |
+//! | `tracing`     | no      | Emits `tracing` spans for the phases of a conversion                      |
+//! | `competitors` | no      | Only enables the `competitors` benchmark, see `benches/`                  |
+//!
+//! Without the `tracing` feature, this crate has no dependencies. The converter
+//! minimizes the tags it emits while writing them: Empty elements are removed,
+//! and an element that is closed and opened again right away with the same
+//! tag, e.g. for two colors that are written the same, stays open instead.
+//! HTML that is part of the input (see [`Converter::skip_escape`]) is kept as
+//! it is. Only with [`OutputCompat::V0`], the `optimize` feature removes empty
+//! and redundant tags in a second pass like the 0.x versions, including tags
+//! in the input.
+//!
+//! With the `tracing` feature, [`Converter::convert`], [`convert_cow`] and
+//! [`convert_to_writer`] enter a `convert` span, which contains a span for each
+//! phase: `escape`, `parse` (which includes minimizing the tags), `optimize`
+//! (only with [`OutputCompat::V0`]) and `pretty`. The spans are at the debug
+//! level and have `input_len` and `output_len` fields with the lengths in
+//! bytes. The phases of other methods emit spans as well, but without a
+//! `convert` span. A warning event is emitted when input is returned unchanged
+//! because of [`detect_double_escape`](Converter::detect_double_escape).
+//! Without a subscriber, the spans have no measurable overhead.
+//!
+//! [`convert_cow`]: Converter::convert_cow
+//! [`convert_to_writer`]: Converter::convert_to_writer
+//!
+//! ## Untrusted input
+//!
+//! Converting a string never panics, whatever it contains, so untrusted input
+//! (e.g. in a server) can't crash the program. Invalid escape sequences result
+//! in an [`Error`], or are ignored with [`lossy`](Converter::lossy). A control
+//! character such as `NUL` in the middle of a sequence, e.g. `ESC[3\x001m`,
+//! aborts it: the sequence and the control character are removed, and the
+//! rest (`1m`) is text. Line breaks and tabs are kept. This is
+//! checked by the `no_panic` fuzz target in `fuzz/`, which calls every
+//! conversion method with all combinations of options. A panic in a
+//! [`text_transform`](Converter::text_transform) hook is not caught. Don't
+//! enable [`detect_double_escape`](Converter::detect_double_escape) for
+//! untrusted input, since input that looks like HTML isn't escaped.
+//!
+//! ## Public API
+//!
+//! Everything intended for users is exported from the crate root; the modules
+//! are private, so moving code between them doesn't break downstream crates.
+//! The public items are:
+//!
+//! - [`convert`] and the [`Converter`] builder
+//! - [`PreparedConverter`] and [`ConfigError`], returned by
+//!   [`Converter::build`]
+//! - [`text_fragments`], which returns the text without escape sequences, and
+//!   [`strip_ansi`] and [`strip_ansi_to_writer`], which concatenate it
+//! - [`looks_like_html`], which detects input that was converted already
+//! - [`visible_width`], which returns the number of columns of terminal output
+//! - [`AnsiParser`], which splits the input into [`AnsiFragment`]s, and the
+//!   [`SgrCode`]s and [`SgrColor`]s returned by [`AnsiFragment::sgr_codes`]
+//! - [`StyleState`], the state used by [`Converter::convert_append`]
+//! - [`StyledSegment`], returned by [`Converter::to_segments`]
+//! - [`ConverterStream`], returned by [`Converter::begin`]
+//! - [`ConversionSession`], returned by [`Converter::session`]
+//! - [`FaintBlend`], [`LineWrapper`], [`Newlines`], [`OutputCompat`],
+//!   [`Quote`], [`TagStyle`], [`ThemePalette`], [`UrlSchemes`] and
+//!   [`VarNames`], used to configure a [`Converter`]
+//! - [`Transformed`] and [`Piece`], returned by a
+//!   [`text_transform`](Converter::text_transform) function
+//! - [`Esc`], [`EscAttr`] and [`EscUrl`] for escaping HTML
+//! - [`Error`]
+//!
+//! [`Error`], [`ConfigError`], [`FaintBlend`], [`ThemePalette`],
+//! [`AnsiFragment`], [`SgrCode`], [`SgrColor`] and [`StyledSegment`] are
+//! `#[non_exhaustive]`, so variants and fields can be added in minor releases.
+//! Structs with private fields can only be created with their constructors or
+//! builder methods.
 #![deny(unsafe_code)]
 
-use std::sync::OnceLock;
+use std::{borrow::Cow, fmt, sync::Arc};
 
 mod ansi;
+mod chunk;
 mod color;
+mod combining;
+mod compat;
+mod double_escape;
+mod entity;
 mod error;
 mod esc;
+mod fingerprint;
 mod html;
+mod line_wrapper;
+mod newlines;
+mod parse;
+mod prepared;
+mod pretty;
+mod quote;
+mod rope;
+mod sanitize;
+mod segments;
+mod session;
+mod state;
+mod stream;
+mod tags;
+mod trace;
+mod transform;
+mod url;
+mod vars;
+mod width;
 
 use ansi::{Ansi, AnsiIter};
-use color::Color;
+use color::{Color, Palette};
+use rope::Rope;
+use trace::phase;
 
-pub use error::Error;
-pub use esc::Esc;
-
-use regex::Regex;
+pub use ansi::{text_fragments, TextFragments};
+pub use color::{FaintBlend, ThemePalette};
+pub use compat::OutputCompat;
+pub use double_escape::looks_like_html;
+pub use error::{ConfigError, Error};
+pub use esc::{Esc, EscAttr, EscUrl};
+pub use line_wrapper::LineWrapper;
+pub use newlines::Newlines;
+pub use parse::{
+    strip_ansi, strip_ansi_to_writer, AnsiFragment, AnsiParser, SgrCode, SgrCodes, SgrColor,
+};
+pub use prepared::PreparedConverter;
+pub use quote::Quote;
+pub use segments::StyledSegment;
+pub use session::ConversionSession;
+pub use state::StyleState;
+pub use stream::ConverterStream;
+pub use tags::TagStyle;
+pub use transform::{Piece, Transformed};
+pub use url::UrlSchemes;
+pub use vars::VarNames;
+pub use width::visible_width;
 
 /// Converts a string containing ANSI escape codes to HTML.
 ///
@@ -124,13 +272,199 @@ pub fn convert(ansi_string: &str) -> Result<String, Error> {
 ///     "<h1> <i></i> <b>Hello <span style='color:var(--custom-red,#a00)'>world!</span></b> </h1>",
 /// );
 /// ```
-#[derive(Clone, Debug, Default)]
+///
+/// The builder methods take the converter by value and return the modified
+/// one, so their result must be assigned. Otherwise, the compiler warns:
+///
+/// ```compile_fail
+/// #![deny(unused_must_use)]
+/// let mut converter = ansi_to_html::Converter::new();
+/// converter.clone().skip_escape(true); // doesn't change `converter`
+/// converter = converter.skip_escape(true);
+/// ```
+///
+/// Converters can be compared, e.g. in tests. A
+/// [`text_transform`](Converter::text_transform) is only equal to itself,
+/// i.e. in clones of the converter it was set on. A converter that was
+/// [built](Converter::build) equals the one it was built from.
+#[derive(Clone, Debug)]
+#[must_use = "the builder methods return a modified converter instead of changing it"]
 pub struct Converter {
     skip_escape: bool,
     skip_optimize: bool,
     four_bit_var_prefix: Option<String>,
+    four_bit_classes: Option<String>,
+    sanitize: bool,
+    allowed_tags: Vec<String>,
+    skip_css_vars: bool,
+    faint_blend: Option<FaintBlend>,
+    theme: Option<ThemePalette>,
+    dual_theme: Option<(ThemePalette, ThemePalette)>,
+    skip_light_dark: bool,
+    adapt_to_theme: bool,
+    elide_default_colors: bool,
+    default_colors: Option<[[u8; 3]; 2]>,
+    grayscale_levels: Option<u8>,
+    eight_bit_palette: Option<Arc<[[u8; 3]; 256]>>,
+    max_sequence_len: usize,
+    pretty: bool,
+    var_names: Option<VarNames>,
+    text_transform: Option<transform::TextTransform>,
+    bidi_isolate: bool,
+    normalize_trailing_whitespace: bool,
+    line_safe: bool,
+    line_wrapper: Option<LineWrapper>,
+    render_links: bool,
+    link_schemes: UrlSchemes,
+    newlines: Newlines,
+    carriage_return_overwrites: bool,
+    emulate_cursor_movement: bool,
+    lossy: bool,
+    attach_combining: bool,
+    render_blink: bool,
+    redact_concealed: bool,
+    tag_style: TagStyle,
+    flat_spans: bool,
+    attribute_quotes: Quote,
+    output_compat: OutputCompat,
+    detect_double_escape: bool,
+    /// Only set in the copy that a [`PreparedConverter`] converts with, so it can't get out of date
+    palette: Option<Arc<Palette>>,
 }
 
+impl Default for Converter {
+    fn default() -> Self {
+        Self {
+            skip_escape: false,
+            skip_optimize: false,
+            four_bit_var_prefix: None,
+            four_bit_classes: None,
+            sanitize: false,
+            allowed_tags: Vec::new(),
+            skip_css_vars: false,
+            faint_blend: None,
+            theme: None,
+            dual_theme: None,
+            skip_light_dark: false,
+            adapt_to_theme: false,
+            elide_default_colors: false,
+            default_colors: None,
+            grayscale_levels: None,
+            eight_bit_palette: None,
+            max_sequence_len: 4096,
+            pretty: false,
+            var_names: None,
+            text_transform: None,
+            bidi_isolate: false,
+            normalize_trailing_whitespace: false,
+            line_safe: false,
+            line_wrapper: None,
+            render_links: false,
+            link_schemes: UrlSchemes::default(),
+            newlines: Newlines::Keep,
+            carriage_return_overwrites: false,
+            emulate_cursor_movement: false,
+            lossy: false,
+            attach_combining: true,
+            render_blink: false,
+            redact_concealed: false,
+            tag_style: TagStyle::Presentational,
+            flat_spans: false,
+            attribute_quotes: Quote::Single,
+            output_compat: OutputCompat::Current,
+            detect_double_escape: false,
+            palette: None,
+        }
+    }
+}
+
+impl PartialEq for Converter {
+    fn eq(&self, other: &Self) -> bool {
+        // the palette is computed from the other options
+        let Self {
+            skip_escape,
+            skip_optimize,
+            four_bit_var_prefix,
+            four_bit_classes,
+            sanitize,
+            allowed_tags,
+            skip_css_vars,
+            faint_blend,
+            theme,
+            dual_theme,
+            skip_light_dark,
+            adapt_to_theme,
+            elide_default_colors,
+            default_colors,
+            grayscale_levels,
+            eight_bit_palette,
+            max_sequence_len,
+            pretty,
+            var_names,
+            text_transform,
+            bidi_isolate,
+            normalize_trailing_whitespace,
+            line_safe,
+            line_wrapper,
+            render_links,
+            link_schemes,
+            newlines,
+            carriage_return_overwrites,
+            emulate_cursor_movement,
+            lossy,
+            attach_combining,
+            render_blink,
+            redact_concealed,
+            tag_style,
+            flat_spans,
+            attribute_quotes,
+            output_compat,
+            detect_double_escape,
+            palette: _,
+        } = self;
+        *skip_escape == other.skip_escape
+            && *skip_optimize == other.skip_optimize
+            && *four_bit_var_prefix == other.four_bit_var_prefix
+            && *four_bit_classes == other.four_bit_classes
+            && *sanitize == other.sanitize
+            && *allowed_tags == other.allowed_tags
+            && *skip_css_vars == other.skip_css_vars
+            && *faint_blend == other.faint_blend
+            && *theme == other.theme
+            && *dual_theme == other.dual_theme
+            && *skip_light_dark == other.skip_light_dark
+            && *adapt_to_theme == other.adapt_to_theme
+            && *elide_default_colors == other.elide_default_colors
+            && *default_colors == other.default_colors
+            && *grayscale_levels == other.grayscale_levels
+            && *eight_bit_palette == other.eight_bit_palette
+            && *max_sequence_len == other.max_sequence_len
+            && *pretty == other.pretty
+            && *var_names == other.var_names
+            && *text_transform == other.text_transform
+            && *bidi_isolate == other.bidi_isolate
+            && *normalize_trailing_whitespace == other.normalize_trailing_whitespace
+            && *line_safe == other.line_safe
+            && *line_wrapper == other.line_wrapper
+            && *render_links == other.render_links
+            && *link_schemes == other.link_schemes
+            && *newlines == other.newlines
+            && *carriage_return_overwrites == other.carriage_return_overwrites
+            && *emulate_cursor_movement == other.emulate_cursor_movement
+            && *lossy == other.lossy
+            && *attach_combining == other.attach_combining
+            && *render_blink == other.render_blink
+            && *redact_concealed == other.redact_concealed
+            && *tag_style == other.tag_style
+            && *flat_spans == other.flat_spans
+            && *attribute_quotes == other.attribute_quotes
+            && *output_compat == other.output_compat
+            && *detect_double_escape == other.detect_double_escape
+    }
+}
+
+impl Eq for Converter {}
+
 #[deprecated(note = "this is now a type alias for the `Converter` builder")]
 pub type Opts = Converter;
 
@@ -141,12 +475,27 @@ impl Converter {
     }
 
     /// Avoids escaping special HTML characters prior to conversion.
+    ///
+    /// The input is treated as HTML. Escape sequences in the middle of an
+    /// entity, e.g. `&l\x1b[31mt;`, are applied after the entity, so no tag is
+    /// inserted into it.
     pub fn skip_escape(mut self, skip: bool) -> Self {
         self.skip_escape = skip;
         self
     }
 
-    /// Skips removing some useless HTML tags.
+    /// Writes every tag as it is produced, instead of minimizing them.
+    ///
+    /// By default, elements that would be empty are left out, and an element
+    /// that is closed and opened again right away with the same tag stays
+    /// open instead. With this option, all of these tags are written. The
+    /// rendered HTML is the same either way.
+    ///
+    /// With [`OutputCompat::V0`], tags aren't minimized while converting.
+    /// Instead, the `optimize` feature removes empty and redundant tags in a
+    /// second pass, including tags in the input (see
+    /// [`skip_escape`](Converter::skip_escape)). This option disables that
+    /// pass.
     pub fn skip_optimize(mut self, skip: bool) -> Self {
         self.skip_optimize = skip;
         self
@@ -158,52 +507,1854 @@ impl Converter {
         self
     }
 
+    /// Renders the 4-bit colors with CSS classes instead of inline styles, so
+    /// they can be styled by a stylesheet. The classes are named like the
+    /// colors with the given prefix, e.g. `red` and `bright-blue` for text
+    /// colors, and `bg-red` and `bg-bright-blue` for backgrounds. The prefix
+    /// may contain letters, digits, `-` and `_`.
+    ///
+    /// The classes take precedence over CSS variables, so the
+    /// [`four_bit_var_prefix`](Converter::four_bit_var_prefix),
+    /// [`var_names`](Converter::var_names) and
+    /// [`skip_css_vars`](Converter::skip_css_vars) are ignored when this is
+    /// set; [`build`](Converter::build) reports them as errors. 8-bit and RGB
+    /// colors still use inline styles.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().four_bit_classes(Some("term-".into()));
+    /// assert_eq!(
+    ///     converter.convert("\x1b[31;104mred").unwrap(),
+    ///     "<span class='term-red'><span class='term-bg-bright-blue'>red</span></span>",
+    /// );
+    /// ```
+    pub fn four_bit_classes(mut self, prefix: Option<String>) -> Self {
+        self.four_bit_classes = prefix;
+        self
+    }
+
+    /// Uses custom names for the CSS variables of the 4-bit colors, instead of
+    /// `--red`, `--bright-red` etc. with the
+    /// [`four_bit_var_prefix`](Converter::four_bit_var_prefix), which is
+    /// ignored when this is set.
+    ///
+    /// Use [`VarNames::stylesheet`] to declare the variables.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, VarNames};
+    ///
+    /// let names = VarNames::new(|name| format!("color-terminal-{name}")).unwrap();
+    /// let converter = Converter::new().var_names(Some(names));
+    /// assert_eq!(
+    ///     converter.convert("\x1b[31mred").unwrap(),
+    ///     "<span style='color:var(--color-terminal-red,#a00)'>red</span>",
+    /// );
+    /// ```
+    pub fn var_names(mut self, names: Option<VarNames>) -> Self {
+        self.var_names = names;
+        self
+    }
+
+    /// Uses hardcoded colors instead of CSS variables for the 4-bit colors.
+    ///
+    /// This is useful where CSS variables aren't supported, e.g. when the HTML
+    /// is sanitized. When this is enabled, the
+    /// [`four_bit_var_prefix`](Converter::four_bit_var_prefix) and the
+    /// [`var_names`](Converter::var_names) are ignored.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().skip_css_vars(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[31mred").unwrap(),
+    ///     "<span style='color:#a00'>red</span>",
+    /// );
+    /// ```
+    pub fn skip_css_vars(mut self, skip: bool) -> Self {
+        self.skip_css_vars = skip;
+        self
+    }
+
+    /// Renders faint text with a concrete color instead of `opacity`.
+    ///
+    /// The color is computed by blending the text color (or the default
+    /// foreground, if no color is set) with the background. This is useful
+    /// where the `opacity` property is stripped, e.g. by HTML sanitizers.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, FaintBlend};
+    ///
+    /// let converter =
+    ///     Converter::new().faint_blend(Some(FaintBlend::new([255, 255, 255], [0, 0, 0])));
+    /// assert_eq!(
+    ///     converter.convert("\x1b[2mfaint").unwrap(),
+    ///     "<span style='color:#ababab'>faint</span>",
+    /// );
+    /// ```
+    pub fn faint_blend(mut self, blend: Option<FaintBlend>) -> Self {
+        self.faint_blend = blend;
+        self
+    }
+
+    /// Uses the colors of a terminal theme for the 16 colors of the palette
+    /// (including the first 16 8-bit colors, so `38;5;1` and `31` agree),
+    /// instead of the default colors such as `#a00`.
+    ///
+    /// The colors are used as the fallback of the CSS variables, or directly
+    /// with [`skip_css_vars`](Converter::skip_css_vars). With
+    /// [`faint_blend`](Converter::faint_blend), faint text is blended with the
+    /// foreground and background of the theme instead of the colors of the
+    /// [`FaintBlend`]. Besides custom palettes, there are built-in ones like
+    /// [`ThemePalette::solarized_dark`]. For a page that supports both a light
+    /// and a dark color scheme, use [`dual_theme`](Converter::dual_theme)
+    /// instead.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, ThemePalette};
+    ///
+    /// let converter = Converter::new().palette(ThemePalette::solarized_dark());
+    /// assert_eq!(
+    ///     converter.convert("\x1b[31mred").unwrap(),
+    ///     "<span style='color:var(--red,#dc322f)'>red</span>",
+    /// );
+    ///
+    /// let converter = converter.skip_css_vars(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[38;5;1mred").unwrap(),
+    ///     "<span style='color:#dc322f'>red</span>",
+    /// );
+    /// ```
+    pub fn palette(mut self, palette: ThemePalette) -> Self {
+        self.theme = Some(palette);
+        self
+    }
+
+    /// Uses different colors depending on whether the page has a light or a
+    /// dark color scheme, without a stylesheet.
+    ///
+    /// The 16 colors of the palette (including the first 16 8-bit colors) are
+    /// emitted with the CSS `light-dark()` function, e.g.
+    /// `color:light-dark(#c00,#f55)`, which picks the color matching the
+    /// `color-scheme` of the element (usually `prefers-color-scheme`). Other
+    /// colors are the same in both themes. With
+    /// [`faint_blend`](Converter::faint_blend), faint text is blended with the
+    /// foreground and background of each theme instead of the colors of the
+    /// [`FaintBlend`].
+    ///
+    /// When CSS variables are used for the 4-bit colors (the default, see
+    /// [`skip_css_vars`](Converter::skip_css_vars)), the stylesheet defining
+    /// them is expected to adapt to the color scheme, so only the dark color
+    /// is used as fallback.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, ThemePalette};
+    ///
+    /// let mut colors = [[0; 3]; 16];
+    /// colors[1] = [0xcc, 0, 0];
+    /// let light = ThemePalette::new([0, 0, 0], [255, 255, 255], colors);
+    /// colors[1] = [0xff, 0x55, 0x55];
+    /// let dark = ThemePalette::new([255, 255, 255], [0, 0, 0], colors);
+    ///
+    /// let converter = Converter::new().skip_css_vars(true).dual_theme(light, dark);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[31mred").unwrap(),
+    ///     "<span style='color:light-dark(#cc0000,#ff5555)'>red</span>",
+    /// );
+    ///
+    /// let converter = Converter::new().dual_theme(light, dark);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[31mred").unwrap(),
+    ///     "<span style='color:var(--red,#ff5555)'>red</span>",
+    /// );
+    /// ```
+    pub fn dual_theme(mut self, light: ThemePalette, dark: ThemePalette) -> Self {
+        self.dual_theme = Some((light, dark));
+        self
+    }
+
+    /// Emits only the colors of the dark theme set with
+    /// [`dual_theme`](Converter::dual_theme), instead of using the CSS
+    /// `light-dark()` function.
+    ///
+    /// This is useful for browsers that don't support `light-dark()`, which
+    /// ignore styles that use it.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, ThemePalette};
+    ///
+    /// let light = ThemePalette::new([0, 0, 0], [255, 255, 255], [[0xcc, 0, 0]; 16]);
+    /// let dark = ThemePalette::new([255, 255, 255], [0, 0, 0], [[0xff, 0x55, 0x55]; 16]);
+    /// let converter = Converter::new()
+    ///     .skip_css_vars(true)
+    ///     .dual_theme(light, dark)
+    ///     .skip_light_dark(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[31mred").unwrap(),
+    ///     "<span style='color:#ff5555'>red</span>",
+    /// );
+    /// ```
+    pub fn skip_light_dark(mut self, skip: bool) -> Self {
+        self.skip_light_dark = skip;
+        self
+    }
+
+    /// Adapts text colors that are hard to read on the background of the
+    /// [`palette`](Converter::palette) or of each theme of the
+    /// [`dual_theme`](Converter::dual_theme).
+    ///
+    /// Output meant for a dark terminal often uses colors like bright yellow or
+    /// light gray, which are unreadable on a light background. With this
+    /// option, the text colors that aren't part of the 16 colors of the
+    /// palette (8-bit colors from 16 to 255 and RGB colors) are darkened on a
+    /// light background and lightened on a dark background, until their
+    /// contrast ratio to the background is at least 4.5:1, the minimum for
+    /// normal text in WCAG 2. The 16 colors are taken from the palette, which
+    /// should be chosen for its background, like the light variants in
+    /// [`ThemePalette::default_light`]. Background colors aren't changed.
+    ///
+    /// With `dual_theme`, an adapted color can differ between the themes, so
+    /// it is emitted with `light-dark()` like the colors of the palette.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, ThemePalette};
+    ///
+    /// let converter = Converter::new()
+    ///     .palette(ThemePalette::default_light())
+    ///     .adapt_to_theme(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[38;5;226myellow").unwrap(),
+    ///     "<span style='color:#787800'>yellow</span>",
+    /// );
+    /// ```
+    pub fn adapt_to_theme(mut self, adapt: bool) -> Self {
+        self.adapt_to_theme = adapt;
+        self
+    }
+
+    /// Omits text colors that look like the default text color, and
+    /// background colors that look like the background color. Default:
+    /// `false`.
+    ///
+    /// Many programs set a color like white (`\x1b[37m`) for normal text, so
+    /// it is wrapped in a `<span>` although it looks like the text around it.
+    /// With this option, such colors are treated like the default colors. The
+    /// default colors are the [`default_colors`](Converter::default_colors),
+    /// if they are set. Otherwise, they are those of the
+    /// [`palette`](Converter::palette), or of both themes of the
+    /// [`dual_theme`](Converter::dual_theme) (only the dark one with
+    /// [`skip_light_dark`](Converter::skip_light_dark)). Without a theme, they
+    /// are the colors of the [`faint_blend`](Converter::faint_blend), or those
+    /// of [`ThemePalette::default_dark`].
+    ///
+    /// Colors are compared as they are emitted, e.g. as the fallback of a CSS
+    /// variable, so a stylesheet that changes the variables should match the
+    /// theme. Reverse video (`\x1b[7m`) uses the same default colors for the
+    /// colors that aren't set, so it looks the same with this option.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, ThemePalette};
+    ///
+    /// let converter = Converter::new().elide_default_colors(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[37mfoo \x1b[31mbar\x1b[0m").unwrap(),
+    ///     "foo <span style='color:var(--red,#a00)'>bar</span>",
+    /// );
+    ///
+    /// let converter = converter.palette(ThemePalette::default_light());
+    /// assert_eq!(
+    ///     converter.convert("\x1b[30;47mfoo").unwrap(),
+    ///     "<span style='background:var(--white,#aaaaaa)'>foo</span>",
+    /// );
+    /// ```
+    pub fn elide_default_colors(mut self, elide: bool) -> Self {
+        self.elide_default_colors = elide;
+        self
+    }
+
+    /// Sets the default text and background colors, each as `[red, green,
+    /// blue]`, e.g. those of the page that contains the HTML.
+    ///
+    /// Reverse video (`\x1b[7m`) swaps the text and background colors. A color
+    /// that isn't set is replaced with the default one, so reversed text
+    /// without colors has the default text color as its background. By
+    /// default, the default colors are those of the
+    /// [`palette`](Converter::palette), or of the dark theme of the
+    /// [`dual_theme`](Converter::dual_theme). Without a theme, they are the
+    /// colors of the [`faint_blend`](Converter::faint_blend), or those of
+    /// [`ThemePalette::default_dark`], i.e. light gray on black.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::Converter;
+    ///
+    /// let converter = Converter::new();
+    /// assert_eq!(
+    ///     converter.convert("\x1b[7mfoo\x1b[31mbar").unwrap(),
+    ///     "<span style='color:#000000'><span style='background:#aaaaaa'>foo</span>\
+    ///      <span style='background:var(--red,#a00)'>bar</span></span>",
+    /// );
+    ///
+    /// let converter = converter.default_colors([0xab, 0xb2, 0xbf], [0x1e, 0x21, 0x27]);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[7mfoo").unwrap(),
+    ///     "<span style='color:#1e2127'><span style='background:#abb2bf'>foo</span></span>",
+    /// );
+    /// ```
+    pub fn default_colors(mut self, foreground: [u8; 3], background: [u8; 3]) -> Self {
+        self.default_colors = Some([foreground, background]);
+        self
+    }
+
+    /// Reduces the number of grays used for 8-bit colors.
+    ///
+    /// With `Some(n)`, the colors of the grayscale ramp (232-255) and the grays
+    /// of the 6×6×6 color cube are mapped to the nearest of `n` evenly spaced
+    /// grays from black to white. Values below 2 are treated as 2. Other colors
+    /// are not changed.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().grayscale_levels(Some(3));
+    /// assert_eq!(
+    ///     converter.convert("\x1b[38;5;240mgray").unwrap(),
+    ///     "<span style='color:#808080'>gray</span>",
+    /// );
+    /// ```
+    pub fn grayscale_levels(mut self, levels: Option<u8>) -> Self {
+        self.grayscale_levels = levels;
+        self
+    }
+
+    /// Replaces the colors of the 8-bit palette, as `[red, green, blue]`.
+    ///
+    /// The colors 16-255 (the 6×6×6 color cube and the grayscale ramp) are
+    /// taken from the array instead of the default table. The first 16
+    /// entries are ignored: these are the colors of the 16-color palette,
+    /// which stay the same as the 4-bit colors, so that `38;5;1` and `31`
+    /// agree. Set them with [`palette`](Converter::palette) instead. With
+    /// [`grayscale_levels`](Converter::grayscale_levels), the grays of the
+    /// default table are quantized, and the other colors are taken from the
+    /// array.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let mut colors = [[0; 3]; 256];
+    /// colors[200] = [0xd7, 0x5f, 0xaf];
+    /// let converter = ansi_to_html::Converter::new().eight_bit_palette(Some(colors));
+    /// assert_eq!(
+    ///     converter.convert("\x1b[38;5;200mpink").unwrap(),
+    ///     "<span style='color:#d75faf'>pink</span>",
+    /// );
+    /// ```
+    pub fn eight_bit_palette(mut self, colors: Option<[[u8; 3]; 256]>) -> Self {
+        self.eight_bit_palette = colors.map(Arc::new);
+        self
+    }
+
+    /// Sets the maximum length of an escape sequence in bytes. The default is
+    /// 4096.
+    ///
+    /// Longer sequences are not interpreted; like unsupported sequences that
+    /// are not recognized, they are emitted as text. This bounds the work spent
+    /// on a single sequence, e.g. for crafted input containing megabytes of
+    /// digits after `ESC [`. In
+    /// [`convert_append`](Converter::convert_append), an incomplete sequence at
+    /// the end of the input is only kept for the next call if it is not longer
+    /// than this.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().max_sequence_len(8);
+    /// assert_eq!(converter.convert("\x1b[1;1;1;1mA").unwrap(), "\x1b[1;1;1;1mA");
+    /// assert_eq!(converter.convert("\x1b[1;1mA").unwrap(), "<b>A</b>");
+    /// ```
+    pub fn max_sequence_len(mut self, len: usize) -> Self {
+        self.max_sequence_len = len;
+        self
+    }
+
+    /// Pretty-prints the HTML, with every tag and text run on its own line,
+    /// indented by its nesting depth.
+    ///
+    /// This is meant for inspecting the generated markup. The line breaks and
+    /// indentation are wrapped in HTML comments, so the rendered text is the
+    /// same as without this option, even inside `<pre>`. Removing all
+    /// `<!--\n   -->` comments yields the compact output.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().pretty(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1mbold \x1b[3mitalic").unwrap(),
+    ///     "<b><!--\n  -->bold <!--\n  --><i><!--\n    -->italic<!--\n  --></i><!--\n--></b>",
+    /// );
+    /// ```
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Escapes HTML in the input that doesn't look pre-escaped, if
+    /// [`skip_escape`](Converter::skip_escape) is enabled.
+    ///
+    /// This is a middle ground between full escaping and passing the input
+    /// through unchanged: `<` and `&` are escaped, unless they start a
+    /// character reference (like `&lt;` or `&#39;`) or one of the tags
+    /// declared with [`allowed_tags`](Converter::allowed_tags). Allowed tags
+    /// may only have `class` and `title` attributes; other tags are escaped.
+    /// This includes tags with a `style` attribute, which could position
+    /// content over the page or load resources.
+    ///
+    /// This has no effect unless `skip_escape` is enabled. The default
+    /// behavior is unchanged: Without `skip_escape`, everything is escaped;
+    /// with `skip_escape` but without `sanitize`, nothing is escaped.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::Converter;
+    ///
+    /// let converter = Converter::new()
+    ///     .skip_escape(true)
+    ///     .sanitize(true)
+    ///     .allowed_tags(&["b"]);
+    ///
+    /// let input = "<b>a &amp; b</b> & <img src=x onerror=alert(1)>";
+    /// assert_eq!(
+    ///     converter.convert(input).unwrap(),
+    ///     "<b>a &amp; b</b> &amp; &lt;img src=x onerror=alert(1)>",
+    /// );
+    /// ```
+    pub fn sanitize(mut self, sanitize: bool) -> Self {
+        self.sanitize = sanitize;
+        self
+    }
+
+    /// Declares the tags that are kept when [`sanitize`](Converter::sanitize)
+    /// is enabled, e.g. `&["b", "span"]`. Tag names are compared
+    /// case-insensitively.
+    pub fn allowed_tags(mut self, tags: &[&str]) -> Self {
+        self.allowed_tags = tags.iter().map(|&t| t.to_owned()).collect();
+        self
+    }
+
+    /// Sets a function that transforms every run of text before it is
+    /// written, e.g. to turn URLs into links.
+    ///
+    /// A run is the text between two changes of the styling, so the function
+    /// never sees escape sequences or tags added by the converter. It is called
+    /// with the unescaped text and returns a [`Transformed`] value; replacement
+    /// text is escaped, unless [`skip_escape`](Converter::skip_escape) is
+    /// enabled, in which case the function receives and returns HTML.
+    ///
+    /// Text that is split across several runs (e.g. a URL whose second half is
+    /// bold) is passed to the function in several calls, and in
+    /// [`convert_append`](Converter::convert_append), runs are also split
+    /// where the input is split.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, Piece, Transformed};
+    /// use regex::Regex;
+    ///
+    /// let url = Regex::new(r"https?://[^\s<>]+").unwrap();
+    /// let converter = Converter::new().text_transform(move |text| {
+    ///     if !url.is_match(text) {
+    ///         return Transformed::Unchanged;
+    ///     }
+    ///     let mut pieces = Vec::new();
+    ///     let mut last = 0;
+    ///     for m in url.find_iter(text) {
+    ///         pieces.push(Piece::Text(text[last..m.start()].to_owned()));
+    ///         pieces.push(Piece::link(m.as_str(), m.as_str()));
+    ///         last = m.end();
+    ///     }
+    ///     pieces.push(Piece::Text(text[last..].to_owned()));
+    ///     Transformed::Pieces(pieces)
+    /// });
+    ///
+    /// assert_eq!(
+    ///     converter.convert("see \x1b[1mhttps://example.com/?a&b\x1b[0m <here>").unwrap(),
+    ///     "see <b><a href='https://example.com/?a&amp;b'>https://example.com/?a&amp;b</a></b> &lt;here&gt;",
+    /// );
+    /// ```
+    pub fn text_transform(
+        mut self,
+        transform: impl Fn(&str) -> Transformed + Send + Sync + 'static,
+    ) -> Self {
+        self.text_transform = Some(transform::TextTransform::new(transform));
+        self
+    }
+
+    /// Wraps every line of every text run in a `<bdi>` element, which isolates
+    /// it from the surrounding text for the Unicode bidirectional algorithm.
+    ///
+    /// Without this, right-to-left text (e.g. Arabic or Hebrew) can swap
+    /// places with neighboring left-to-right text that has a different style,
+    /// although a terminal shows them in the order they were printed. With
+    /// this option, the runs stay in order, while the direction of the text
+    /// within each run is still detected.
+    ///
+    /// The [`text_transform`](Converter::text_transform) is called for each
+    /// line of a run separately.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().bidi_isolate(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1mשלום\x1b[0m /tmp\nok").unwrap(),
+    ///     "<b><bdi>שלום</bdi></b><bdi> /tmp</bdi>\n<bdi>ok</bdi>",
+    /// );
+    /// ```
+    pub fn bidi_isolate(mut self, isolate: bool) -> Self {
+        self.bidi_isolate = isolate;
+        self
+    }
+
+    /// Writes spaces and line breaks at the end of a line without the styles
+    /// that aren't visible on them.
+    ///
+    /// Whether a program colors trailing spaces often changes between versions,
+    /// although nothing visible changes. This option makes the output more
+    /// stable, e.g. for snapshot tests: Line breaks, and spaces before a line
+    /// break, are written outside of color, bold, faint and italic tags, like
+    /// the whitespace at the end of the input (see the [crate docs](crate)).
+    /// Spaces with a background color are kept as is, and underlining and
+    /// crossing out are kept, since they are visible.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().normalize_trailing_whitespace(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1mfoo  \x1b[0m\nbar").unwrap(),
+    ///     converter.convert("\x1b[1mfoo\x1b[0m  \nbar").unwrap(),
+    /// );
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1mfoo  \x1b[0m\nbar").unwrap(),
+    ///     "<b>foo</b>  \nbar",
+    /// );
+    /// ```
+    pub fn normalize_trailing_whitespace(mut self, normalize: bool) -> Self {
+        self.normalize_trailing_whitespace = normalize;
+        self
+    }
+
+    /// Closes all tags before every line break, so every line of the output
+    /// is valid HTML on its own. Default: `false`.
+    ///
+    /// The styles that are active after the line break are opened again
+    /// before the next text, so the output renders the same. This is useful
+    /// when the HTML is processed line by line, e.g. to add line numbers; see
+    /// also [`convert_lines`](Converter::convert_lines). A line break in a
+    /// sequence that is kept as text (see
+    /// [`max_sequence_len`](Converter::max_sequence_len)) closes the tags as
+    /// well. This can't be combined with [`pretty`](Converter::pretty), which
+    /// adds line breaks inside of comments.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().line_safe(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1mfoo\nbar\x1b[0m\n").unwrap(),
+    ///     "<b>foo</b>\n<b>bar</b>\n",
+    /// );
+    /// ```
+    pub fn line_safe(mut self, line_safe: bool) -> Self {
+        self.line_safe = line_safe;
+        self
+    }
+
+    /// Wraps every line of the output in an element, e.g. to number the lines
+    /// with CSS counters. Default: `None`.
+    ///
+    /// Like with [`line_safe`](Converter::line_safe), all tags are closed
+    /// before a line break, so the styles never cross the boundary of a
+    /// wrapper. The line break is after the wrapper, and so is the `\r` of a
+    /// `\r\n`. An empty line is wrapped as well, but there is no wrapper
+    /// after a line break at the end of the input. The wrapper of an empty
+    /// line isn't removed by the optimization (see
+    /// [`skip_optimize`](Converter::skip_optimize)).
+    ///
+    /// The lines are numbered from 1 in every conversion. A
+    /// [`ConverterStream`] and [`convert_chunked`](Converter::convert_chunked)
+    /// continue the numbering, and so does
+    /// [`convert_append`](Converter::convert_append) with its [`StyleState`].
+    /// A line that is split between two calls of `convert_append` is wrapped
+    /// in two elements with the same number. This can't be combined with
+    /// [`pretty`](Converter::pretty).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, LineWrapper};
+    ///
+    /// let wrapper = LineWrapper::new("span", Some("line"), true).unwrap();
+    /// let converter = Converter::new().line_wrapper(Some(wrapper));
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1mfoo\n\nbar\x1b[0m\n").unwrap(),
+    ///     "<span class='line' data-line='1'><b>foo</b></span>\n\
+    ///      <span class='line' data-line='2'></span>\n\
+    ///      <span class='line' data-line='3'><b>bar</b></span>\n",
+    /// );
+    /// ```
+    pub fn line_wrapper(mut self, wrapper: Option<LineWrapper>) -> Self {
+        self.line_wrapper = wrapper;
+        self
+    }
+
+    /// Converts OSC 8 hyperlinks to `<a href='…'>` elements. By default, they
+    /// are stripped and only their text is kept.
+    ///
+    /// A hyperlink starts with `ESC]8;;URL ESC\` (or `BEL` instead of `ESC\`)
+    /// and ends with `ESC]8;; ESC\`. Parameters such as `id=1` between the
+    /// first two semicolons are ignored. Links are only rendered if the URL
+    /// scheme is allowed by the [`link_schemes`](Converter::link_schemes);
+    /// otherwise only the text is kept.
+    ///
+    /// SGR codes don't end a link. The `<a>` tag is outside of all other
+    /// tags, so they're closed and re-opened when a link starts or ends.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().render_links(true);
+    /// assert_eq!(
+    ///     converter
+    ///         .convert("\x1b]8;;https://example.com/?a&b\x1b\\\x1b[1mlink\x1b[0m\x1b]8;;\x1b\\")
+    ///         .unwrap(),
+    ///     "<a href='https://example.com/?a&amp;b'><b>link</b></a>",
+    /// );
+    /// ```
+    pub fn render_links(mut self, render: bool) -> Self {
+        self.render_links = render;
+        self
+    }
+
+    /// Sets the URL schemes of links that are rendered with
+    /// [`render_links`](Converter::render_links). The default allows `http`,
+    /// `https`, `mailto` and `file`.
+    pub fn link_schemes(mut self, schemes: UrlSchemes) -> Self {
+        self.link_schemes = schemes;
+        self
+    }
+
+    /// Sets how line endings are normalized. Captured terminal output often
+    /// contains `\r\n`, and sometimes a lone `\r` at the end of a line.
+    ///
+    /// - [`Newlines::Keep`] (the default) keeps them as they are.
+    /// - [`Newlines::CrLfToLf`] replaces `\r\n` with `\n`.
+    /// - [`Newlines::Lf`] also replaces lone `\r` with `\n`.
+    ///
+    /// A `\r` followed by escape sequences and a `\n` is a line ending as well.
+    /// The line endings are normalized before anything else, so a lone `\r` in
+    /// the middle of a line is just kept or replaced. The `\r` that are kept
+    /// overwrite text like in a terminal with
+    /// [`carriage_return_overwrites`](Converter::carriage_return_overwrites).
+    ///
+    /// With [`convert_append`](Converter::convert_append), a `\r` at the end of
+    /// the input is stored in the state until the next call, since it may be
+    /// followed by a `\n`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, Newlines};
+    ///
+    /// let input = "\x1b[1mbold\r\n50%\r100%\r\n";
+    /// let crlf = Converter::new().newline_normalization(Newlines::CrLfToLf);
+    /// assert_eq!(crlf.convert(input).unwrap(), "<b>bold\n50%\r100%</b>\n");
+    /// let lf = Converter::new().newline_normalization(Newlines::Lf);
+    /// assert_eq!(lf.convert(input).unwrap(), "<b>bold\n50%\n100%</b>\n");
+    /// ```
+    pub fn newline_normalization(mut self, newlines: Newlines) -> Self {
+        self.newlines = newlines;
+        self
+    }
+
+    /// Moves back to the start of the line at a `\r` that isn't followed by a
+    /// line break, so the text after it overwrites the text before it like in
+    /// a terminal. Default: `false`.
+    ///
+    /// Progress bars, e.g. of `cargo`, `pip` and `wget`, print every frame on
+    /// the same line after a `\r`, so only the last frame is shown. If it is
+    /// shorter than the text before it, the rest of the text stays, with its
+    /// styling. A `\r\n` is a line break as usual. A `\r` at the end of the
+    /// input is removed.
+    ///
+    /// Every character counts as one column, except that characters that
+    /// extend the preceding character (e.g. combining accents) are in its
+    /// column. An HTML entity is a single character, as well as a tag in the
+    /// input with [`skip_escape`](Converter::skip_escape). Text isn't
+    /// overwritten across calls of [`convert_append`](Converter::convert_append)
+    /// or chunks of a [stream](Converter::begin), since it's already written.
+    ///
+    /// The erase-in-line sequences are interpreted as well, which spinners
+    /// (e.g. of `indicatif`) print after the `\r` to clear the previous frame:
+    /// `ESC[K` erases the line from the cursor to the end, `ESC[1K` from the
+    /// start to the cursor and `ESC[2K` the whole line. Erased text before the
+    /// cursor becomes spaces without styling.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().carriage_return_overwrites(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1m 50%\x1b[0m loading\r\x1b[32m100%\x1b[0m\r\ndone").unwrap(),
+    ///     "<span style='color:var(--green,#0a0)'>100%</span> loading\r\ndone",
+    /// );
+    /// assert_eq!(converter.convert("⠋ loading\r\x1b[2K✔ done").unwrap(), "✔ done");
+    /// ```
+    pub fn carriage_return_overwrites(mut self, overwrite: bool) -> Self {
+        self.carriage_return_overwrites = overwrite;
+        self
+    }
+
+    /// Moves the cursor up and down between the last lines, so that progress
+    /// output that redraws several lines shows only the last frame. This
+    /// implies [`carriage_return_overwrites`](Converter::carriage_return_overwrites).
+    /// Default: `false`.
+    ///
+    /// Multi-line progress bars, e.g. of `cargo` and `docker`, move the cursor
+    /// up with `ESC[<n>A` and overwrite the lines below it. These sequences
+    /// are interpreted:
+    ///
+    /// - `ESC[<n>A` and `ESC[<n>B` move the cursor up or down by `n` lines,
+    ///   staying in the same column
+    /// - `ESC[<n>G` moves the cursor to column `n`, starting at 1
+    /// - the erase-in-line sequences, e.g. `ESC[2K`
+    ///
+    /// A line break moves the cursor to the start of the next line. The
+    /// cursor stops at the first and the last line, since the output doesn't
+    /// scroll, and can only reach the last 256 lines. Text written after the
+    /// end of a line is preceded by spaces. This isn't a terminal emulator:
+    /// other sequences that move the cursor are removed as before, and long
+    /// lines don't wrap. As with `carriage_return_overwrites`, the cursor
+    /// can't move across calls of [`convert_append`](Converter::convert_append),
+    /// chunks of a [stream](Converter::begin) or chunks of
+    /// [`convert_chunked`](Converter::convert_chunked).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().emulate_cursor_movement(true);
+    /// let frames = "a: 10%\nb: 20%\n\x1b[2A\ra: 60%\n\x1b[2Kb: done\n";
+    /// assert_eq!(converter.convert(frames).unwrap(), "a: 60%\nb: done\n");
+    /// ```
+    pub fn emulate_cursor_movement(mut self, emulate: bool) -> Self {
+        self.emulate_cursor_movement = emulate;
+        self
+    }
+
+    /// Returns whether text can be overwritten, see
+    /// [`carriage_return_overwrites`](Converter::carriage_return_overwrites)
+    fn overwrites(&self) -> bool {
+        self.carriage_return_overwrites || self.emulate_cursor_movement
+    }
+
+    /// Ignores invalid SGR codes instead of returning an error.
+    ///
+    /// By default, conversion fails with [`Error::ParseInt`] for numbers that
+    /// are too large (e.g. `\x1b[256m`), and with [`Error::InvalidAnsi`] for
+    /// unknown codes or incomplete colors (e.g. `\x1b[38;5m`). In lossy mode,
+    /// these codes are ignored like unsupported codes, while the other codes
+    /// of the same sequence are still applied. This is useful for large logs,
+    /// where a single corrupted sequence shouldn't prevent the conversion.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().lossy(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1;256mbold\x1b[0m \x1b[38;5mplain").unwrap(),
+    ///     "<b>bold</b> plain",
+    /// );
+    /// assert!(ansi_to_html::convert("\x1b[1;256mbold").is_err());
+    /// ```
+    pub fn lossy(mut self, lossy: bool) -> Self {
+        self.lossy = lossy;
+        self
+    }
+
+    /// Keeps characters that extend the preceding character in its element
+    /// when the style changes between them. Enabled by default.
+    ///
+    /// Otherwise, a combining accent after a color change (e.g. in
+    /// `e\x1b[31m\u{301}`) would be in a different element than its base
+    /// character, and browsers render it detached from it. With this option,
+    /// the new style starts after combining marks, variation selectors, emoji
+    /// skin tone modifiers and zero-width joiners, and after the character
+    /// that follows a zero-width joiner, so emoji ZWJ sequences stay together.
+    /// Only the common blocks of these characters are recognized, not all
+    /// grapheme cluster boundaries.
+    ///
+    /// Characters aren't attached across the chunks of
+    /// [`convert_chunked`](Converter::convert_chunked) and
+    /// [`convert_append`](Converter::convert_append), since the tags are
+    /// closed at the end of each chunk.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let input = "e\x1b[31m\u{301}x";
+    /// assert_eq!(
+    ///     ansi_to_html::convert(input).unwrap(),
+    ///     "e\u{301}<span style='color:var(--red,#a00)'>x</span>",
+    /// );
+    /// let converter = ansi_to_html::Converter::new().attach_combining(false);
+    /// assert_eq!(
+    ///     converter.convert(input).unwrap(),
+    ///     "e<span style='color:var(--red,#a00)'>\u{301}x</span>",
+    /// );
+    /// ```
+    pub fn attach_combining(mut self, attach: bool) -> Self {
+        self.attach_combining = attach;
+        self
+    }
+
+    /// Renders blinking text (SGR codes 5 and 6) as `<span class='blink'>`.
+    /// By default, blinking is ignored.
+    ///
+    /// Slow and rapid blinking use the same class. The class name starts with
+    /// the [`four_bit_var_prefix`](Converter::four_bit_var_prefix), e.g.
+    /// `term-blink`. The animation isn't included in the HTML; see
+    /// [`blink_css`](Converter::blink_css).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().render_blink(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[5mblink\x1b[25m steady").unwrap(),
+    ///     "<span class='blink'>blink</span> steady",
+    /// );
+    /// ```
+    pub fn render_blink(mut self, render: bool) -> Self {
+        self.render_blink = render;
+        self
+    }
+
+    /// Replaces concealed text (SGR code 8) with spaces, instead of only making
+    /// it transparent. Concealed text is usually a password or another secret,
+    /// which should not be in the HTML at all. Every character except line
+    /// breaks becomes one space, so the layout doesn't change.
+    ///
+    /// This applies to [`to_segments`](Converter::to_segments) as well.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let input = "Password: \x1b[8mhunter2\x1b[28m";
+    /// assert_eq!(
+    ///     ansi_to_html::convert(input).unwrap(),
+    ///     "Password: <span style='color:transparent'>hunter2</span>",
+    /// );
+    /// let converter = ansi_to_html::Converter::new().redact_concealed(true);
+    /// assert_eq!(
+    ///     converter.convert(input).unwrap(),
+    ///     "Password: <span style='color:transparent'>       </span>",
+    /// );
+    /// ```
+    pub fn redact_concealed(mut self, redact: bool) -> Self {
+        self.redact_concealed = redact;
+        self
+    }
+
+    /// Sets the tags used for bold, italic and crossed-out text.
+    ///
+    /// The default, [`TagStyle::Presentational`], uses `<b>`, `<i>` and
+    /// `<s>`. [`TagStyle::Semantic`] uses `<strong>`, `<em>` and `<del>`
+    /// instead, e.g. for pages whose CSS reset changes how `<b>` and `<i>` are
+    /// rendered. With [`OutputCompat::V0`], the optimization of HTML that is
+    /// part of the input (see [`skip_escape`](Converter::skip_escape)) only
+    /// handles the selected tags.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, TagStyle};
+    ///
+    /// let converter = Converter::new().tag_style(TagStyle::Semantic);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1;3mbold italic\x1b[23m bold").unwrap(),
+    ///     "<strong><em>bold italic</em> bold</strong>",
+    /// );
+    /// ```
+    pub fn tag_style(mut self, style: TagStyle) -> Self {
+        self.tag_style = style;
+        self
+    }
+
+    /// Wraps each run of text in a single `<span>` with the combined styles,
+    /// instead of nesting an element for each style. Default: `false`.
+    ///
+    /// Bold, italic and the lines (underline, overline and crossing out) are
+    /// CSS properties then, so the [`tag_style`](Converter::tag_style) is
+    /// ignored. The output is easier to post-process and much smaller when the
+    /// styles change often, since all tags are closed and reopened at once. It
+    /// renders the same as the nested elements. Links are still `<a>` elements
+    /// outside of the spans.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().flat_spans(true);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1;4;31mwarning\x1b[24m:\x1b[0m ok").unwrap(),
+    ///     "<span style='font-weight:bold;color:var(--red,#a00);text-decoration:underline'>\
+    ///     warning</span><span style='font-weight:bold;color:var(--red,#a00)'>:</span> ok",
+    /// );
+    /// ```
+    pub fn flat_spans(mut self, flat: bool) -> Self {
+        self.flat_spans = flat;
+        self
+    }
+
+    /// Sets the quotes around the attribute values of the generated tags. The
+    /// default is [`Quote::Single`]; [`Quote::Double`] is for tools that only
+    /// accept double quotes, e.g. some XHTML serializers.
+    ///
+    /// Quotes within the values are escaped either way. HTML in the input (see
+    /// [`skip_escape`](Converter::skip_escape)) and HTML returned by a
+    /// [`text_transform`](Converter::text_transform) is kept as is. With
+    /// [`OutputCompat::V0`], the optimization of HTML in the input handles
+    /// both quotes.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, Quote};
+    ///
+    /// let converter = Converter::new().attribute_quotes(Quote::Double);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[2;31mfaint red").unwrap(),
+    ///     r#"<span style="opacity:0.67"><span style="color:var(--red,#a00)">faint red</span></span>"#,
+    /// );
+    /// ```
+    pub fn attribute_quotes(mut self, quote: Quote) -> Self {
+        self.attribute_quotes = quote;
+        self
+    }
+
+    /// Keeps the exact output of an earlier version. Default:
+    /// [`OutputCompat::Current`].
+    ///
+    /// New versions may change the HTML, e.g. to remove more redundant tags,
+    /// as long as it renders the same. This breaks tests that compare the
+    /// output byte for byte. With [`OutputCompat::V0`], such changes are
+    /// skipped, so the tests can be updated separately from the dependency.
+    /// Changes that fix incorrect output are not skipped.
+    ///
+    /// `OutputCompat::V0` will be deprecated in 1.1 and removed in 2.0.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, OutputCompat};
+    ///
+    /// let converter = Converter::new().output_compat(OutputCompat::V0);
+    /// assert_eq!(
+    ///     converter.convert("\x1b[1mbold\x1b[0m").unwrap(),
+    ///     "<b>bold</b>",
+    /// );
+    /// ```
+    pub fn output_compat(mut self, compat: OutputCompat) -> Self {
+        self.output_compat = compat;
+        self
+    }
+
+    /// Returns the input unchanged if it [looks like HTML](looks_like_html),
+    /// e.g. because it was converted already. Default: `false`.
+    ///
+    /// Converting HTML again escapes its entities a second time, e.g. `&lt;`
+    /// becomes `&amp;lt;`, so it is displayed as `&lt;`. Input containing
+    /// escape sequences is always converted. The check is done by
+    /// [`convert`](Converter::convert),
+    /// [`convert_cow`](Converter::convert_cow),
+    /// [`convert_to_writer`](Converter::convert_to_writer) and
+    /// [`convert_line`](Converter::convert_line). With the `tracing` feature,
+    /// they emit a warning event when the input is returned unchanged.
+    ///
+    /// Since the input isn't escaped, this must not be enabled for untrusted
+    /// input. It has no effect with [`skip_escape`](Converter::skip_escape).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new();
+    /// let html = converter.convert("\x1b[1m<b> & </b>").unwrap();
+    /// assert_eq!(html, "<b>&lt;b&gt; &amp; &lt;/b&gt;</b>");
+    /// assert_eq!(
+    ///     converter.convert(&html).unwrap(),
+    ///     "&lt;b&gt;&amp;lt;b&amp;gt; &amp;amp; &amp;lt;/b&amp;gt;&lt;/b&gt;",
+    /// );
+    ///
+    /// let converter = converter.detect_double_escape(true);
+    /// assert_eq!(converter.convert(&html).unwrap(), html);
+    /// ```
+    pub fn detect_double_escape(mut self, detect: bool) -> Self {
+        self.detect_double_escape = detect;
+        self
+    }
+
+    /// Returns the CSS that animates the class of blinking text, see
+    /// [`render_blink`](Converter::render_blink). The text is hidden for half
+    /// a second every second, unless the user prefers reduced motion.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new().four_bit_var_prefix(Some("term-".into()));
+    /// assert!(converter.blink_css().starts_with(".term-blink {"));
+    /// ```
+    #[must_use]
+    pub fn blink_css(&self) -> String {
+        let prefix = self.four_bit_var_prefix.as_deref().unwrap_or_default();
+        format!(
+            ".{prefix}blink {{
+  animation: {prefix}blink 1s step-end infinite;
+}}
+@keyframes {prefix}blink {{
+  50% {{
+    visibility: hidden;
+  }}
+}}
+@media (prefers-reduced-motion: reduce) {{
+  .{prefix}blink {{
+    animation: none;
+  }}
+}}
+"
+        )
+    }
+
+    /// Checks the options and prepares the converter for converting many
+    /// inputs.
+    ///
+    /// Returns an error if an option is invalid or has no effect because of
+    /// another option, see [`ConfigError`]. The
+    /// [`PreparedConverter`] computes the HTML of the 4-bit colors
+    /// once, instead of for every color change. The output is the same.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{ConfigError, Converter};
+    ///
+    /// let converter = Converter::new().four_bit_var_prefix(Some("term-".into())).build().unwrap();
+    /// assert_eq!(
+    ///     converter.convert("\x1b[31mred").unwrap(),
+    ///     "<span style='color:var(--term-red,#a00)'>red</span>",
+    /// );
+    ///
+    /// let error = Converter::new().sanitize(true).build().unwrap_err();
+    /// assert_eq!(error, ConfigError::SanitizeWithoutSkipEscape);
+    /// ```
+    pub fn build(self) -> Result<PreparedConverter, ConfigError> {
+        prepared::check(&self)?;
+        Ok(PreparedConverter::new(self))
+    }
+
     /// Converts a string containing ANSI escape codes to HTML.
+    ///
+    /// If a sequence is invalid, the error contains its position in the
+    /// input, see [`Error::span`].
     pub fn convert(&self, input: &str) -> Result<String, Error> {
-        let Converter {
-            skip_escape,
-            skip_optimize,
-            ref four_bit_var_prefix,
-        } = *self;
+        self.convert_cow(input).map(Cow::into_owned)
+    }
+
+    /// Converts bytes containing ANSI escape codes to HTML, e.g. the output of
+    /// a process that was read from a pty.
+    ///
+    /// Invalid UTF-8, like binary data or a multibyte character that is cut
+    /// off, is replaced with `U+FFFD` (`�`), like with
+    /// [`String::from_utf8_lossy`]. If the input is valid UTF-8, it isn't
+    /// copied before it is converted. An escape sequence interrupted by
+    /// invalid UTF-8, e.g. `ESC[3\xff1m`, isn't recognized, so it is
+    /// displayed as text. The [span](Error::span) of an error is the byte
+    /// range in `input`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new();
+    /// assert_eq!(
+    ///     converter.convert_bytes(b"\x1b[1mbold\xff\x1b[0m \xe2\x9c").unwrap(),
+    ///     "<b>bold\u{fffd}</b> \u{fffd}",
+    /// );
+    /// ```
+    pub fn convert_bytes(&self, input: &[u8]) -> Result<String, Error> {
+        match String::from_utf8_lossy(input) {
+            Cow::Borrowed(input) => self.convert(input),
+            Cow::Owned(text) => self.convert(&text).map_err(|e| e.in_bytes(input)),
+        }
+    }
+
+    /// Converts a string containing ANSI escape codes to HTML, and borrows
+    /// the input if it is already the HTML.
+    ///
+    /// This is the case for plain text without escape sequences and without
+    /// characters that have to be escaped (`<>&'"`), unless a
+    /// [`text_transform`](Converter::text_transform),
+    /// [`bidi_isolate`](Converter::bidi_isolate) or a
+    /// [`line_wrapper`](Converter::line_wrapper) is set. With
+    /// [`skip_escape`](Converter::skip_escape), only `<` has to be absent
+    /// with [`OutputCompat::V0`], since it optimizes tags in the input, and
+    /// none of them otherwise. Checking this is much faster
+    /// than converting, so this is useful when most of the input is plain
+    /// text, e.g. when converting a log line by line. Otherwise, the result is
+    /// the same as that of [`convert`](Converter::convert).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// let converter = ansi_to_html::Converter::new();
+    /// assert!(matches!(converter.convert_cow("plain text").unwrap(), Cow::Borrowed(_)));
+    /// assert_eq!(converter.convert_cow("a & b").unwrap(), "a &amp; b");
+    /// assert_eq!(converter.convert_cow("\x1b[1mbold").unwrap(), "<b>bold</b>");
+    /// ```
+    pub fn convert_cow<'a>(&self, input: &'a str) -> Result<Cow<'a, str>, Error> {
+        let phase = phase!("convert", input.len());
+        if self.is_unchanged(input) || self.is_converted(input) {
+            phase.output_len(input.len());
+            return Ok(Cow::Borrowed(input));
+        }
 
-        let html = if skip_escape {
-            html::ansi_to_html(input, ansi_regex(), four_bit_var_prefix.to_owned())?
+        // The HTML is built in blocks and joined once, instead of in a string
+        // that is copied whenever it grows. Escaping and tags make the HTML
+        // somewhat longer than the input.
+        let mut rope = Rope::new(input.len() + input.len() / 4);
+        self.write_html(input, &mut rope)?;
+        let html = rope.into_string();
+        let html = if self.pretty {
+            pretty::pretty(&html)
         } else {
-            let input = Esc(input).to_string();
-            html::ansi_to_html(&input, ansi_regex(), four_bit_var_prefix.to_owned())?
+            html
         };
 
-        let html = if skip_optimize { html } else { optimize(&html) };
+        phase.output_len(html.len());
+        Ok(Cow::Owned(html))
+    }
 
-        Ok(html)
+    /// Returns whether converting the input doesn't change it, because it
+    /// contains nothing that is converted, escaped or normalized
+    fn is_unchanged(&self, input: &str) -> bool {
+        if self.text_transform.is_some() || self.bidi_isolate || self.line_wrapper.is_some() {
+            return false;
+        }
+        let escape = !self.skip_escape || self.sanitize;
+        // the tags in the input are only changed by the optimization of `OutputCompat::V0`
+        let optimize_input = compat::optimizes_v0(self);
+        let normalize = self.newlines != Newlines::Keep || self.overwrites();
+        let is_special = |b: u8| {
+            (b == b'\x1b')
+                | ((escape | optimize_input) & (b == b'<'))
+                | (escape & ((b == b'>') | (b == b'&') | (b == b'\'') | (b == b'"')))
+                | (normalize & (b == b'\r'))
+        };
+        // without branches, the check of a chunk is vectorized
+        !(input.as_bytes().chunks(64))
+            .any(|chunk| chunk.iter().fold(false, |acc, &b| acc | is_special(b)))
     }
-}
 
-#[deprecated(note = "Use the `convert` method of the `Converter` builder")]
-pub fn convert_with_opts(input: &str, converter: &Converter) -> Result<String, Error> {
-    converter.convert(input)
-}
+    /// Returns whether the input is returned unchanged because it was
+    /// converted already, see
+    /// [`detect_double_escape`](Converter::detect_double_escape)
+    fn is_converted(&self, input: &str) -> bool {
+        let converted = self.detect_double_escape && !self.skip_escape && looks_like_html(input);
+        #[cfg(feature = "tracing")]
+        if converted {
+            tracing::warn!(
+                input_len = input.len(),
+                "the input looks like HTML and is not converted again"
+            );
+        }
+        converted
+    }
 
-const ANSI_REGEX: &str = r"\u{1b}(\[[0-9;?]*[A-HJKSTfhilmnsu]|\(B)";
-const OPT_REGEX_1: &str = r"<span \w+='[^']*'></span>|<b></b>|<i></i>|<u></u>|<s></s>";
-const OPT_REGEX_2: &str = "</b><b>|</i><i>|</u><u>|</s><s>";
+    /// Converts a string containing ANSI escape codes to HTML, and writes it
+    /// to `out` while converting.
+    ///
+    /// The output is the same as that of [`convert`](Converter::convert), but
+    /// the whole HTML isn't built in memory, which matters for large inputs
+    /// such as long terminal sessions. The input is converted in pieces of a
+    /// few KiB that end after a line break, and their HTML is written as soon
+    /// as it is converted. Only [`pretty`](Converter::pretty) needs the whole
+    /// HTML, so it is built in memory when pretty printing is enabled.
+    ///
+    /// If the input contains an invalid sequence, the HTML of the input before
+    /// it may have been written already. If writing fails, an
+    /// [`Error::Write`] is returned.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new();
+    /// let mut html = String::new();
+    /// converter.convert_to_writer("\x1b[1mfoo\nbar", &mut html).unwrap();
+    /// assert_eq!(html, "<b>foo\nbar</b>");
+    /// ```
+    pub fn convert_to_writer(&self, input: &str, out: &mut impl fmt::Write) -> Result<(), Error> {
+        if self.is_converted(input) {
+            return out.write_str(input).map_err(|err| Error::Write { err });
+        }
+        if self.pretty {
+            let html = self.convert(input)?;
+            return out.write_str(&html).map_err(|err| Error::Write { err });
+        }
+        let phase = phase!("convert", input.len());
+        let len = self.write_html(input, out)?;
+        phase.output_len(len);
+        Ok(())
+    }
+
+    /// Writes the optimized HTML, but not pretty printed, in pieces to `out`,
+    /// and returns its length
+    fn write_html(&self, input: &str, out: &mut impl fmt::Write) -> Result<usize, Error> {
+        let normalized = newlines::normalize(input, self.newlines);
+        let escaped = self.escape(&normalized);
+        let mut written = 0;
+        let mut write = |html: &str| {
+            written += html.len();
+            out.write_str(html)
+        };
+        // HTML that can't be optimized yet, because tags at its end may be removed together
+        // with tags that follow
+        let mut held_back = String::new();
+        html::ansi_to_html_with(&escaped, self, |html| {
+            if !compat::optimizes_v0(self) {
+                return write(html);
+            }
+            held_back.push_str(html);
+            let len = compat::optimizable_len(&held_back);
+            if len > 0 {
+                let rest = held_back.split_off(len);
+                write(&compat::optimize_v0(
+                    std::mem::replace(&mut held_back, rest),
+                    self,
+                ))?;
+            }
+            Ok(())
+        })
+        .map_err(|e| e.locate(input, 0))?;
+
+        if !held_back.is_empty() {
+            write(&compat::optimize_v0(held_back, self)).map_err(|err| Error::Write { err })?;
+        }
+        Ok(written)
+    }
+
+    /// Converts a single line containing ANSI escape codes to HTML, and
+    /// appends it to `out`.
+    ///
+    /// This is a faster alternative to [`convert`](Converter::convert) for
+    /// input that is already split into lines, e.g. in log processors calling
+    /// the converter for every line. It writes directly into `out`, so `out`
+    /// can be reused for many lines. With [`OutputCompat::V0`], it skips the
+    /// final optimization pass, so the output may differ slightly from
+    /// `convert`, but renders the same. Lines without escape sequences are
+    /// just escaped.
+    ///
+    /// The line shouldn't contain a line break (otherwise, the text is converted
+    /// all the same). Styles are not carried over from one line to the next; use
+    /// [`convert_append`](Converter::convert_append) for that.
+    ///
+    /// If an error is returned, `out` is unchanged.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new();
+    /// let mut html = String::new();
+    /// let lines = [("\x1b[1mfoo", "<b>foo</b>"), ("bar & baz", "bar &amp; baz")];
+    /// for (line, expected) in lines {
+    ///     html.clear();
+    ///     converter.convert_line(line, &mut html).unwrap();
+    ///     assert_eq!(html, expected);
+    /// }
+    /// ```
+    pub fn convert_line(&self, line: &str, out: &mut String) -> Result<(), Error> {
+        if self.is_converted(line) {
+            out.push_str(line);
+            return Ok(());
+        }
+        let normalized = newlines::normalize(line, self.newlines);
+        let input = self.escape(&normalized);
+        let overwrite = self.overwrites() && input.contains('\r');
+        if !input.contains('\x1b')
+            && self.text_transform.is_none()
+            && !self.bidi_isolate
+            && self.line_wrapper.is_none()
+            && !overwrite
+        {
+            out.push_str(&input);
+            return Ok(());
+        }
+        let result = if self.pretty {
+            let mut html = String::new();
+            html::ansi_to_html_into(&input, self, Default::default(), &mut html)
+                .map(|_| out.push_str(&pretty::pretty(&html)))
+        } else {
+            html::ansi_to_html_into(&input, self, Default::default(), out).map(|_| ())
+        };
+        result.map_err(|e| e.locate(line, 0))
+    }
+
+    /// Converts a string containing ANSI escape codes to HTML, split into
+    /// chunks with at most `max_elements` elements each.
+    ///
+    /// This is meant for frontends that render the chunks separately, e.g. a
+    /// virtualized log viewer, since a single huge HTML fragment is slow to
+    /// insert into the DOM. Every chunk is valid HTML on its own: Styles that
+    /// are active at the end of a chunk are closed, and re-opened in the next
+    /// chunk. Concatenating the chunks yields HTML that renders the same as the
+    /// output of [`convert`](Converter::convert).
+    ///
+    /// Chunks end after a line break when possible. Otherwise they end before
+    /// an escape sequence, so a chunk may exceed the limit when a single line
+    /// without escape sequences contains more elements (e.g. tags in the input
+    /// with [`skip_escape`](Converter::skip_escape)), or when `max_elements` is
+    /// smaller than the number of styles that are active at once. The
+    /// [`text_transform`](Converter::text_transform) may be called more than
+    /// once for the same text, and text runs are split at chunk boundaries.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new();
+    /// let input = "\x1b[1mbold\n\x1b[0;3mitalic \x1b[0;4munderlined\n\x1b[0mplain";
+    /// let chunks = converter.convert_chunked(input, 2).unwrap();
+    /// // the first chunk ends at the line break, although `<i>` would fit
+    /// assert_eq!(
+    ///     chunks,
+    ///     ["<b>bold</b>\n", "<i>italic </i><u>underlined\n</u>plain"],
+    /// );
+    /// ```
+    pub fn convert_chunked(&self, input: &str, max_elements: usize) -> Result<Vec<String>, Error> {
+        let normalized = newlines::normalize(input, self.newlines);
+        let escaped = self.escape(&normalized);
+        let chunks =
+            chunk::convert_chunked(&escaped, self, max_elements).map_err(|e| e.locate(input, 0))?;
+
+        Ok(chunks
+            .into_iter()
+            .map(|html| {
+                let html = compat::optimize_v0(html, self);
+                if self.pretty {
+                    pretty::pretty(&html)
+                } else {
+                    html
+                }
+            })
+            .collect())
+    }
+
+    /// Converts a string containing ANSI escape codes to HTML, and returns
+    /// the lines of the HTML. Every line is valid HTML on its own, see
+    /// [`line_safe`](Converter::line_safe).
+    ///
+    /// The lines are split like [`str::lines`], so they don't contain the
+    /// line breaks, and a line break at the end of the input doesn't start
+    /// another line. [`pretty`](Converter::pretty) is ignored.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new();
+    /// let lines = converter.convert_lines("\x1b[31mred\nstill red\x1b[0m\n").unwrap();
+    /// assert_eq!(
+    ///     lines,
+    ///     [
+    ///         "<span style='color:var(--red,#a00)'>red</span>",
+    ///         "<span style='color:var(--red,#a00)'>still red</span>",
+    ///     ],
+    /// );
+    /// ```
+    pub fn convert_lines(&self, input: &str) -> Result<Vec<String>, Error> {
+        let converter = self.clone().line_safe(true).pretty(false);
+        let html = converter.convert(input)?;
+        Ok(html.lines().map(String::from).collect())
+    }
+
+    /// Converts the input to runs of styled text instead of HTML, e.g. to
+    /// render it in a terminal UI or a PDF.
+    ///
+    /// Adjacent text with the same styling is merged into one segment. The
+    /// options that only concern HTML, like escaping, sanitizing, text
+    /// transforms and the colors of the CSS, have no effect; the text is
+    /// returned as it is in the input. The [line endings are
+    /// normalized](Converter::newline_normalization), and the
+    /// [`max_sequence_len`](Converter::max_sequence_len),
+    /// [`lossy`](Converter::lossy), [`render_links`](Converter::render_links)
+    /// and [`attach_combining`](Converter::attach_combining) options apply.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, SgrColor};
+    ///
+    /// let segments = Converter::new().to_segments("a \x1b[1;31m<b>\x1b[0m").unwrap();
+    /// assert_eq!(segments.len(), 2);
+    /// assert_eq!(segments[0].text, "a ");
+    /// assert!(!segments[0].bold);
+    /// assert_eq!(segments[1].text, "<b>");
+    /// assert!(segments[1].bold);
+    /// assert_eq!(segments[1].foreground, Some(SgrColor::Basic(1)));
+    /// ```
+    pub fn to_segments(&self, input: &str) -> Result<Vec<StyledSegment>, Error> {
+        segments::to_segments(input, self)
+    }
+
+    /// Returns a hash of the text and its styling, e.g. to use as the key of
+    /// a cache of converted HTML.
+    ///
+    /// The hash is computed from the [segments](Converter::to_segments), so
+    /// inputs that only differ in redundant escape sequences (e.g. setting the
+    /// same color twice, or resetting styles that aren't set) have the same
+    /// fingerprint. The converter's options aren't included; if the HTML of
+    /// several converters is cached, the key should identify the converter as
+    /// well.
+    ///
+    /// The hash function is 64-bit FNV-1a, not `std`'s randomly seeded
+    /// hasher, so fingerprints are the same in every process and on every
+    /// platform, and can be stored. They may change in a semver-incompatible
+    /// release. As with every 64-bit hash, different inputs may collide.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new();
+    /// let a = converter.fingerprint("\x1b[1mbold\x1b[0m plain").unwrap();
+    /// let b = converter.fingerprint("\x1b[0m\x1b[1;1mbold\x1b[22m\x1b[39m plain").unwrap();
+    /// assert_eq!(a, b);
+    /// assert_ne!(a, converter.fingerprint("\x1b[3mbold\x1b[0m plain").unwrap());
+    /// ```
+    pub fn fingerprint(&self, input: &str) -> Result<u64, Error> {
+        let segments = segments::to_segments(input, self)?;
+        Ok(fingerprint::fingerprint(&segments))
+    }
 
-fn ansi_regex() -> &'static Regex {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    REGEX.get_or_init(|| Regex::new(ANSI_REGEX).unwrap())
+    /// Splits the input before text at its end that may be completed by the next
+    /// input: An escape sequence that is cut off, an HTML entity with
+    /// `skip_escape`, or a `\r` with newline normalization or carriage return
+    /// overwrites.
+    fn split_pending<'a>(&self, full: &'a str) -> (&'a str, &'a str) {
+        let (mut input, mut pending) = match state::split_incomplete_sequence(full) {
+            (_, pending) if pending.len() > self.max_sequence_len => (full, ""),
+            split => split,
+        };
+        if self.skip_escape {
+            // an HTML entity that is cut off is completed by the next call as well
+            if let Some(start) = entity::incomplete_start(input) {
+                pending = &full[start..];
+                input = &input[..start];
+            }
+        }
+        let cr_pending = self.newlines != Newlines::Keep || self.overwrites();
+        if cr_pending && input.ends_with('\r') {
+            // it may be followed by `\n` in the next call
+            pending = &full[input.len() - 1..];
+            input = &input[..input.len() - 1];
+        }
+        (input, pending)
+    }
+
+    /// Escapes the input according to the `skip_escape` and `sanitize` options
+    fn escape<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let phase = phase!("escape", input.len());
+        let escaped = match (self.skip_escape, self.sanitize) {
+            (false, _) if !input.contains(['&', '<', '>', '"', '\'']) => Cow::Borrowed(input),
+            (false, _) => Cow::Owned(Esc(input).to_string()),
+            (true, false) => entity::move_sequences_out(input),
+            (true, true) => {
+                let html = sanitize::sanitize(input, &self.allowed_tags);
+                Cow::Owned(entity::move_sequences_out(&html).into_owned())
+            }
+        };
+        phase.output_len(escaped.len());
+        escaped
+    }
+
+    /// Converts input that is appended to previously converted input, and
+    /// appends the HTML to `out`.
+    ///
+    /// This is meant for append-only buffers like log files: Instead of
+    /// converting the whole buffer whenever something is appended, only the new
+    /// part is converted. `state` must be the state returned by the previous
+    /// call (or [`StyleState::new()`] for the first call); it is updated to the
+    /// state at the end of `new_input`.
+    ///
+    /// Styles that are active at the end of `new_input` are closed, so `out`
+    /// is always valid HTML, and re-opened in the next call. Therefore the
+    /// output is not byte-for-byte equal to converting the whole buffer at
+    /// once, but it renders the same. An escape sequence that is cut off at the
+    /// end of `new_input` is stored in the state and converted in the next call,
+    /// as well as an HTML entity with [`skip_escape`](Converter::skip_escape).
+    ///
+    /// If an error is returned, `state` and `out` are unchanged.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{Converter, StyleState};
+    ///
+    /// let converter = Converter::new();
+    /// let mut state = StyleState::new();
+    /// let mut html = String::new();
+    ///
+    /// converter.convert_append(&mut state, "\x1b[1mfoo\x1b[3", &mut html).unwrap();
+    /// converter.convert_append(&mut state, "1mbar", &mut html).unwrap();
+    /// assert_eq!(
+    ///     html,
+    ///     "<b>foo</b><b><span style='color:var(--red,#a00)'>bar</span></b>",
+    /// );
+    /// ```
+    pub fn convert_append(
+        &self,
+        state: &mut StyleState,
+        new_input: &str,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        let full = if state.pending.is_empty() {
+            Cow::Borrowed(new_input)
+        } else {
+            Cow::Owned(format!("{}{new_input}", state.pending))
+        };
+        let (input, pending) = self.split_pending(&full);
+
+        let input = newlines::normalize(input, self.newlines);
+        let input = self.escape(&input);
+        let (html, position) = html::ansi_to_html_from(&input, self, state.position.clone())
+            .map_err(|e| e.locate(&full, state.pending.len()))?;
+
+        let html = compat::optimize_v0(html, self);
+        if self.pretty {
+            out.push_str(&pretty::pretty(&html));
+        } else {
+            out.push_str(&html);
+        }
+        *state = StyleState {
+            position,
+            pending: pending.to_owned(),
+        };
+        Ok(())
+    }
+
+    /// Starts converting input that arrives in chunks, e.g. the live output of
+    /// a process that is sent to a browser incrementally.
+    ///
+    /// Unlike [`convert_append`](Converter::convert_append), the tags that are
+    /// open at the end of a chunk stay open, and are only closed by
+    /// [`finish`](ConverterStream::finish). Therefore the HTML of a single
+    /// chunk isn't valid on its own, but the concatenated HTML of all chunks is.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let mut stream = ansi_to_html::Converter::new().begin();
+    /// let mut html = stream.feed("\x1b[1mfoo\x1b[3").unwrap();
+    /// html += &stream.feed("1mbar\n").unwrap();
+    /// html += &stream.finish();
+    /// assert_eq!(html, "<b>foo<span style='color:var(--red,#a00)'>bar</span></b>\n");
+    /// ```
+    pub fn begin(&self) -> ConverterStream {
+        ConverterStream::new(self.clone())
+    }
+
+    /// Starts a session of conversions that reuse their allocations, e.g. for
+    /// converting many lines of a log on the same thread.
+    ///
+    /// Each conversion returns the same HTML as [`convert`](Converter::convert),
+    /// borrowed from the session until the next conversion. Styles aren't
+    /// carried over from one input to the next; use
+    /// [`begin`](Converter::begin) for input that arrives in chunks.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let converter = ansi_to_html::Converter::new();
+    /// let mut session = converter.session();
+    /// assert_eq!(session.convert("\x1b[1mbold").unwrap(), "<b>bold</b>");
+    /// assert_eq!(session.convert("plain").unwrap(), "plain");
+    /// let html: String = session.convert_owned("\x1b[3mitalic").unwrap();
+    /// assert_eq!(html, "<i>italic</i>");
+    /// ```
+    pub fn session(&self) -> ConversionSession<'_> {
+        ConversionSession::new(self)
+    }
 }
 
-fn optimize(html: &str) -> String {
-    static REGEXES: OnceLock<(Regex, Regex)> = OnceLock::new();
-    let (regex1, regex2) = REGEXES.get_or_init(|| {
-        (
-            Regex::new(OPT_REGEX_1).unwrap(),
-            Regex::new(OPT_REGEX_2).unwrap(),
-        )
-    });
+/// The options of a converter. Since the builder methods are named after the
+/// options, the names of boolean options start with `is_`, and the other
+/// names start with `get_`.
+///
+/// ## Example
+///
+/// ```
+/// use ansi_to_html::{Converter, Newlines};
+///
+/// let converter = Converter::new().skip_escape(true).newline_normalization(Newlines::Lf);
+/// assert!(converter.is_skip_escape());
+/// assert_eq!(converter.get_newline_normalization(), Newlines::Lf);
+/// assert_eq!(converter.get_four_bit_var_prefix(), None);
+/// ```
+impl Converter {
+    /// Returns whether special HTML characters are kept, see
+    /// [`skip_escape`](Converter::skip_escape)
+    pub fn is_skip_escape(&self) -> bool {
+        self.skip_escape
+    }
+
+    /// Returns whether the tags aren't minimized, see
+    /// [`skip_optimize`](Converter::skip_optimize)
+    pub fn is_skip_optimize(&self) -> bool {
+        self.skip_optimize
+    }
+
+    /// Returns the prefix of the CSS variables, see
+    /// [`four_bit_var_prefix`](Converter::four_bit_var_prefix)
+    pub fn get_four_bit_var_prefix(&self) -> Option<&str> {
+        self.four_bit_var_prefix.as_deref()
+    }
+
+    /// Returns the prefix of the classes of the 4-bit colors, see
+    /// [`four_bit_classes`](Converter::four_bit_classes)
+    pub fn get_four_bit_classes(&self) -> Option<&str> {
+        self.four_bit_classes.as_deref()
+    }
+
+    /// Returns whether HTML in the input that doesn't look pre-escaped is
+    /// escaped, see [`sanitize`](Converter::sanitize)
+    pub fn is_sanitize(&self) -> bool {
+        self.sanitize
+    }
 
-    let html = regex1.replace_all(html, "");
-    let html = regex2.replace_all(&html, "");
+    /// Returns the tags that are kept when sanitizing, see
+    /// [`allowed_tags`](Converter::allowed_tags)
+    pub fn get_allowed_tags(&self) -> &[String] {
+        &self.allowed_tags
+    }
+
+    /// Returns whether the colors are hardcoded instead of using CSS variables,
+    /// see [`skip_css_vars`](Converter::skip_css_vars)
+    pub fn is_skip_css_vars(&self) -> bool {
+        self.skip_css_vars
+    }
+
+    /// Returns the colors that faint text is blended with, see
+    /// [`faint_blend`](Converter::faint_blend)
+    pub fn get_faint_blend(&self) -> Option<FaintBlend> {
+        self.faint_blend
+    }
+
+    /// Returns the palette of the colors, see [`palette`](Converter::palette)
+    pub fn get_palette(&self) -> Option<&ThemePalette> {
+        self.theme.as_ref()
+    }
+
+    /// Returns the light and the dark palette, see
+    /// [`dual_theme`](Converter::dual_theme)
+    pub fn get_dual_theme(&self) -> Option<(&ThemePalette, &ThemePalette)> {
+        self.dual_theme.as_ref().map(|(light, dark)| (light, dark))
+    }
+
+    /// Returns whether only the colors of the dark theme are emitted, see
+    /// [`skip_light_dark`](Converter::skip_light_dark)
+    pub fn is_skip_light_dark(&self) -> bool {
+        self.skip_light_dark
+    }
+
+    /// Returns whether text colors that are hard to read on the background are
+    /// adapted, see [`adapt_to_theme`](Converter::adapt_to_theme)
+    pub fn is_adapt_to_theme(&self) -> bool {
+        self.adapt_to_theme
+    }
+
+    /// Returns whether colors that look like the default colors are omitted,
+    /// see [`elide_default_colors`](Converter::elide_default_colors)
+    pub fn is_elide_default_colors(&self) -> bool {
+        self.elide_default_colors
+    }
+
+    /// Returns the default text and background colors, if they were set, see
+    /// [`default_colors`](Converter::default_colors)
+    pub fn get_default_colors(&self) -> Option<([u8; 3], [u8; 3])> {
+        self.default_colors.map(|[fg, bg]| (fg, bg))
+    }
+
+    /// Returns the number of grays of the 8-bit colors, see
+    /// [`grayscale_levels`](Converter::grayscale_levels)
+    pub fn get_grayscale_levels(&self) -> Option<u8> {
+        self.grayscale_levels
+    }
+
+    /// Returns the colors of the 8-bit palette, see
+    /// [`eight_bit_palette`](Converter::eight_bit_palette)
+    pub fn get_eight_bit_palette(&self) -> Option<&[[u8; 3]; 256]> {
+        self.eight_bit_palette.as_deref()
+    }
+
+    /// Returns the maximum length of an escape sequence, see
+    /// [`max_sequence_len`](Converter::max_sequence_len)
+    pub fn get_max_sequence_len(&self) -> usize {
+        self.max_sequence_len
+    }
+
+    /// Returns whether the HTML is pretty-printed, see
+    /// [`pretty`](Converter::pretty)
+    pub fn is_pretty(&self) -> bool {
+        self.pretty
+    }
+
+    /// Returns the names of the CSS variables, see
+    /// [`var_names`](Converter::var_names)
+    pub fn get_var_names(&self) -> Option<&VarNames> {
+        self.var_names.as_ref()
+    }
+
+    /// Returns whether a function transforms the runs of text, see
+    /// [`text_transform`](Converter::text_transform)
+    pub fn has_text_transform(&self) -> bool {
+        self.text_transform.is_some()
+    }
+
+    /// Returns whether every line of every text run is wrapped in a `<bdi>`
+    /// element, see [`bidi_isolate`](Converter::bidi_isolate)
+    pub fn is_bidi_isolate(&self) -> bool {
+        self.bidi_isolate
+    }
+
+    /// Returns whether whitespace at the end of a line is written without the
+    /// styles, see
+    /// [`normalize_trailing_whitespace`](Converter::normalize_trailing_whitespace)
+    pub fn is_normalize_trailing_whitespace(&self) -> bool {
+        self.normalize_trailing_whitespace
+    }
+
+    /// Returns whether all tags are closed before every line break, see
+    /// [`line_safe`](Converter::line_safe)
+    pub fn is_line_safe(&self) -> bool {
+        self.line_safe
+    }
 
-    html.to_string()
+    /// Returns the element that every line is wrapped in, see
+    /// [`line_wrapper`](Converter::line_wrapper)
+    pub fn get_line_wrapper(&self) -> Option<&LineWrapper> {
+        self.line_wrapper.as_ref()
+    }
+
+    /// Returns whether OSC 8 hyperlinks are converted to links, see
+    /// [`render_links`](Converter::render_links)
+    pub fn is_render_links(&self) -> bool {
+        self.render_links
+    }
+
+    /// Returns the URL schemes of the links that are rendered, see
+    /// [`link_schemes`](Converter::link_schemes)
+    pub fn get_link_schemes(&self) -> &UrlSchemes {
+        &self.link_schemes
+    }
+
+    /// Returns how line breaks are normalized, see
+    /// [`newline_normalization`](Converter::newline_normalization)
+    pub fn get_newline_normalization(&self) -> Newlines {
+        self.newlines
+    }
+
+    /// Returns whether a `\r` moves back to the start of the line, see
+    /// [`carriage_return_overwrites`](Converter::carriage_return_overwrites)
+    pub fn is_carriage_return_overwrites(&self) -> bool {
+        self.carriage_return_overwrites
+    }
+
+    /// Returns whether the cursor moves up and down between the last lines, see
+    /// [`emulate_cursor_movement`](Converter::emulate_cursor_movement)
+    pub fn is_emulate_cursor_movement(&self) -> bool {
+        self.emulate_cursor_movement
+    }
+
+    /// Returns whether invalid SGR codes are ignored instead of returning an
+    /// error, see [`lossy`](Converter::lossy)
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
+
+    /// Returns whether characters that extend the preceding character are kept
+    /// in its element, see [`attach_combining`](Converter::attach_combining)
+    pub fn is_attach_combining(&self) -> bool {
+        self.attach_combining
+    }
+
+    /// Returns whether blinking text is rendered, see
+    /// [`render_blink`](Converter::render_blink)
+    pub fn is_render_blink(&self) -> bool {
+        self.render_blink
+    }
+
+    /// Returns whether concealed text is replaced with spaces, see
+    /// [`redact_concealed`](Converter::redact_concealed)
+    pub fn is_redact_concealed(&self) -> bool {
+        self.redact_concealed
+    }
+
+    /// Returns the tags of bold, italic and crossed-out text, see
+    /// [`tag_style`](Converter::tag_style)
+    pub fn get_tag_style(&self) -> TagStyle {
+        self.tag_style
+    }
+
+    /// Returns whether each run of text is wrapped in a single `<span>`, see
+    /// [`flat_spans`](Converter::flat_spans)
+    pub fn is_flat_spans(&self) -> bool {
+        self.flat_spans
+    }
+
+    /// Returns the quotes around attribute values, see
+    /// [`attribute_quotes`](Converter::attribute_quotes)
+    pub fn get_attribute_quotes(&self) -> Quote {
+        self.attribute_quotes
+    }
+
+    /// Returns which version's output is reproduced, see
+    /// [`output_compat`](Converter::output_compat)
+    pub fn get_output_compat(&self) -> OutputCompat {
+        self.output_compat
+    }
+
+    /// Returns whether input that looks like HTML is returned unchanged, see
+    /// [`detect_double_escape`](Converter::detect_double_escape)
+    pub fn is_detect_double_escape(&self) -> bool {
+        self.detect_double_escape
+    }
+}
+
+#[deprecated(note = "Use the `convert` method of the `Converter` builder")]
+pub fn convert_with_opts(input: &str, converter: &Converter) -> Result<String, Error> {
+    converter.convert(input)
 }
@@ -0,0 +1,102 @@
+use std::fmt::Write;
+
+use crate::{vars, Error, Quote};
+
+/// The element that every line of the output is wrapped in, see
+/// [`Converter::line_wrapper`](crate::Converter::line_wrapper).
+///
+/// The tag name must start with an ASCII letter and may only contain ASCII
+/// letters, digits and `-`. The class may only contain the characters allowed
+/// in [`VarNames`](crate::VarNames), so neither can break out of the tag.
+///
+/// ## Example
+///
+/// ```
+/// use ansi_to_html::LineWrapper;
+///
+/// let wrapper = LineWrapper::new("span", Some("line"), true).unwrap();
+/// assert_eq!(wrapper.tag(), "span");
+/// assert_eq!(wrapper.class(), Some("line"));
+///
+/// assert!(LineWrapper::new("span class", None, false).is_err());
+/// assert!(LineWrapper::new("div", Some("a'b"), false).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineWrapper {
+    tag: String,
+    class: Option<String>,
+    data_line: bool,
+}
+
+impl LineWrapper {
+    /// Creates a wrapper with the given tag name and class. With `data_line`,
+    /// the element has a `data-line` attribute with the number of the line,
+    /// starting at 1.
+    ///
+    /// Returns an error if the tag name or the class isn't valid.
+    pub fn new(tag: &str, class: Option<&str>, data_line: bool) -> Result<Self, Error> {
+        let valid_tag = tag.starts_with(|c: char| c.is_ascii_alphabetic())
+            && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid_tag {
+            return Err(Error::InvalidLineWrapper {
+                name: tag.to_string(),
+            });
+        }
+        if let Some(class) = class.filter(|class| !vars::is_valid(class)) {
+            return Err(Error::InvalidLineWrapper {
+                name: class.to_string(),
+            });
+        }
+        Ok(Self {
+            tag: tag.to_string(),
+            class: class.map(str::to_string),
+            data_line,
+        })
+    }
+
+    /// Returns the tag name, e.g. `span`
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Returns the class of the element, if it has one
+    pub fn class(&self) -> Option<&str> {
+        self.class.as_deref()
+    }
+
+    /// Returns whether the element has a `data-line` attribute
+    pub fn data_line(&self) -> bool {
+        self.data_line
+    }
+
+    /// Returns the opening tag of the given line, with single quotes
+    pub(crate) fn opening_tag(&self, line: usize) -> String {
+        let mut tag = format!("<{}", self.tag);
+        if let Some(class) = &self.class {
+            let _ = write!(tag, " class='{class}'");
+        }
+        if self.data_line {
+            let _ = write!(tag, " data-line='{line}'");
+        }
+        tag.push('>');
+        tag
+    }
+
+    /// Returns the closing tag
+    pub(crate) fn closing_tag(&self) -> String {
+        format!("</{}>", self.tag)
+    }
+
+    /// Returns the element of an empty line with the given quotes, if it
+    /// doesn't depend on the number of the line. It must not be removed as an
+    /// empty element by the optimization of `OutputCompat::V0`.
+    pub(crate) fn empty_element(&self, quotes: Quote) -> Option<String> {
+        if self.data_line {
+            return None;
+        }
+        let mut element = String::new();
+        quotes.push_tags(&self.opening_tag(0), &mut element);
+        element.push_str(&self.closing_tag());
+        Some(element)
+    }
+}
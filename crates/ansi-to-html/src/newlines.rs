@@ -0,0 +1,64 @@
+use std::borrow::Cow;
+
+use crate::ansi::find_sequence;
+
+/// How line endings are normalized, see
+/// [`Converter::newline_normalization`](crate::Converter::newline_normalization).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Newlines {
+    /// Line endings are kept as they are.
+    #[default]
+    Keep,
+    /// `\r\n` and lone `\r` are replaced with `\n`.
+    Lf,
+    /// `\r\n` is replaced with `\n`. Lone `\r` are kept.
+    CrLfToLf,
+}
+
+/// Normalizes the line endings in the text between the escape sequences. A
+/// `\r` and `\n` that are only separated by escape sequences are a line
+/// ending as well, e.g. in `\r\x1b[0m\n`.
+pub(crate) fn normalize(input: &str, newlines: Newlines) -> Cow<'_, str> {
+    if newlines == Newlines::Keep || !input.contains('\r') {
+        return Cow::Borrowed(input);
+    }
+
+    let mut out = String::with_capacity(input.len());
+    let mut pos = 0;
+    while pos < input.len() {
+        let (text_end, next) = match find_sequence(&input[pos..]) {
+            Some(range) => (pos + range.start, pos + range.end),
+            None => (input.len(), input.len()),
+        };
+        let text = &input[pos..text_end];
+        let mut last = 0;
+        for (i, _) in text.match_indices('\r') {
+            out.push_str(&text[last..i]);
+            last = i + 1;
+            // the `\r` of a `\r\n` is removed
+            if !starts_with_lf(&input[pos + i + 1..]) {
+                out.push(match newlines {
+                    Newlines::Lf => '\n',
+                    _ => '\r',
+                });
+            }
+        }
+        out.push_str(&text[last..]);
+        out.push_str(&input[text_end..next]);
+        pos = next;
+    }
+    Cow::Owned(out)
+}
+
+/// Returns whether the input starts with `\n`, ignoring escape sequences
+fn starts_with_lf(mut input: &str) -> bool {
+    loop {
+        if input.starts_with('\n') {
+            return true;
+        }
+        match find_sequence(input) {
+            Some(range) if range.start == 0 => input = &input[range.end..],
+            _ => return false,
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! A public view of the escape sequences and SGR codes that the converter
+//! recognizes, see [`AnsiParser`].
+//!
+//! The types are separate from the ones used by the converter, so the
+//! converter's internals can change without breaking this API.
+
+use std::{fmt, iter::Map, num::ParseIntError, str::Split};
+
+use crate::{
+    ansi::{find_sequence, iter_next, Ansi},
+    Error,
+};
+
+/// An iterator that splits a string into escape sequences and the text
+/// between them.
+///
+/// The same sequences are recognized as by [`Converter`](crate::Converter):
+/// CSI sequences such as `ESC[1;31m` or `ESC[K`, `ESC(B`, and OSC sequences
+/// terminated by `BEL` or `ESC \`. An `ESC` that doesn't start such a sequence
+/// is part of the text. A sequence that is aborted by a control character
+/// (see [`text_fragments`](crate::text_fragments)) is a single sequence
+/// fragment. Empty text fragments are skipped, so concatenating all
+/// fragments results in the input.
+///
+/// ## Example
+///
+/// ```
+/// use ansi_to_html::{AnsiFragment, AnsiParser};
+///
+/// let fragments: Vec<_> = AnsiParser::new("\x1b[1mbold\x1b[0m\x1b[K plain").collect();
+/// assert_eq!(
+///     fragments,
+///     [
+///         AnsiFragment::Sequence("\x1b[1m"),
+///         AnsiFragment::Text("bold"),
+///         AnsiFragment::Sequence("\x1b[0m"),
+///         AnsiFragment::Sequence("\x1b[K"),
+///         AnsiFragment::Text(" plain"),
+///     ]
+/// );
+/// ```
+#[derive(Clone, Debug)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct AnsiParser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> AnsiParser<'a> {
+    /// Creates a parser for the input
+    pub fn new(input: &'a str) -> Self {
+        Self { rest: input }
+    }
+}
+
+impl<'a> Iterator for AnsiParser<'a> {
+    type Item = AnsiFragment<'a>;
+
+    fn next(&mut self) -> Option<AnsiFragment<'a>> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let (fragment, rest) = match find_sequence(self.rest) {
+            Some(range) if range.start == 0 => {
+                let (sequence, rest) = self.rest.split_at(range.end);
+                (AnsiFragment::Sequence(sequence), rest)
+            }
+            Some(range) => {
+                let (text, rest) = self.rest.split_at(range.start);
+                (AnsiFragment::Text(text), rest)
+            }
+            None => (AnsiFragment::Text(self.rest), ""),
+        };
+        self.rest = rest;
+        Some(fragment)
+    }
+}
+
+impl std::iter::FusedIterator for AnsiParser<'_> {}
+
+/// Returns the input without escape sequences, i.e. the text that is shown by
+/// a terminal.
+///
+/// The same sequences are removed as by [`AnsiParser`], including OSC
+/// sequences and `ESC(B`. An `ESC` that doesn't start a valid sequence is
+/// kept. This is much faster than converting the input to HTML and removing
+/// the tags, e.g. to compute the display width of the text or to index it for
+/// search.
+///
+/// ## Example
+///
+/// ```
+/// let input = "\x1b[1mbold\x1b[0m \x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07";
+/// assert_eq!(ansi_to_html::strip_ansi(input), "bold link");
+/// ```
+#[must_use]
+pub fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    // writing to a `String` can't fail
+    let _ = strip_ansi_to_writer(input, &mut out);
+    out
+}
+
+/// Like [`strip_ansi`], but writes the text to `out` instead of returning it.
+/// Only an error of the writer is returned.
+pub fn strip_ansi_to_writer(input: &str, out: &mut impl fmt::Write) -> fmt::Result {
+    for fragment in AnsiParser::new(input) {
+        if let AnsiFragment::Text(text) = fragment {
+            out.write_str(text)?;
+        }
+    }
+    Ok(())
+}
+
+/// A part of the input, returned by [`AnsiParser`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AnsiFragment<'a> {
+    /// An escape sequence, including the `ESC`, e.g. `"\x1b[1;31m"`
+    Sequence(&'a str),
+    /// Text without escape sequences, never empty
+    Text(&'a str),
+}
+
+impl<'a> AnsiFragment<'a> {
+    /// Returns the fragment as it appears in the input
+    pub fn as_str(&self) -> &'a str {
+        match *self {
+            Self::Sequence(s) | Self::Text(s) => s,
+        }
+    }
+
+    /// Returns an iterator over the codes of an SGR sequence (a sequence such
+    /// as `ESC[1;31m`, which sets the text style), or `None` if the fragment is
+    /// text or a different sequence.
+    ///
+    /// The codes are interpreted as by [`Converter`](crate::Converter). An
+    /// invalid code results in an [`Error`] without a [span](Error::span),
+    /// after which the remaining codes are still returned.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::{AnsiFragment, SgrCode, SgrColor};
+    ///
+    /// let sequence = AnsiFragment::Sequence("\x1b[1;38;5;208;49m");
+    /// let codes: Vec<_> = sequence.sgr_codes().unwrap().map(Result::unwrap).collect();
+    /// assert_eq!(
+    ///     codes,
+    ///     [
+    ///         SgrCode::Bold,
+    ///         SgrCode::Foreground(SgrColor::Indexed(208)),
+    ///         SgrCode::DefaultBackground,
+    ///     ]
+    /// );
+    ///
+    /// assert!(AnsiFragment::Sequence("\x1b[K").sgr_codes().is_none());
+    /// ```
+    pub fn sgr_codes(&self) -> Option<SgrCodes<'a>> {
+        let Self::Sequence(sequence) = *self else {
+            return None;
+        };
+        let params = sequence.strip_prefix("\x1b[")?.strip_suffix('m')?;
+        // `ESC[m` is the same as `ESC[0m`, and a trailing `;` is ignored
+        let params = if params.is_empty() { "0" } else { params };
+        let params = params.strip_suffix(';').unwrap_or(params);
+        Some(SgrCodes {
+            sequence,
+            codes: params.split(';').map(str::parse as ParseCode),
+        })
+    }
+}
+
+type ParseCode = fn(&str) -> Result<u8, ParseIntError>;
+
+/// The iterator returned by [`AnsiFragment::sgr_codes`]
+#[derive(Clone, Debug)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct SgrCodes<'a> {
+    sequence: &'a str,
+    codes: Map<Split<'a, char>, ParseCode>,
+}
+
+impl Iterator for SgrCodes<'_> {
+    type Item = Result<SgrCode, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let code = match self.codes.next()? {
+            Ok(code) => code,
+            Err(e) => return Some(Err(Error::from(e).in_sequence(self.sequence))),
+        };
+        let ansi = match iter_next(code, &mut self.codes) {
+            Ok(ansi) => ansi,
+            Err(e) => return Some(Err(e.in_sequence(self.sequence))),
+        };
+        Some(Ok(match ansi {
+            Ansi::Noop => SgrCode::Unsupported(code),
+            Ansi::Reset => SgrCode::Reset,
+            Ansi::Bold => SgrCode::Bold,
+            Ansi::Faint => SgrCode::Faint,
+            Ansi::Italic => SgrCode::Italic,
+            Ansi::Underline => SgrCode::Underline,
+            Ansi::Blink => SgrCode::Blink,
+            Ansi::RapidBlink => SgrCode::RapidBlink,
+            Ansi::Reverse => SgrCode::Reverse,
+            Ansi::Conceal => SgrCode::Conceal,
+            Ansi::CrossedOut => SgrCode::CrossedOut,
+            Ansi::Overline => SgrCode::Overline,
+            Ansi::BoldOff => SgrCode::BoldOff,
+            Ansi::BoldAndFaintOff => SgrCode::NormalIntensity,
+            Ansi::ItalicOff => SgrCode::ItalicOff,
+            Ansi::UnderlineOff => SgrCode::UnderlineOff,
+            Ansi::BlinkOff => SgrCode::BlinkOff,
+            Ansi::ReverseOff => SgrCode::ReverseOff,
+            Ansi::ConcealOff => SgrCode::ConcealOff,
+            Ansi::CrossedOutOff => SgrCode::CrossedOutOff,
+            Ansi::OverlineOff => SgrCode::OverlineOff,
+            Ansi::ForgroundColor(color) => SgrCode::Foreground(color.to_sgr_color()),
+            Ansi::DefaultForegroundColor => SgrCode::DefaultForeground,
+            Ansi::BackgroundColor(color) => SgrCode::Background(color.to_sgr_color()),
+            Ansi::DefaultBackgroundColor => SgrCode::DefaultBackground,
+        }))
+    }
+}
+
+impl std::iter::FusedIterator for SgrCodes<'_> {}
+
+/// A code of an SGR sequence, returned by [`AnsiFragment::sgr_codes`].
+///
+/// This enum is `#[non_exhaustive]`: when the converter supports more styles,
+/// codes that are [`Unsupported`](SgrCode::Unsupported) now get their own
+/// variants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SgrCode {
+    /// `0`, resets all styles
+    Reset,
+    /// `1`
+    Bold,
+    /// `2`
+    Faint,
+    /// `3`
+    Italic,
+    /// `4`
+    Underline,
+    /// `5`, slow blink
+    Blink,
+    /// `6`, rapid blink
+    RapidBlink,
+    /// `7`, reverse video, which swaps the text and background colors
+    Reverse,
+    /// `8`, hidden text
+    Conceal,
+    /// `9`
+    CrossedOut,
+    /// `21`, which ends bold text in this crate, although some terminals
+    /// treat it as double underline
+    BoldOff,
+    /// `22`, ends bold and faint text
+    NormalIntensity,
+    /// `23`
+    ItalicOff,
+    /// `24`
+    UnderlineOff,
+    /// `25`, ends slow and rapid blinking
+    BlinkOff,
+    /// `27`, ends reverse video
+    ReverseOff,
+    /// `28`
+    ConcealOff,
+    /// `29`
+    CrossedOutOff,
+    /// `30`–`37`, `38;5;<n>`, `38;2;<r>;<g>;<b>` and `90`–`97`
+    Foreground(SgrColor),
+    /// `39`
+    DefaultForeground,
+    /// `40`–`47`, `48;5;<n>`, `48;2;<r>;<g>;<b>` and `100`–`107`
+    Background(SgrColor),
+    /// `49`
+    DefaultBackground,
+    /// `53`
+    Overline,
+    /// `55`
+    OverlineOff,
+    /// A valid code that the converter ignores, e.g. `20` (fraktur)
+    Unsupported(u8),
+}
+
+/// A color set by an [`SgrCode`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SgrColor {
+    /// One of the 16 colors of the terminal's palette: 0–7 for black, red,
+    /// green, yellow, blue, magenta, cyan and white, and 8–15 for their bright
+    /// variants
+    Basic(u8),
+    /// One of the 256 colors of the 8-bit palette (`38;5;<n>`)
+    Indexed(u8),
+    /// A 24-bit color (`38;2;<r>;<g>;<b>`)
+    Rgb(u8, u8, u8),
+}
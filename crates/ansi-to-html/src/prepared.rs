@@ -0,0 +1,175 @@
+//! The checked form of the [`Converter`] builder, see [`Converter::build`].
+
+use std::{borrow::Cow, fmt, sync::Arc};
+
+use crate::{
+    color::Palette, html, vars, ConfigError, ConversionSession, Converter, ConverterStream, Error,
+    StyleState, StyledSegment, TagStyle, UrlSchemes,
+};
+
+/// A converter with checked options, created by [`Converter::build`].
+///
+/// It has the same conversion methods as [`Converter`] and produces the same
+/// HTML, but some of the work that depends only on the options is done once
+/// when it is built.
+///
+/// ## Example
+///
+/// ```
+/// let converter = ansi_to_html::Converter::new().build().unwrap();
+/// let mut html = String::new();
+/// for line in ["\x1b[1mbold", "\x1b[32mgreen"] {
+///     converter.convert_line(line, &mut html).unwrap();
+/// }
+/// assert_eq!(html, "<b>bold</b><span style='color:var(--green,#0a0)'>green</span>");
+/// ```
+#[derive(Clone, Debug)]
+pub struct PreparedConverter {
+    /// The options it was built from
+    converter: Converter,
+    /// The same options with the palette, which is used for converting
+    prepared: Converter,
+}
+
+impl PreparedConverter {
+    pub(crate) fn new(converter: Converter) -> Self {
+        let palette = Palette::new(html::css_vars(&converter), html::themes(&converter));
+        let mut prepared = converter.clone();
+        prepared.palette = Some(Arc::new(palette));
+        Self {
+            converter,
+            prepared,
+        }
+    }
+
+    /// Returns the options it was built from, see
+    /// [`Converter::is_skip_escape`] and the other getters
+    pub fn converter(&self) -> &Converter {
+        &self.converter
+    }
+
+    /// See [`Converter::convert`]
+    pub fn convert(&self, input: &str) -> Result<String, Error> {
+        self.prepared.convert(input)
+    }
+
+    /// See [`Converter::convert_bytes`]
+    pub fn convert_bytes(&self, input: &[u8]) -> Result<String, Error> {
+        self.prepared.convert_bytes(input)
+    }
+
+    /// See [`Converter::convert_cow`]
+    pub fn convert_cow<'a>(&self, input: &'a str) -> Result<Cow<'a, str>, Error> {
+        self.prepared.convert_cow(input)
+    }
+
+    /// See [`Converter::convert_to_writer`]
+    pub fn convert_to_writer(&self, input: &str, out: &mut impl fmt::Write) -> Result<(), Error> {
+        self.prepared.convert_to_writer(input, out)
+    }
+
+    /// See [`Converter::convert_line`]
+    pub fn convert_line(&self, line: &str, out: &mut String) -> Result<(), Error> {
+        self.prepared.convert_line(line, out)
+    }
+
+    /// See [`Converter::convert_chunked`]
+    pub fn convert_chunked(&self, input: &str, max_elements: usize) -> Result<Vec<String>, Error> {
+        self.prepared.convert_chunked(input, max_elements)
+    }
+
+    /// See [`Converter::convert_lines`]
+    pub fn convert_lines(&self, input: &str) -> Result<Vec<String>, Error> {
+        self.prepared.convert_lines(input)
+    }
+
+    /// See [`Converter::convert_append`]
+    pub fn convert_append(
+        &self,
+        state: &mut StyleState,
+        new_input: &str,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        self.prepared.convert_append(state, new_input, out)
+    }
+
+    /// See [`Converter::to_segments`]
+    pub fn to_segments(&self, input: &str) -> Result<Vec<StyledSegment>, Error> {
+        self.prepared.to_segments(input)
+    }
+
+    /// See [`Converter::fingerprint`]
+    pub fn fingerprint(&self, input: &str) -> Result<u64, Error> {
+        self.prepared.fingerprint(input)
+    }
+
+    /// See [`Converter::begin`]
+    pub fn begin(&self) -> ConverterStream {
+        self.prepared.begin()
+    }
+
+    /// See [`Converter::session`]
+    pub fn session(&self) -> ConversionSession<'_> {
+        self.prepared.session()
+    }
+}
+
+/// Returns an error if an option is invalid or has no effect
+pub(crate) fn check(converter: &Converter) -> Result<(), ConfigError> {
+    if let Some(prefix) = &converter.four_bit_var_prefix {
+        if !prefix.is_empty() && !vars::is_valid(prefix) {
+            return Err(ConfigError::InvalidVarPrefix {
+                prefix: prefix.clone(),
+            });
+        }
+        let classes = converter.four_bit_classes.is_some();
+        if converter.var_names.is_some() || converter.skip_css_vars || classes {
+            return Err(ConfigError::IgnoredVarPrefix);
+        }
+    }
+    if let Some(prefix) = &converter.four_bit_classes {
+        if !prefix.is_empty() && !vars::is_valid(prefix) {
+            return Err(ConfigError::InvalidClassPrefix {
+                prefix: prefix.clone(),
+            });
+        }
+        if converter.skip_css_vars {
+            return Err(ConfigError::IgnoredSkipCssVars);
+        }
+    }
+    let without_vars = converter.skip_css_vars || converter.four_bit_classes.is_some();
+    if converter.var_names.is_some() && without_vars {
+        return Err(ConfigError::IgnoredVarNames);
+    }
+    if converter.sanitize && !converter.skip_escape {
+        return Err(ConfigError::SanitizeWithoutSkipEscape);
+    }
+    if !converter.allowed_tags.is_empty() && !converter.sanitize {
+        return Err(ConfigError::AllowedTagsWithoutSanitize);
+    }
+    if !converter.render_links && converter.link_schemes != UrlSchemes::default() {
+        return Err(ConfigError::LinkSchemesWithoutRenderLinks);
+    }
+    if converter.skip_light_dark && converter.dual_theme.is_none() {
+        return Err(ConfigError::SkipLightDarkWithoutDualTheme);
+    }
+    if converter.adapt_to_theme && converter.theme.is_none() && converter.dual_theme.is_none() {
+        return Err(ConfigError::AdaptWithoutTheme);
+    }
+    if converter.theme.is_some() && converter.dual_theme.is_some() {
+        return Err(ConfigError::IgnoredPalette);
+    }
+    if converter.flat_spans && converter.tag_style != TagStyle::Presentational {
+        return Err(ConfigError::IgnoredTagStyle);
+    }
+    if converter.detect_double_escape && converter.skip_escape {
+        return Err(ConfigError::DetectDoubleEscapeWithSkipEscape);
+    }
+    if converter.line_safe && converter.pretty {
+        return Err(ConfigError::LineSafeWithPretty);
+    }
+    if converter.line_wrapper.is_some() && converter.pretty {
+        return Err(ConfigError::LineWrapperWithPretty);
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! Pretty-printing of the generated HTML, see [`Converter::pretty`](crate::Converter::pretty).
+//!
+//! The HTML is parsed into a tree, which is then serialized with every tag and
+//! text run on its own line. The line breaks and indentation are put into HTML
+//! comments, so they don't change the rendered text, even inside `<pre>`.
+
+/// Elements that don't have an end tag
+const VOID_ELEMENTS: &[&str] = &["br", "hr", "img", "wbr"];
+
+#[derive(Debug, PartialEq, Eq)]
+enum Node<'a> {
+    Text(&'a str),
+    /// A tag without content, e.g. a void element, a comment or an end tag
+    /// without matching start tag
+    Tag(&'a str),
+    Element {
+        start: &'a str,
+        children: Vec<Node<'a>>,
+        /// Empty if the element isn't closed
+        end: &'a str,
+    },
+}
+
+/// Pretty-prints the HTML
+pub(crate) fn pretty(html: &str) -> String {
+    let phase = crate::trace::phase!("pretty", html.len());
+    let mut out = String::with_capacity(html.len() * 2);
+    write_nodes(&parse(html), 0, &mut out);
+    phase.output_len(out.len());
+    out
+}
+
+fn parse(mut html: &str) -> Vec<Node<'_>> {
+    // the open elements, with their start tag and their children so far
+    let mut stack: Vec<(&str, Vec<Node<'_>>)> = vec![("", Vec::new())];
+
+    while !html.is_empty() {
+        let Some(len) = tag_len(html) else {
+            let len = text_len(html);
+            stack.last_mut().unwrap().1.push(Node::Text(&html[..len]));
+            html = &html[len..];
+            continue;
+        };
+        let (token, rest) = html.split_at(len);
+        html = rest;
+
+        let name = tag_name(token);
+        if token.starts_with("</") {
+            let is_open = stack.len() > 1 && tag_name(stack.last().unwrap().0) == name;
+            if is_open {
+                let (start, children) = stack.pop().unwrap();
+                let element = Node::Element {
+                    start,
+                    children,
+                    end: token,
+                };
+                stack.last_mut().unwrap().1.push(element);
+            } else {
+                stack.last_mut().unwrap().1.push(Node::Tag(token));
+            }
+        } else if token.starts_with("<!")
+            || token.ends_with("/>")
+            || VOID_ELEMENTS.iter().any(|v| v.eq_ignore_ascii_case(name))
+        {
+            stack.last_mut().unwrap().1.push(Node::Tag(token));
+        } else {
+            stack.push((token, Vec::new()));
+        }
+    }
+
+    while stack.len() > 1 {
+        let (start, children) = stack.pop().unwrap();
+        let element = Node::Element {
+            start,
+            children,
+            end: "",
+        };
+        stack.last_mut().unwrap().1.push(element);
+    }
+    stack.pop().unwrap().1
+}
+
+/// Returns the length of the tag at the start of `html`, if it starts with a
+/// tag or comment
+fn tag_len(html: &str) -> Option<usize> {
+    if html.starts_with("<!--") {
+        return html.find("-->").map(|i| i + 3);
+    }
+    let rest = html.strip_prefix('<')?;
+    let rest = rest.strip_prefix('/').unwrap_or(rest);
+    if !rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '!') {
+        return None;
+    }
+    html.find('>').map(|i| i + 1)
+}
+
+/// Returns the length of the text at the start of `html`, which ends before the
+/// next tag
+fn text_len(html: &str) -> usize {
+    let mut i = html.chars().next().map_or(0, char::len_utf8);
+    while let Some(j) = html[i..].find('<') {
+        if tag_len(&html[i + j..]).is_some() {
+            return i + j;
+        }
+        i += j + 1;
+    }
+    html.len()
+}
+
+fn tag_name(tag: &str) -> &str {
+    let name = tag.trim_start_matches(['<', '/']);
+    let end = name
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(name.len());
+    &name[..end]
+}
+
+fn write_nodes(nodes: &[Node<'_>], depth: usize, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) | Node::Tag(text) => {
+                write_line_break(depth, out);
+                out.push_str(text);
+            }
+            Node::Element {
+                start,
+                children,
+                end,
+            } => {
+                write_line_break(depth, out);
+                out.push_str(start);
+                write_nodes(children, depth + 1, out);
+                if !end.is_empty() {
+                    write_line_break(depth, out);
+                    out.push_str(end);
+                }
+            }
+        }
+    }
+}
+
+/// Starts a new line with the given indentation, unless this is the start of
+/// the output
+fn write_line_break(depth: usize, out: &mut String) {
+    if !out.is_empty() {
+        out.push_str("<!--\n");
+        out.extend(std::iter::repeat_n("  ", depth));
+        out.push_str("-->");
+    }
+}
@@ -0,0 +1,22 @@
+/// The quotes around the attribute values of the generated HTML, see
+/// [`Converter::attribute_quotes`](crate::Converter::attribute_quotes).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quote {
+    /// `'`, e.g. `<span style='opacity:0.67'>`
+    #[default]
+    Single,
+    /// `"`, e.g. `<span style="opacity:0.67">`
+    Double,
+}
+
+impl Quote {
+    /// Appends generated tags, which use single quotes, with these quotes. The
+    /// attribute values of generated tags are escaped, so all single quotes in
+    /// them delimit a value.
+    pub(crate) fn push_tags(self, tags: &str, out: &mut String) {
+        match self {
+            Quote::Single => out.push_str(tags),
+            Quote::Double => out.extend(tags.chars().map(|c| if c == '\'' { '"' } else { c })),
+        }
+    }
+}
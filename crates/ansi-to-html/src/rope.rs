@@ -0,0 +1,61 @@
+//! Building large HTML without reallocating, see [`Rope`].
+
+use std::fmt;
+
+/// The capacity of a block. It is above the threshold at which the allocator
+/// maps memory directly, so a block is returned to the OS as soon as it is
+/// dropped.
+const BLOCK_LEN: usize = 1 << 18;
+
+/// A string that is written in blocks of [`BLOCK_LEN`] bytes.
+///
+/// When a `String` grows, the text written so far is copied into a buffer of
+/// twice the size, so building large HTML copies it about twice and needs up
+/// to three times its size while reallocating. The blocks are copied only
+/// once, into a string of the exact length, by [`Rope::into_string`].
+#[derive(Debug)]
+pub(crate) struct Rope {
+    blocks: Vec<String>,
+    len: usize,
+    /// The capacity of the next block
+    block_len: usize,
+}
+
+impl Rope {
+    /// Creates a rope whose first block has room for about `len_hint` bytes,
+    /// so small HTML is written into a single block of about the right size.
+    pub(crate) fn new(len_hint: usize) -> Self {
+        Rope {
+            blocks: Vec::new(),
+            len: 0,
+            block_len: len_hint.clamp(64, BLOCK_LEN),
+        }
+    }
+
+    pub(crate) fn into_string(mut self) -> String {
+        if self.blocks.len() == 1 {
+            return self.blocks.pop().unwrap_or_default();
+        }
+        let mut string = String::with_capacity(self.len);
+        for block in self.blocks {
+            string.push_str(&block);
+        }
+        string
+    }
+}
+
+impl fmt::Write for Rope {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.blocks.last_mut() {
+            Some(block) if block.capacity() - block.len() >= s.len() => block.push_str(s),
+            _ => {
+                let mut block = String::with_capacity(self.block_len.max(s.len()));
+                block.push_str(s);
+                self.blocks.push(block);
+                self.block_len = BLOCK_LEN;
+            }
+        }
+        self.len += s.len();
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+//! Sanitizing of pre-escaped input, see [`Converter::sanitize`](crate::Converter::sanitize).
+
+/// Attributes that may appear in allowed tags. Tags with other attributes (like
+/// `onclick`, `href` or `style`, which could inject arbitrary CSS) are escaped.
+const ALLOWED_ATTRS: &[&str] = &["class", "title"];
+
+/// Elements that don't have an end tag
+const VOID_ELEMENTS: &[&str] = &["br", "hr", "wbr"];
+
+/// Escapes `<` and `&` unless they start a character reference (like `&lt;` or
+/// `&#39;`) or one of the allowed tags.
+///
+/// End tags are only kept if they close the most recently opened allowed tag,
+/// and tags that are still open at the end are closed, so the converter's own
+/// tags can't be closed prematurely.
+pub(crate) fn sanitize(input: &str, allowed_tags: &[String]) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    let mut open_tags: Vec<&str> = Vec::new();
+
+    while let Some(i) = rest.find(['<', '&']) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let len = if rest.starts_with('&') {
+            entity_len(rest)
+        } else {
+            tag_len(rest, allowed_tags).filter(|&len| {
+                let tag = &rest[..len];
+                match tag_name(tag) {
+                    (true, name) if open_tags.last() == Some(&name) => {
+                        open_tags.pop();
+                        true
+                    }
+                    (true, _) => false,
+                    (false, name) => {
+                        let is_void = VOID_ELEMENTS.iter().any(|v| v.eq_ignore_ascii_case(name));
+                        if !is_void && !tag.ends_with("/>") {
+                            open_tags.push(name);
+                        }
+                        true
+                    }
+                }
+            })
+        };
+        match len {
+            Some(len) => {
+                out.push_str(&rest[..len]);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push_str(if rest.starts_with('&') {
+                    "&amp;"
+                } else {
+                    "&lt;"
+                });
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    for name in open_tags.into_iter().rev() {
+        out.push_str("</");
+        out.push_str(name);
+        out.push('>');
+    }
+    out
+}
+
+/// Returns whether the tag is an end tag, and the tag name
+fn tag_name(tag: &str) -> (bool, &str) {
+    let (is_end_tag, rest) = match tag[1..].strip_prefix('/') {
+        Some(rest) => (true, rest),
+        None => (false, &tag[1..]),
+    };
+    let len = rest.bytes().take_while(u8::is_ascii_alphanumeric).count();
+    (is_end_tag, &rest[..len])
+}
+
+/// Returns the length of the character reference at the start of `s`
+fn entity_len(s: &str) -> Option<usize> {
+    let b = s.as_bytes();
+    let (start, valid): (usize, fn(&u8) -> bool) = match b.get(1) {
+        Some(b'#') if matches!(b.get(2), Some(b'x' | b'X')) => (3, u8::is_ascii_hexdigit),
+        Some(b'#') => (2, u8::is_ascii_digit),
+        _ => (1, u8::is_ascii_alphanumeric),
+    };
+    let len = b[start..].iter().take_while(|c| valid(c)).count();
+    if len == 0 || b.get(start + len) != Some(&b';') {
+        return None;
+    }
+    if start == 1 && !b[1].is_ascii_alphabetic() {
+        return None;
+    }
+    Some(start + len + 1)
+}
+
+/// Returns the length of the allowed tag at the start of `s`
+fn tag_len(s: &str, allowed_tags: &[String]) -> Option<usize> {
+    let (is_end_tag, mut rest) = match s[1..].strip_prefix('/') {
+        Some(rest) => (true, rest),
+        None => (false, &s[1..]),
+    };
+
+    let name_len = rest.bytes().take_while(u8::is_ascii_alphanumeric).count();
+    let name = &rest[..name_len];
+    if name.is_empty() || !allowed_tags.iter().any(|t| t.eq_ignore_ascii_case(name)) {
+        return None;
+    }
+    rest = &rest[name_len..];
+
+    if !is_end_tag {
+        while let Some(after_ws) = rest.strip_prefix(|c: char| c.is_ascii_whitespace()) {
+            rest = after_ws.trim_start_matches(|c: char| c.is_ascii_whitespace());
+            if rest.starts_with(['>', '/']) {
+                break;
+            }
+            rest = skip_attribute(rest)?;
+        }
+        rest = rest.strip_prefix('/').unwrap_or(rest);
+    } else {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    }
+
+    rest.strip_prefix('>')?;
+    Some(s.len() - rest.len() + 1)
+}
+
+/// Skips an allowed attribute with an optional quoted or unquoted value
+fn skip_attribute(s: &str) -> Option<&str> {
+    let name_len = s
+        .bytes()
+        .take_while(|&c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_')
+        .count();
+    let name = &s[..name_len];
+    if !ALLOWED_ATTRS.iter().any(|a| a.eq_ignore_ascii_case(name)) {
+        return None;
+    }
+
+    let Some(value) = s[name_len..].strip_prefix('=') else {
+        return Some(&s[name_len..]);
+    };
+    match value.as_bytes().first()? {
+        &quote @ (b'\'' | b'"') => {
+            let end = value[1..].find(quote as char)?;
+            let content = &value[1..end + 1];
+            if content.contains('<') {
+                return None;
+            }
+            Some(&value[end + 2..])
+        }
+        _ => {
+            let len = value
+                .bytes()
+                .take_while(|c| !c.is_ascii_whitespace() && !b"\"'=<>`".contains(c))
+                .count();
+            (len > 0).then_some(&value[len..])
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Conversion to runs of styled text instead of HTML, see
+//! [`Converter::to_segments`](crate::Converter::to_segments).
+
+use std::sync::Arc;
+
+use crate::{
+    ansi::find_sequence, combining, html, html::CurrentStyling, newlines, Ansi, AnsiIter,
+    Converter, Error, SgrColor,
+};
+
+/// A run of text with the same styling, returned by
+/// [`Converter::to_segments`](crate::Converter::to_segments)
+///
+/// The attributes are the ones the converter renders; unsupported styles such
+/// as doubly underlined text are ignored like in the HTML.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StyledSegment {
+    /// The text, without escape sequences. It is never empty.
+    pub text: String,
+    pub bold: bool,
+    pub faint: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub overline: bool,
+    pub crossed_out: bool,
+    /// Slow or rapid blinking, if
+    /// [`render_blink`](crate::Converter::render_blink) is enabled
+    pub blink: bool,
+    /// Reverse video: the text is rendered with the colors swapped, and with
+    /// the [default colors](crate::Converter::default_colors) for the ones
+    /// that are `None`
+    pub reverse: bool,
+    /// Concealed text, e.g. a password. With
+    /// [`redact_concealed`](crate::Converter::redact_concealed), the text is
+    /// replaced with spaces.
+    pub concealed: bool,
+    /// The text color, or `None` for the default color
+    pub foreground: Option<SgrColor>,
+    /// The background color, or `None` for the default background
+    pub background: Option<SgrColor>,
+    /// The target of the OSC 8 hyperlink containing the text, if
+    /// [`render_links`](crate::Converter::render_links) is enabled
+    pub link: Option<String>,
+}
+
+pub(crate) fn to_segments(input: &str, converter: &Converter) -> Result<Vec<StyledSegment>, Error> {
+    let normalized = newlines::normalize(input, converter.newlines);
+    let mut segments = Segments {
+        list: Vec::new(),
+        last_styling: None,
+        styling: CurrentStyling::default(),
+        attach_combining: converter.attach_combining,
+        redact_concealed: converter.redact_concealed,
+    };
+
+    let mut rest = &*normalized;
+    while let Some(range) = find_sequence(rest) {
+        segments.push(&rest[..range.start]);
+        let sequence = &rest[range.clone()];
+        rest = &rest[range.end..];
+
+        if sequence.len() > converter.max_sequence_len {
+            segments.push(sequence);
+            continue;
+        }
+        if converter.render_links {
+            if let Some(uri) = html::hyperlink(sequence) {
+                let allowed = !uri.is_empty() && converter.link_schemes.allows(uri);
+                segments.styling.link = allowed.then(|| Arc::from(uri));
+                continue;
+            }
+        }
+        let sgr = sequence
+            .strip_prefix("\x1b[")
+            .and_then(|s| s.strip_suffix('m'));
+        let Some(nums) = sgr else {
+            continue;
+        };
+        if nums.is_empty() {
+            segments.styling.apply(Ansi::Reset);
+            continue;
+        }
+        let nums = nums.strip_suffix(';').unwrap_or(nums);
+        for ansi in AnsiIter::new(nums.split(';').map(|n| n.parse::<u8>())) {
+            match ansi {
+                Ok(ansi) if ansi.is_blink() && !converter.render_blink => {}
+                Ok(ansi) => segments.styling.apply(ansi),
+                Err(_) if converter.lossy => {}
+                Err(e) => return Err(e.in_sequence(sequence).locate(input, 0)),
+            }
+        }
+    }
+    segments.push(rest);
+    Ok(segments.list)
+}
+
+struct Segments {
+    list: Vec<StyledSegment>,
+    /// The styling of the last segment
+    last_styling: Option<CurrentStyling>,
+    styling: CurrentStyling,
+    attach_combining: bool,
+    redact_concealed: bool,
+}
+
+impl Segments {
+    fn push(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let redacted;
+        let mut text = if self.redact_concealed && self.styling.is_concealed() {
+            redacted = html::redact(text);
+            &*redacted
+        } else {
+            text
+        };
+        if let Some(last) = self.list.last_mut() {
+            if self.last_styling.as_ref() == Some(&self.styling) {
+                last.text.push_str(text);
+                return;
+            }
+            if self.attach_combining {
+                // characters that extend the last character keep its styling
+                let mut prev = last.text.chars().next_back();
+                let attached = text
+                    .char_indices()
+                    .find(|&(_, c)| {
+                        let extends = prev.is_some_and(|prev| combining::extends(prev, c));
+                        prev = Some(c);
+                        !extends
+                    })
+                    .map_or(text.len(), |(i, _)| i);
+                last.text.push_str(&text[..attached]);
+                text = &text[attached..];
+                if text.is_empty() {
+                    return;
+                }
+            }
+        }
+        self.list.push(self.styling.to_segment(text.to_string()));
+        self.last_styling = Some(self.styling.clone());
+    }
+}
@@ -0,0 +1,65 @@
+//! Repeated conversions that reuse their allocations, see
+//! [`Converter::session`](crate::Converter::session).
+
+use crate::{compat, html, html::Buffers, newlines, trace::phase, Converter, Error};
+
+/// A sequence of conversions with the same converter that reuse their
+/// allocations, created by [`Converter::session`].
+///
+/// Each conversion is independent: Styles aren't carried over from one input
+/// to the next, and the HTML is the same as that of
+/// [`convert`](Converter::convert). The buffers for the styles, the tags and
+/// the HTML are kept when a conversion ends, and cleared before the next one
+/// uses them. This saves allocations when many small inputs are converted on
+/// the same thread, e.g. the lines of a log. With [`pretty`](Converter::pretty)
+/// or [`OutputCompat::V0`](crate::OutputCompat::V0), the HTML is built in a new
+/// string and copied into the buffer.
+#[derive(Debug)]
+pub struct ConversionSession<'a> {
+    converter: &'a Converter,
+    html: String,
+    buffers: Buffers,
+}
+
+impl<'a> ConversionSession<'a> {
+    pub(crate) fn new(converter: &'a Converter) -> Self {
+        Self {
+            converter,
+            html: String::new(),
+            buffers: Buffers::default(),
+        }
+    }
+
+    /// Converts the input and returns the HTML, which is borrowed from the
+    /// session until the next conversion.
+    ///
+    /// If an error is returned, the session can be used for the next input.
+    pub fn convert(&mut self, input: &str) -> Result<&str, Error> {
+        self.html.clear();
+        let converter = self.converter;
+        if converter.is_unchanged(input) || converter.is_converted(input) {
+            self.html.push_str(input);
+            return Ok(&self.html);
+        }
+        if converter.pretty || compat::optimizes_v0(converter) {
+            // the whole HTML is needed
+            self.html.push_str(&converter.convert(input)?);
+            return Ok(&self.html);
+        }
+        let phase = phase!("convert", input.len());
+        let normalized = newlines::normalize(input, converter.newlines);
+        let escaped = converter.escape(&normalized);
+        html::ansi_to_html_reusing(&escaped, converter, &mut self.html, &mut self.buffers)
+            .map_err(|e| e.locate(input, 0))?;
+        phase.output_len(self.html.len());
+        Ok(&self.html)
+    }
+
+    /// Like [`convert`](ConversionSession::convert), but returns the HTML as
+    /// an owned string. The buffer of the HTML is moved out, so the next
+    /// conversion allocates a new one.
+    pub fn convert_owned(&mut self, input: &str) -> Result<String, Error> {
+        self.convert(input)?;
+        Ok(std::mem::take(&mut self.html))
+    }
+}
@@ -0,0 +1,61 @@
+use crate::html::Position;
+
+/// The state at the end of some converted input, used by
+/// [`Converter::convert_append`](crate::Converter::convert_append).
+///
+/// This contains the styles that are active at the end of the input, and an
+/// escape sequence (or HTML entity, with
+/// [`skip_escape`](crate::Converter::skip_escape)) that is incomplete because
+/// the input ended in the middle of it. With
+/// [`newline_normalization`](crate::Converter::newline_normalization), a `\r`
+/// at the end of the input is stored as well. Two states compare equal if
+/// converting the same text with them produces the same HTML.
+///
+/// For example, if the state is the [default](StyleState::is_default) after a
+/// reset, the HTML converted so far doesn't influence anything that is appended
+/// later.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StyleState {
+    pub(crate) position: Position,
+    pub(crate) pending: String,
+}
+
+impl StyleState {
+    /// Creates the state at the start of the input, with no active styles.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if no styles are active and no text is pending.
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Splits the input before an escape sequence that is cut off at the end of the
+/// input, so it can be completed by the next call.
+pub(crate) fn split_incomplete_sequence(input: &str) -> (&str, &str) {
+    // An OSC sequence can contain anything except BEL and ESC, so it needs to be
+    // checked first
+    if let Some(start) = input.rfind("\x1b]") {
+        let payload = &input[start + 2..];
+        if !payload.contains('\x07') && !payload.contains("\x1b\\") {
+            return input.split_at(start);
+        }
+    }
+
+    if let Some(start) = input.rfind('\x1b') {
+        let incomplete = match &input[start + 1..] {
+            "" | "(" => true,
+            tail => tail.strip_prefix('[').is_some_and(|params| {
+                params
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || b == b';' || b == b'?')
+            }),
+        };
+        if incomplete {
+            return input.split_at(start);
+        }
+    }
+    (input, "")
+}
@@ -0,0 +1,81 @@
+//! Conversion of input that arrives in chunks, see
+//! [`Converter::begin`](crate::Converter::begin).
+//!
+//! The state of the minifier is kept between the chunks, including the styling
+//! of the tags that are still open, so they aren't closed and re-opened at
+//! every chunk boundary like with
+//! [`convert_append`](crate::Converter::convert_append).
+
+use std::borrow::Cow;
+
+use crate::{compat, html, html::StreamState, newlines, Converter, Error};
+
+/// A conversion of input that arrives in chunks, created by
+/// [`Converter::begin`].
+///
+/// Styles that are active at the end of a chunk stay open in the next chunk.
+/// An escape sequence that is cut off at the end of a chunk is converted with
+/// the next chunk. The options of the converter apply, except for
+/// [`pretty`](Converter::pretty), since the HTML of a chunk isn't a complete
+/// tree. The [`text_transform`](Converter::text_transform) is called for each
+/// chunk separately.
+#[derive(Clone, Debug)]
+pub struct ConverterStream {
+    converter: Converter,
+    state: StreamState,
+    /// The end of the input that may be completed by the next chunk
+    pending: String,
+}
+
+impl ConverterStream {
+    pub(crate) fn new(converter: Converter) -> Self {
+        Self {
+            converter,
+            state: StreamState::default(),
+            pending: String::new(),
+        }
+    }
+
+    /// Converts the next chunk of the input and returns its HTML. The tags that
+    /// are open at the end of the chunk are not closed.
+    ///
+    /// If an error is returned, the stream is unchanged, so the chunk can be
+    /// skipped.
+    pub fn feed(&mut self, chunk: &str) -> Result<String, Error> {
+        let converter = &self.converter;
+        let full = if self.pending.is_empty() {
+            Cow::Borrowed(chunk)
+        } else {
+            Cow::Owned(format!("{}{chunk}", self.pending))
+        };
+        let (input, pending) = converter.split_pending(&full);
+
+        let input = newlines::normalize(input, converter.newlines);
+        let input = converter.escape(&input);
+        let mut html = String::new();
+        let state = html::stream_chunk(&input, converter, self.state.clone(), &mut html)
+            .map_err(|e| e.locate(&full, self.pending.len()))?;
+
+        self.state = state;
+        self.pending = pending.to_owned();
+        Ok(compat::optimize_v0(html, converter))
+    }
+
+    /// Ends the stream and returns the remaining HTML, which closes all open
+    /// tags. Text that was held back (e.g. `\x1b[3` at the end of the last
+    /// chunk) is converted as it is; invalid codes in it are ignored.
+    #[must_use = "the HTML of the held back input is returned"]
+    pub fn finish(self) -> String {
+        let converter = Converter {
+            lossy: true,
+            ..self.converter
+        };
+        let input = newlines::normalize(&self.pending, converter.newlines);
+        let input = converter.escape(&input);
+        let mut html = String::new();
+        let state = html::stream_chunk(&input, &converter, self.state.clone(), &mut html)
+            .unwrap_or(self.state);
+        html::stream_end(&converter, state, &mut html);
+        html
+    }
+}
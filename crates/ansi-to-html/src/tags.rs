@@ -0,0 +1,33 @@
+/// Which tags are used for bold, italic and crossed-out text, see
+/// [`Converter::tag_style`](crate::Converter::tag_style).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TagStyle {
+    /// `<b>`, `<i>` and `<s>`
+    #[default]
+    Presentational,
+    /// `<strong>`, `<em>` and `<del>`, which screen readers may announce
+    Semantic,
+}
+
+impl TagStyle {
+    pub(crate) fn bold(self) -> (&'static str, &'static str) {
+        match self {
+            TagStyle::Presentational => ("<b>", "</b>"),
+            TagStyle::Semantic => ("<strong>", "</strong>"),
+        }
+    }
+
+    pub(crate) fn italic(self) -> (&'static str, &'static str) {
+        match self {
+            TagStyle::Presentational => ("<i>", "</i>"),
+            TagStyle::Semantic => ("<em>", "</em>"),
+        }
+    }
+
+    pub(crate) fn crossed_out(self) -> (&'static str, &'static str) {
+        match self {
+            TagStyle::Presentational => ("<s>", "</s>"),
+            TagStyle::Semantic => ("<del>", "</del>"),
+        }
+    }
+}
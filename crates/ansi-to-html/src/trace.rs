@@ -0,0 +1,42 @@
+//! Spans for the phases of a conversion, which are emitted with the `tracing`
+//! feature. Without it, [`phase!`] expands to a unit struct and its arguments
+//! aren't evaluated, so there is no overhead.
+
+/// A phase of a conversion, whose span is exited when this is dropped
+#[cfg(feature = "tracing")]
+pub(crate) struct Phase(pub(crate) tracing::span::EnteredSpan);
+
+/// A phase of a conversion, which is a no-op without the `tracing` feature
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Phase;
+
+impl Phase {
+    /// Records the length of the output of the phase in bytes
+    #[inline(always)]
+    pub(crate) fn output_len(&self, _len: usize) {
+        #[cfg(feature = "tracing")]
+        self.0.record("output_len", _len);
+    }
+}
+
+/// Enters a debug-level span with the name of the phase and the length of its
+/// input in bytes. The length of its output is recorded with
+/// [`Phase::output_len`].
+macro_rules! phase {
+    ($name:literal, $input_len:expr) => {{
+        #[cfg(feature = "tracing")]
+        let phase = $crate::trace::Phase(
+            tracing::debug_span!(
+                $name,
+                input_len = $input_len,
+                output_len = tracing::field::Empty,
+            )
+            .entered(),
+        );
+        #[cfg(not(feature = "tracing"))]
+        let phase = $crate::trace::Phase;
+        phase
+    }};
+}
+
+pub(crate) use phase;
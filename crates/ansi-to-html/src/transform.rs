@@ -0,0 +1,133 @@
+use std::{fmt, sync::Arc};
+
+use crate::{Esc, EscUrl};
+
+/// The result of a [`Converter::text_transform`](crate::Converter::text_transform)
+/// hook.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transformed {
+    /// The text is kept.
+    Unchanged,
+    /// The text is replaced.
+    Text(String),
+    /// The text is replaced by text and HTML.
+    Pieces(Vec<Piece>),
+}
+
+/// A part of [`Transformed::Pieces`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Piece {
+    /// Text, which is escaped like the input, unless
+    /// [`skip_escape`](crate::Converter::skip_escape) is enabled.
+    Text(String),
+    /// HTML, which is inserted as is. Text within it must be escaped with
+    /// [`Esc`], [`EscAttr`](crate::EscAttr) or [`EscUrl`].
+    Html(String),
+}
+
+impl Piece {
+    /// Returns a link to `url` with the given text. Both are escaped.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use ansi_to_html::Piece;
+    ///
+    /// assert_eq!(
+    ///     Piece::link("https://example.com/?a=1&b=2", "<example>"),
+    ///     Piece::Html(
+    ///         "<a href='https://example.com/?a=1&amp;b=2'>&lt;example&gt;</a>".into()
+    ///     ),
+    /// );
+    /// ```
+    pub fn link(url: &str, text: &str) -> Self {
+        Piece::Html(format!("<a href='{}'>{}</a>", EscUrl(url), Esc(text)))
+    }
+}
+
+type TransformFn = dyn Fn(&str) -> Transformed + Send + Sync;
+
+#[derive(Clone)]
+pub(crate) struct TextTransform(Arc<TransformFn>);
+
+impl fmt::Debug for TextTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TextTransform(..)")
+    }
+}
+
+/// Transforms are equal if they are the same closure, e.g. in clones of a converter. Closures
+/// can't be compared otherwise.
+impl PartialEq for TextTransform {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for TextTransform {}
+
+impl TextTransform {
+    pub(crate) fn new(f: impl Fn(&str) -> Transformed + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    /// Transforms a run of text and appends it to `out`. Unless `skip_escape`
+    /// is enabled, `run` is escaped, so it's unescaped before calling the hook.
+    pub(crate) fn apply(&self, run: &str, skip_escape: bool, out: &mut String) {
+        let escape = |text: &str, out: &mut String| {
+            if skip_escape {
+                out.push_str(text);
+            } else {
+                out.push_str(&Esc(text).to_string());
+            }
+        };
+
+        let text = if skip_escape {
+            run.to_string()
+        } else {
+            unescape(run)
+        };
+        match (self.0)(&text) {
+            Transformed::Unchanged => out.push_str(run),
+            Transformed::Text(text) => escape(&text, out),
+            Transformed::Pieces(pieces) => {
+                for piece in pieces {
+                    match piece {
+                        Piece::Text(text) => escape(&text, out),
+                        Piece::Html(html) => out.push_str(&html),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reverses [`Esc`]
+pub(crate) fn unescape(html: &str) -> String {
+    const ENTITIES: [(&str, char); 5] = [
+        ("&amp;", '&'),
+        ("&lt;", '<'),
+        ("&gt;", '>'),
+        ("&quot;", '"'),
+        ("&#39;", '\''),
+    ];
+
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(i) = rest.find('&') {
+        text.push_str(&rest[..i]);
+        rest = &rest[i..];
+        match ENTITIES.iter().find(|(entity, _)| rest.starts_with(entity)) {
+            Some(&(entity, c)) => {
+                text.push(c);
+                rest = &rest[entity.len()..];
+            }
+            None => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
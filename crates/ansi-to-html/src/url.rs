@@ -0,0 +1,66 @@
+/// A list of URL schemes that are allowed in generated links.
+///
+/// URLs from the input (e.g. the targets of OSC 8 hyperlinks) are only emitted
+/// when their scheme is in this list, so malicious input can't inject
+/// `javascript:` URLs. URLs without a scheme are rejected as well.
+///
+/// The default allows `http`, `https`, `mailto` and `file`.
+///
+/// ## Example
+///
+/// ```
+/// use ansi_to_html::UrlSchemes;
+///
+/// let schemes = UrlSchemes::default();
+/// assert!(schemes.allows("https://example.com"));
+/// assert!(schemes.allows("MAILTO:me@example.com"));
+/// assert!(!schemes.allows("javascript:alert(1)"));
+/// assert!(!schemes.allows("/relative/path"));
+///
+/// let schemes = UrlSchemes::new(["https"]);
+/// assert!(!schemes.allows("http://example.com"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UrlSchemes {
+    schemes: Vec<String>,
+}
+
+impl UrlSchemes {
+    /// Creates an allowlist containing the given schemes, e.g. `["https", "ftp"]`.
+    ///
+    /// Schemes are compared case-insensitively.
+    pub fn new<I, S>(schemes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let schemes = schemes
+            .into_iter()
+            .map(|s| s.into().to_ascii_lowercase())
+            .collect();
+        Self { schemes }
+    }
+
+    /// Returns whether the scheme of the URL is in the allowlist.
+    pub fn allows(&self, url: &str) -> bool {
+        match scheme(url) {
+            Some(scheme) => self.schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)),
+            None => false,
+        }
+    }
+}
+
+impl Default for UrlSchemes {
+    fn default() -> Self {
+        Self::new(["http", "https", "mailto", "file"])
+    }
+}
+
+/// Returns the scheme of the URL, if it has a syntactically valid one.
+fn scheme(url: &str) -> Option<&str> {
+    let (scheme, _) = url.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next()?.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
@@ -0,0 +1,90 @@
+use std::fmt::Write;
+
+use crate::{
+    color::{Color, FourBitColor},
+    Error,
+};
+
+/// Custom names for the CSS variables of the 16 4-bit colors, see
+/// [`Converter::var_names`](crate::Converter::var_names).
+///
+/// The names are given without the leading `--`. They may only contain ASCII
+/// letters and digits, `-`, `_` and non-ASCII characters, so they can't break
+/// out of the `style` attribute.
+///
+/// ## Example
+///
+/// ```
+/// use ansi_to_html::VarNames;
+///
+/// let names = VarNames::new(|name| format!("color-terminal-{name}")).unwrap();
+/// assert_eq!(names.get("red"), Some("color-terminal-red"));
+/// assert_eq!(names.get("bright-red"), Some("color-terminal-bright-red"));
+///
+/// assert!(VarNames::new(|name| format!("{name})")).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VarNames {
+    names: Vec<String>,
+}
+
+impl VarNames {
+    /// Creates the names by calling `f` with the default name of each color:
+    /// `black`, `red`, `green`, `yellow`, `blue`, `magenta`, `cyan`, `white`,
+    /// and the same with a `bright-` prefix.
+    ///
+    /// Returns an error if one of the names isn't valid.
+    pub fn new(mut f: impl FnMut(&str) -> String) -> Result<Self, Error> {
+        let names = FourBitColor::ALL
+            .iter()
+            .map(|color| {
+                let name = f(&color.to_string());
+                if is_valid(&name) {
+                    Ok(name)
+                } else {
+                    Err(Error::InvalidVarName { name })
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { names })
+    }
+
+    /// Returns the name for the color with the given default name, e.g. `red`.
+    pub fn get(&self, default_name: &str) -> Option<&str> {
+        FourBitColor::ALL
+            .iter()
+            .position(|color| color.to_string() == default_name)
+            .map(|i| self.names[i].as_str())
+    }
+
+    /// Returns a stylesheet that declares the variables on `:root` with the
+    /// default colors, which can be changed to customize the colors.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let names = ansi_to_html::VarNames::new(|name| format!("term-{name}")).unwrap();
+    /// assert!(names.stylesheet().contains("  --term-bright-red: #f55;\n"));
+    /// ```
+    #[must_use]
+    pub fn stylesheet(&self) -> String {
+        let mut css = String::from(":root {\n");
+        for (color, name) in FourBitColor::ALL.iter().zip(&self.names) {
+            let _ = writeln!(css, "  --{name}: {};", Color::FourBit(*color));
+        }
+        css.push_str("}\n");
+        css
+    }
+
+    pub(crate) fn name(&self, color: FourBitColor) -> &str {
+        &self.names[color as usize]
+    }
+}
+
+/// Returns whether the name may be used in a `style` attribute
+pub(crate) fn is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_') || !c.is_ascii())
+}
@@ -0,0 +1,88 @@
+//! The number of columns that text takes in a terminal, see [`visible_width`].
+//!
+//! This is not a full implementation of the East Asian Width property. Only
+//! the blocks of wide CJK characters and the emoji that terminals usually
+//! display with two columns are included.
+
+use crate::{ansi::text_fragments, combining};
+
+/// Ranges of characters that take two columns, sorted
+const WIDE: &[(char, char)] = &[
+    // Hangul Jamo
+    ('\u{1100}', '\u{115F}'),
+    // CJK radicals, punctuation, Hiragana, Katakana, ..., Yi
+    ('\u{2E80}', '\u{303E}'),
+    ('\u{3041}', '\u{A4CF}'),
+    // Hangul syllables
+    ('\u{AC00}', '\u{D7A3}'),
+    // CJK compatibility ideographs
+    ('\u{F900}', '\u{FAFF}'),
+    // CJK compatibility forms
+    ('\u{FE30}', '\u{FE4F}'),
+    // fullwidth forms
+    ('\u{FF00}', '\u{FF60}'),
+    ('\u{FFE0}', '\u{FFE6}'),
+    // emoji
+    ('\u{1F300}', '\u{1F64F}'),
+    ('\u{1F680}', '\u{1F6FF}'),
+    ('\u{1F900}', '\u{1F9FF}'),
+    ('\u{1FA70}', '\u{1FAFF}'),
+    // CJK unified ideographs extensions
+    ('\u{20000}', '\u{3FFFD}'),
+];
+
+/// Tab stops are every 8 columns, like in most terminals
+const TAB_WIDTH: usize = 8;
+
+/// Returns the number of columns that the text takes in a terminal, i.e. the
+/// width of its widest line.
+///
+/// Escape sequences are ignored, like in [`text_fragments`]. Wide CJK
+/// characters and most emoji take two columns, characters that extend the
+/// preceding character (e.g. combining accents) take none, and a tab moves to
+/// the next multiple of 8 columns. A `\r` moves back to the start of the line,
+/// and other control characters take no columns. The width of characters that
+/// terminals display differently (e.g. symbols like `✔`) is 1.
+///
+/// ## Example
+///
+/// ```
+/// use ansi_to_html::visible_width;
+///
+/// assert_eq!(visible_width("\x1b[1mbold\x1b[0m text"), 9);
+/// assert_eq!(visible_width("日本語\ne\u{301}"), 6);
+/// assert_eq!(visible_width("a\tb"), 9);
+/// ```
+#[must_use]
+pub fn visible_width(text: &str) -> usize {
+    let mut max = 0;
+    let mut column = 0;
+    let mut prev = '\n';
+    for c in text_fragments(text).flat_map(str::chars) {
+        match c {
+            '\n' | '\r' => column = 0,
+            '\t' => column = (column / TAB_WIDTH + 1) * TAB_WIDTH,
+            c if c.is_control() || combining::extends(prev, c) => {}
+            c if is_wide(c) => column += 2,
+            _ => column += 1,
+        }
+        max = max.max(column);
+        prev = c;
+    }
+    max
+}
+
+fn is_wide(c: char) -> bool {
+    c >= WIDE[0].0
+        && WIDE
+            .binary_search_by(|&(start, end)| {
+                if end < c {
+                    std::cmp::Ordering::Less
+                } else if start > c {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+}
@@ -18,6 +18,18 @@ fn human_readable_to_ansi(s: &str) -> String {
             match inner {
                 // Control
                 "res" => out.push('0'),
+                // Styles
+                "bold" => out.push('1'),
+                "underline" => out.push('4'),
+                "underline_off" => out.push_str("24"),
+                "overline" => out.push_str("53"),
+                "overline_off" => out.push_str("55"),
+                "blink" => out.push('5'),
+                "rapid_blink" => out.push('6'),
+                "blink_off" => out.push_str("25"),
+                "reverse" => out.push('7'),
+                "conceal" => out.push('8'),
+                "conceal_off" => out.push_str("28"),
                 // Basic colors
                 "blue" => out.push_str("34"),
                 "cyan" => out.push_str("36"),
@@ -87,6 +99,302 @@ fn ariadne() {
      <span style='color:#585858'>  |</span>            <span style='color:var(--blue,#00a)'>`---</span> This is of type Str
     <span style='color:#949494'>---&#39;</span>
     "###);
+
+    // with a single style per run, the flat spans are the same
+    let flat = ansi_to_html::Converter::new().flat_spans(true);
+    assert_eq!(flat.convert(&styled).unwrap(), converted);
+}
+
+#[test]
+fn overline() {
+    use ansi_to_html::SgrCode;
+
+    let readable = "{{ overline }}over{{ underline }}both{{ overline_off }}under{{ res }} \
+        {{ overline }}{{ red }}red{{ overline_off }} plain{{ res }}\n{{ overline }}trailing   {{ res }}\nend";
+    let styled = human_readable_to_ansi(readable);
+
+    let converted = ansi_to_html::convert(&styled).unwrap();
+    insta::assert_snapshot!(converted, @"
+    <span style='text-decoration:overline'>over</span><u><span style='text-decoration:overline'>both</span>under</u> <span style='text-decoration:overline'><span style='color:var(--red,#a00)'>red</span></span><span style='color:var(--red,#a00)'> plain</span>
+    <span style='text-decoration:overline'>trailing   </span>
+    end
+    ");
+
+    // the overline is visible on trailing spaces
+    let converter = ansi_to_html::Converter::new().normalize_trailing_whitespace(true);
+    insta::assert_snapshot!(converter.convert(&styled).unwrap(), @"
+    <span style='text-decoration:overline'>over</span><u><span style='text-decoration:overline'>both</span>under</u> <span style='text-decoration:overline'><span style='color:var(--red,#a00)'>red</span></span><span style='color:var(--red,#a00)'> plain</span>
+    <span style='text-decoration:overline'>trailing   </span>
+    end
+    ");
+
+    let segments = converter.to_segments("\x1b[53mover\x1b[55m").unwrap();
+    assert!(segments[0].overline);
+
+    let codes = ansi_to_html::AnsiFragment::Sequence("\x1b[53;55m").sgr_codes();
+    let codes: Vec<_> = codes.unwrap().map(Result::unwrap).collect();
+    assert_eq!(codes, [SgrCode::Overline, SgrCode::OverlineOff]);
+}
+
+#[test]
+fn blink() {
+    use ansi_to_html::{Converter, SgrCode};
+
+    let readable = "{{ blink }}slow{{ blink_off }} {{ rapid_blink }}{{ bold }}rapid \
+        {{ red }}red{{ blink_off }} steady{{ res }} {{ blink }}{{ rapid_blink }}both";
+    let styled = human_readable_to_ansi(readable);
+
+    // blinking is ignored by default
+    insta::assert_snapshot!(ansi_to_html::convert(&styled).unwrap(), @"slow <b>rapid <span style='color:var(--red,#a00)'>red steady</span></b> both");
+
+    let converter = Converter::new().render_blink(true);
+    insta::assert_snapshot!(converter.convert(&styled).unwrap(), @"<span class='blink'>slow</span> <b><span class='blink'>rapid <span style='color:var(--red,#a00)'>red</span></span><span style='color:var(--red,#a00)'> steady</span></b> <span class='blink'>both</span>");
+
+    let converter = converter.four_bit_var_prefix(Some("term-".into()));
+    insta::assert_snapshot!(converter.convert("\x1b[6mfast").unwrap(), @"<span class='term-blink'>fast</span>");
+    insta::assert_snapshot!(converter.blink_css(), @"
+    .term-blink {
+      animation: term-blink 1s step-end infinite;
+    }
+    @keyframes term-blink {
+      50% {
+        visibility: hidden;
+      }
+    }
+    @media (prefers-reduced-motion: reduce) {
+      .term-blink {
+        animation: none;
+      }
+    }
+    ");
+
+    let segments = converter.to_segments("\x1b[5mon\x1b[25moff").unwrap();
+    assert!(segments[0].blink);
+    assert!(!segments[1].blink);
+    let segments = Converter::new()
+        .to_segments("\x1b[5mon\x1b[25moff")
+        .unwrap();
+    assert_eq!(segments.len(), 1);
+    assert!(!segments[0].blink);
+
+    let codes = ansi_to_html::AnsiFragment::Sequence("\x1b[5;6;25m").sgr_codes();
+    let codes: Vec<_> = codes.unwrap().map(Result::unwrap).collect();
+    assert_eq!(
+        codes,
+        [SgrCode::Blink, SgrCode::RapidBlink, SgrCode::BlinkOff]
+    );
+}
+
+#[test]
+fn tag_style() {
+    use ansi_to_html::{Converter, TagStyle};
+
+    let input =
+        "\x1b[1mbold \x1b[3mitalic\x1b[23m \x1b[9mcrossed\x1b[0m \x1b[1m\x1b[0m\x1b[3;9m\x1b[0m";
+    let converter = Converter::new().tag_style(TagStyle::Semantic);
+    let html = converter.convert(input).unwrap();
+    insta::assert_snapshot!(html, @"<strong>bold <em>italic</em> <del>crossed</del></strong>");
+    let presentational = Converter::new().convert(input).unwrap();
+    insta::assert_snapshot!(presentational, @"<b>bold <i>italic</i> <s>crossed</s></b>");
+
+    let mut written = String::new();
+    converter.convert_to_writer(input, &mut written).unwrap();
+    assert_eq!(written, html);
+    // empty tags spanning several chunks aren't removed from a stream
+    let input = input
+        .strip_suffix("\x1b[1m\x1b[0m\x1b[3;9m\x1b[0m")
+        .unwrap();
+    let mut stream = converter.begin();
+    let mut streamed = String::new();
+    for c in input.split_inclusive('m') {
+        streamed += &stream.feed(c).unwrap();
+    }
+    streamed += &stream.finish();
+    assert_eq!(streamed, html);
+
+    // HTML of the input is kept, but with the `optimize` feature, `OutputCompat::V0` optimizes the
+    // selected tags in it
+    let input = "<strong>a</strong><strong>b</strong><em></em><b>c</b><b>d</b>";
+    let skip_escape = converter.clone().skip_escape(true);
+    assert_eq!(skip_escape.convert(input).unwrap(), input);
+    let v0 = skip_escape.output_compat(ansi_to_html::OutputCompat::V0);
+    if cfg!(feature = "optimize") {
+        insta::assert_snapshot!(v0.convert(input).unwrap(), @"<strong>ab</strong><b>c</b><b>d</b>");
+    } else {
+        assert_eq!(v0.convert(input).unwrap(), input);
+    }
+}
+
+#[test]
+fn flat_spans() {
+    use ansi_to_html::{Converter, FaintBlend};
+
+    let input = "\x1b[1;4;31;44mall\x1b[0m \x1b[3;9;53mlines\x1b[4m!\x1b[0m\n\
+        \x1b[2mfaint \x1b[31mred\x1b[8mhidden\x1b[0m \x1b[5mblink\x1b[0m";
+    let converter = Converter::new().flat_spans(true).render_blink(true);
+    let html = converter.convert(input).unwrap();
+    insta::assert_snapshot!(html, @"
+    <span style='font-weight:bold;color:var(--red,#a00);background:var(--blue,#00a);text-decoration:underline'>all</span> <span style='font-style:italic;text-decoration:overline line-through'>lines</span><span style='font-style:italic;text-decoration:underline overline line-through'>!</span>
+    <span style='opacity:0.67'>faint </span><span style='opacity:0.67;color:var(--red,#a00)'>red</span><span style='opacity:0.67;color:transparent'>hidden</span> <span class='blink'>blink</span>
+    ");
+
+    // the color of concealed text and the blended color come last, so they take precedence
+    let blend = FaintBlend::new([255; 3], [0; 3]);
+    let blended = converter
+        .clone()
+        .faint_blend(Some(blend))
+        .four_bit_classes(Some("t-".into()));
+    insta::assert_snapshot!(blended.convert(input).unwrap(), @"
+    <span class='t-red t-bg-blue' style='font-weight:bold;text-decoration:underline'>all</span> <span style='font-style:italic;text-decoration:overline line-through'>lines</span><span style='font-style:italic;text-decoration:underline overline line-through'>!</span>
+    <span style='color:#ababab'>faint </span><span style='color:#720000'>red</span><span style='color:transparent'>hidden</span> <span class='blink'>blink</span>
+    ");
+
+    // links are outside of the spans, and a span is only reopened when the styling changes
+    let linked = "\x1b]8;;https://example.com\x1b\\\x1b[1mlink\x1b]8;;\x1b\\ bold\n\x1b[1mstill";
+    let converter = converter.render_links(true);
+    insta::assert_snapshot!(converter.convert(linked).unwrap(), @"
+    <a href='https://example.com'><span style='font-weight:bold'>link</span></a><span style='font-weight:bold'> bold
+    still</span>
+    ");
+
+    let mut written = String::new();
+    converter.convert_to_writer(input, &mut written).unwrap();
+    assert_eq!(written, html);
+    let mut stream = converter.begin();
+    let mut streamed = String::new();
+    for c in input.split_inclusive('m') {
+        streamed += &stream.feed(c).unwrap();
+    }
+    streamed += &stream.finish();
+    assert_eq!(streamed, html);
+    // the tags of a chunk are closed before the spaces at its end
+    let chunked = html.replace("faint </span>", "faint</span> ");
+    assert_eq!(
+        converter.convert_chunked(input, 1).unwrap().concat(),
+        chunked
+    );
+}
+
+#[test]
+fn attribute_quotes() {
+    use ansi_to_html::{Converter, Quote};
+
+    let input = "\x1b]8;;https://example.com/?q='a'\x1b\\\x1b[1;2;5;31mlink\x1b]8;;\x1b\\\x1b[0m \
+        \x1b[38;5;200;48;2;1;2;3mrgb";
+    let converter = Converter::new()
+        .render_links(true)
+        .render_blink(true)
+        .attribute_quotes(Quote::Double);
+    insta::assert_snapshot!(converter.convert(input).unwrap(), @r#"<a href="https://example.com/?q=%27a%27"><b><span style="opacity:0.67"><span class="blink"><span style="color:var(--red,#a00)">link</span></span></span></b></a> <span style="color:#ff00d7"><span style="background:#010203">rgb</span></span>"#);
+    let classes = converter
+        .clone()
+        .four_bit_classes(Some("t-".into()))
+        .flat_spans(true);
+    insta::assert_snapshot!(classes.convert(input).unwrap(), @r#"<a href="https://example.com/?q=%27a%27"><span class="blink t-red" style="font-weight:bold;opacity:0.67">link</span></a> <span style="color:#ff00d7;background:#010203">rgb</span>"#);
+    let html = converter
+        .clone()
+        .attribute_quotes(Quote::Single)
+        .convert(input)
+        .unwrap();
+    assert_eq!(html, converter.convert(input).unwrap().replace('"', "'"));
+    assert_eq!(
+        html,
+        Converter::new()
+            .render_links(true)
+            .render_blink(true)
+            .convert(input)
+            .unwrap()
+    );
+
+    // quotes in the input are kept, and with the `optimize` feature, `OutputCompat::V0` removes
+    // empty spans with either quotes
+    let input = "<span title=\"it's\"></span><span class='x'></span>\x1b[1m\"'\x1b[2m\x1b[0m";
+    let skip_escape = converter.clone().skip_escape(true);
+    insta::assert_snapshot!(skip_escape.convert(input).unwrap(), @r#"<span title="it's"></span><span class='x'></span><b>"'</b>"#);
+    let v0 = skip_escape.output_compat(ansi_to_html::OutputCompat::V0);
+    if cfg!(feature = "optimize") {
+        insta::assert_snapshot!(v0.convert(input).unwrap(), @r#"<b>"'</b>"#);
+    } else {
+        insta::assert_snapshot!(v0.convert(input).unwrap(), @r#"<span title="it's"></span><span class='x'></span><b>"'</b>"#);
+    }
+}
+
+#[test]
+fn conceal() {
+    use ansi_to_html::{Converter, SgrCode};
+
+    let readable = "Password: {{ conceal }}hunter2{{ conceal_off }} ok {{ red }}{{ conceal }}a<b\n\
+        c{{ reverse }}d{{ res }} {{ underline }}{{ conceal }}x{{ underline_off }}y";
+    let styled = human_readable_to_ansi(readable);
+
+    // the concealed span is innermost, so the color doesn't make the text visible, not even
+    // with reverse video
+    insta::assert_snapshot!(ansi_to_html::convert(&styled).unwrap(), @"
+    Password: <span style='color:transparent'>hunter2</span> ok <span style='color:var(--red,#a00)'><span style='color:transparent'>a&lt;b
+    c</span></span><span style='color:#000000'><span style='background:var(--red,#a00)'><span style='color:transparent'>d</span></span></span> <u><span style='color:transparent'>x</span></u><span style='color:transparent'>y</span>
+    ");
+
+    let converter = Converter::new().redact_concealed(true);
+    let styled = human_readable_to_ansi("{{ red }}{{ conceal }}a<b & c{{ conceal_off }}!");
+    insta::assert_snapshot!(converter.convert(&styled).unwrap(), @"<span style='color:var(--red,#a00)'><span style='color:transparent'>       </span>!</span>");
+
+    // the end of concealed text isn't lost between chunks
+    let mut stream = converter.begin();
+    let mut html = stream.feed("\x1b[8mhunter").unwrap();
+    html.push_str(&stream.feed("2\x1b[28m visible").unwrap());
+    html.push_str(&stream.finish());
+    insta::assert_snapshot!(html, @"<span style='color:transparent'>       </span> visible");
+
+    let segments = converter.to_segments("\x1b[8mhunter2\x1b[28m!").unwrap();
+    assert!(segments[0].concealed);
+    assert_eq!(segments[0].text, "       ");
+    assert!(!segments[1].concealed);
+    let segments = Converter::new().to_segments("\x1b[8mhunter2").unwrap();
+    assert_eq!(segments[0].text, "hunter2");
+
+    let codes = ansi_to_html::AnsiFragment::Sequence("\x1b[8;28m").sgr_codes();
+    let codes: Vec<_> = codes.unwrap().map(Result::unwrap).collect();
+    assert_eq!(codes, [SgrCode::Conceal, SgrCode::ConcealOff]);
+}
+
+#[test]
+fn reverse_video() {
+    use ansi_to_html::{Converter, OutputCompat, SgrCode, ThemePalette};
+
+    // the text and background colors are swapped
+    insta::assert_snapshot!(ansi_to_html::convert("\x1b[31;44;7mA\x1b[27mB").unwrap(), @"<span style='color:var(--blue,#00a)'><span style='background:var(--red,#a00)'>A</span></span><span style='color:var(--red,#a00)'><span style='background:var(--blue,#00a)'>B</span></span>");
+
+    // unset colors are replaced by the default text and background color
+    insta::assert_snapshot!(ansi_to_html::convert("\x1b[7mA\x1b[31mB\x1b[39;44mC\x1b[0mD").unwrap(), @"<span style='color:#000000'><span style='background:#aaaaaa'>A</span><span style='background:var(--red,#a00)'>B</span></span><span style='color:var(--blue,#00a)'><span style='background:#aaaaaa'>C</span></span>D");
+
+    // the default colors can be configured
+    let converter = Converter::new().default_colors([0xab, 0xb2, 0xbf], [0x1e, 0x21, 0x27]);
+    insta::assert_snapshot!(converter.convert("\x1b[7mA\x1b[32mB").unwrap(), @"<span style='color:#1e2127'><span style='background:#abb2bf'>A</span><span style='background:var(--green,#0a0)'>B</span></span>");
+
+    // and otherwise come from the palette
+    let converter = Converter::new().palette(ThemePalette::solarized_dark());
+    insta::assert_snapshot!(converter.convert("\x1b[7mA").unwrap(), @"<span style='color:#002b36'><span style='background:#839496'>A</span></span>");
+
+    // reversed trailing spaces keep their background
+    assert_eq!(
+        ansi_to_html::convert("\x1b[7mA  \n").unwrap(),
+        "<span style='color:#000000'><span style='background:#aaaaaa'>A  </span></span>\n",
+    );
+
+    // the 0.x versions ignored reverse video
+    let v0 = Converter::new().output_compat(OutputCompat::V0);
+    assert_eq!(
+        v0.convert("\x1b[7;31mA").unwrap(),
+        v0.convert("\x1b[31mA").unwrap()
+    );
+
+    let segments = Converter::new().to_segments("\x1b[7mA\x1b[27mB").unwrap();
+    assert!(segments[0].reverse);
+    assert!(!segments[1].reverse);
+
+    let codes = ansi_to_html::AnsiFragment::Sequence("\x1b[7;27m").sgr_codes();
+    let codes: Vec<_> = codes.unwrap().map(Result::unwrap).collect();
+    assert_eq!(codes, [SgrCode::Reverse, SgrCode::ReverseOff]);
 }
 
 #[test]
@@ -94,3 +402,2449 @@ fn semicolon_before_terminator() {
     let converted = ansi_to_html::convert("\x1b[31;mRed\x1b[0;m Plain").unwrap();
     insta::assert_snapshot!(converted, @"<span style='color:var(--red,#a00)'>Red</span> Plain");
 }
+
+#[test]
+fn osc_sequences_are_stripped() {
+    let st =
+        ansi_to_html::convert("\x1b]8;;javascript:alert(1)\x1b\\click\x1b]8;;\x1b\\ me").unwrap();
+    insta::assert_snapshot!(st, @"click me");
+    let bel = ansi_to_html::convert("\x1b]0;title\x07\x1b[1mbold").unwrap();
+    insta::assert_snapshot!(bel, @"<b>bold</b>");
+}
+
+#[test]
+fn control_characters_abort_sequences() {
+    use ansi_to_html::{AnsiFragment, AnsiParser};
+
+    // found by the `no_panic` fuzz target
+    let nul = ansi_to_html::convert("\x1b[3\x001m\x1b[1mbold").unwrap();
+    insta::assert_snapshot!(nul, @"1m<b>bold</b>");
+    let others = ansi_to_html::convert("a\x1b[1;\x07b\x1b(\x18c\x1b\x1fd\x1b[\x7fe").unwrap();
+    insta::assert_debug_snapshot!(others, @r#""abcd\u{1b}[\u{7f}e""#);
+    // line breaks and tabs are kept, so the lines don't change
+    let whitespace = ansi_to_html::convert("a\x1b[1\nb\x1b[\r\nc\x1b\td").unwrap();
+    insta::assert_debug_snapshot!(whitespace, @r#""a\nb\r\nc\td""#);
+    // an ESC starts a new sequence
+    let esc = ansi_to_html::convert("\x1b[3\x1b[1mbold").unwrap();
+    insta::assert_debug_snapshot!(esc, @r#""\u{1b}[3<b>bold</b>""#);
+
+    let input = "\x1b[3\x001m\x1b[\n";
+    assert_eq!(ansi_to_html::strip_ansi(input), "1m\n");
+    let fragments: Vec<_> = AnsiParser::new(input).collect();
+    assert_eq!(
+        fragments,
+        [
+            AnsiFragment::Sequence("\x1b[3\x00"),
+            AnsiFragment::Text("1m"),
+            AnsiFragment::Sequence("\x1b["),
+            AnsiFragment::Text("\n"),
+        ]
+    );
+
+    // a sequence that is cut off before the control character is completed by the next call
+    let converter = ansi_to_html::Converter::new();
+    let mut state = ansi_to_html::StyleState::new();
+    let mut html = String::new();
+    for piece in ["\x1b[3", "\x001m", "\x1b", "\x00x"] {
+        converter
+            .convert_append(&mut state, piece, &mut html)
+            .unwrap();
+    }
+    assert_eq!(html, "1mx");
+}
+
+#[test]
+fn javascript_urls_are_rejected() {
+    use ansi_to_html::{EscUrl, UrlSchemes};
+
+    let schemes = UrlSchemes::default();
+    for url in [
+        "javascript:alert(1)",
+        "JavaScript:alert(1)",
+        " javascript:x",
+        "java\tscript:x",
+    ] {
+        assert!(!schemes.allows(url), "{url:?} should be rejected");
+    }
+    assert!(schemes.allows("file:///home/user/Cargo.toml"));
+    assert_eq!(
+        EscUrl("https://x.org/\"><script>").to_string(),
+        "https://x.org/%22%3E%3Cscript%3E"
+    );
+}
+
+#[test]
+fn convert_append() {
+    use ansi_to_html::{Converter, StyleState};
+
+    let input = "\x1b[1mbold \x1b[31mred\nstill red\x1b[0m plain\n\x1b[44mblue bg\x1b[0m\n";
+    let converter = Converter::new();
+
+    let mut full_state = StyleState::new();
+    let mut full = String::new();
+    converter
+        .convert_append(&mut full_state, input, &mut full)
+        .unwrap();
+    assert_eq!(full, converter.convert(input).unwrap());
+    assert!(full_state.is_default());
+
+    // Split in every possible position, including inside of escape sequences
+    for i in 1..input.len() {
+        let mut state = StyleState::new();
+        let mut html = String::new();
+        converter
+            .convert_append(&mut state, &input[..i], &mut html)
+            .unwrap();
+        converter
+            .convert_append(&mut state, &input[i..], &mut html)
+            .unwrap();
+        assert_eq!(state, full_state);
+        assert!(!html.contains('\x1b'), "split at {i}: {html:?}");
+    }
+
+    let mut state = StyleState::new();
+    let mut html = String::new();
+    for chunk in ["\x1b[1mbold \x1b[3", "1mred\n", "still red\x1b[0m plain"] {
+        converter
+            .convert_append(&mut state, chunk, &mut html)
+            .unwrap();
+    }
+    insta::assert_snapshot!(html, @"
+    <b>bold</b> <b><span style='color:var(--red,#a00)'>red</span></b>
+    <b><span style='color:var(--red,#a00)'>still red</span></b> plain
+    ");
+}
+
+#[test]
+fn fixed_nesting_order() {
+    let convert = |s| ansi_to_html::convert(s).unwrap();
+
+    assert_eq!(convert("\x1b[1;91mA"), convert("\x1b[91;1mA"));
+    assert_eq!(convert("\x1b[1m\x1b[91mA"), convert("\x1b[91m\x1b[1mA"));
+    insta::assert_snapshot!(convert("\x1b[91;1mA"), @"<b><span style='color:var(--bright-red,#f55)'>A</span></b>");
+
+    // Styles are re-opened in the same order after a partial clear
+    insta::assert_snapshot!(convert("\x1b[44;4;91;3;1mA\x1b[23mB\x1b[22;1mC\x1b[92mD"), @"<b><i><u><span style='color:var(--bright-red,#f55)'><span style='background:var(--blue,#00a)'>A</span></span></u></i><u><span style='color:var(--bright-red,#f55)'><span style='background:var(--blue,#00a)'>BC</span></span><span style='color:var(--bright-green,#5f5)'><span style='background:var(--blue,#00a)'>D</span></span></u></b>");
+}
+
+#[test]
+fn grayscale_levels() {
+    #[rustfmt::skip]
+    let table: [(u8, [&str; 24]); 2] = [
+        (4, [
+            "#000000", "#000000", "#000000", "#000000", "#555555", "#555555",
+            "#555555", "#555555", "#555555", "#555555", "#555555", "#555555",
+            "#aaaaaa", "#aaaaaa", "#aaaaaa", "#aaaaaa", "#aaaaaa", "#aaaaaa",
+            "#aaaaaa", "#aaaaaa", "#aaaaaa", "#ffffff", "#ffffff", "#ffffff",
+        ]),
+        (8, [
+            "#000000", "#000000", "#242424", "#242424", "#242424", "#494949",
+            "#494949", "#494949", "#494949", "#6d6d6d", "#6d6d6d", "#6d6d6d",
+            "#929292", "#929292", "#929292", "#929292", "#b6b6b6", "#b6b6b6",
+            "#b6b6b6", "#b6b6b6", "#dbdbdb", "#dbdbdb", "#dbdbdb", "#ffffff",
+        ]),
+    ];
+    for (levels, expected) in table {
+        let converter = ansi_to_html::Converter::new().grayscale_levels(Some(levels));
+        for (code, expected) in (232..=255).zip(expected) {
+            let html = converter.convert(&format!("\x1b[38;5;{code}mx")).unwrap();
+            assert_eq!(
+                html,
+                format!("<span style='color:{expected}'>x</span>"),
+                "{code}"
+            );
+        }
+    }
+
+    // Grays of the color cube are quantized, other colors are unchanged
+    let converter = ansi_to_html::Converter::new().grayscale_levels(Some(4));
+    let html = converter
+        .convert("\x1b[48;5;145ma\x1b[48;5;146mb\x1b[37mc")
+        .unwrap();
+    insta::assert_snapshot!(html, @"<span style='background:#aaaaaa'>a</span><span style='background:#afafd7'>b</span><span style='color:var(--white,#aaa)'><span style='background:#afafd7'>c</span></span>");
+}
+
+#[test]
+fn eight_bit_palette() {
+    use ansi_to_html::{Converter, ThemePalette};
+
+    let mut colors = [[0; 3]; 256];
+    for (i, color) in colors.iter_mut().enumerate() {
+        *color = [i as u8, 0x10, 0x20];
+    }
+    let input = "\x1b[38;5;200ma\x1b[48;5;16mb\x1b[0m \x1b[38;5;1mc\x1b[0m \x1b[31md";
+    let converter = Converter::new().skip_css_vars(true);
+    insta::assert_snapshot!(converter.convert(input).unwrap(), @"<span style='color:#ff00d7'>a<span style='background:#000'>b</span></span> <span style='color:#a00'>c</span> <span style='color:#a00'>d</span>");
+    let custom = converter.clone().eight_bit_palette(Some(colors));
+    insta::assert_snapshot!(custom.convert(input).unwrap(), @"<span style='color:#c81020'>a<span style='background:#101020'>b</span></span> <span style='color:#a00'>c</span> <span style='color:#a00'>d</span>");
+
+    // the first 16 colors are the ones of the 16-color palette
+    let custom = custom.palette(ThemePalette::solarized_dark());
+    insta::assert_snapshot!(custom.convert(input).unwrap(), @"<span style='color:#c81020'>a<span style='background:#101020'>b</span></span> <span style='color:#dc322f'>c</span> <span style='color:#dc322f'>d</span>");
+
+    // grays of the default table are quantized
+    let custom = Converter::new()
+        .eight_bit_palette(Some(colors))
+        .grayscale_levels(Some(4));
+    insta::assert_snapshot!(custom.convert("\x1b[38;5;240ma\x1b[38;5;200mb").unwrap(), @"<span style='color:#555555'>a</span><span style='color:#c81020'>b</span>");
+}
+
+#[test]
+fn max_sequence_len() {
+    use std::time::{Duration, Instant};
+
+    use ansi_to_html::{Converter, StyleState};
+
+    let converter = Converter::new();
+    let digits = "1;".repeat(5_000_000);
+
+    // A terminated sequence that is too long is emitted as text
+    let input = format!("\x1b[1mbold\x1b[{digits}mA");
+    let start = Instant::now();
+    let html = converter.convert(&input).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(html.starts_with("<b>bold\x1b[1;1;1;"));
+    assert!(html.ends_with("1;mA</b>"));
+
+    // An incomplete sequence that is too long isn't kept for the next call
+    let mut state = StyleState::new();
+    let mut html = String::new();
+    let start = Instant::now();
+    converter
+        .convert_append(&mut state, "\x1b[3mitalic\x1b]8;;", &mut html)
+        .unwrap();
+    for chunk in digits.as_bytes().chunks(1_000_000) {
+        let chunk = std::str::from_utf8(chunk).unwrap();
+        converter
+            .convert_append(&mut state, chunk, &mut html)
+            .unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(html.starts_with("<i>italic</i><i>\x1b]8;;1;1;"));
+    assert_eq!(html.matches("<i>").count(), 11);
+
+    // Shorter sequences still work
+    let converter = converter.max_sequence_len(5);
+    insta::assert_snapshot!(converter.convert("\x1b[1mA\x1b[1;31mB\x1b[31mC").unwrap(), @"<b>A[1;31mB<span style='color:var(--red,#a00)'>C</span></b>");
+}
+
+#[test]
+fn convert_line() {
+    use ansi_to_html::Converter;
+
+    let converter = Converter::new();
+    let mut html = String::from("<pre>");
+    for line in [
+        "\x1b[1;31mbold red\x1b[22m red",
+        "<plain>",
+        "\x1b[2m\x1b[0m",
+        "\x1b[2mA",
+    ] {
+        converter.convert_line(line, &mut html).unwrap();
+        html.push('\n');
+    }
+    insta::assert_snapshot!(html, @"
+    <pre><b><span style='color:var(--red,#a00)'>bold red</span></b><span style='color:var(--red,#a00)'> red</span>
+    &lt;plain&gt;
+
+    <span style='opacity:0.67'>A</span>
+    ");
+
+    // Errors leave the buffer unchanged
+    assert!(converter.convert_line("\x1b[38;5mx", &mut html).is_err());
+    assert!(html.ends_with("</span>\n"));
+}
+
+#[test]
+fn pretty() {
+    use ansi_to_html::Converter;
+
+    let input = "\x1b[1mbold \x1b[31mred <\x1b[4munderline\x1b[0m plain\x1b[3m italic";
+    let html = Converter::new().pretty(true).convert(input).unwrap();
+    insta::assert_snapshot!(html, @"
+    <b><!--
+      -->bold <!--
+      --><span style='color:var(--red,#a00)'><!--
+        -->red &lt;<!--
+      --></span><!--
+      --><u><!--
+        --><span style='color:var(--red,#a00)'><!--
+          -->underline<!--
+        --></span><!--
+      --></u><!--
+    --></b><!--
+    --> plain<!--
+    --><i><!--
+      --> italic<!--
+    --></i>
+    ");
+
+    // Stripping the added line breaks yields the compact output
+    let compact = Converter::new().convert(input).unwrap();
+    let stripped = regex::Regex::new("<!--\n *-->")
+        .unwrap()
+        .replace_all(&html, "");
+    assert_eq!(stripped, compact);
+
+    let mut appended = String::new();
+    let mut state = ansi_to_html::StyleState::new();
+    let converter = Converter::new().pretty(true);
+    converter
+        .convert_append(&mut state, input, &mut appended)
+        .unwrap();
+    assert_eq!(appended, html);
+}
+
+#[test]
+fn four_bit_classes() {
+    use ansi_to_html::Converter;
+
+    let converter = Converter::new().four_bit_classes(Some("term-".into()));
+    insta::assert_snapshot!(
+        converter.convert("\x1b[31mred \x1b[101mon bright red \x1b[38;5;1m8-bit\x1b[0m \x1b[2;34mfaint").unwrap(),
+        @"<span class='term-red'>red <span class='term-bg-bright-red'>on bright red </span></span><span style='color:#a00'><span class='term-bg-bright-red'>8-bit</span></span> <span style='opacity:0.67'><span class='term-blue'>faint</span></span>"
+    );
+    // with the `optimize` feature, `OutputCompat::V0` removes empty spans in the input like spans
+    // with a `style`
+    let html = (converter.clone().skip_escape(true)).output_compat(ansi_to_html::OutputCompat::V0);
+    let input = "<span class='term-red'></span>x\x1b[32m<b></b>y";
+    if cfg!(feature = "optimize") {
+        insta::assert_snapshot!(html.convert(input).unwrap(), @"x<span class='term-green'>y</span>");
+    } else {
+        insta::assert_snapshot!(html.convert(input).unwrap(), @"<span class='term-red'></span>x<span class='term-green'><b></b>y</span>");
+    }
+    let converter = Converter::new().four_bit_classes(Some(String::new()));
+    insta::assert_snapshot!(converter.convert("\x1b[97;40mx").unwrap(), @"<span class='bright-white'><span class='bg-black'>x</span></span>");
+}
+
+#[test]
+fn var_names() {
+    use ansi_to_html::{Converter, VarNames};
+
+    let names = VarNames::new(|name| format!("color-terminal-{name}")).unwrap();
+    let converter = Converter::new().var_names(Some(names.clone()));
+    insta::assert_snapshot!(
+        converter.convert("\x1b[31mred \x1b[101mon bright red \x1b[38;5;1m8-bit").unwrap(),
+        @"<span style='color:var(--color-terminal-red,#a00)'>red <span style='background:var(--color-terminal-bright-red,#f55)'>on bright red </span></span><span style='color:#a00'><span style='background:var(--color-terminal-bright-red,#f55)'>8-bit</span></span>"
+    );
+    insta::assert_snapshot!(names.stylesheet(), @"
+    :root {
+      --color-terminal-black: #000;
+      --color-terminal-red: #a00;
+      --color-terminal-green: #0a0;
+      --color-terminal-yellow: #a60;
+      --color-terminal-blue: #00a;
+      --color-terminal-magenta: #a0a;
+      --color-terminal-cyan: #0aa;
+      --color-terminal-white: #aaa;
+      --color-terminal-bright-black: #555;
+      --color-terminal-bright-red: #f55;
+      --color-terminal-bright-green: #5f5;
+      --color-terminal-bright-yellow: #ff5;
+      --color-terminal-bright-blue: #55f;
+      --color-terminal-bright-magenta: #f5f;
+      --color-terminal-bright-cyan: #5ff;
+      --color-terminal-bright-white: #fff;
+    }
+    ");
+
+    // The names can't break out of the `style` attribute
+    for invalid in ["", "a b", "x'", "y)", "z;color:red"] {
+        assert!(VarNames::new(|_| invalid.to_string()).is_err());
+    }
+}
+
+/// Also run with `cargo test --no-default-features`
+#[test]
+fn optimize_feature() {
+    use ansi_to_html::{Converter, OutputCompat};
+
+    let converter = Converter::new().skip_escape(true);
+    let input = "\x1b[1ma\x1b[0m\x1b[1mb<i></i></b><b>c\x1b[0m";
+    let unoptimized = converter
+        .clone()
+        .skip_optimize(true)
+        .convert(input)
+        .unwrap();
+    assert_eq!(unoptimized, "<b>ab<i></i></b><b>c</b>");
+    // The converter always minimizes the tags it emits, and keeps the tags of the input
+    assert_eq!(converter.convert(input).unwrap(), unoptimized);
+
+    // With `OutputCompat::V0`, the `optimize` feature also removes redundant
+    // tags that were in the input
+    let v0 = converter
+        .output_compat(OutputCompat::V0)
+        .convert(input)
+        .unwrap();
+    if cfg!(feature = "optimize") {
+        assert_eq!(v0, "<b>abc</b>");
+    } else {
+        assert_eq!(v0, unoptimized);
+    }
+}
+
+#[test]
+fn optimize_while_converting() {
+    use ansi_to_html::{Converter, OutputCompat};
+
+    // the bold style at the end opens empty elements, and the second pass of `OutputCompat::V0`
+    // only removes the inner one
+    let input = "\x1b]8;;https://example.com\x1b\\\x1b[4mlink\x1b]8;;\x1b\\ \x1b[1m";
+    let converter = Converter::new().render_links(true);
+    let unoptimized = converter.clone().skip_optimize(true);
+    insta::assert_snapshot!(unoptimized.convert(input).unwrap(), @"<a href='https://example.com'><u>link</u></a><u> </u>");
+    insta::assert_snapshot!(converter.convert(input).unwrap(), @"<a href='https://example.com'><u>link</u></a><u> </u>");
+    let v0 = converter.clone().output_compat(OutputCompat::V0);
+    if cfg!(feature = "optimize") {
+        insta::assert_snapshot!(v0.convert(input).unwrap(), @"<a href='https://example.com'><u>link</u></a><u> </u><b></b>");
+    } else {
+        insta::assert_snapshot!(v0.convert(input).unwrap(), @"<a href='https://example.com'><u>link</u></a><u> </u><b><u></u></b>");
+    }
+
+    // `convert_line` optimizes the same way
+    let mut html = String::new();
+    converter.convert_line(input, &mut html).unwrap();
+    assert_eq!(html, converter.convert(input).unwrap());
+}
+
+#[test]
+fn merge_identical_tags() {
+    use ansi_to_html::{Converter, OutputCompat};
+
+    // the colors are different, but they are written the same
+    let input = "\x1b[31ma\x1b[38;5;1mb\x1b[1;91mc\x1b[38;5;9md";
+    let converter = Converter::new().skip_css_vars(true);
+    insta::assert_snapshot!(converter.convert(input).unwrap(), @"<span style='color:#a00'>ab</span><b><span style='color:#f55'>cd</span></b>");
+    let flat = converter.clone().flat_spans(true);
+    insta::assert_snapshot!(flat.convert(input).unwrap(), @"<span style='color:#a00'>ab</span><span style='font-weight:bold;color:#f55'>cd</span>");
+    let gray = Converter::new().grayscale_levels(Some(4));
+    insta::assert_snapshot!(gray.convert("\x1b[38;5;240ma\x1b[38;5;241mb\x1b[38;5;242mc").unwrap(), @"<span style='color:#555555'>abc</span>");
+
+    // the output of `OutputCompat::V0` is kept
+    let v0 = converter.output_compat(OutputCompat::V0);
+    insta::assert_snapshot!(v0.convert(input).unwrap(), @"<span style='color:#a00'>a</span><span style='color:#a00'>b</span><b><span style='color:#f55'>c</span><span style='color:#f55'>d</span></b>");
+}
+
+#[test]
+fn merge_reopened_underline() {
+    use ansi_to_html::{Converter, OutputCompat};
+
+    let readable = "{{ underline }}a{{ res }}{{ underline }}b{{ underline_off }}{{ underline }}c{{ res }} \
+        {{ underline }}{{ red }}d{{ res }}{{ underline }}{{ red }}e{{ res }}{{ bold }}{{ underline }}f\
+        {{ res }}{{ underline }}g{{ res }}";
+    let styled = human_readable_to_ansi(readable);
+
+    let converted = Converter::new().convert(&styled).unwrap();
+    insta::assert_snapshot!(converted, @"<u>abc</u> <u><span style='color:var(--red,#a00)'>de</span></u><b><u>f</u></b><u>g</u>");
+    // a reset followed by the same styles never emits tags, even without optimizing
+    for converter in [
+        Converter::new().skip_optimize(true),
+        Converter::new().output_compat(OutputCompat::V0),
+    ] {
+        assert_eq!(converter.convert(&styled).unwrap(), converted);
+    }
+    let flat = Converter::new().flat_spans(true);
+    insta::assert_snapshot!(flat.convert(&styled).unwrap(), @"<span style='text-decoration:underline'>abc</span> <span style='color:var(--red,#a00);text-decoration:underline'>de</span><span style='font-weight:bold;text-decoration:underline'>f</span><span style='text-decoration:underline'>g</span>");
+}
+
+#[test]
+fn sanitize() {
+    use ansi_to_html::Converter;
+
+    let converter = Converter::new()
+        .skip_escape(true)
+        .sanitize(true)
+        .allowed_tags(&["b", "SPAN"]);
+
+    let pre_escaped =
+        "&lt;tag&gt; &#39;x&#x27; &copy; \x1b[31m<span class='x'>AT&amp;T</span>\x1b[0m";
+    insta::assert_snapshot!(converter.convert(pre_escaped).unwrap(), @"&lt;tag&gt; &#39;x&#x27; &copy; <span style='color:var(--red,#a00)'><span class='x'>AT&amp;T</span></span>");
+
+    let hostile = "ok <img src=x onerror=alert(1)> <b onclick='alert(1)'>x</b> \
+        <script>alert(1)</script> <span title='<script>'>y</span> a && b &foo <b>fine</b> <b>unclosed";
+    insta::assert_snapshot!(converter.convert(hostile).unwrap(), @"ok &lt;img src=x onerror=alert(1)> &lt;b onclick='alert(1)'>x&lt;/b> &lt;script>alert(1)&lt;/script> &lt;span title='&lt;script>'>y&lt;/span> a &amp;&amp; b &amp;foo <b>fine</b> <b>unclosed</b>");
+
+    // inline styles could overlay the page or load resources
+    let styled =
+        "<span style='position:fixed;inset:0'>x</span> <b style=\"background:url(//x)\">y</b>";
+    insta::assert_snapshot!(converter.convert(styled).unwrap(), @r#"&lt;span style='position:fixed;inset:0'>x&lt;/span> &lt;b style="background:url(//x)">y&lt;/b>"#);
+
+    // Without `skip_escape`, everything is escaped as before
+    let escaped = Converter::new()
+        .sanitize(true)
+        .convert("<b>&amp;</b>")
+        .unwrap();
+    assert_eq!(escaped, "&lt;b&gt;&amp;amp;&lt;/b&gt;");
+}
+
+#[test]
+fn entities_split_by_sequences() {
+    use ansi_to_html::{Converter, StyleState};
+
+    let converter = Converter::new().skip_escape(true);
+    let convert = |input: &str| converter.convert(input).unwrap();
+
+    // The color change is moved after the entity
+    insta::assert_snapshot!(convert("a &l\x1b[31mt; b\x1b[0m"), @"a &lt;<span style='color:var(--red,#a00)'> b</span>");
+    insta::assert_snapshot!(convert("&#\x1b[1m3\x1b[3m9;x\x1b[0m &amp;\x1b[1m!\x1b[0m"), @"&#39;<b><i>x</i></b> &amp;<b>!</b>");
+
+    // Ampersands that don't form entities are left alone
+    insta::assert_snapshot!(convert("a &\x1b[31m b &x\x1b[1my z\x1b[0m && &;"), @"a &<span style='color:var(--red,#a00)'> b &x</span><b><span style='color:var(--red,#a00)'>y z</span></b> && &;");
+
+    // An entity that is cut off is completed by the next call
+    let mut state = StyleState::new();
+    let mut html = String::new();
+    for chunk in ["x &l", "\x1b[32mt", "; y &", "gt; z \x1b[0m&"] {
+        converter
+            .convert_append(&mut state, chunk, &mut html)
+            .unwrap();
+    }
+    // the last `&` is pending until it's clear that it doesn't start an entity
+    assert!(!state.is_default());
+    converter
+        .convert_append(&mut state, " end", &mut html)
+        .unwrap();
+    assert!(state.is_default());
+    insta::assert_snapshot!(html, @"x &lt;<span style='color:var(--green,#0a0)'> y</span> <span style='color:var(--green,#0a0)'>&gt; z</span> & end");
+}
+
+#[test]
+fn text_transform() {
+    use ansi_to_html::{Converter, Piece, StyleState, Transformed};
+
+    let linkify = |text: &str| match text.find("https://") {
+        Some(start) => {
+            let end = text[start..].find(' ').map_or(text.len(), |i| start + i);
+            let url = &text[start..end];
+            Transformed::Pieces(vec![
+                Piece::Text(text[..start].to_owned()),
+                Piece::link(url, url),
+                Piece::Text(text[end..].to_owned()),
+            ])
+        }
+        None => Transformed::Unchanged,
+    };
+    let converter = Converter::new().text_transform(linkify);
+    let convert = |input: &str| converter.convert(input).unwrap();
+
+    // The hook receives unescaped text, and text pieces are escaped again
+    insta::assert_snapshot!(convert("<a> https://x.org/?a&b \x1b[1mbold\x1b[0m"), @"&lt;a&gt; <a href='https://x.org/?a&amp;b'>https://x.org/?a&amp;b</a> <b>bold</b>");
+
+    // A URL split across two styled runs is passed to the hook in two calls,
+    // so only the first half is linked
+    insta::assert_snapshot!(convert("https://x.org/\x1b[1mpath\x1b[0m"), @"<a href='https://x.org/'>https://x.org/</a><b>path</b>");
+
+    // Replacement text is escaped
+    let upper = Converter::new().text_transform(|text| Transformed::Text(text.to_uppercase()));
+    insta::assert_snapshot!(upper.convert("a<b \x1b[31mc&d").unwrap(), @"A&lt;B <span style='color:var(--red,#a00)'>C&amp;D</span>");
+    let mut html = String::new();
+    upper.convert_line("plain & simple", &mut html).unwrap();
+    insta::assert_snapshot!(html, @"PLAIN &amp; SIMPLE");
+
+    // With skip_escape, the hook receives and returns HTML
+    let raw = Converter::new()
+        .skip_escape(true)
+        .text_transform(|text| Transformed::Text(text.replace("<b>", "<i>")));
+    insta::assert_snapshot!(raw.convert("<b>x\x1b[1m<b>").unwrap(), @"<i>x<b><i></b>");
+
+    // Runs are split where the input is split in convert_append
+    let mut state = StyleState::new();
+    let mut html = String::new();
+    for chunk in ["https://x", ".org/ \x1b[3", "2mhttps://y.org/"] {
+        converter
+            .convert_append(&mut state, chunk, &mut html)
+            .unwrap();
+    }
+    insta::assert_snapshot!(html, @"<a href='https://x'>https://x</a>.org/ <span style='color:var(--green,#0a0)'><a href='https://y.org/'>https://y.org/</a></span>");
+}
+
+#[test]
+fn convert_chunked() {
+    use ansi_to_html::Converter;
+
+    let converter = Converter::new();
+    let chunks = |input: &str, max| converter.convert_chunked(input, max).unwrap().join("|");
+
+    insta::assert_snapshot!(chunks("", 1), @"");
+    insta::assert_snapshot!(chunks("a\nb <c>\n", 0), @"
+    a
+    b &lt;c&gt;
+    ");
+    // chunks end after line breaks when possible
+    insta::assert_snapshot!(chunks("\x1b[1ma\nb \x1b[3mc\x1b[4md\n\x1b[0me", 2), @"
+    <b>a</b>
+    |<b>b <i>c</i></b>|<b><i><u>d
+    </u></i></b>e
+    ");
+    // without line breaks, before an escape sequence
+    insta::assert_snapshot!(chunks("\x1b[31ma\x1b[32mb\x1b[33mc\x1b[34md", 2), @"<span style='color:var(--red,#a00)'>a</span><span style='color:var(--green,#0a0)'>b</span>|<span style='color:var(--yellow,#a60)'>c</span><span style='color:var(--blue,#00a)'>d</span>");
+    // a chunk exceeds the limit if more styles are active at its start
+    insta::assert_snapshot!(chunks("\x1b[1;3;4ma\nb\n\x1b[0mc", 1), @"
+    <b><i><u>a
+    b
+    </u></i></b>c
+    ");
+}
+
+#[test]
+fn bidi_isolate() {
+    use ansi_to_html::{Converter, Transformed};
+
+    let converter = Converter::new().bidi_isolate(true);
+    let convert = |input: &str| converter.convert(input).unwrap();
+
+    insta::assert_snapshot!(convert("\x1b[31mمرحبا\x1b[0m <a>\n\nb\x1b[1m\nc"), @"
+    <span style='color:var(--red,#a00)'><bdi>مرحبا</bdi></span><bdi> &lt;a&gt;</bdi>
+
+    <bdi>b</bdi><b>
+    <bdi>c</bdi></b>
+    ");
+
+    let mut html = String::new();
+    converter.convert_line("no sequences", &mut html).unwrap();
+    insta::assert_snapshot!(html, @"<bdi>no sequences</bdi>");
+
+    // the text transform is called for every line
+    let bracketed = converter
+        .clone()
+        .text_transform(|text| Transformed::Text(format!("[{text}]")));
+    insta::assert_snapshot!(bracketed.convert("a\nb\x1b[1mc").unwrap(), @"
+    <bdi>[a]</bdi>
+    <bdi>[b]</bdi><b><bdi>[c]</bdi></b>
+    ");
+}
+
+#[test]
+fn normalize_trailing_whitespace() {
+    use ansi_to_html::Converter;
+
+    let converter = Converter::new().normalize_trailing_whitespace(true);
+    let convert = |input: &str| converter.convert(input).unwrap();
+
+    // variants of the same output that only differ in the styling of trailing spaces
+    let variants = [
+        "\x1b[31mred  \x1b[0m\n\x1b[1mbold\x1b[0m   ",
+        "\x1b[31mred\x1b[0m  \n\x1b[1mbold   \x1b[0m",
+        "\x1b[31mred \x1b[32m \n\x1b[0;1mbold \x1b[3m  ",
+        "\x1b[31mred  \n\x1b[0;1mbold\x1b[0;34m   \x1b[0m",
+    ];
+    for variant in &variants[1..] {
+        assert_eq!(convert(variant), convert(variants[0]), "{variant:?}");
+    }
+    // insta trims trailing spaces, so the output is compared directly
+    assert_eq!(
+        convert(variants[0]),
+        "<span style='color:var(--red,#a00)'>red</span>  \n<b>bold</b>   ",
+    );
+
+    // spaces before text keep their styling; spaces with a background,
+    // underline or strikethrough are visible
+    assert_eq!(
+        convert("\x1b[1ma  b  \x1b[41m  \x1b[0m\n\x1b[4;31mc  \x1b[9;42m  \x1b[0m \n"),
+        "<b>a  b  <span style='background:var(--red,#a00)'>  </span></b>\n\
+         <u><span style='color:var(--red,#a00)'>c</span>  <s><span style='color:var(--red,#a00)'>\
+         <span style='background:var(--green,#0a0)'>  </span></span></s></u> \n",
+    );
+
+    // disabled by default
+    assert_eq!(
+        Converter::new().convert(variants[0]).unwrap(),
+        "<span style='color:var(--red,#a00)'>red  </span>\n<b>bold</b>   ",
+    );
+}
+
+#[test]
+fn trailing_whitespace() {
+    use ansi_to_html::{Converter, OutputCompat};
+
+    let converter = Converter::new();
+    let convert = |input: &str| converter.convert(input).unwrap();
+
+    // reset, then a line break
+    insta::assert_debug_snapshot!(convert("\x1b[31mred\x1b[0m\n"), @r#""<span style='color:var(--red,#a00)'>red</span>\n""#);
+    // a line break at the end without a reset
+    assert_eq!(convert("\x1b[31mred\n"), convert("\x1b[31mred\x1b[0m\n"));
+    // multiple blank lines at the end, with a reset between them
+    insta::assert_debug_snapshot!(convert("\x1b[1;31mred\r\n\n\x1b[0m\n\x1b[4m\n"), @r#""<b><span style='color:var(--red,#a00)'>red</span></b>\r\n\n\n\n""#);
+    // line breaks before text keep their styling
+    insta::assert_debug_snapshot!(convert("\x1b[1mbold\n\nbold\n  "), @r#""<b>bold\n\nbold</b>\n  ""#);
+    // spaces at the end only keep the styles that are visible on them
+    insta::assert_debug_snapshot!(convert("\x1b[1;4mbold  \x1b[22;24;41m \x1b[0m  "), @r#""<b><u>bold</u></b><u>  </u><span style='background:var(--red,#a00)'> </span>  ""#);
+    assert_eq!(convert("\x1b[31mred  "), convert("\x1b[31mred\x1b[0m  "));
+    // a `\r` and a `\n` with different styles stay two line breaks
+    insta::assert_debug_snapshot!(convert("a\r\x1b[1m\n"), @r#""a\r<b>\n</b>""#);
+    insta::assert_debug_snapshot!(convert("a\x1b[1m\r\x1b[0m\n"), @r#""a<b>\r</b>\n""#);
+
+    // the unminified output and a stream are the same
+    for input in [
+        "\x1b[31mred\n",
+        "\x1b[31mred\n\n\x1b[1m\n",
+        "\x1b[4mu\x1b[24m\r\n",
+        "\x1b[1mbold \n \x1b[42m \n",
+    ] {
+        let unminified = converter.clone().skip_optimize(true);
+        assert_eq!(
+            unminified.convert(input).unwrap(),
+            convert(input),
+            "{input:?}"
+        );
+        let mut stream = converter.begin();
+        let mut html = stream.feed(&input[..input.len() - 1]).unwrap();
+        html += &stream.feed("\n").unwrap();
+        html += &stream.finish();
+        assert_eq!(html, convert(input), "{input:?}");
+    }
+
+    // not with the output of the 0.x versions
+    let v0 = converter.output_compat(OutputCompat::V0);
+    insta::assert_debug_snapshot!(v0.convert("\x1b[31mred  \n").unwrap(), @r#""<span style='color:var(--red,#a00)'>red  \n</span>""#);
+}
+
+#[test]
+fn line_safe() {
+    use ansi_to_html::Converter;
+
+    let converter = Converter::new().line_safe(true);
+    let input = "\x1b[1mbold\n\x1b[31mred\n\n\x1b[0;4munderlined\x1b[0m plain\n";
+    insta::assert_snapshot!(converter.convert(input).unwrap(), @"
+    <b>bold</b>
+    <b><span style='color:var(--red,#a00)'>red</span></b>
+
+    <u>underlined</u> plain
+    ");
+    // adjacent tags aren't merged across the line breaks
+    let html = converter
+        .clone()
+        .skip_optimize(true)
+        .convert(input)
+        .unwrap();
+    assert_eq!(html, converter.convert(input).unwrap());
+    let mut written = String::new();
+    converter.convert_to_writer(input, &mut written).unwrap();
+    assert_eq!(written, html);
+
+    // the styles of the next line are opened only once
+    let html = converter.convert("\x1b[1ma\n\x1b[0;3mb\nc").unwrap();
+    insta::assert_snapshot!(html, @"
+    <b>a</b>
+    <i>b</i>
+    <i>c</i>
+    ");
+
+    let links = converter.clone().render_links(true);
+    let html = links
+        .convert("\x1b]8;;https://example.com\x07\x1b[1mtwo\nlines\x1b]8;;\x07")
+        .unwrap();
+    insta::assert_snapshot!(html, @"
+    <a href='https://example.com'><b>two</b></a>
+    <a href='https://example.com'><b>lines</b></a>
+    ");
+
+    let spaces = converter.clone().normalize_trailing_whitespace(true);
+    let html = spaces.convert("\x1b[1;41m a \n\x1b[49m b \nc").unwrap();
+    insta::assert_snapshot!(html, @"
+    <b><span style='background:var(--red,#a00)'> a </span></b>
+    <b> b</b> 
+    <b>c</b>
+    ");
+
+    let progress = converter.clone().carriage_return_overwrites(true);
+    let html = progress
+        .convert("\x1b[32m10%\r50%\r\n100%\x1b[0m\n")
+        .unwrap();
+    insta::assert_debug_snapshot!(html, @r#""<span style='color:var(--green,#0a0)'>50%</span>\r\n<span style='color:var(--green,#0a0)'>100%</span>\n""#);
+
+    // a stream closes the tags at line breaks as well
+    let mut stream = converter.begin();
+    let mut streamed = stream.feed("\x1b[1ma\nb").unwrap();
+    streamed += &stream.feed("\nc").unwrap();
+    streamed += &stream.finish();
+    insta::assert_snapshot!(streamed, @"
+    <b>a</b>
+    <b>b</b>
+    <b>c</b>
+    ");
+}
+
+#[test]
+fn convert_lines() {
+    use ansi_to_html::Converter;
+
+    let converter = Converter::new();
+    let input = "\x1b[1mbold\r\n\x1b[31mred\n\n\x1b[0mplain \x1b[4m\n";
+    let lines = converter.convert_lines(input).unwrap();
+    insta::assert_debug_snapshot!(lines, @r#"
+    [
+        "<b>bold</b>",
+        "<b><span style='color:var(--red,#a00)'>red</span></b>",
+        "",
+        "plain ",
+    ]
+    "#);
+    let prepared = converter.clone().build().unwrap();
+    assert_eq!(prepared.convert_lines(input).unwrap(), lines);
+    assert_eq!(
+        converter.clone().pretty(true).convert_lines(input).unwrap(),
+        lines
+    );
+    assert_eq!(
+        lines.join("\n"),
+        converter
+            .clone()
+            .line_safe(true)
+            .convert(input)
+            .unwrap()
+            .replace("\r\n", "\n")
+            .strip_suffix('\n')
+            .unwrap()
+    );
+
+    assert!(converter.convert_lines("").unwrap().is_empty());
+    assert!(converter.convert_lines("a\n\x1b[38;5m").is_err());
+}
+
+#[test]
+fn line_wrapper() {
+    use ansi_to_html::{Converter, LineWrapper, OutputCompat, Quote, StyleState};
+
+    let wrapper = LineWrapper::new("span", Some("line"), true).unwrap();
+    let converter = Converter::new().line_wrapper(Some(wrapper));
+    let input = "\x1b[1mbold\n\x1b[31mred\r\n\n\x1b[0;4munderlined\x1b[0m plain\n";
+    insta::assert_debug_snapshot!(converter.convert(input).unwrap(), @r#""<span class='line' data-line='1'><b>bold</b></span>\n<span class='line' data-line='2'><b><span style='color:var(--red,#a00)'>red</span></b></span>\r\n<span class='line' data-line='3'></span>\n<span class='line' data-line='4'><u>underlined</u> plain</span>\n""#);
+    // plain text is wrapped as well
+    insta::assert_snapshot!(converter.convert("a\nb").unwrap(), @"
+    <span class='line' data-line='1'>a</span>
+    <span class='line' data-line='2'>b</span>
+    ");
+
+    // the wrappers of empty lines aren't removed by the optimization
+    let unnumbered = LineWrapper::new("span", Some("line"), false).unwrap();
+    for wrapper in [unnumbered, LineWrapper::new("b", None, false).unwrap()] {
+        for quotes in [Quote::Single, Quote::Double] {
+            let converter = (converter.clone())
+                .line_wrapper(Some(wrapper.clone()))
+                .attribute_quotes(quotes);
+            let html = converter.convert(input).unwrap();
+            let skipped = converter
+                .clone()
+                .skip_optimize(true)
+                .convert(input)
+                .unwrap();
+            assert_eq!(html, skipped);
+            assert_eq!(html.lines().count(), 4);
+            assert!(html
+                .lines()
+                .all(|line| line.starts_with(&format!("<{}", wrapper.tag()))));
+        }
+    }
+    let html = converter
+        .clone()
+        .skip_escape(true)
+        .output_compat(OutputCompat::V0)
+        .convert("<b></b>a\n<i></i>\n")
+        .unwrap();
+    if cfg!(feature = "optimize") {
+        insta::assert_snapshot!(html, @"
+        <span class='line' data-line='1'>a</span>
+        <span class='line' data-line='2'></span>
+        ");
+    } else {
+        insta::assert_snapshot!(html, @"
+        <span class='line' data-line='1'><b></b>a</span>
+        <span class='line' data-line='2'><i></i></span>
+        ");
+    }
+
+    let mut written = String::new();
+    converter.convert_to_writer(input, &mut written).unwrap();
+    assert_eq!(written, converter.convert(input).unwrap());
+
+    // the line break isn't part of the isolated text
+    let bidi = converter.clone().bidi_isolate(true);
+    insta::assert_debug_snapshot!(bidi.convert("a\r\nb").unwrap(), @r#""<span class='line' data-line='1'><bdi>a</bdi></span>\r\n<span class='line' data-line='2'><bdi>b</bdi></span>""#);
+
+    // the numbering continues in the next call
+    let mut state = StyleState::new();
+    let mut html = String::new();
+    converter
+        .convert_append(&mut state, "\x1b[1ma\nb", &mut html)
+        .unwrap();
+    converter
+        .convert_append(&mut state, "c\nd\n", &mut html)
+        .unwrap();
+    insta::assert_snapshot!(html, @"
+    <span class='line' data-line='1'><b>a</b></span>
+    <span class='line' data-line='2'><b>b</b></span><span class='line' data-line='2'><b>c</b></span>
+    <span class='line' data-line='3'><b>d</b></span>
+    ");
+
+    let mut stream = converter.begin();
+    let mut streamed = stream.feed("\x1b[1ma\nb").unwrap();
+    streamed += &stream.feed("c\nd\n").unwrap();
+    streamed += &stream.finish();
+    insta::assert_snapshot!(streamed, @"
+    <span class='line' data-line='1'><b>a</b></span>
+    <span class='line' data-line='2'><b>bc</b></span>
+    <span class='line' data-line='3'><b>d</b></span>
+    ");
+
+    let chunks = converter.convert_chunked("a\nb\nc\n\x1b[1md", 4).unwrap();
+    insta::assert_debug_snapshot!(chunks, @r#"
+    [
+        "<span class='line' data-line='1'>a</span>\n<span class='line' data-line='2'>b</span>\n<span class='line' data-line='3'>c</span>\n",
+        "<span class='line' data-line='4'><b>d</b></span>",
+    ]
+    "#);
+
+    let lines = converter.convert_lines("a\n\nb\n").unwrap();
+    insta::assert_debug_snapshot!(lines, @r#"
+    [
+        "<span class='line' data-line='1'>a</span>",
+        "<span class='line' data-line='2'></span>",
+        "<span class='line' data-line='3'>b</span>",
+    ]
+    "#);
+}
+
+#[test]
+fn render_links() {
+    use ansi_to_html::{Converter, StyleState, UrlSchemes};
+
+    let converter = Converter::new().render_links(true);
+    let convert = |input: &str| converter.convert(input).unwrap();
+    let link = |url: &str| format!("\x1b]8;;{url}\x1b\\");
+    let end = "\x1b]8;;\x1b\\";
+
+    // a reset in the middle of the link doesn't end it
+    insta::assert_snapshot!(
+        convert(&format!("\x1b[1mbold {}link\x1b[0m plain{end} text", link("https://example.com"))),
+        @"<b>bold </b><a href='https://example.com'><b>link</b> plain</a> text",
+    );
+    // `id=` parameters and `BEL` as terminator
+    insta::assert_snapshot!(
+        convert("\x1b]8;id=42;https://a.org/?x=1&y='2'\x07a\x1b]8;;\x07 \x1b]8;id=1:k=v;mailto:me@a.org\x1b\\b"),
+        @"<a href='https://a.org/?x=1&amp;y=%272%27'>a</a> <a href='mailto:me@a.org'>b</a>",
+    );
+    // disallowed schemes are stripped
+    insta::assert_snapshot!(convert(&format!("{}click{end} me", link("javascript:alert(1)"))), @"click me");
+    let https_only = converter.clone().link_schemes(UrlSchemes::new(["https"]));
+    insta::assert_snapshot!(
+        https_only.convert(&format!("{}a{end}{}b", link("http://a.org"), link("https://b.org"))).unwrap(),
+        @"a<a href='https://b.org'>b</a>",
+    );
+
+    // the link is continued in the next call
+    let mut state = StyleState::new();
+    let mut html = String::new();
+    converter
+        .convert_append(
+            &mut state,
+            &format!("{}a\x1b[1m", link("https://a.org")),
+            &mut html,
+        )
+        .unwrap();
+    converter
+        .convert_append(&mut state, &format!("b{end}c\x1b[0m"), &mut html)
+        .unwrap();
+    insta::assert_snapshot!(html, @"<a href='https://a.org'>a</a><a href='https://a.org'><b>b</b></a><b>c</b>");
+    assert!(state.is_default());
+}
+
+#[test]
+fn newline_normalization() {
+    use ansi_to_html::{Converter, Newlines, StyleState};
+
+    let inputs = [
+        "crlf\r\nline\r\n",
+        "lone\rcr",
+        "cr at eof\r",
+        "\x1b[1mstyled\r\x1b[0m\nsplit",
+    ];
+    let convert = |newlines: Newlines| -> Vec<String> {
+        let converter = Converter::new().newline_normalization(newlines);
+        inputs
+            .iter()
+            .map(|i| converter.convert(i).unwrap())
+            .collect()
+    };
+
+    insta::assert_debug_snapshot!(convert(Newlines::Keep), @r#"
+    [
+        "crlf\r\nline\r\n",
+        "lone\rcr",
+        "cr at eof\r",
+        "<b>styled\r</b>\nsplit",
+    ]
+    "#);
+    insta::assert_debug_snapshot!(convert(Newlines::Lf), @r#"
+    [
+        "crlf\nline\n",
+        "lone\ncr",
+        "cr at eof\n",
+        "<b>styled</b>\nsplit",
+    ]
+    "#);
+    insta::assert_debug_snapshot!(convert(Newlines::CrLfToLf), @r#"
+    [
+        "crlf\nline\n",
+        "lone\rcr",
+        "cr at eof\r",
+        "<b>styled</b>\nsplit",
+    ]
+    "#);
+    assert_eq!(Newlines::default(), Newlines::Keep);
+
+    // a `\r\n` split between two calls
+    let converter = Converter::new().newline_normalization(Newlines::Lf);
+    let mut state = StyleState::new();
+    let mut html = String::new();
+    converter
+        .convert_append(&mut state, "a\r", &mut html)
+        .unwrap();
+    assert!(!state.is_default());
+    converter
+        .convert_append(&mut state, "\nb\r", &mut html)
+        .unwrap();
+    converter
+        .convert_append(&mut state, "c", &mut html)
+        .unwrap();
+    assert_eq!(html, "a\nb\nc");
+    assert!(state.is_default());
+}
+
+#[test]
+fn carriage_return_overwrites() {
+    use ansi_to_html::{Converter, Newlines, StyleState};
+
+    let inputs = [
+        // progress bar frames
+        "Downloading \x1b[32m 10%\x1b[0m\rDownloading \x1b[1;32m 55%\x1b[0m\rDownloading \x1b[32m100%\x1b[0m\n",
+        // the tail of a longer line stays, with its styling
+        "\x1b[1;31mcompiling foo\x1b[0m\r\x1b[4mdone\x1b[0m",
+        // a `\r\n` is a line break, also with sequences between
+        "crlf\r\nline\r\x1b[0m\n\r\rtwo\n",
+        "cr at eof\r",
+        // entities and combining characters are one column
+        "<b> e\u{301}x\r&\x1b[3m\u{301}",
+        "a\rb\rc",
+    ];
+    let convert = |converter: Converter| -> Vec<String> {
+        let converter = converter.carriage_return_overwrites(true);
+        inputs
+            .iter()
+            .map(|i| converter.convert(i).unwrap())
+            .collect()
+    };
+
+    insta::assert_debug_snapshot!(convert(Converter::new()), @r#"
+    [
+        "Downloading <span style='color:var(--green,#0a0)'>100%</span>\n",
+        "<u>done</u><b><span style='color:var(--red,#a00)'>iling foo</span></b>",
+        "crlf\r\nline\r\ntwo\n",
+        "cr at eof",
+        "&amp;\u{301}b&gt; e\u{301}x",
+        "c",
+    ]
+    "#);
+    insta::assert_debug_snapshot!(convert(Converter::new().newline_normalization(Newlines::CrLfToLf)), @r#"
+    [
+        "Downloading <span style='color:var(--green,#0a0)'>100%</span>\n",
+        "<u>done</u><b><span style='color:var(--red,#a00)'>iling foo</span></b>",
+        "crlf\nline\ntwo\n",
+        "cr at eof",
+        "&amp;\u{301}b&gt; e\u{301}x",
+        "c",
+    ]
+    "#);
+    // there are no lone `\r` left
+    let lf = Converter::new().newline_normalization(Newlines::Lf);
+    assert_eq!(convert(lf.clone()), inputs.map(|i| lf.convert(i).unwrap()));
+
+    // the other conversion functions overwrite as well
+    let converter = Converter::new().carriage_return_overwrites(true);
+    for input in inputs {
+        let html = converter.convert(input).unwrap();
+        let mut written = String::new();
+        converter.convert_to_writer(input, &mut written).unwrap();
+        assert_eq!(written, html);
+        assert_eq!(
+            converter.convert_chunked(input, 100).unwrap().concat(),
+            html
+        );
+        if !input.contains('\n') {
+            let mut line = String::new();
+            converter.convert_line(input, &mut line).unwrap();
+            assert_eq!(line, html);
+        }
+    }
+
+    let skip_escape = converter.clone().skip_escape(true);
+    assert_eq!(
+        skip_escape.convert("&lt;<b>b</b>\r&gt;").unwrap(),
+        "&gt;<b>b</b>"
+    );
+
+    // text isn't overwritten across calls, but a `\r\n` is recognized
+    let mut state = StyleState::new();
+    let mut html = String::new();
+    for input in ["50%\r", "\n70%\r", "100%"] {
+        converter
+            .convert_append(&mut state, input, &mut html)
+            .unwrap();
+    }
+    assert_eq!(html, "50%\r\n70%100%");
+}
+
+#[test]
+fn erase_line() {
+    use ansi_to_html::Converter;
+
+    // A spinner as drawn by `indicatif`: the cursor is hidden, every frame is
+    // preceded by `\r\x1b[2K` (`console`'s `clear_line`), and the finished
+    // message replaces the last frame.
+    let spinner = concat!(
+        "\x1b[?25l",
+        "\r\x1b[2K\x1b[32m⠁\x1b[0m Resolving dependencies",
+        "\r\x1b[2K\x1b[32m⠂\x1b[0m Resolving dependencies",
+        "\r\x1b[2K\x1b[32m⠄\x1b[0m Fetching \x1b[1mserde\x1b[0m (1/12)",
+        "\r\x1b[2K\x1b[32m⡀\x1b[0m Fetching \x1b[1mregex\x1b[0m (7/12)",
+        "\r\x1b[2K\x1b[32m✔\x1b[0m Done",
+        "\n\x1b[?25h",
+    );
+    let inputs = [
+        spinner,
+        // without `\r`, the erased text before the cursor becomes spaces
+        "\x1b[41mone two\x1b[2K three\n",
+        "one two\rxyz\x1b[K\n",
+        "one two\r\x1b[1mxyz\x1b[1K!\n",
+        "\x1b[41mone\x1b[1K\r\x1b[1Kx\n",
+        // other sequences ending in `K` are still removed
+        "one\r\x1b[3K\x1b[?2Ktwo",
+    ];
+    let converter = Converter::new().carriage_return_overwrites(true);
+    let html: Vec<_> = inputs.map(|i| converter.convert(i).unwrap()).into();
+    insta::assert_debug_snapshot!(html, @r#"
+    [
+        "<span style='color:var(--green,#0a0)'>✔</span> Done\n",
+        "       <span style='background:var(--red,#a00)'> three</span>\n",
+        "xyz\n",
+        "   <b>!</b>two\n",
+        "<span style='background:var(--red,#a00)'>x</span>  \n",
+        "two",
+    ]
+    "#);
+
+    // the sequences are removed without `carriage_return_overwrites`
+    assert_eq!(Converter::new().convert("a\x1b[Kb\x1b[2K").unwrap(), "ab");
+}
+
+#[test]
+fn emulate_cursor_movement() {
+    use ansi_to_html::Converter;
+
+    // Progress in the format of docker buildkit: the lines of the last frame
+    // are overwritten after moving up with `ESC[<n>A` and to the first column.
+    let frame = |time: &str, steps: &[&str]| {
+        let mut frame = format!("\x1b[1m[+] Building {time}\x1b[0m\n");
+        for step in steps {
+            frame += &format!("\x1b[2K => {step}\n");
+        }
+        frame
+    };
+    let docker = [
+        frame("0.1s (1/3)", &["\x1b[34m[1/3] FROM alpine\x1b[0m"]),
+        format!(
+            "\x1b[2A\x1b[1G{}",
+            frame(
+                "0.9s (2/3)",
+                &["[1/3] FROM alpine 0.8s", "\x1b[34m[2/3] RUN make\x1b[0m"]
+            )
+        ),
+        format!(
+            "\x1b[3A\x1b[1G{}",
+            frame(
+                "1.5s (3/3) FINISHED",
+                &["[1/3] FROM alpine 0.8s", "[2/3] RUN make 0.6s"]
+            )
+        ),
+    ]
+    .concat();
+    let inputs = [
+        docker.as_str(),
+        // the column stays the same when moving up or down
+        "one\ntwo\nthree\x1b[2AX\x1b[BY\x1b[8GZ",
+        // the cursor stops at the first and the last line
+        "\x1b[5Atop\n\x1b[9Bend\x1b[0Aup\x1b[99999Ax",
+        // moving back to the right column after a `\r`
+        "\x1b[31m50%\x1b[0m loading\r\x1b[5G100\x1b[K",
+        // other sequences that move the cursor are removed
+        "ab\x1b[Hc\x1b[3Cd\x1b[1;2Ae\x1b[2J",
+    ];
+    let converter = Converter::new().emulate_cursor_movement(true);
+    let html: Vec<_> = inputs.map(|i| converter.convert(i).unwrap()).into();
+    insta::assert_debug_snapshot!(html, @r#"
+    [
+        "<b>[+] Building 1.5s (3/3) FINISHED</b>\n =&gt; [1/3] FROM alpine 0.8s\n =&gt; [2/3] RUN make 0.6s\n",
+        "one  X\ntwo   YZ\nthree",
+        "topupx\nend",
+        "<span style='color:var(--red,#a00)'>50%</span> 100",
+        "abcde",
+    ]
+    "#);
+
+    // the cursor can only move up to the last 256 lines
+    let mut input = "old\n".repeat(300);
+    input += "\x1b[300Anew\n";
+    let html = converter.convert(&input).unwrap();
+    assert_eq!(html, "old\n".repeat(45) + "new\n" + &"old\n".repeat(254));
+
+    // the cursor moves across the pieces of `convert_to_writer`
+    let mut input = "\x1b[1mline\x1b[0m\n".repeat(2000);
+    input += "\x1b[1999A\rmoved\x1b[2000B\x1b[1Gdone";
+    let html = converter.convert(&input).unwrap();
+    assert!(html.ends_with("<b>line</b>\ndone"));
+    let mut written = String::new();
+    converter.convert_to_writer(&input, &mut written).unwrap();
+    assert_eq!(written, html);
+}
+
+#[test]
+fn visible_width() {
+    use ansi_to_html::visible_width;
+
+    let widths = [
+        "",
+        "plain",
+        "\x1b[1;31mred\x1b[0m \x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\",
+        // the widest line counts
+        "short\nthe longest line\nmedium line",
+        "日本語のテキスト",
+        "한국어 text",
+        "ｆｕｌｌ",
+        "🦀 and 👩\u{200D}👩\u{200D}👧",
+        "e\u{301}\x1b[1m\u{301}",
+        "tab\tstop\t",
+        "progress 10%\rdone",
+        "\x07bell\x08",
+        "✔ ok",
+    ]
+    .map(|text| (text, visible_width(text)));
+    insta::assert_debug_snapshot!(widths, @r#"
+    [
+        (
+            "",
+            0,
+        ),
+        (
+            "plain",
+            5,
+        ),
+        (
+            "\u{1b}[1;31mred\u{1b}[0m \u{1b}]8;;https://example.com\u{1b}\\link\u{1b}]8;;\u{1b}\\",
+            8,
+        ),
+        (
+            "short\nthe longest line\nmedium line",
+            16,
+        ),
+        (
+            "日本語のテキスト",
+            16,
+        ),
+        (
+            "한국어 text",
+            11,
+        ),
+        (
+            "ｆｕｌｌ",
+            8,
+        ),
+        (
+            "🦀 and 👩\u{200d}👩\u{200d}👧",
+            9,
+        ),
+        (
+            "e\u{301}\u{1b}[1m\u{301}",
+            1,
+        ),
+        (
+            "tab\tstop\t",
+            16,
+        ),
+        (
+            "progress 10%\rdone",
+            12,
+        ),
+        (
+            "\u{7}bell\u{8}",
+            4,
+        ),
+        (
+            "✔ ok",
+            4,
+        ),
+    ]
+    "#);
+}
+
+#[test]
+fn detect_double_escape() {
+    use ansi_to_html::{looks_like_html, Converter};
+
+    let converter = Converter::new().render_links(true);
+    let converted = [
+        "\x1b[1merror\x1b[0m: expected `<T>`, found `&'a str`\n",
+        "\x1b[32m\"quoted\"\x1b[0m & <tags> & more\n\x1b[4munderlined\n",
+        "\x1b]8;;https://example.com/?a=1&b=2\x1b\\link\x1b]8;;\x1b\\ <>",
+    ]
+    .map(|input| converter.convert(input).unwrap());
+    let plain = [
+        // too few entities for the length
+        "Tom &amp; Jerry are a cat and a mouse, respectively, in an animated series",
+        // a `&` that isn't an entity
+        "&lt;b&gt; & &lt;i&gt;",
+        "&lt;b&gt;&nbsp&lt;/b&gt;",
+        // an escape sequence
+        "\x1b[1m&lt;b&gt;&lt;/b&gt;",
+        "&lt;",
+        "",
+    ];
+    for html in &converted {
+        assert!(looks_like_html(html), "{html:?}");
+    }
+    for text in plain {
+        assert!(!looks_like_html(text), "{text:?}");
+    }
+
+    let converter = converter.detect_double_escape(true);
+    for html in &converted {
+        assert_eq!(&converter.convert(html).unwrap(), html);
+        let mut written = String::new();
+        converter.convert_to_writer(html, &mut written).unwrap();
+        assert_eq!(&written, html);
+        let mut line = String::new();
+        converter.convert_line(html, &mut line).unwrap();
+        assert_eq!(&line, html);
+    }
+    let html: Vec<_> = plain.map(|text| converter.convert(text).unwrap()).into();
+    insta::assert_debug_snapshot!(html, @r#"
+    [
+        "Tom &amp;amp; Jerry are a cat and a mouse, respectively, in an animated series",
+        "&amp;lt;b&amp;gt; &amp; &amp;lt;i&amp;gt;",
+        "&amp;lt;b&amp;gt;&amp;nbsp&amp;lt;/b&amp;gt;",
+        "<b>&amp;lt;b&amp;gt;&amp;lt;/b&amp;gt;</b>",
+        "&amp;lt;",
+        "",
+    ]
+    "#);
+
+    // mixed content: a converted line with new terminal output is converted
+    let mixed = format!("{}\x1b[31m&lt;new&gt;", converted[0]);
+    assert!(!looks_like_html(&mixed));
+    insta::assert_snapshot!(converter.convert(&mixed).unwrap(), @"
+    &lt;b&gt;error&lt;/b&gt;: expected `&amp;lt;T&amp;gt;`, found `&amp;amp;&amp;#39;a str`
+    <span style='color:var(--red,#a00)'>&amp;lt;new&amp;gt;</span>
+    ");
+}
+
+#[test]
+fn lossy() {
+    use ansi_to_html::{Converter, Error};
+
+    let inputs = [
+        // out-of-range numbers
+        "\x1b[1;256mbold\x1b[0m \x1b[99999;3mitalic",
+        // truncated 8-bit colors
+        "\x1b[4m\x1b[38;5munderlined\x1b[0m \x1b[48;5;1;38;5mred background",
+        // truncated RGB colors
+        "\x1b[31mred \x1b[38;2;10;20mstill red\x1b[1;48;2;1m bold",
+    ];
+
+    let strict = Converter::new();
+    for input in inputs {
+        let error = strict.convert(input).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::ParseInt { .. } | Error::InvalidAnsi { .. }
+        ));
+    }
+
+    let lossy = Converter::new().lossy(true);
+    let converted: Vec<String> = inputs.iter().map(|i| lossy.convert(i).unwrap()).collect();
+    insta::assert_debug_snapshot!(converted, @r#"
+    [
+        "<b>bold</b> <i>italic</i>",
+        "<u>underlined</u> <span style='background:#a00'>red background</span>",
+        "<span style='color:var(--red,#a00)'>red still red</span><b><span style='color:var(--red,#a00)'> bold</span></b>",
+    ]
+    "#);
+
+    let mut html = String::new();
+    lossy.convert_line("\x1b[38;5mx", &mut html).unwrap();
+    assert_eq!(html, "x");
+}
+
+#[test]
+fn error_span() {
+    use ansi_to_html::{Converter, StyleState};
+
+    let converter = Converter::new();
+    // the span refers to the input, not to the escaped text
+    let input = "<a> & \x1b[1mbold\x1b[0m \x1b[38;7mbroken";
+    let error = converter.convert(input).unwrap_err();
+    assert_eq!(error.span(), Some(19..26));
+    assert_eq!(error.sequence(), Some("\x1b[38;7m"));
+    assert_eq!(&input[error.span().unwrap()], "\x1b[38;7m");
+    insta::assert_snapshot!(error, @r#"Invalid ANSI: Expected 2 or 5, got 7 in "\u{1b}[38;7m" at bytes 19..26"#);
+
+    let error = converter.convert("\x1b[1;256m").unwrap_err();
+    insta::assert_snapshot!(error, @r#"number too large to fit in target type in "\u{1b}[1;256m" at bytes 0..8"#);
+
+    let mut html = String::new();
+    let error = converter
+        .convert_line("ok \x1b[99m", &mut html)
+        .unwrap_err();
+    assert_eq!(error.span(), Some(3..8));
+
+    let error = converter
+        .convert_chunked("a\nb\n\x1b[38;5m", 1)
+        .unwrap_err();
+    assert_eq!(error.span(), Some(4..11));
+
+    // a sequence that started in the previous call starts at 0
+    let mut state = StyleState::new();
+    converter
+        .convert_append(&mut state, "ok \x1b[38", &mut html)
+        .unwrap();
+    let error = converter
+        .convert_append(&mut state, ";9m", &mut html)
+        .unwrap_err();
+    assert_eq!(error.span(), Some(0..3));
+    assert_eq!(error.sequence(), Some("\x1b[38;9m"));
+}
+
+#[test]
+fn stream() {
+    use ansi_to_html::{Converter, Newlines};
+
+    let feed_all = |converter: &Converter, chunks: &[&str]| {
+        let mut stream = converter.begin();
+        let mut html: Vec<String> = chunks.iter().map(|c| stream.feed(c).unwrap()).collect();
+        html.push(stream.finish());
+        html
+    };
+
+    // the styles stay open across chunks, even in the middle of an escape sequence
+    let chunks = [
+        "\x1b[1mbold \x1b[3",
+        "1mred\n",
+        "still red\x1b[0m",
+        " plain <\x1b[",
+    ];
+    insta::assert_debug_snapshot!(feed_all(&Converter::new(), &chunks), @r#"
+    [
+        "<b>bold",
+        " <span style='color:var(--red,#a00)'>red",
+        "\nstill red</span></b>",
+        " plain &lt;",
+        "\u{1b}[",
+    ]
+    "#);
+
+    // links, held back trailing spaces and cut off `\r\n`
+    let converter = Converter::new()
+        .render_links(true)
+        .normalize_trailing_whitespace(true)
+        .newline_normalization(Newlines::CrLfToLf);
+    let chunks = [
+        "\x1b]8;;https://example.com\x07\x1b[4mlink  ",
+        "\r",
+        "\n\x1b]8;;\x07 x",
+    ];
+    insta::assert_debug_snapshot!(feed_all(&converter, &chunks), @r#"
+    [
+        "<a href='https://example.com'><u>link",
+        "",
+        "  \n</u></a><u> x",
+        "</u>",
+    ]
+    "#);
+
+    // an invalid chunk can be skipped
+    let mut stream = Converter::new().begin();
+    assert_eq!(stream.feed("\x1b[1mbold").unwrap(), "<b>bold");
+    let error = stream.feed(" \x1b[38;7mbroken").unwrap_err();
+    assert_eq!(error.span(), Some(1..8));
+    assert_eq!(stream.feed(" more").unwrap(), " more");
+    assert_eq!(stream.finish(), "</b>");
+}
+
+#[test]
+fn convert_to_writer() {
+    use std::{fmt, io::Read};
+
+    use ansi_to_html::{Converter, Error, OutputCompat, Quote, Transformed};
+
+    static COMPRESSED_TERMINAL_SESSION: &[u8] = include_bytes!("../assets/terminal_session.gz");
+    let mut session = String::new();
+    flate2::read::GzDecoder::new(COMPRESSED_TERMINAL_SESSION)
+        .read_to_string(&mut session)
+        .unwrap();
+
+    /// Records the pieces of HTML, and fails after `limit` bytes
+    struct Pieces {
+        pieces: Vec<String>,
+        limit: usize,
+    }
+    impl fmt::Write for Pieces {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let len: usize = self.pieces.iter().map(String::len).sum();
+            if len + s.len() > self.limit {
+                return Err(fmt::Error);
+            }
+            self.pieces.push(s.to_string());
+            Ok(())
+        }
+    }
+
+    let converters = [
+        Converter::new(),
+        Converter::new().skip_optimize(true),
+        Converter::new().skip_escape(true),
+        Converter::new().normalize_trailing_whitespace(true),
+        Converter::new().bidi_isolate(true),
+        Converter::new().text_transform(|text| Transformed::Text(text.to_uppercase())),
+        Converter::new().pretty(true),
+        Converter::new().attribute_quotes(Quote::Double),
+    ];
+    for converter in &converters {
+        let mut writer = Pieces {
+            pieces: Vec::new(),
+            limit: usize::MAX,
+        };
+        converter.convert_to_writer(&session, &mut writer).unwrap();
+        assert_eq!(writer.pieces.concat(), converter.convert(&session).unwrap());
+    }
+
+    // the HTML is written while converting
+    let mut writer = Pieces {
+        pieces: Vec::new(),
+        limit: usize::MAX,
+    };
+    Converter::new()
+        .convert_to_writer(&session, &mut writer)
+        .unwrap();
+    assert!(writer.pieces.len() > 10);
+
+    // with the `optimize` feature and `OutputCompat::V0`, an attribute with line breaks is removed
+    // with its empty element, even if it is longer than a piece
+    let converter = (Converter::new().skip_escape(true)).output_compat(OutputCompat::V0);
+    let lines = "line\n".repeat(5000);
+    for input in [
+        format!("a<span title='{lines}'></span>b"),
+        format!("a<span title=\"'{lines}\"></span>b"),
+    ] {
+        let mut html = String::new();
+        converter.convert_to_writer(&input, &mut html).unwrap();
+        if cfg!(feature = "optimize") {
+            assert_eq!(html, "ab");
+        } else {
+            assert_eq!(html, input);
+        }
+    }
+
+    let mut writer = Pieces {
+        pieces: Vec::new(),
+        limit: 100,
+    };
+    let error = Converter::new()
+        .convert_to_writer(&session, &mut writer)
+        .unwrap_err();
+    assert!(matches!(error, Error::Write { .. }));
+    insta::assert_snapshot!(error, @"Writing the HTML failed: an error occurred when formatting an argument");
+}
+
+#[test]
+fn attach_combining() {
+    use ansi_to_html::Converter;
+
+    let convert = |input: &str| ansi_to_html::convert(input).unwrap();
+    // combining accents stay with their base character
+    insta::assert_debug_snapshot!(convert("cafe\x1b[1m\u{301}\x1b[0m and nai\x1b[32m\u{308}\u{304}ve"), @r#""cafe\u{301} and nai\u{308}\u{304}<span style='color:var(--green,#0a0)'>ve</span>""#);
+    // a line break isn't extended
+    insta::assert_debug_snapshot!(convert("a\n\x1b[31m\u{301}"), @r#""a\n<span style='color:var(--red,#a00)'>\u{301}</span>""#);
+    // emoji ZWJ sequences, a skin tone modifier and a variation selector
+    insta::assert_debug_snapshot!(convert("👨\x1b[31m\u{200D}👩\u{200D}👧 done"), @r#""👨\u{200d}👩\u{200d}👧<span style='color:var(--red,#a00)'> done</span>""#);
+    insta::assert_debug_snapshot!(convert("👨\u{200D}\x1b[31m👩 done"), @r#""👨\u{200d}👩<span style='color:var(--red,#a00)'> done</span>""#);
+    insta::assert_debug_snapshot!(convert("👍\x1b[44m\u{1F3FD}\x1b[0m ❤\x1b[1m\u{FE0F}!"), @r#""👍🏽 ❤\u{fe0f}<b>!</b>""#);
+    // held back spaces aren't trailing when a mark is attached to them
+    let converter = Converter::new().normalize_trailing_whitespace(true);
+    insta::assert_debug_snapshot!(converter.convert("\x1b[1ma \x1b[0m\u{301}\n").unwrap(), @r#""<b>a \u{301}</b>\n""#);
+
+    let converter = Converter::new().attach_combining(false);
+    insta::assert_debug_snapshot!(converter.convert("cafe\x1b[1m\u{301}").unwrap(), @r#""cafe<b>\u{301}</b>""#);
+
+    // the last character of a stream chunk is remembered
+    let mut stream = Converter::new().begin();
+    let mut html = stream.feed("e").unwrap();
+    html += &stream.feed("\x1b[31m\u{301}x").unwrap();
+    html += &stream.finish();
+    insta::assert_debug_snapshot!(html, @r#""e\u{301}<span style='color:var(--red,#a00)'>x</span>""#);
+}
+
+#[test]
+fn conversion_session() {
+    use ansi_to_html::{Converter, LineWrapper, OutputCompat};
+
+    let converters = [
+        Converter::new(),
+        Converter::new().skip_optimize(true),
+        Converter::new().flat_spans(true),
+        Converter::new().bidi_isolate(true),
+        Converter::new().normalize_trailing_whitespace(true),
+        Converter::new().line_wrapper(Some(LineWrapper::new("span", Some("line"), true).unwrap())),
+        Converter::new().emulate_cursor_movement(true),
+        Converter::new().pretty(true),
+        Converter::new().output_compat(OutputCompat::V0),
+    ];
+    // styles that are open at the end of an input must not leak into the next one
+    let inputs = [
+        "\x1b[1;31mbold red\x1b[4m  ",
+        "plain",
+        "\x1b]8;;https://example.com\x1b\\link",
+        "a & b\n",
+        "\x1b[1m\x1b[38;5;300minvalid",
+        "\x1b[3mitalic\r\n\x1b[0m",
+        "",
+        "\x1b[1mbold\x1b[22m plain\nnext line",
+    ];
+    for converter in &converters {
+        let mut session = converter.session();
+        for input in inputs.iter().chain(inputs.iter().rev()) {
+            match converter.convert(input) {
+                Ok(html) => assert_eq!(session.convert(input).unwrap(), html, "{input:?}"),
+                Err(e) => assert_eq!(
+                    session.convert(input).unwrap_err().to_string(),
+                    e.to_string(),
+                    "{input:?}"
+                ),
+            }
+        }
+        assert_eq!(
+            session.convert_owned(inputs[0]).unwrap(),
+            converter.convert(inputs[0]).unwrap()
+        );
+        // the session still works after its buffer was moved out
+        assert_eq!(
+            session.convert(inputs[1]).unwrap(),
+            converter.convert(inputs[1]).unwrap()
+        );
+    }
+
+    let prepared = Converter::new().build().unwrap();
+    let mut session = prepared.session();
+    insta::assert_snapshot!(session.convert("\x1b[1mbold").unwrap(), @"<b>bold</b>");
+    insta::assert_snapshot!(session.convert("\x1b[3mitalic").unwrap(), @"<i>italic</i>");
+}
+
+#[test]
+fn convert_cow() {
+    use std::borrow::Cow;
+
+    use ansi_to_html::{Converter, Newlines};
+
+    let converters = [
+        Converter::new(),
+        Converter::new().skip_escape(true),
+        Converter::new().skip_escape(true).sanitize(true),
+        Converter::new().newline_normalization(Newlines::Lf),
+        Converter::new()
+            .normalize_trailing_whitespace(true)
+            .pretty(true),
+        Converter::new().bidi_isolate(true),
+    ];
+    let inputs = [
+        "plain text  ",
+        "two\nlines\n",
+        "a & b",
+        "&amp; &lt;",
+        "<b></b>x",
+        "it's \"quoted\"",
+        "cr\r\nlf",
+        "\x1b[1mbold",
+        "",
+    ];
+    let mut borrowed = Vec::new();
+    for (i, converter) in converters.iter().enumerate() {
+        for input in inputs {
+            let html = converter.convert_cow(input).unwrap();
+            assert_eq!(html, converter.convert(input).unwrap(), "{i}: {input:?}");
+            if let Cow::Borrowed(_) = html {
+                borrowed.push(format!("{i}: {input:?}"));
+            }
+        }
+    }
+    insta::assert_debug_snapshot!(borrowed, @r#"
+    [
+        "0: \"plain text  \"",
+        "0: \"two\\nlines\\n\"",
+        "0: \"cr\\r\\nlf\"",
+        "0: \"\"",
+        "1: \"plain text  \"",
+        "1: \"two\\nlines\\n\"",
+        "1: \"a & b\"",
+        "1: \"&amp; &lt;\"",
+        "1: \"<b></b>x\"",
+        "1: \"it's \\\"quoted\\\"\"",
+        "1: \"cr\\r\\nlf\"",
+        "1: \"\"",
+        "2: \"plain text  \"",
+        "2: \"two\\nlines\\n\"",
+        "2: \"cr\\r\\nlf\"",
+        "2: \"\"",
+        "3: \"plain text  \"",
+        "3: \"two\\nlines\\n\"",
+        "3: \"\"",
+        "4: \"plain text  \"",
+        "4: \"two\\nlines\\n\"",
+        "4: \"cr\\r\\nlf\"",
+        "4: \"\"",
+    ]
+    "#);
+}
+
+#[test]
+fn convert_bytes() {
+    use ansi_to_html::Converter;
+
+    let converter = Converter::new();
+    // invalid UTF-8 before, after and inside of escape sequences
+    let input = b"\xff\x1b[1mbold\xc3\x1b[0m \x1b[3\xfe1mtext\x1b]8;;\xf0\x9f\x07link\x1b]8;;\x07 cut \xe2\x9c";
+    insta::assert_debug_snapshot!(converter.convert_bytes(input).unwrap(), @r#""�<b>bold�</b> \u{1b}[3�1mtextlink cut �""#);
+    let lossy = String::from_utf8_lossy(input);
+    assert_eq!(
+        converter.convert_bytes(input).unwrap(),
+        converter.convert(&lossy).unwrap()
+    );
+
+    let valid = "\x1b[31mred ✓\x1b[0m";
+    assert_eq!(
+        converter.convert_bytes(valid.as_bytes()).unwrap(),
+        converter.convert(valid).unwrap()
+    );
+
+    // the span of an error is the range in the bytes
+    let input = b"\xff\xfe\xe2\x9c ok \x1b[38;5m";
+    let error = converter.convert_bytes(input).unwrap_err();
+    assert_eq!(&input[error.span().unwrap()], b"\x1b[38;5m");
+    let error = converter.convert_bytes(b"\x1b[38;5m\xff").unwrap_err();
+    assert_eq!(error.span(), Some(0..7));
+}
+
+#[test]
+fn ansi_parser() {
+    use ansi_to_html::{AnsiFragment, AnsiParser};
+
+    let input = "\x1b[1mbold\x1b[0m\x1b]8;;https://a.b\x07link\x1b]8;;\x1b\\ \x1bX \x1b[9z\x1b[K";
+    let fragments: Vec<_> = AnsiParser::new(input).collect();
+    insta::assert_debug_snapshot!(fragments, @r#"
+    [
+        Sequence(
+            "\u{1b}[1m",
+        ),
+        Text(
+            "bold",
+        ),
+        Sequence(
+            "\u{1b}[0m",
+        ),
+        Sequence(
+            "\u{1b}]8;;https://a.b\u{7}",
+        ),
+        Text(
+            "link",
+        ),
+        Sequence(
+            "\u{1b}]8;;\u{1b}\\",
+        ),
+        Text(
+            " \u{1b}X \u{1b}[9z",
+        ),
+        Sequence(
+            "\u{1b}[K",
+        ),
+    ]
+    "#);
+    assert_eq!(
+        fragments.iter().map(|f| f.as_str()).collect::<String>(),
+        input
+    );
+    let texts = fragments.iter().filter_map(|f| match f {
+        AnsiFragment::Text(text) => Some(*text),
+        _ => None,
+    });
+    assert!(texts.eq(ansi_to_html::text_fragments(input)));
+    assert_eq!(AnsiParser::new("").next(), None);
+
+    let sequences = [
+        "\x1b[m",
+        "\x1b[1;2;3;4;9m",
+        "\x1b[21;22;23;24;29;0m",
+        "\x1b[31;91;42;102;39;49;m",
+        "\x1b[38;2;255;0;128;48;5;16m",
+        "\x1b[5;73m",
+        "\x1b[1;38;7;4m",
+        "\x1b[300;1m",
+    ];
+    let mut codes = Vec::new();
+    for sequence in sequences {
+        let fragment = AnsiFragment::Sequence(sequence);
+        let mut failed = false;
+        for code in fragment.sgr_codes().unwrap() {
+            failed |= code.is_err();
+            codes.push(match code {
+                Ok(code) => format!("{sequence:?}: {code:?}"),
+                Err(e) => format!("{sequence:?}: {e}"),
+            });
+        }
+        // the codes are interpreted like in the converter
+        assert_eq!(ansi_to_html::convert(sequence).is_err(), failed);
+    }
+    insta::assert_debug_snapshot!(codes, @r#"
+    [
+        "\"\\u{1b}[m\": Reset",
+        "\"\\u{1b}[1;2;3;4;9m\": Bold",
+        "\"\\u{1b}[1;2;3;4;9m\": Faint",
+        "\"\\u{1b}[1;2;3;4;9m\": Italic",
+        "\"\\u{1b}[1;2;3;4;9m\": Underline",
+        "\"\\u{1b}[1;2;3;4;9m\": CrossedOut",
+        "\"\\u{1b}[21;22;23;24;29;0m\": BoldOff",
+        "\"\\u{1b}[21;22;23;24;29;0m\": NormalIntensity",
+        "\"\\u{1b}[21;22;23;24;29;0m\": ItalicOff",
+        "\"\\u{1b}[21;22;23;24;29;0m\": UnderlineOff",
+        "\"\\u{1b}[21;22;23;24;29;0m\": CrossedOutOff",
+        "\"\\u{1b}[21;22;23;24;29;0m\": Reset",
+        "\"\\u{1b}[31;91;42;102;39;49;m\": Foreground(Basic(1))",
+        "\"\\u{1b}[31;91;42;102;39;49;m\": Foreground(Basic(9))",
+        "\"\\u{1b}[31;91;42;102;39;49;m\": Background(Basic(2))",
+        "\"\\u{1b}[31;91;42;102;39;49;m\": Background(Basic(10))",
+        "\"\\u{1b}[31;91;42;102;39;49;m\": DefaultForeground",
+        "\"\\u{1b}[31;91;42;102;39;49;m\": DefaultBackground",
+        "\"\\u{1b}[38;2;255;0;128;48;5;16m\": Foreground(Rgb(255, 0, 128))",
+        "\"\\u{1b}[38;2;255;0;128;48;5;16m\": Background(Indexed(16))",
+        "\"\\u{1b}[5;73m\": Blink",
+        "\"\\u{1b}[5;73m\": Unsupported(73)",
+        "\"\\u{1b}[1;38;7;4m\": Bold",
+        "\"\\u{1b}[1;38;7;4m\": Invalid ANSI: Expected 2 or 5, got 7 in \"\\u{1b}[1;38;7;4m\"",
+        "\"\\u{1b}[1;38;7;4m\": Underline",
+        "\"\\u{1b}[300;1m\": number too large to fit in target type in \"\\u{1b}[300;1m\"",
+        "\"\\u{1b}[300;1m\": Bold",
+    ]
+    "#);
+
+    assert!(AnsiFragment::Text("\x1b[1m").sgr_codes().is_none());
+    assert!(AnsiFragment::Sequence("\x1b[?25h").sgr_codes().is_none());
+    assert!(AnsiFragment::Sequence("\x1b(B").sgr_codes().is_none());
+}
+
+#[test]
+fn strip_ansi() {
+    use ansi_to_html::{strip_ansi, strip_ansi_to_writer};
+
+    let input = "\x1b[1;31mred\x1b(B\x1b[m \x1b]8;;https://a.b\x07link\x1b]8;;\x1b\\ \x1b]0;title\x1b\\\x1bX \x1b[9z\x1b[K\x1b";
+    insta::assert_debug_snapshot!(strip_ansi(input), @r#""red link \u{1b}X \u{1b}[9z\u{1b}""#);
+    assert_eq!(
+        strip_ansi(input),
+        ansi_to_html::text_fragments(input).collect::<String>()
+    );
+
+    let mut out = String::from("> ");
+    strip_ansi_to_writer(input, &mut out).unwrap();
+    assert_eq!(out, format!("> {}", strip_ansi(input)));
+
+    assert_eq!(strip_ansi(""), "");
+    assert_eq!(strip_ansi("\x1b[1m\x1b[0m"), "");
+    assert_eq!(strip_ansi("plain\ntext"), "plain\ntext");
+}
+
+fn light_theme() -> ansi_to_html::ThemePalette {
+    let mut colors = [[0; 3]; 16];
+    for (i, color) in colors.iter_mut().enumerate() {
+        *color = [i as u8 * 8; 3];
+    }
+    colors[1] = [0xcc, 0, 0];
+    ansi_to_html::ThemePalette::new([0x11; 3], [0xff; 3], colors)
+}
+
+fn dark_theme() -> ansi_to_html::ThemePalette {
+    let mut colors = [[0; 3]; 16];
+    for (i, color) in colors.iter_mut().enumerate() {
+        *color = [i as u8 * 8; 3];
+    }
+    colors[1] = [0xff, 0x55, 0x55];
+    ansi_to_html::ThemePalette::new([0xee; 3], [0x22; 3], colors)
+}
+
+#[test]
+fn dual_theme() {
+    use ansi_to_html::{Converter, FaintBlend};
+
+    let input =
+        "\x1b[31mred \x1b[41mon red\x1b[0m \x1b[38;5;1m8-bit red\x1b[0m \x1b[32msame\x1b[0m \
+        \x1b[38;5;208m8-bit\x1b[0m \x1b[38;2;1;2;3mrgb\x1b[0m \x1b[2mfaint \x1b[31mred\x1b[0m";
+    let converter = Converter::new()
+        .skip_css_vars(true)
+        .dual_theme(light_theme(), dark_theme());
+    insta::assert_snapshot!(converter.convert(input).unwrap(), @"<span style='color:light-dark(#cc0000,#ff5555)'>red <span style='background:light-dark(#cc0000,#ff5555)'>on red</span></span> <span style='color:light-dark(#cc0000,#ff5555)'>8-bit red</span> <span style='color:#101010'>same</span> <span style='color:#ff8700'>8-bit</span> <span style='color:#010203'>rgb</span> <span style='opacity:0.67'>faint <span style='color:light-dark(#cc0000,#ff5555)'>red</span></span>");
+
+    // computed colors are blended with the colors of each theme
+    let blended = converter
+        .clone()
+        .faint_blend(Some(FaintBlend::new([255; 3], [0; 3])));
+    insta::assert_snapshot!(blended.convert(input).unwrap(), @"<span style='color:light-dark(#cc0000,#ff5555)'>red <span style='background:light-dark(#cc0000,#ff5555)'>on red</span></span> <span style='color:light-dark(#cc0000,#ff5555)'>8-bit red</span> <span style='color:#101010'>same</span> <span style='color:#ff8700'>8-bit</span> <span style='color:#010203'>rgb</span> <span style='color:light-dark(#606060,#ababab)'>faint <span style='color:light-dark(#dd5454,#b64444)'>red</span></span>");
+
+    // older browsers ignore `light-dark()`
+    let dark = converter.clone().skip_light_dark(true);
+    insta::assert_snapshot!(dark.convert(input).unwrap(), @"<span style='color:#ff5555'>red <span style='background:#ff5555'>on red</span></span> <span style='color:#ff5555'>8-bit red</span> <span style='color:#101010'>same</span> <span style='color:#ff8700'>8-bit</span> <span style='color:#010203'>rgb</span> <span style='opacity:0.67'>faint <span style='color:#ff5555'>red</span></span>");
+
+    // the dark colors are used as fallback of the CSS variables
+    let vars = Converter::new().dual_theme(light_theme(), dark_theme());
+    insta::assert_snapshot!(vars.convert(input).unwrap(), @"<span style='color:var(--red,#ff5555)'>red <span style='background:var(--red,#ff5555)'>on red</span></span> <span style='color:light-dark(#cc0000,#ff5555)'>8-bit red</span> <span style='color:var(--green,#101010)'>same</span> <span style='color:#ff8700'>8-bit</span> <span style='color:#010203'>rgb</span> <span style='opacity:0.67'>faint <span style='color:var(--red,#ff5555)'>red</span></span>");
+}
+
+#[test]
+fn palette() {
+    use ansi_to_html::{Converter, FaintBlend, ThemePalette};
+
+    let input =
+        "\x1b[31mred \x1b[41mon red\x1b[0m \x1b[38;5;1m8-bit red\x1b[0m \x1b[93mbright\x1b[0m \
+        \x1b[38;5;208m8-bit\x1b[0m \x1b[2mfaint \x1b[31mred\x1b[0m";
+    let converter = Converter::new().palette(ThemePalette::solarized_dark());
+    insta::assert_snapshot!(converter.convert(input).unwrap(), @"<span style='color:var(--red,#dc322f)'>red <span style='background:var(--red,#dc322f)'>on red</span></span> <span style='color:#dc322f'>8-bit red</span> <span style='color:var(--bright-yellow,#657b83)'>bright</span> <span style='color:#ff8700'>8-bit</span> <span style='opacity:0.67'>faint <span style='color:var(--red,#dc322f)'>red</span></span>");
+
+    let literal = converter.clone().skip_css_vars(true);
+    insta::assert_snapshot!(literal.convert(input).unwrap(), @"<span style='color:#dc322f'>red <span style='background:#dc322f'>on red</span></span> <span style='color:#dc322f'>8-bit red</span> <span style='color:#657b83'>bright</span> <span style='color:#ff8700'>8-bit</span> <span style='opacity:0.67'>faint <span style='color:#dc322f'>red</span></span>");
+
+    // faint text is blended with the foreground and background of the palette
+    let blended = literal.faint_blend(Some(FaintBlend::new([255; 3], [0; 3])));
+    insta::assert_snapshot!(blended.convert(input).unwrap(), @"<span style='color:#dc322f'>red <span style='background:#dc322f'>on red</span></span> <span style='color:#dc322f'>8-bit red</span> <span style='color:#657b83'>bright</span> <span style='color:#ff8700'>8-bit</span> <span style='color:#587176'>faint <span style='color:#933031'>red</span></span>");
+
+    // the default palette has the same colors as no palette
+    let default = Converter::new().skip_css_vars(true);
+    let palette = default.clone().palette(ThemePalette::default_dark());
+    for code in (30..38).chain(90..98) {
+        let input = format!("\x1b[{code}mtext");
+        let color = |converter: &Converter| {
+            let html = converter.convert(&input).unwrap();
+            let color = &html[html.find('#').unwrap() + 1..html.find("'>").unwrap()];
+            let long: String = color.chars().flat_map(|c| [c; 2]).collect();
+            if color.len() == 3 {
+                long
+            } else {
+                color.to_string()
+            }
+        };
+        assert_eq!(color(&default), color(&palette));
+    }
+}
+
+#[test]
+fn adapt_to_theme() {
+    use ansi_to_html::{Converter, ThemePalette};
+
+    let themes = [ThemePalette::default_light(), ThemePalette::default_dark()];
+    let converters = themes.into_iter().flat_map(|theme| {
+        let converter = Converter::new().skip_css_vars(true).palette(theme);
+        [converter.clone(), converter.adapt_to_theme(true)]
+    });
+    let converters: Vec<Converter> = converters.collect();
+    let codes = (30..38).chain(90..98).map(|code| code.to_string());
+    let eight_bit = [17, 28, 208, 226, 232, 244, 250, 231].map(|code| format!("38;5;{code}"));
+    let rgb = ["38;2;255;255;0".to_string(), "38;2;0;0;128".to_string()];
+    // light and dark theme, each without and with adaptation: the 16 colors
+    // are taken from the palette, the others are adapted
+    let table: Vec<String> = (codes.chain(eight_bit).chain(rgb))
+        .map(|code| {
+            let colors = converters.iter().map(|converter| {
+                let html = converter.convert(&format!("\x1b[{code}mtext")).unwrap();
+                html[html.find(':').unwrap() + 1..html.find("'>").unwrap()].to_string()
+            });
+            let colors: Vec<String> = colors.collect();
+            format!("{code:>14}: {}", colors.join(" "))
+        })
+        .collect();
+    insta::assert_snapshot!(table.join("\n"), @"
+                30: #000000 #000000 #000000 #000000
+                31: #aa0000 #aa0000 #aa0000 #aa0000
+                32: #00aa00 #00aa00 #00aa00 #00aa00
+                33: #aa6600 #aa6600 #aa6600 #aa6600
+                34: #0000aa #0000aa #0000aa #0000aa
+                35: #aa00aa #aa00aa #aa00aa #aa00aa
+                36: #00aaaa #00aaaa #00aaaa #00aaaa
+                37: #aaaaaa #aaaaaa #aaaaaa #aaaaaa
+                90: #555555 #555555 #555555 #555555
+                91: #ff5555 #ff5555 #ff5555 #ff5555
+                92: #00aa00 #00aa00 #55ff55 #55ff55
+                93: #aa8800 #aa8800 #ffff55 #ffff55
+                94: #5555ff #5555ff #5555ff #5555ff
+                95: #ff55ff #ff55ff #ff55ff #ff55ff
+                96: #009999 #009999 #55ffff #55ffff
+                97: #777777 #777777 #ffffff #ffffff
+           38;5;17: #00005f #00005f #00005f #7070a5
+           38;5;28: #008700 #008700 #008700 #088b08
+          38;5;208: #ff8700 #af5d00 #ff8700 #ff8700
+          38;5;226: #ff0 #787800 #ff0 #ff0
+          38;5;232: #080808 #080808 #080808 #7c7c7c
+          38;5;244: #808080 #747474 #808080 #808080
+          38;5;250: #bcbcbc #767676 #bcbcbc #bcbcbc
+          38;5;231: #fff #707070 #fff #fff
+    38;2;255;255;0: #ffff00 #787800 #ffff00 #ffff00
+      38;2;0;0;128: #000080 #000080 #000080 #7070b8
+    ");
+
+    // background colors aren't changed
+    let input = "\x1b[48;5;226mon yellow\x1b[0m \x1b[2;38;5;226mfaint";
+    insta::assert_snapshot!(converters[1].convert(input).unwrap(), @"<span style='background:#ff0'>on yellow</span> <span style='opacity:0.67'><span style='color:#787800'>faint</span></span>");
+
+    let dual = Converter::new()
+        .skip_css_vars(true)
+        .dual_theme(ThemePalette::default_light(), ThemePalette::default_dark())
+        .adapt_to_theme(true);
+    insta::assert_snapshot!(dual.convert("\x1b[38;5;226mA\x1b[38;5;17mB\x1b[38;5;208mC").unwrap(), @"<span style='color:light-dark(#787800,#ff0)'>A</span><span style='color:light-dark(#00005f,#7070a5)'>B</span><span style='color:light-dark(#af5d00,#ff8700)'>C</span>");
+}
+
+#[test]
+fn elide_default_colors() {
+    use ansi_to_html::{Converter, FaintBlend, ThemePalette};
+
+    // `ls --color` style output: white for normal text, which is also the default text color
+    let converter = Converter::new().elide_default_colors(true);
+    let input = "\x1b[37mfile\x1b[0m \x1b[1;34mdir\x1b[0m \x1b[37mfile\x1b[0m";
+    insta::assert_snapshot!(Converter::new().convert(input).unwrap(), @"<span style='color:var(--white,#aaa)'>file</span> <b><span style='color:var(--blue,#00a)'>dir</span></b> <span style='color:var(--white,#aaa)'>file</span>");
+    insta::assert_snapshot!(converter.convert(input).unwrap(), @"file <b><span style='color:var(--blue,#00a)'>dir</span></b> file");
+
+    // any color that looks like the default is elided, and black backgrounds too
+    let input = "\x1b[38;5;7mA\x1b[38;2;170;170;170mB\x1b[40mC\x1b[48;5;16mD\x1b[38;5;15mE\x1b[0m";
+    insta::assert_snapshot!(converter.convert(input).unwrap(), @"ABCD<span style='color:#fff'>E</span>");
+    insta::assert_snapshot!(converter.clone().flat_spans(true).convert("\x1b[1;37;40mbold").unwrap(), @"<span style='font-weight:bold'>bold</span>");
+
+    // the default colors of the theme; with two themes, only colors that are the default in both
+    let white = ThemePalette::new([0xff; 3], [0; 3], ThemePalette::default_dark().colors);
+    let input = "\x1b[38;5;15mA\x1b[38;2;255;255;255mB\x1b[97mC\x1b[40mD";
+    let palette = converter.clone().palette(white);
+    insta::assert_snapshot!(palette.convert(input).unwrap(), @"ABCD");
+    let dual = converter
+        .clone()
+        .dual_theme(ThemePalette::default_light(), white);
+    insta::assert_snapshot!(dual.convert(input).unwrap(), @"<span style='color:light-dark(#777777,#ffffff)'>A</span><span style='color:#ffffff'>B</span><span style='color:var(--bright-white,#ffffff)'>C<span style='background:var(--black,#000000)'>D</span></span>");
+    let dark_only = dual.skip_light_dark(true);
+    assert_eq!(dark_only.convert(input).unwrap(), "ABCD");
+
+    // adapted colors are compared, and faint text is blended with the default text color
+    let adapted = converter
+        .clone()
+        .palette(ThemePalette::default_light())
+        .adapt_to_theme(true);
+    insta::assert_snapshot!(adapted.convert("\x1b[38;5;16mA\x1b[38;5;226mB").unwrap(), @"A<span style='color:#787800'>B</span>");
+    let blend = converter
+        .clone()
+        .faint_blend(Some(FaintBlend::new([0xff; 3], [0x20; 3])));
+    assert_eq!(
+        blend
+            .convert("\x1b[2;97mfaint\x1b[38;2;32;32;32;48;2;32;32;32m")
+            .unwrap(),
+        blend.convert("\x1b[2mfaint").unwrap(),
+    );
+
+    // reverse video swaps in the same default colors, so elided colors look the same
+    assert_eq!(
+        converter.convert("\x1b[7;37;40mA").unwrap(),
+        converter.convert("\x1b[7mA").unwrap(),
+    );
+    insta::assert_snapshot!(converter.convert("\x1b[7;37;40mA").unwrap(), @"<span style='color:#000000'><span style='background:#aaaaaa'>A</span></span>");
+    // and the configured default colors are elided instead of the ones of the theme
+    let custom = converter
+        .clone()
+        .default_colors([0xab, 0xb2, 0xbf], [0x1e, 0x21, 0x27]);
+    insta::assert_snapshot!(custom.convert("\x1b[37mA\x1b[38;2;171;178;191;48;2;30;33;39mB").unwrap(), @"<span style='color:var(--white,#aaa)'>A</span>B");
+
+    // streams and sessions treat the colors the same
+    let input = "\x1b[37mfoo\x1b[31mbar\x1b[37mbaz\x1b[0m";
+    let mut stream = converter.begin();
+    let mut html = String::new();
+    for chunk in ["\x1b[37mfoo\x1b[3", "1mbar\x1b[37mbaz", "\x1b[0m"] {
+        html += &stream.feed(chunk).unwrap();
+    }
+    html += &stream.finish();
+    assert_eq!(html, converter.convert(input).unwrap());
+    assert_eq!(
+        converter.session().convert(input).unwrap(),
+        converter.convert(input).unwrap()
+    );
+}
+
+#[test]
+fn converter_eq() {
+    use ansi_to_html::{Converter, Transformed};
+
+    let converter = Converter::new().skip_escape(true).render_links(true);
+    assert_eq!(converter, converter.clone());
+    assert_eq!(
+        converter,
+        Converter::new().render_links(true).skip_escape(true)
+    );
+    assert_ne!(converter, Converter::new());
+    assert_eq!(Converter::new(), Converter::default());
+
+    // closures are only equal to themselves
+    let transformed = converter.text_transform(|_| Transformed::Unchanged);
+    assert_eq!(transformed, transformed.clone());
+    assert_ne!(
+        transformed,
+        transformed
+            .clone()
+            .text_transform(|_| Transformed::Unchanged)
+    );
+
+    // the palette computed by `build` doesn't make a difference
+    let built = transformed.clone().build().unwrap();
+    assert_eq!(built.converter(), &transformed);
+}
+
+#[test]
+fn getters() {
+    use ansi_to_html::{Converter, Newlines, OutputCompat, Quote, TagStyle, ThemePalette};
+
+    let converter = Converter::new();
+    assert!(!converter.is_skip_escape());
+    assert!(converter.is_attach_combining());
+    assert!(!converter.has_text_transform());
+    assert_eq!(converter.get_four_bit_var_prefix(), None);
+    assert_eq!(converter.get_allowed_tags(), [] as [String; 0]);
+    assert_eq!(converter.get_palette(), None);
+    assert_eq!(converter.get_default_colors(), None);
+    assert_eq!(converter.get_max_sequence_len(), 4096);
+    assert_eq!(converter.get_newline_normalization(), Newlines::Keep);
+    assert_eq!(converter.get_tag_style(), TagStyle::Presentational);
+    assert_eq!(converter.get_attribute_quotes(), Quote::Single);
+    assert_eq!(converter.get_output_compat(), OutputCompat::Current);
+
+    let (light, dark) = (ThemePalette::default_light(), ThemePalette::default_dark());
+    let converter = converter
+        .skip_escape(true)
+        .four_bit_var_prefix(Some("term-".into()))
+        .allowed_tags(&["b"])
+        .palette(ThemePalette::solarized_dark())
+        .dual_theme(light, dark)
+        .default_colors([1; 3], [2; 3])
+        .eight_bit_palette(Some([[3; 3]; 256]))
+        .text_transform(|_| ansi_to_html::Transformed::Unchanged)
+        .newline_normalization(Newlines::Lf);
+    assert!(converter.is_skip_escape());
+    assert!(converter.has_text_transform());
+    assert_eq!(converter.get_four_bit_var_prefix(), Some("term-"));
+    assert_eq!(converter.get_allowed_tags(), ["b"]);
+    assert_eq!(
+        converter.get_palette(),
+        Some(&ThemePalette::solarized_dark())
+    );
+    assert_eq!(converter.get_dual_theme(), Some((&light, &dark)));
+    assert_eq!(converter.get_default_colors(), Some(([1; 3], [2; 3])));
+    assert_eq!(converter.get_eight_bit_palette(), Some(&[[3; 3]; 256]));
+    assert_eq!(converter.get_newline_normalization(), Newlines::Lf);
+
+    // the getters are available after building
+    let built = Converter::new().skip_escape(true).build().unwrap();
+    assert!(built.converter().is_skip_escape());
+}
+
+#[test]
+fn build() {
+    use ansi_to_html::{
+        Converter, FaintBlend, LineWrapper, StyleState, TagStyle, ThemePalette, UrlSchemes,
+        VarNames,
+    };
+
+    let names = || VarNames::new(|name| format!("t-{name}")).unwrap();
+    let prefix = || Some("term-".to_string());
+    let errors = [
+        Converter::new().four_bit_var_prefix(Some("x)".into())),
+        Converter::new().four_bit_var_prefix(Some("a'b".into())),
+        Converter::new()
+            .four_bit_var_prefix(prefix())
+            .var_names(Some(names())),
+        Converter::new()
+            .four_bit_var_prefix(prefix())
+            .skip_css_vars(true),
+        Converter::new()
+            .var_names(Some(names()))
+            .skip_css_vars(true),
+        Converter::new()
+            .four_bit_var_prefix(prefix())
+            .four_bit_classes(prefix()),
+        Converter::new()
+            .var_names(Some(names()))
+            .four_bit_classes(prefix()),
+        Converter::new().four_bit_classes(Some("a b".into())),
+        Converter::new()
+            .four_bit_classes(prefix())
+            .skip_css_vars(true),
+        Converter::new().sanitize(true),
+        Converter::new().skip_escape(true).allowed_tags(&["b"]),
+        Converter::new().link_schemes(UrlSchemes::new(["https"])),
+        Converter::new().skip_light_dark(true),
+        Converter::new().adapt_to_theme(true),
+        Converter::new()
+            .palette(ThemePalette::solarized_light())
+            .dual_theme(light_theme(), dark_theme()),
+        Converter::new()
+            .flat_spans(true)
+            .tag_style(TagStyle::Semantic),
+        Converter::new()
+            .skip_escape(true)
+            .detect_double_escape(true),
+        Converter::new().line_safe(true).pretty(true),
+        Converter::new()
+            .line_wrapper(LineWrapper::new("div", None, true).ok())
+            .pretty(true),
+    ]
+    .map(|converter| converter.build().unwrap_err().to_string());
+    insta::assert_debug_snapshot!(errors, @r#"
+    [
+        "Invalid CSS variable prefix: \"x)\"",
+        "Invalid CSS variable prefix: \"a'b\"",
+        "The `four_bit_var_prefix` is ignored with `var_names`, `skip_css_vars` or `four_bit_classes`",
+        "The `four_bit_var_prefix` is ignored with `var_names`, `skip_css_vars` or `four_bit_classes`",
+        "The `var_names` are ignored with `skip_css_vars` or `four_bit_classes`",
+        "The `four_bit_var_prefix` is ignored with `var_names`, `skip_css_vars` or `four_bit_classes`",
+        "The `var_names` are ignored with `skip_css_vars` or `four_bit_classes`",
+        "Invalid CSS class prefix: \"a b\"",
+        "The `skip_css_vars` option is ignored with `four_bit_classes`",
+        "The `sanitize` option has no effect without `skip_escape`",
+        "The `allowed_tags` have no effect without `sanitize`",
+        "The `link_schemes` have no effect without `render_links`",
+        "The `skip_light_dark` option has no effect without `dual_theme`",
+        "The `adapt_to_theme` option has no effect without `palette` or `dual_theme`",
+        "The `palette` is ignored with `dual_theme`",
+        "The `tag_style` is ignored with `flat_spans`",
+        "The `detect_double_escape` option has no effect with `skip_escape`",
+        "The `line_safe` option can't be combined with `pretty`",
+        "The `line_wrapper` can't be combined with `pretty`",
+    ]
+    "#);
+
+    let converters = [
+        Converter::new(),
+        Converter::new().four_bit_var_prefix(prefix()),
+        Converter::new().four_bit_var_prefix(Some(String::new())),
+        Converter::new().var_names(Some(names())),
+        Converter::new().skip_css_vars(true),
+        Converter::new().four_bit_classes(prefix()),
+        Converter::new().grayscale_levels(Some(4)),
+        Converter::new().eight_bit_palette(Some([[1, 2, 3]; 256])),
+        Converter::new().faint_blend(Some(FaintBlend::new([255; 3], [0; 3]))),
+        Converter::new()
+            .skip_escape(true)
+            .sanitize(true)
+            .allowed_tags(&["b"]),
+        Converter::new()
+            .render_links(true)
+            .link_schemes(UrlSchemes::new(["https"])),
+        Converter::new().dual_theme(light_theme(), dark_theme()),
+        Converter::new()
+            .skip_css_vars(true)
+            .dual_theme(light_theme(), dark_theme()),
+        Converter::new().palette(ThemePalette::solarized_dark()),
+        Converter::new()
+            .flat_spans(true)
+            .faint_blend(Some(FaintBlend::new([255; 3], [0; 3]))),
+    ];
+    let mut input = String::new();
+    for code in (30..38).chain(40..48).chain(90..98).chain(100..108) {
+        input += &format!("\x1b[{code}m{code} \x1b[2mfaint\x1b[22m\n");
+    }
+    input += "\x1b[38;5;240;48;2;1;2;3m8-bit and rgb\x1b[0m <b>&amp;</b>\n";
+
+    // the colors that are computed once are the same as the ones computed every time
+    for converter in converters {
+        let prepared = converter.clone().build().unwrap();
+        assert_eq!(
+            prepared.convert(&input).unwrap(),
+            converter.convert(&input).unwrap()
+        );
+
+        let (mut a, mut b) = (String::new(), String::new());
+        let (mut state_a, mut state_b) = (StyleState::new(), StyleState::new());
+        for line in input.split_inclusive('\n') {
+            prepared.convert_append(&mut state_a, line, &mut a).unwrap();
+            converter
+                .convert_append(&mut state_b, line, &mut b)
+                .unwrap();
+        }
+        assert_eq!(a, b);
+
+        let mut stream = prepared.begin();
+        let streamed = stream.feed(&input).unwrap() + &stream.finish();
+        assert_eq!(streamed, converter.begin().feed(&input).unwrap());
+    }
+
+    let prepared = Converter::new()
+        .four_bit_var_prefix(prefix())
+        .build()
+        .unwrap();
+    let html = prepared.convert("\x1b[31mred \x1b[101mbright bg").unwrap();
+    insta::assert_snapshot!(html, @"<span style='color:var(--term-red,#a00)'>red <span style='background:var(--term-bright-red,#f55)'>bright bg</span></span>");
+
+    // the options of a built converter don't keep its colors when they are changed
+    let options = prepared.converter().clone();
+    let classes = options.clone().four_bit_classes(Some("t-".into()));
+    insta::assert_snapshot!(classes.convert("\x1b[31mred").unwrap(), @"<span class='t-red'>red</span>");
+    let solarized = options.palette(ThemePalette::solarized_dark());
+    insta::assert_snapshot!(solarized.convert("\x1b[31mred").unwrap(), @"<span style='color:var(--term-red,#dc322f)'>red</span>");
+}
+
+#[test]
+fn to_segments() {
+    use ansi_to_html::{Converter, Newlines, StyledSegment};
+
+    /// Shows the text and the attributes that are set
+    fn show(segments: Vec<StyledSegment>) -> Vec<String> {
+        let show = |s: StyledSegment| {
+            let flags = [
+                (s.bold, "bold"),
+                (s.faint, "faint"),
+                (s.italic, "italic"),
+                (s.underline, "underline"),
+                (s.crossed_out, "crossed_out"),
+            ];
+            let mut attrs: Vec<String> = flags
+                .into_iter()
+                .filter(|&(on, _)| on)
+                .map(|(_, name)| name.to_string())
+                .collect();
+            attrs.extend(s.foreground.map(|c| format!("fg {c:?}")));
+            attrs.extend(s.background.map(|c| format!("bg {c:?}")));
+            attrs.extend(s.link.map(|link| format!("link {link}")));
+            format!("{:?} {}", s.text, attrs.join(", "))
+        };
+        segments.into_iter().map(show).collect()
+    }
+
+    let converter = Converter::new();
+    let input = "plain <&> \x1b[1;2mbold faint\x1b[22;3;4;9m styles\x1b[0m\n\
+        \x1b[31;48;5;17mcolors\x1b[1m\x1b[1m merged\x1b[0m\x1b[K\x1b[38;2;1;2;3mrgb\x1b[m";
+    insta::assert_debug_snapshot!(show(converter.to_segments(input).unwrap()), @r#"
+    [
+        "\"plain <&> \" ",
+        "\"bold faint\" bold, faint",
+        "\" styles\" italic, underline, crossed_out",
+        "\"\\n\" ",
+        "\"colors\" fg Basic(1), bg Indexed(17)",
+        "\" merged\" bold, fg Basic(1), bg Indexed(17)",
+        "\"rgb\" fg Rgb(1, 2, 3)",
+    ]
+    "#);
+
+    // the text is the same as without escape sequences
+    let text: String = ansi_to_html::text_fragments(input).collect();
+    let segments = converter.to_segments(input).unwrap();
+    assert_eq!(
+        segments.iter().map(|s| s.text.as_str()).collect::<String>(),
+        text
+    );
+    assert!(segments.iter().all(|s| !s.text.is_empty()));
+
+    let converter = Converter::new()
+        .render_links(true)
+        .newline_normalization(Newlines::Lf)
+        .max_sequence_len(20);
+    let input = "e\x1b[31m\u{301}x\r\n\x1b]8;;https://a.b\x07link\x1b]8;;\x07 \x1b[1;1;1;1;1;1;1;1;1;1mlong";
+    insta::assert_debug_snapshot!(show(converter.to_segments(input).unwrap()), @r#"
+    [
+        "\"e\\u{301}\" ",
+        "\"x\\n\" fg Basic(1)",
+        "\"link\" fg Basic(1), link https://a.b",
+        "\" \\u{1b}[1;1;1;1;1;1;1;1;1;1mlong\" fg Basic(1)",
+    ]
+    "#);
+    let converter = converter.attach_combining(false);
+    insta::assert_debug_snapshot!(show(converter.to_segments("e\x1b[31m\u{301}x").unwrap()), @r#"
+    [
+        "\"e\" ",
+        "\"\\u{301}x\" fg Basic(1)",
+    ]
+    "#);
+
+    let error = Converter::new()
+        .to_segments("ok \x1b[1;38;7mbroken")
+        .unwrap_err();
+    assert_eq!(error.span(), Some(3..12));
+    let lossy = Converter::new().lossy(true);
+    insta::assert_debug_snapshot!(show(lossy.to_segments("ok \x1b[1;38;7mbroken").unwrap()), @r#"
+    [
+        "\"ok \" ",
+        "\"broken\" bold",
+    ]
+    "#);
+    assert_eq!(Converter::new().to_segments("").unwrap(), []);
+}
+
+#[test]
+fn fingerprint() {
+    use ansi_to_html::Converter;
+
+    let converter = Converter::new();
+    // these render the same, see the `lossy` test
+    let lossy = Converter::new().lossy(true);
+    let a = "\x1b[31mred \x1b[38;2;10;20mstill red\x1b[1;48;2;1m bold";
+    let b = "\x1b[31mred still red\x1b[1m bold";
+    assert_eq!(lossy.convert(a).unwrap(), converter.convert(b).unwrap());
+    assert_eq!(
+        lossy.fingerprint(a).unwrap(),
+        converter.fingerprint(b).unwrap()
+    );
+
+    // redundant codes don't change the fingerprint
+    let fingerprint = converter.fingerprint(b).unwrap();
+    let redundant = [
+        "\x1b[0m\x1b[31;31mred \x1b[22mstill red\x1b[49;1m\x1b[1m bold\x1b[0m",
+        "\x1b[31mred \x1b[1m\x1b[22mstill red\x1b[1;31m bold",
+        "\x1b[31mred \x1b[32m\x1b[31mstill \x1b[Kred\x1b[1m bold",
+    ];
+    for input in redundant {
+        assert_eq!(
+            converter.fingerprint(input).unwrap(),
+            fingerprint,
+            "{input:?}"
+        );
+    }
+
+    // the text or the styling is different
+    let different = [
+        "\x1b[32mred still red\x1b[1m bold",
+        "\x1b[91mred still red\x1b[1m bold",
+        "\x1b[38;5;1mred still red\x1b[1m bold",
+        "\x1b[31mred still red\x1b[3m bold",
+        "\x1b[31mred still red\x1b[1;7m bold",
+        "\x1b[31mred still red \x1b[1mbold",
+        "\x1b[31mred still red\x1b[1m bold!",
+        "red still red bold",
+    ];
+    for input in different {
+        assert_ne!(
+            converter.fingerprint(input).unwrap(),
+            fingerprint,
+            "{input:?}"
+        );
+    }
+
+    // reverse video is part of the styling
+    assert_ne!(
+        converter.fingerprint("\x1b[7mfoo").unwrap(),
+        converter.fingerprint("foo").unwrap()
+    );
+
+    // the hash is stable
+    insta::assert_snapshot!(fingerprint, @"4319572583147807435");
+    assert!(converter.fingerprint("\x1b[38;7m").is_err());
+}
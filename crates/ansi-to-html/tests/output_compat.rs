@@ -0,0 +1,78 @@
+//! The output with `OutputCompat::V0`, which must stay the same byte for byte
+//! until `OutputCompat::V0` is removed. If an entry of the snapshot changes,
+//! the change of the output must be skipped with `OutputCompat::V0`; only
+//! entries for new inputs or options may be accepted.
+//!
+//! The snapshot is taken with the `optimize` feature, which changes the output.
+#![cfg(feature = "optimize")]
+
+use std::{fmt::Write, fs, path::Path};
+
+use ansi_to_html::{Converter, OutputCompat, Quote, TagStyle};
+
+/// Inputs from the documentation, in addition to the fuzzing corpora
+static DOCUMENTED: &[&str] = &[
+    "<h1> \x1b[1mHello \x1b[31mworld! </h1>",
+    "\x1b[1;91mbold\x1b[0m \x1b[91;1mbold\x1b[0m",
+    "\x1b[2;31mfaint red",
+    "\x1b[1;4;31mwarning\x1b[24m:\x1b[0m ok",
+    "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\ \x1b[5mblink\x1b[8m concealed",
+    "\x1b[38;5;200;48;2;1;2;3mcolors\x1b[39m \x1b[49m\x1b[1;2;3;9;53m lines \n",
+];
+
+fn converters() -> Vec<(&'static str, Converter)> {
+    let v0 = Converter::new().output_compat(OutputCompat::V0);
+    vec![
+        ("default", v0.clone()),
+        ("skip_escape", v0.clone().skip_escape(true)),
+        ("skip_optimize", v0.clone().skip_optimize(true)),
+        (
+            "var_prefix",
+            v0.clone().four_bit_var_prefix(Some("t-".into())),
+        ),
+        ("classes", v0.clone().four_bit_classes(Some("t-".into()))),
+        (
+            "links_blink",
+            v0.clone().render_links(true).render_blink(true),
+        ),
+        ("semantic", v0.clone().tag_style(TagStyle::Semantic)),
+        ("flat", v0.clone().flat_spans(true)),
+        ("double_quotes", v0.clone().attribute_quotes(Quote::Double)),
+        ("pretty", v0.pretty(true)),
+    ]
+}
+
+fn corpus() -> Vec<(String, String)> {
+    let mut inputs: Vec<_> = (DOCUMENTED.iter().enumerate())
+        .map(|(i, input)| (format!("documented/{i}"), input.to_string()))
+        .collect();
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+    let mut files = Vec::new();
+    for dir in fs::read_dir(&corpus).unwrap() {
+        for file in fs::read_dir(dir.unwrap().path()).unwrap() {
+            files.push(file.unwrap().path());
+        }
+    }
+    files.sort();
+    for file in files {
+        if let Ok(input) = fs::read_to_string(&file) {
+            let name = file.strip_prefix(&corpus).unwrap();
+            inputs.push((name.display().to_string(), input));
+        }
+    }
+    inputs
+}
+
+#[test]
+fn v0_output_is_stable() {
+    let converters = converters();
+    let mut out = String::new();
+    for (name, input) in corpus() {
+        writeln!(out, "=== {name}").unwrap();
+        for (option, converter) in &converters {
+            let html = converter.convert(&input).map_err(|e| e.to_string());
+            writeln!(out, "{option}: {html:?}").unwrap();
+        }
+    }
+    insta::assert_snapshot!("v0_corpus", out);
+}
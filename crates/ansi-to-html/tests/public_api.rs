@@ -0,0 +1,275 @@
+//! A curated list of the public API. This fails to compile when an item is
+//! removed or its signature changes, which would break downstream crates.
+//!
+//! When adding public items, add them here as well.
+
+use ansi_to_html::{
+    AnsiFragment, AnsiParser, ConfigError, Converter, ConverterStream, Error, Esc, EscAttr, EscUrl,
+    FaintBlend, LineWrapper, Newlines, OutputCompat, Piece, PreparedConverter, Quote, SgrCode,
+    SgrCodes, SgrColor, StyleState, StyledSegment, TagStyle, TextFragments, ThemePalette,
+    Transformed, UrlSchemes, VarNames,
+};
+
+#[test]
+fn functions() {
+    let _: fn(&str) -> Result<String, Error> = ansi_to_html::convert;
+    let _: fn(&str) -> TextFragments<'_> = ansi_to_html::text_fragments;
+    assert_traits::<TextFragments<'static>>();
+    let _: fn(&str) -> String = ansi_to_html::strip_ansi;
+    let _: fn(&str, &mut String) -> std::fmt::Result = ansi_to_html::strip_ansi_to_writer;
+    let _: fn(&str) -> bool = ansi_to_html::looks_like_html;
+    let _: fn(&str) -> usize = ansi_to_html::visible_width;
+    #[allow(deprecated)]
+    let _: fn(&str, &Converter) -> Result<String, Error> = ansi_to_html::convert_with_opts;
+}
+
+#[test]
+fn converter() {
+    let _: fn() -> Converter = Converter::new;
+    let _: fn(Converter, bool) -> Converter = Converter::skip_escape;
+    let _: fn(Converter, bool) -> Converter = Converter::skip_optimize;
+    let _: fn(Converter, Option<String>) -> Converter = Converter::four_bit_var_prefix;
+    let _: fn(Converter, Option<String>) -> Converter = Converter::four_bit_classes;
+    let _: fn(Converter, bool) -> Converter = Converter::skip_css_vars;
+    let _: fn(Converter, Option<FaintBlend>) -> Converter = Converter::faint_blend;
+    let _: fn(Converter, ThemePalette) -> Converter = Converter::palette;
+    let _: fn(Converter, ThemePalette, ThemePalette) -> Converter = Converter::dual_theme;
+    let _: fn(Converter, bool) -> Converter = Converter::skip_light_dark;
+    let _: fn(Converter, bool) -> Converter = Converter::adapt_to_theme;
+    let _: fn(Converter, bool) -> Converter = Converter::elide_default_colors;
+    let _: fn(Converter, [u8; 3], [u8; 3]) -> Converter = Converter::default_colors;
+    let _: fn(Converter, Option<u8>) -> Converter = Converter::grayscale_levels;
+    let _: fn(Converter, Option<[[u8; 3]; 256]>) -> Converter = Converter::eight_bit_palette;
+    let _: fn(Converter, usize) -> Converter = Converter::max_sequence_len;
+    let _: fn(Converter, bool) -> Converter = Converter::pretty;
+    let _: fn(Converter, Option<VarNames>) -> Converter = Converter::var_names;
+    let _: fn(Converter, bool) -> Converter = Converter::sanitize;
+    let _: fn(Converter, &[&str]) -> Converter = Converter::allowed_tags;
+    type TransformFn = fn(&str) -> Transformed;
+    let _: fn(Converter, TransformFn) -> Converter = Converter::text_transform;
+    let _: fn(Converter, bool) -> Converter = Converter::bidi_isolate;
+    let _: fn(Converter, bool) -> Converter = Converter::normalize_trailing_whitespace;
+    let _: fn(Converter, bool) -> Converter = Converter::line_safe;
+    let _: fn(Converter, Option<LineWrapper>) -> Converter = Converter::line_wrapper;
+    let _: fn(Converter, bool) -> Converter = Converter::render_links;
+    let _: fn(Converter, UrlSchemes) -> Converter = Converter::link_schemes;
+    let _: fn(Converter, Newlines) -> Converter = Converter::newline_normalization;
+    let _: fn(Converter, bool) -> Converter = Converter::carriage_return_overwrites;
+    let _: fn(Converter, bool) -> Converter = Converter::emulate_cursor_movement;
+    let _: fn(Converter, bool) -> Converter = Converter::lossy;
+    let _: fn(Converter, bool) -> Converter = Converter::attach_combining;
+    let _: fn(Converter, bool) -> Converter = Converter::render_blink;
+    let _: fn(&Converter) -> String = Converter::blink_css;
+    let _: fn(Converter, bool) -> Converter = Converter::redact_concealed;
+    let _: fn(Converter, TagStyle) -> Converter = Converter::tag_style;
+    let _: fn(Converter, bool) -> Converter = Converter::flat_spans;
+    let _: fn(Converter, Quote) -> Converter = Converter::attribute_quotes;
+    let _: fn(Converter, OutputCompat) -> Converter = Converter::output_compat;
+    let _: fn(Converter, bool) -> Converter = Converter::detect_double_escape;
+
+    let _: fn(&Converter, &str) -> Result<String, Error> = Converter::convert;
+    let _: fn(&Converter, &[u8]) -> Result<String, Error> = Converter::convert_bytes;
+    let _: for<'a> fn(&Converter, &'a str) -> Result<std::borrow::Cow<'a, str>, Error> =
+        Converter::convert_cow;
+    let _: fn(&Converter, &str, &mut String) -> Result<(), Error> = Converter::convert_to_writer;
+    let _: fn(&Converter, &str, &mut String) -> Result<(), Error> = Converter::convert_line;
+    let _: fn(&Converter, &str, usize) -> Result<Vec<String>, Error> = Converter::convert_chunked;
+    let _: fn(&Converter, &str) -> Result<Vec<String>, Error> = Converter::convert_lines;
+    let _: fn(&Converter, &mut StyleState, &str, &mut String) -> Result<(), Error> =
+        Converter::convert_append;
+    let _: fn(&Converter) -> ConverterStream = Converter::begin;
+    let _: fn(&Converter, &str) -> Result<Vec<StyledSegment>, Error> = Converter::to_segments;
+    let _: fn(&Converter, &str) -> Result<u64, Error> = Converter::fingerprint;
+    let _: fn(Converter) -> Result<PreparedConverter, ConfigError> = Converter::build;
+    let _: fn(&mut ConverterStream, &str) -> Result<String, Error> = ConverterStream::feed;
+    let _: fn(ConverterStream) -> String = ConverterStream::finish;
+    assert_traits::<ConverterStream>();
+    let _: fn(&Converter) -> bool = Converter::is_skip_escape;
+    let _: fn(&Converter) -> bool = Converter::is_skip_optimize;
+    let _: fn(&Converter) -> Option<&str> = Converter::get_four_bit_var_prefix;
+    let _: fn(&Converter) -> Option<&str> = Converter::get_four_bit_classes;
+    let _: fn(&Converter) -> bool = Converter::is_sanitize;
+    let _: fn(&Converter) -> &[String] = Converter::get_allowed_tags;
+    let _: fn(&Converter) -> bool = Converter::is_skip_css_vars;
+    let _: fn(&Converter) -> Option<FaintBlend> = Converter::get_faint_blend;
+    let _: fn(&Converter) -> Option<&ThemePalette> = Converter::get_palette;
+    let _: fn(&Converter) -> Option<(&ThemePalette, &ThemePalette)> = Converter::get_dual_theme;
+    let _: fn(&Converter) -> bool = Converter::is_skip_light_dark;
+    let _: fn(&Converter) -> bool = Converter::is_adapt_to_theme;
+    let _: fn(&Converter) -> bool = Converter::is_elide_default_colors;
+    type DefaultColorsFn = fn(&Converter) -> Option<([u8; 3], [u8; 3])>;
+    let _: DefaultColorsFn = Converter::get_default_colors;
+    let _: fn(&Converter) -> Option<u8> = Converter::get_grayscale_levels;
+    let _: fn(&Converter) -> Option<&[[u8; 3]; 256]> = Converter::get_eight_bit_palette;
+    let _: fn(&Converter) -> usize = Converter::get_max_sequence_len;
+    let _: fn(&Converter) -> bool = Converter::is_pretty;
+    let _: fn(&Converter) -> Option<&VarNames> = Converter::get_var_names;
+    let _: fn(&Converter) -> bool = Converter::has_text_transform;
+    let _: fn(&Converter) -> bool = Converter::is_bidi_isolate;
+    let _: fn(&Converter) -> bool = Converter::is_normalize_trailing_whitespace;
+    let _: fn(&Converter) -> bool = Converter::is_line_safe;
+    let _: fn(&Converter) -> Option<&LineWrapper> = Converter::get_line_wrapper;
+    let _: fn(&Converter) -> bool = Converter::is_render_links;
+    let _: fn(&Converter) -> &UrlSchemes = Converter::get_link_schemes;
+    let _: fn(&Converter) -> Newlines = Converter::get_newline_normalization;
+    let _: fn(&Converter) -> bool = Converter::is_carriage_return_overwrites;
+    let _: fn(&Converter) -> bool = Converter::is_emulate_cursor_movement;
+    let _: fn(&Converter) -> bool = Converter::is_lossy;
+    let _: fn(&Converter) -> bool = Converter::is_attach_combining;
+    let _: fn(&Converter) -> bool = Converter::is_render_blink;
+    let _: fn(&Converter) -> bool = Converter::is_redact_concealed;
+    let _: fn(&Converter) -> TagStyle = Converter::get_tag_style;
+    let _: fn(&Converter) -> bool = Converter::is_flat_spans;
+    let _: fn(&Converter) -> Quote = Converter::get_attribute_quotes;
+    let _: fn(&Converter) -> OutputCompat = Converter::get_output_compat;
+    let _: fn(&Converter) -> bool = Converter::is_detect_double_escape;
+
+    assert_traits::<Converter>();
+    assert_eq_traits::<Converter>();
+    let _ = Converter::default();
+}
+
+#[test]
+fn prepared_converter() {
+    let _: fn(&PreparedConverter, &str) -> Result<String, Error> = PreparedConverter::convert;
+    let _: fn(&PreparedConverter, &[u8]) -> Result<String, Error> =
+        PreparedConverter::convert_bytes;
+    let _: for<'a> fn(&PreparedConverter, &'a str) -> Result<std::borrow::Cow<'a, str>, Error> =
+        PreparedConverter::convert_cow;
+    let _: fn(&PreparedConverter, &str, &mut String) -> Result<(), Error> =
+        PreparedConverter::convert_to_writer;
+    let _: fn(&PreparedConverter, &str, &mut String) -> Result<(), Error> =
+        PreparedConverter::convert_line;
+    let _: fn(&PreparedConverter, &str, usize) -> Result<Vec<String>, Error> =
+        PreparedConverter::convert_chunked;
+    let _: fn(&PreparedConverter, &str) -> Result<Vec<String>, Error> =
+        PreparedConverter::convert_lines;
+    let _: fn(&PreparedConverter, &mut StyleState, &str, &mut String) -> Result<(), Error> =
+        PreparedConverter::convert_append;
+    let _: fn(&PreparedConverter) -> ConverterStream = PreparedConverter::begin;
+    let _: fn(&PreparedConverter, &str) -> Result<Vec<StyledSegment>, Error> =
+        PreparedConverter::to_segments;
+    let _: fn(&PreparedConverter, &str) -> Result<u64, Error> = PreparedConverter::fingerprint;
+    let _: fn(&PreparedConverter) -> &Converter = PreparedConverter::converter;
+    assert_traits::<PreparedConverter>();
+
+    let _: ConfigError = ConfigError::InvalidVarPrefix {
+        prefix: String::new(),
+    };
+    let _: ConfigError = ConfigError::InvalidClassPrefix {
+        prefix: String::new(),
+    };
+    let _: [ConfigError; 13] = [
+        ConfigError::IgnoredVarPrefix,
+        ConfigError::IgnoredVarNames,
+        ConfigError::IgnoredSkipCssVars,
+        ConfigError::SanitizeWithoutSkipEscape,
+        ConfigError::AllowedTagsWithoutSanitize,
+        ConfigError::LinkSchemesWithoutRenderLinks,
+        ConfigError::SkipLightDarkWithoutDualTheme,
+        ConfigError::AdaptWithoutTheme,
+        ConfigError::IgnoredPalette,
+        ConfigError::IgnoredTagStyle,
+        ConfigError::DetectDoubleEscapeWithSkipEscape,
+        ConfigError::LineSafeWithPretty,
+        ConfigError::LineWrapperWithPretty,
+    ];
+    fn error_traits<T: std::error::Error + Clone + Send + Sync + 'static>() {}
+    error_traits::<ConfigError>();
+}
+
+#[test]
+fn other_types() {
+    let _: fn() -> StyleState = StyleState::new;
+    let _: fn(&StyleState) -> bool = StyleState::is_default;
+    assert_traits::<StyleState>();
+    let _: StyleState = Default::default();
+
+    let _: fn([&'static str; 1]) -> UrlSchemes = UrlSchemes::new;
+    let _: fn(&UrlSchemes, &str) -> bool = UrlSchemes::allows;
+    assert_traits::<UrlSchemes>();
+    assert_traits::<Newlines>();
+    assert_traits::<TagStyle>();
+    assert_traits::<Quote>();
+    assert_traits::<OutputCompat>();
+    let _: Newlines = Default::default();
+    let _: TagStyle = Default::default();
+    let _: Quote = Default::default();
+    let _: OutputCompat = Default::default();
+    let _: UrlSchemes = Default::default();
+
+    type NameFn = fn(&str) -> String;
+    let _: fn(NameFn) -> Result<VarNames, Error> = VarNames::new;
+    let _: for<'a> fn(&'a VarNames, &str) -> Option<&'a str> = VarNames::get;
+    let _: fn(&VarNames) -> String = VarNames::stylesheet;
+    assert_traits::<VarNames>();
+
+    type NewWrapperFn = fn(&str, Option<&str>, bool) -> Result<LineWrapper, Error>;
+    let _: NewWrapperFn = LineWrapper::new;
+    let _: fn(&LineWrapper) -> &str = LineWrapper::tag;
+    let _: fn(&LineWrapper) -> Option<&str> = LineWrapper::class;
+    let _: fn(&LineWrapper) -> bool = LineWrapper::data_line;
+    assert_traits::<LineWrapper>();
+    assert_eq_traits::<LineWrapper>();
+
+    let _: fn([u8; 3], [u8; 3]) -> FaintBlend = FaintBlend::new;
+    let blend = FaintBlend::new([1, 2, 3], [4, 5, 6]);
+    let _: ([u8; 3], [u8; 3]) = (blend.foreground, blend.background);
+    assert_traits::<FaintBlend>();
+
+    type Colors = [[u8; 3]; 16];
+    let _: fn([u8; 3], [u8; 3], Colors) -> ThemePalette = ThemePalette::new;
+    let theme = ThemePalette::new([1, 2, 3], [4, 5, 6], [[7, 8, 9]; 16]);
+    let _: ([u8; 3], [u8; 3], Colors) = (theme.foreground, theme.background, theme.colors);
+    let _: [fn() -> ThemePalette; 4] = [
+        ThemePalette::default_dark,
+        ThemePalette::default_light,
+        ThemePalette::solarized_dark,
+        ThemePalette::solarized_light,
+    ];
+    assert_traits::<ThemePalette>();
+
+    let _: Transformed = Transformed::Unchanged;
+    let _: Transformed = Transformed::Text(String::new());
+    let _: Transformed = Transformed::Pieces(vec![Piece::Text(String::new())]);
+    let _: Piece = Piece::Html(String::new());
+    let _: fn(&str, &str) -> Piece = Piece::link;
+    assert_traits::<Transformed>();
+    assert_traits::<Piece>();
+
+    let _: fn(&'static str) -> AnsiParser<'static> = AnsiParser::new;
+    let _: Option<AnsiFragment<'_>> = AnsiParser::new("").next();
+    let _: fn(&AnsiFragment<'static>) -> &'static str = AnsiFragment::as_str;
+    type SgrCodesFn = fn(&AnsiFragment<'static>) -> Option<SgrCodes<'static>>;
+    let _: SgrCodesFn = AnsiFragment::sgr_codes;
+    let _: AnsiFragment = AnsiFragment::Text("");
+    let _: AnsiFragment = AnsiFragment::Sequence("");
+    let _: Option<Result<SgrCode, Error>> = AnsiFragment::Sequence("\x1b[m")
+        .sgr_codes()
+        .and_then(|mut codes| codes.next());
+    let _: SgrCode = SgrCode::Unsupported(20);
+    let _: [SgrCode; 3] = [SgrCode::Blink, SgrCode::RapidBlink, SgrCode::BlinkOff];
+    let _: [SgrCode; 2] = [SgrCode::Conceal, SgrCode::ConcealOff];
+    let _: [SgrCode; 2] = [SgrCode::Reverse, SgrCode::ReverseOff];
+    let _: SgrCode = SgrCode::Foreground(SgrColor::Basic(1));
+    let _: SgrCode = SgrCode::Background(SgrColor::Indexed(1));
+    let _: SgrColor = SgrColor::Rgb(1, 2, 3);
+    assert_traits::<AnsiParser<'static>>();
+    assert_traits::<AnsiFragment<'static>>();
+    assert_traits::<SgrCodes<'static>>();
+    assert_traits::<SgrCode>();
+    assert_traits::<SgrColor>();
+
+    let _: String = Esc("<").to_string();
+    let _: String = EscAttr("'").to_string();
+    let _: String = EscUrl("https://example.com").to_string();
+
+    let _: fn(&Error) -> Option<std::ops::Range<usize>> = Error::span;
+    let _: fn(&Error) -> Option<&str> = Error::sequence;
+    fn error_traits<T: std::error::Error + Send + Sync + 'static>() {}
+    error_traits::<Error>();
+}
+
+fn assert_traits<T: Clone + std::fmt::Debug + Send + Sync + 'static>() {}
+
+fn assert_eq_traits<T: PartialEq + Eq>() {}
@@ -0,0 +1,163 @@
+//! The spans and events emitted with the `tracing` feature. Run with
+//! `cargo test --features tracing --test tracing`.
+
+use std::{
+    fmt::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+/// A subscriber that records all spans with their parents and fields, and the
+/// levels and fields of events
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Spans>>);
+
+#[derive(Default)]
+struct Spans {
+    spans: Vec<CapturedSpan>,
+    /// The indices of the entered spans
+    stack: Vec<usize>,
+    events: Vec<String>,
+}
+
+struct CapturedSpan {
+    name: &'static str,
+    parent: Option<usize>,
+    fields: Vec<(&'static str, u64)>,
+}
+
+impl Visit for CapturedSpan {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.retain(|&(name, _)| name != field.name());
+        self.fields.push((field.name(), value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        panic!("unexpected field {field}: {value:?}");
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let mut spans = self.0.lock().unwrap();
+        let parent = match attrs.parent() {
+            Some(id) => Some(id.into_u64() as usize - 1),
+            None if attrs.is_contextual() => spans.stack.last().copied(),
+            None => None,
+        };
+        let mut span = CapturedSpan {
+            name: attrs.metadata().name(),
+            parent,
+            fields: Vec::new(),
+        };
+        attrs.record(&mut span);
+        spans.spans.push(span);
+        Id::from_u64(spans.spans.len() as u64)
+    }
+
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        let mut spans = self.0.lock().unwrap();
+        values.record(&mut spans.spans[id.into_u64() as usize - 1]);
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let fields = event.fields().map(|field| field.name()).collect::<Vec<_>>();
+        let event = format!("{} {}", event.metadata().level(), fields.join(" "));
+        self.0.lock().unwrap().events.push(event);
+    }
+
+    fn enter(&self, id: &Id) {
+        self.0
+            .lock()
+            .unwrap()
+            .stack
+            .push(id.into_u64() as usize - 1);
+    }
+
+    fn exit(&self, _: &Id) {
+        self.0.lock().unwrap().stack.pop();
+    }
+}
+
+impl Capture {
+    /// Returns the spans as an indented tree, in the order they were created
+    fn tree(&self) -> String {
+        let spans = self.0.lock().unwrap();
+        let mut out = String::new();
+        for span in &spans.spans {
+            let depth = std::iter::successors(span.parent, |&i| spans.spans[i].parent).count();
+            write!(out, "{:1$}{name}", "", depth * 2, name = span.name).unwrap();
+            for (name, value) in &span.fields {
+                write!(out, " {name}={value}").unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[test]
+fn convert_spans() {
+    let converter = ansi_to_html::Converter::new().pretty(true);
+    let input = "\x1b[1m<bold>\n\x1b[0m\x1b[3m\x1b[0mplain";
+
+    let capture = Capture::default();
+    let html =
+        tracing::subscriber::with_default(capture.clone(), || converter.convert(input).unwrap());
+    insta::assert_snapshot!(html, @"
+    <b><!--
+      -->&lt;bold&gt;
+    <!--
+    --></b><!--
+    -->plain
+    ");
+    insta::assert_snapshot!(capture.tree(), @"
+    convert input_len=28 output_len=51
+      escape input_len=28 output_len=34
+      parse input_len=34 output_len=25
+      pretty input_len=25 output_len=51
+    ");
+}
+
+#[test]
+fn convert_to_writer_spans() {
+    let converter = ansi_to_html::Converter::new().skip_optimize(true);
+    let input = "\x1b[1mbold\x1b[0m";
+
+    let capture = Capture::default();
+    let mut html = String::new();
+    tracing::subscriber::with_default(capture.clone(), || {
+        converter.convert_to_writer(input, &mut html).unwrap()
+    });
+    insta::assert_snapshot!(html, @"<b>bold</b>");
+    insta::assert_snapshot!(capture.tree(), @"
+    convert input_len=12 output_len=11
+      escape input_len=12 output_len=12
+      parse input_len=12 output_len=11
+    ");
+}
+
+#[test]
+fn double_escape_event() {
+    let converter = ansi_to_html::Converter::new();
+    let html = converter.convert("\x1b[1m<b> & </b>").unwrap();
+    let converter = converter.detect_double_escape(true);
+
+    let capture = Capture::default();
+    let unchanged =
+        tracing::subscriber::with_default(capture.clone(), || converter.convert(&html).unwrap());
+    assert_eq!(unchanged, html);
+    let spans = capture.0.lock().unwrap();
+    assert_eq!(spans.events, ["WARN message input_len"]);
+}
@@ -14,16 +14,64 @@
 //! ```
 
 use std::{
-    io,
+    env, fmt, io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
     string::FromUtf8Error,
 };
 
+/// The error returned when a command can't be created
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The shell wasn't found. Contains the name or path of the shell
+    ShellNotFound(String),
+    /// The `script` command wasn't found
+    ScriptNotFound,
+    /// Another I/O error
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ShellNotFound(shell) => write!(f, "shell `{shell}` not found"),
+            Error::ScriptNotFound => f.write_str("the `script` command was not found"),
+            Error::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::NotFound, e),
+        }
+    }
+}
+
 /// Creates a command that is executed by bash, pretending to be a tty.
 ///
 /// This means that the command will assume that terminal colors and
 /// other terminal features are available.
-pub fn bash_command(command: &str) -> io::Result<Command> {
+pub fn bash_command(command: &str) -> Result<Command, Error> {
     let mut command = make_script_command(command, Some("bash"))?;
     command.stdout(Stdio::piped()).stderr(Stdio::piped());
 
@@ -34,7 +82,7 @@ pub fn bash_command(command: &str) -> io::Result<Command> {
 ///
 /// This means that the command will assume that terminal colors and
 /// other terminal features are available.
-pub fn command(command: &str, shell: Option<&str>) -> io::Result<Command> {
+pub fn command(command: &str, shell: Option<&str>) -> Result<Command, Error> {
     let mut command = make_script_command(command, shell)?;
     command.stdout(Stdio::piped()).stderr(Stdio::piped());
 
@@ -44,6 +92,8 @@ pub fn command(command: &str, shell: Option<&str>) -> io::Result<Command> {
 /// Wraps the command in the `script` command that can execute it
 /// pretending to be a tty.
 ///
+/// Returns an error if the shell or the `script` command can't be found.
+///
 /// - [Linux docs](https://man7.org/linux/man-pages/man1/script.1.html)
 /// - [FreeBSD docs](https://www.freebsd.org/cgi/man.cgi?query=script&sektion=0&manpath=FreeBSD+12.2-RELEASE+and+Ports&arch=default&format=html)
 /// - [Apple docs](https://opensource.apple.com/source/shell_cmds/shell_cmds-170/script/script.1.auto.html)
@@ -61,14 +111,18 @@ pub fn command(command: &str, shell: Option<&str>) -> io::Result<Command> {
 ///
 /// assert!(output.status.success());
 /// ```
-pub fn make_script_command(c: &str, shell: Option<&str>) -> io::Result<Command> {
-    let shell = which_shell(shell.unwrap_or("bash"))?;
+pub fn make_script_command(c: &str, shell: Option<&str>) -> Result<Command, Error> {
+    let shell = shell.unwrap_or("bash");
+    let shell = which(shell).ok_or_else(|| Error::ShellNotFound(shell.to_string()))?;
+    if which("script").is_none() {
+        return Err(Error::ScriptNotFound);
+    }
 
     #[cfg(any(target_os = "linux", target_os = "android"))]
     {
         let mut command = Command::new("script");
         command.args(["-qec", c, "/dev/null"]);
-        command.env("SHELL", shell.trim());
+        command.env("SHELL", shell);
 
         Ok(command)
     }
@@ -76,7 +130,7 @@ pub fn make_script_command(c: &str, shell: Option<&str>) -> io::Result<Command>
     #[cfg(any(target_os = "macos", target_os = "freebsd"))]
     {
         let mut command = Command::new("script");
-        command.args(&["-q", "/dev/null", shell.trim(), "-c", c]);
+        command.args(["-q", "/dev/null"]).arg(shell).args(["-c", c]);
         Ok(command)
     }
 
@@ -108,20 +162,30 @@ pub fn get_stdout(stdout: Vec<u8>) -> Result<String, FromUtf8Error> {
     }
 }
 
-fn which_shell(shell: &str) -> io::Result<String> {
-    let which = Command::new("which")
-        .arg(shell)
-        .stdout(Stdio::piped())
-        .output()?;
-
-    if which.status.success() {
-        Ok(String::from_utf8(which.stdout).unwrap())
-    } else {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            String::from_utf8(which.stderr).unwrap(),
-        ))
+/// Returns the path of the executable. If `name` doesn't contain a `/`, it is looked up in the
+/// `PATH`.
+///
+/// This is how the shell and the `script` command are found by [`make_script_command`].
+///
+/// ## Example
+///
+/// ```
+/// assert!(fake_tty::which("sh").is_some());
+/// assert!(fake_tty::which("/nonexistent/sh").is_none());
+/// ```
+pub fn which(name: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    };
+
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
     }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
 }
 
 #[cfg(test)]
@@ -130,7 +194,7 @@ mod tests {
         let output = crate::bash_command(s).unwrap().output().unwrap();
         let s1 = crate::get_stdout(output.stdout).unwrap();
 
-        if crate::which_shell("zsh").is_ok() {
+        if crate::which("zsh").is_some() {
             let output = crate::command(s, Some("zsh")).unwrap().output().unwrap();
             let s2 = crate::get_stdout(output.stdout).unwrap();
 
@@ -155,6 +219,13 @@ mod tests {
         assert_eq!(run(r#"echo "Hello \$\`' world!""#), "Hello $`' world!\n");
     }
 
+    #[test]
+    fn missing_shell() {
+        let err = crate::command("ls", Some("/nonexistent/sh")).unwrap_err();
+        assert!(matches!(err, crate::Error::ShellNotFound(s) if s == "/nonexistent/sh"));
+        assert!(crate::which("sh").is_some());
+    }
+
     #[test]
     fn echo_and_cat() {
         assert_eq!(
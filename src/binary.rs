@@ -0,0 +1,81 @@
+//! Detection of binary output, e.g. of `cat image.png`, which is replaced by a placeholder unless
+//! `--allow-binary` is passed.
+//!
+//! Only the text between escape sequences is inspected, so output with lots of colors isn't
+//! mistaken for binary data.
+
+/// Output is considered binary if more than 1 in this many characters is suspicious.
+const SUSPICIOUS_RATIO: usize = 10;
+
+/// Returns whether the output looks like binary data: It contains a NUL byte, or many control
+/// characters or invalid UTF-8 sequences (which were replaced with `U+FFFD`).
+pub(crate) fn is_binary(output: &str) -> bool {
+    let mut total = 0;
+    let mut suspicious = 0;
+    for fragment in ansi_to_html::text_fragments(output) {
+        for c in fragment.chars() {
+            match c {
+                '\0' => return true,
+                // line breaks, tabs, backspace, bell and escape characters that don't start a
+                // known sequence are common in terminal output
+                '\n' | '\r' | '\t' | '\x08' | '\x07' | '\x1b' => {}
+                '\u{FFFD}' => suspicious += 1,
+                c if c.is_control() => suspicious += 1,
+                _ => {}
+            }
+            total += 1;
+        }
+    }
+    suspicious * SUSPICIOUS_RATIO > total
+}
+
+/// Returns the size in bytes of output that was decoded lossily. Every invalid byte was replaced
+/// with the 3-byte `U+FFFD`, so this is approximately the size of the original output.
+pub(crate) fn original_size(output: &str) -> usize {
+    output.len() - 2 * output.matches('\u{FFFD}').count()
+}
+
+/// Formats a number of bytes, e.g. `1.2 MiB`
+pub(crate) fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{bytes} bytes");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::{format_size, is_binary};
+
+    #[test]
+    fn binary_detection() {
+        assert!(is_binary("PNG\0\0\0"));
+        assert!(is_binary("\u{FFFD}PNG\r\n\x1a\n\u{FFFD}\u{FFFD}\x02\x03"));
+        assert!(!is_binary(""));
+        assert!(!is_binary("hello\r\nworld\t\x08\x07"));
+        // escape sequences aren't counted, e.g. in a recorded terminal session
+        let compressed: &[u8] = include_bytes!("../crates/ansi-to-html/assets/terminal_session.gz");
+        let mut session = String::new();
+        let mut decoder = flate2::read::GzDecoder::new(compressed);
+        decoder.read_to_string(&mut session).unwrap();
+        assert!(!is_binary(&session));
+        assert!(!is_binary("one invalid byte \u{FFFD} in a longer text"));
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(12), "12 bytes");
+        assert_eq!(format_size(2048), "2.0 KiB");
+        assert_eq!(format_size(1_258_291), "1.2 MiB");
+        assert_eq!(format_size(5 << 30), "5.0 GiB");
+    }
+}
@@ -0,0 +1,82 @@
+//! Recording and replaying the output of commands.
+//!
+//! With `--capture-raw`, the bytes each command wrote to the terminal are saved to disk. With
+//! `--input-file`, the commands aren't run; instead, the contents of the files are shown as their
+//! output. Together, they allow reproducing the HTML of a run without running the commands again.
+//! A session recorded with `script` can be shown as well, see [`crate::typescript`].
+
+use std::{fs, path::PathBuf, time::Duration};
+
+use crate::{cmd, log, opts::Opts, StdError};
+
+/// Returns the path where the raw output of the command with the given index is saved. With a
+/// single command, this is the path given to `--capture-raw`, otherwise it is a file in that
+/// directory.
+fn raw_capture_path(i: usize, opts: &Opts) -> Option<PathBuf> {
+    let path = opts.capture_raw.as_ref()?;
+    if opts.commands.len() == 1 {
+        Some(path.clone())
+    } else {
+        Some(path.join(format!("command-{}.ansi", i + 1)))
+    }
+}
+
+/// Saves the raw stdout of the command, followed by its stderr. Errors are logged as warnings, so
+/// they don't affect the HTML output.
+pub(crate) fn write_raw(i: usize, output: &cmd::Output, opts: &Opts) {
+    let Some(path) = raw_capture_path(i, opts) else {
+        return;
+    };
+
+    let mut bytes = output.raw_stdout.clone();
+    bytes.extend_from_slice(output.stderr.as_bytes());
+
+    let result = match path.parent() {
+        Some(dir) if opts.commands.len() > 1 => fs::create_dir_all(dir),
+        _ => Ok(()),
+    };
+    match result.and_then(|()| fs::write(&path, bytes)) {
+        Ok(()) => log::info(
+            Some(i),
+            format_args!("saved raw output to {}", path.display()),
+        ),
+        Err(e) => log::warn(
+            Some(i),
+            format_args!("failed to save raw output to {}: {e}", path.display()),
+        ),
+    }
+}
+
+/// The output of a command that isn't run, from `--input-file` or `--typescript`
+pub(crate) struct CannedOutput {
+    pub output: String,
+    /// The blocks of a `--typescript` session that was split with `--timing`: the byte offset in
+    /// `output` where each block after the first starts, and the time since the start of the
+    /// session
+    pub blocks: Vec<(usize, Duration)>,
+}
+
+/// Reads the files passed with `--input-file`. Line breaks are normalized like in the output of
+/// commands that are run, so a file saved with `--capture-raw` produces the same HTML.
+pub(crate) fn read_input_files(opts: &Opts) -> Result<Vec<CannedOutput>, StdError> {
+    if opts.input_file.len() != opts.commands.len() {
+        return Err(format!(
+            "expected one --input-file per command, got {} files for {} commands",
+            opts.input_file.len(),
+            opts.commands.len()
+        )
+        .into());
+    }
+
+    opts.input_file
+        .iter()
+        .map(|path| {
+            let content =
+                fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+            Ok(CannedOutput {
+                output: String::from_utf8_lossy(&content).replace("\r\n", "\n"),
+                blocks: Vec::new(),
+            })
+        })
+        .collect()
+}
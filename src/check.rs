@@ -0,0 +1,218 @@
+//! `--check`: Instead of writing the `--output` file, the output is compared with its contents.
+//!
+//! This is meant for CI, to verify that HTML files generated by to-html are up to date. With
+//! `--check-diff`, a unified diff of the changes is printed.
+
+use std::{fmt::Write, fs, io, path::Path};
+
+use crate::{log, StdError};
+
+/// Number of unchanged lines shown before and after each change in the diff.
+const CONTEXT: usize = 3;
+
+/// Returns an error if the file doesn't contain the given output.
+pub(crate) fn check_output(path: &Path, output: &str, print_diff: bool) -> Result<(), StdError> {
+    let existing = match fs::read_to_string(path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(format!("{} does not exist", path.display()).into());
+        }
+        Err(e) => return Err(format!("can't read {}: {e}", path.display()).into()),
+    };
+
+    if existing == output {
+        log::info(None, format_args!("{} is up to date", path.display()));
+        return Ok(());
+    }
+    if print_diff {
+        print!(
+            "{}",
+            unified_diff(&existing, output, &path.display().to_string())
+        );
+    }
+    Err(format!("{} is not up to date", path.display()).into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Computes a line-based diff with the longest common subsequence.
+///
+/// This uses Hirschberg's algorithm, which keeps only one row of the subsequence lengths at a
+/// time, so the memory is linear in the number of lines, even for large files.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    diff_into(old, new, &mut lines);
+    lines
+}
+
+/// Appends the diff of `old` and `new` to `lines`. `old` is split in the middle, and `new` where
+/// the longest common subsequences of the halves add up to the longest one.
+fn diff_into<'a>(old: &[&'a str], new: &[&'a str], lines: &mut Vec<Line<'a>>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+    let suffix = (old_rest.iter().rev())
+        .zip(new_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old_rest[..old_rest.len() - suffix];
+    let new_mid = &new_rest[..new_rest.len() - suffix];
+
+    lines.extend(old[..prefix].iter().map(|l| Line::Same(l)));
+    match *old_mid {
+        [] => lines.extend(new_mid.iter().map(|l| Line::Added(l))),
+        [line] => match new_mid.iter().position(|l| *l == line) {
+            Some(i) => {
+                lines.extend(new_mid[..i].iter().map(|l| Line::Added(l)));
+                lines.push(Line::Same(line));
+                lines.extend(new_mid[i + 1..].iter().map(|l| Line::Added(l)));
+            }
+            None => {
+                lines.push(Line::Removed(line));
+                lines.extend(new_mid.iter().map(|l| Line::Added(l)));
+            }
+        },
+        _ if new_mid.is_empty() => lines.extend(old_mid.iter().map(|l| Line::Removed(l))),
+        _ => {
+            let (top, bottom) = old_mid.split_at(old_mid.len() / 2);
+            let forward = lcs_lengths(top.iter().copied(), new_mid.iter().copied());
+            let backward = lcs_lengths(bottom.iter().rev().copied(), new_mid.iter().rev().copied());
+            let split = (0..=new_mid.len())
+                .max_by_key(|&j| (forward[j] + backward[new_mid.len() - j], usize::MAX - j))
+                .unwrap_or(0);
+            diff_into(top, &new_mid[..split], lines);
+            diff_into(bottom, &new_mid[split..], lines);
+        }
+    }
+    lines.extend(
+        old_rest[old_rest.len() - suffix..]
+            .iter()
+            .map(|l| Line::Same(l)),
+    );
+}
+
+/// Returns the lengths of the longest common subsequences of `old` and each prefix of `new`
+fn lcs_lengths<'a>(
+    old: impl Iterator<Item = &'a str>,
+    new: impl Iterator<Item = &'a str> + Clone,
+) -> Vec<usize> {
+    let mut row = vec![0; new.clone().count() + 1];
+    for old_line in old {
+        // the length for the previous line of `old` and the current prefix of `new`
+        let mut diagonal = 0;
+        for (j, new_line) in new.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if old_line == new_line {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+/// Returns a unified diff from the file contents to the new output.
+fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old, &new);
+    let changes: Vec<usize> = (0..lines.len())
+        .filter(|&i| !matches!(lines[i], Line::Same(_)))
+        .collect();
+
+    let mut out = format!("--- {path}\n+++ {path} (generated)\n");
+    let mut k = 0;
+    while k < changes.len() {
+        // changes that are close together are merged into one hunk
+        let start = changes[k].saturating_sub(CONTEXT);
+        let mut end = changes[k] + 1;
+        k += 1;
+        while k < changes.len() && changes[k] <= end + 2 * CONTEXT {
+            end = changes[k] + 1;
+            k += 1;
+        }
+        let end = (end + CONTEXT).min(lines.len());
+
+        let count_old = |lines: &[Line<'_>]| {
+            lines
+                .iter()
+                .filter(|l| !matches!(l, Line::Added(_)))
+                .count()
+        };
+        let count_new = |lines: &[Line<'_>]| {
+            lines
+                .iter()
+                .filter(|l| !matches!(l, Line::Removed(_)))
+                .count()
+        };
+        let hunk = &lines[start..end];
+        let _ = writeln!(
+            out,
+            "@@ -{},{} +{},{} @@",
+            count_old(&lines[..start]) + 1,
+            count_old(hunk),
+            count_new(&lines[..start]) + 1,
+            count_new(hunk),
+        );
+        for line in hunk {
+            let _ = match line {
+                Line::Same(l) => writeln!(out, " {l}"),
+                Line::Removed(l) => writeln!(out, "-{l}"),
+                Line::Added(l) => writeln!(out, "+{l}"),
+            };
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_lines, unified_diff, Line};
+
+    #[test]
+    fn diff_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n";
+        assert_eq!(
+            unified_diff(old, new, "out.html"),
+            "--- out.html\n+++ out.html (generated)\n\
+             @@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+             @@ -10,3 +10,4 @@\n 10\n 11\n 12\n+13\n"
+        );
+        assert_eq!(
+            unified_diff("a\n", "a\n", "x"),
+            "--- x\n+++ x (generated)\n"
+        );
+    }
+
+    #[test]
+    fn diff_is_minimal() {
+        // the example of Myers' paper, with a longest common subsequence of 4 lines
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let lines = diff_lines(&old, &new);
+        let same = lines.iter().filter(|l| matches!(l, Line::Same(_)));
+        assert_eq!(same.count(), 4);
+
+        let old_lines: Vec<_> = (lines.iter())
+            .filter_map(|l| match l {
+                Line::Same(l) | Line::Removed(l) => Some(*l),
+                Line::Added(_) => None,
+            })
+            .collect();
+        let new_lines: Vec<_> = (lines.iter())
+            .filter_map(|l| match l {
+                Line::Same(l) | Line::Added(l) => Some(*l),
+                Line::Removed(_) => None,
+            })
+            .collect();
+        assert_eq!(old_lines, old);
+        assert_eq!(new_lines, new);
+    }
+}
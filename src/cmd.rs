@@ -1,32 +1,443 @@
 use std::{
-    io::{self, Write},
-    path::Path,
-    process::{Child, ExitStatus},
+    cell::RefCell,
+    fmt,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::StdError;
+use regex::Regex;
 
-pub fn run(args: &str, shell: Option<&str>) -> Result<(String, String, ExitStatus), StdError> {
-    let output =
-        fake_tty::command(&format!("{}; printf \"~~////~~\"; pwd", args), shell)?.output()?;
+use crate::{opts::Streams, StdError};
 
-    let stdout = fake_tty::get_stdout(output.stdout)?;
-    let stderr = String::from_utf8(output.stderr)?;
-    let status = output.status;
+/// What responses to prompts are replaced with in the output
+const MASK: &str = "********";
+
+thread_local! {
+    /// The directory in which commands on this thread run, if it isn't the working directory of
+    /// the process. Jobs of `--jobs` run on their own threads, so they can't share the latter.
+    static THREAD_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Makes the commands on this thread run in `dir` instead of the working directory of the
+/// process. A `cd` in a command then only changes this directory. `None` undoes this.
+pub fn set_thread_dir(dir: Option<PathBuf>) {
+    THREAD_DIR.set(dir);
+}
+
+/// Returns the directory in which the next command runs
+pub fn current_dir() -> io::Result<PathBuf> {
+    match THREAD_DIR.with_borrow(Clone::clone) {
+        Some(dir) => Ok(dir),
+        None => std::env::current_dir(),
+    }
+}
+
+/// Changes the directory in which the next command runs, see [`set_thread_dir`]
+pub fn set_current_dir(dir: impl AsRef<Path>) -> io::Result<()> {
+    THREAD_DIR.with_borrow_mut(|thread_dir| match thread_dir {
+        Some(thread_dir) => {
+            *thread_dir = dir.as_ref().to_path_buf();
+            Ok(())
+        }
+        None => std::env::set_current_dir(dir),
+    })
+}
+
+/// Prompts that are answered while the command runs, see `--expect`
+#[derive(Clone, Debug, Default)]
+pub struct Expect {
+    /// The regexes of the prompts and the responses, in the order in which the prompts appear
+    pub prompts: Vec<(Regex, String)>,
+    /// How long to wait for the next prompt while the command prints nothing
+    pub timeout: Duration,
+}
+
+/// The result of running a command
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` if the exit code can't be determined in this shell
+    pub exit_code: Option<i32>,
+    /// The bytes the command wrote to the terminal, exactly as captured. Unlike `stdout`, line
+    /// breaks are not normalized.
+    pub raw_stdout: Vec<u8>,
+    /// Whether the command was killed because it exceeded the timeout
+    pub timed_out: bool,
+    /// The regex of the `--expect` prompt the command was waiting for when it was killed because
+    /// of the `--expect-timeout`
+    pub unanswered: Option<String>,
+}
+
+/// Returns the file name of the shell without `.exe`, e.g. `pwsh` for `C:\bin\pwsh.exe`
+pub fn shell_name(shell: &str) -> &str {
+    let name = shell.rsplit(['/', '\\']).next().unwrap_or(shell);
+    match name.len().checked_sub(4) {
+        Some(i) if name.is_char_boundary(i) && name[i..].eq_ignore_ascii_case(".exe") => &name[..i],
+        _ => name,
+    }
+}
+
+/// Returns whether the shell is PowerShell, which needs a different prompt and different commands
+/// to print the exit code and working directory
+pub fn is_powershell(shell: Option<&str>) -> bool {
+    shell.map(shell_name).is_some_and(|name| {
+        name.eq_ignore_ascii_case("pwsh") || name.eq_ignore_ascii_case("powershell")
+    })
+}
+
+/// Runs the command in the given shell. If it takes longer than the timeout, it is killed, and
+/// the output it printed until then is returned. The prompts of `expect` are answered, and the
+/// responses are masked in the output. The variables in `env` are set for the command. With
+/// `streams`, the stream that isn't selected is discarded.
+///
+/// With `track_cwd`, the shell prints the exit code and working directory after the command, and
+/// the working directory of this process (or thread, see [`set_thread_dir`]) is changed to it, so
+/// the next command runs where this one ended. Otherwise, the command is run unmodified, and the
+/// exit code is the one of `script`, which exits with the status of the shell.
+pub fn run(
+    args: &str,
+    shell: Option<&str>,
+    timeout: Option<Duration>,
+    expect: &Expect,
+    env: &[(String, String)],
+    streams: Streams,
+    track_cwd: bool,
+) -> Result<Output, StdError> {
+    let args = &select_stream(args, shell, streams)?;
+    // The command is terminated with a line break instead of `;`, so it may contain line breaks
+    // and end with a comment or a here-document
+    let script = if !track_cwd {
+        args.to_string()
+    } else if is_powershell(shell) {
+        // `pwd` prints a table in PowerShell
+        format!("{args}\nWrite-Host -NoNewline \"~~////~~$LASTEXITCODE~~\"; (Get-Location).Path")
+    } else {
+        let status_var = match shell.map(shell_name) {
+            Some("fish" | "csh" | "tcsh") => "$status",
+            Some("elvish") => "",
+            _ => "$?",
+        };
+        format!("{args}\nprintf \"~~////~~%s~~\" {status_var}; pwd")
+    };
+    let mut command = fake_tty::command(&script, shell).map_err(explain)?;
+    command.envs(env.iter().map(|(k, v)| (k, v)));
+    if let Some(dir) = THREAD_DIR.with_borrow(Clone::clone) {
+        command.current_dir(dir);
+    }
+    let (output, timed_out, unanswered) = match timeout {
+        _ if !expect.prompts.is_empty() => output_interactive(command, timeout, expect)?,
+        Some(timeout) => {
+            let (output, timed_out) = output_with_timeout(command, timeout)?;
+            (output, timed_out, None)
+        }
+        None => (command.output()?, false, None),
+    };
+
+    let mut raw_stdout = output.stdout.clone();
+    let delimiter = (raw_stdout.windows(8)).rposition(|w| w == b"~~////~~");
+    if let Some(i) = delimiter.filter(|_| track_cwd) {
+        raw_stdout.truncate(i);
+    }
+
+    // binary output is decoded lossily, so it can be detected and replaced by a placeholder
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stdout = fake_tty::get_stdout(stdout.into_bytes())?;
+    // the command writes both streams to the terminal, so this is only what `script` itself wrote
+    let stderr = match streams {
+        Streams::Stdout => String::new(),
+        Streams::Both | Streams::Stderr => String::from_utf8_lossy(&output.stderr).into_owned(),
+    };
+
+    if !track_cwd {
+        let killed = timed_out || unanswered.is_some();
+        // like when the exit code and working directory weren't printed below
+        let stdout = if killed {
+            format!("{}\n", stdout.trim_end())
+        } else {
+            stdout
+        };
+        return Ok(Output {
+            stdout,
+            stderr,
+            exit_code: output.status.code().filter(|_| !killed),
+            raw_stdout,
+            timed_out,
+            unanswered,
+        });
+    }
 
     let stdout = stdout.trim_end();
-    let lb = stdout
-        .rfind("~~////~~")
-        .ok_or_else(|| format!("Delimiter not found in the string {:?}", stdout))
-        .unwrap();
+    let Some(lb) = stdout.rfind("~~////~~") else {
+        if !timed_out && unanswered.is_none() {
+            return Err(format!("Delimiter not found in the string {:?}", stdout).into());
+        }
+        // the command was killed before the exit code and working directory were printed
+        return Ok(Output {
+            stdout: format!("{stdout}\n"),
+            stderr,
+            exit_code: None,
+            raw_stdout,
+            timed_out,
+            unanswered,
+        });
+    };
 
-    let (output, cwd) = stdout.split_at(lb);
-    let cwd = cwd.trim_start_matches("~~////~~").trim_start();
+    let (output, rest) = stdout.split_at(lb);
+    let (code, cwd) = rest
+        .trim_start_matches("~~////~~")
+        .split_once("~~")
+        .unwrap_or_default();
+    // Windows paths may be followed by `\r`
+    let cwd = cwd.trim();
 
-    if !cmp_paths(std::env::current_dir()?, cwd) {
-        std::env::set_current_dir(cwd)?;
+    if !cmp_paths(current_dir()?, cwd) {
+        set_current_dir(cwd)?;
     }
-    Ok((output.to_string(), stderr, status))
+    Ok(Output {
+        stdout: output.to_string(),
+        stderr,
+        exit_code: code.trim().parse().ok(),
+        raw_stdout,
+        timed_out,
+        unanswered,
+    })
+}
+
+/// Wraps the command so that only the stream selected with `--streams` reaches the terminal. The
+/// command runs in a group, not a subshell, so a `cd` still changes the working directory.
+fn select_stream(args: &str, shell: Option<&str>, streams: Streams) -> Result<String, StdError> {
+    let redirect = match streams {
+        Streams::Both => return Ok(args.to_string()),
+        Streams::Stdout => "2>/dev/null",
+        // stderr goes to the terminal, then stdout is discarded
+        Streams::Stderr => "2>&1 >/dev/null",
+    };
+    match shell.map(shell_name) {
+        Some("fish") => Ok(format!("begin\n{args}\nend {redirect}")),
+        Some(name @ ("csh" | "tcsh" | "elvish")) => {
+            Err(format!("--streams is not supported in {name}").into())
+        }
+        Some(name) if is_powershell(shell) => {
+            Err(format!("--streams is not supported in {name}").into())
+        }
+        _ => Ok(format!("{{\n{args}\n}} {redirect}")),
+    }
+}
+
+/// Like [`Command::output`], but kills the process when the timeout is exceeded. Returns whether
+/// it was killed.
+fn output_with_timeout(
+    mut command: Command,
+    timeout: Duration,
+) -> io::Result<(process::Output, bool)> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // the pipes are read in threads, so the process doesn't block when a pipe buffer is full
+    let stdout = read_in_thread(child.stdout.take());
+    let stderr = read_in_thread(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            // killing `script` closes the pseudo-terminal, which ends the command as well
+            child.kill()?;
+            timed_out = true;
+            break child.wait()?;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let output = process::Output {
+        status,
+        stdout: join(stdout)?,
+        stderr: join(stderr)?,
+    };
+    Ok((output, timed_out))
+}
+
+/// Like [`output_with_timeout`], but writes the response to a prompt to the pseudo-terminal when
+/// the prompt appears in the output. If the command prints nothing for `expect.timeout` while a
+/// prompt is pending, it is killed, and the regex of the prompt is returned.
+fn output_interactive(
+    mut command: Command,
+    timeout: Option<Duration>,
+    expect: &Expect,
+) -> io::Result<(process::Output, bool, Option<String>)> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // stdout is sent in chunks as it arrives, since the command waits for the response
+    let (sender, receiver) = mpsc::channel();
+    let mut pipe = child.stdout.take();
+    let stdout_reader = thread::spawn(move || {
+        let mut buf = [0; 4096];
+        while let Some(pipe) = &mut pipe {
+            let n = pipe.read(&mut buf)?;
+            if n == 0 || sender.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+        io::Result::Ok(())
+    });
+    let stderr = read_in_thread(child.stderr.take());
+
+    let mut stdin = child.stdin.take();
+    let mut prompts = expect.prompts.iter();
+    let mut next = prompts.next();
+    let mut stdout = Vec::new();
+    // the prompt is searched in the output after the previous prompt
+    let mut search_start = 0;
+    // the start of the output after each answered prompt, where the response may be echoed
+    let mut answered = Vec::new();
+    let start = Instant::now();
+    let mut last_activity = start;
+    let mut timed_out = false;
+    let mut unanswered = None;
+    let status = loop {
+        let mut received = false;
+        match receiver.recv_timeout(Duration::from_millis(10)) {
+            Ok(chunk) => {
+                stdout.extend(chunk);
+                received = true;
+                last_activity = Instant::now();
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(Duration::from_millis(10)),
+        }
+        if let Some((prompt, response)) = next.filter(|_| received) {
+            let output = String::from_utf8_lossy(&stdout[search_start..]);
+            if prompt.is_match(&ansi_to_html::strip_ansi(&output)) {
+                answer(&mut stdin, response);
+                answered.push((stdout.len(), response));
+                search_start = stdout.len();
+                next = prompts.next();
+                last_activity = Instant::now();
+            }
+        }
+
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        let now = Instant::now();
+        if timeout.is_some_and(|timeout| now >= start + timeout) {
+            timed_out = true;
+        } else if next.is_some() && now >= last_activity + expect.timeout {
+            unanswered = next.map(|(prompt, _)| prompt.to_string());
+        } else {
+            continue;
+        }
+        // killing `script` closes the pseudo-terminal, which ends the command as well
+        child.kill()?;
+        break child.wait()?;
+    };
+
+    drop(stdin);
+    stdout_reader
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("reading the output failed")))?;
+    stdout.extend(receiver.try_iter().flatten());
+    let mut stderr = join(stderr)?;
+
+    // masking the responses from last to first keeps the positions of the earlier ones valid
+    for &(start, response) in answered.iter().rev() {
+        mask(&mut stdout, start, response);
+        mask(&mut stderr, 0, response);
+    }
+    let output = process::Output {
+        status,
+        stdout,
+        stderr,
+    };
+    Ok((output, timed_out, unanswered))
+}
+
+/// Types the response to a prompt, followed by a line break
+fn answer(stdin: &mut Option<ChildStdin>, response: &str) {
+    if let Some(pipe) = stdin {
+        // the command may have exited without reading the response
+        let _ = writeln!(pipe, "{response}").and_then(|()| pipe.flush());
+    }
+}
+
+/// Replaces every occurrence of the response after `start` with [`MASK`]. The response is
+/// usually echoed by the terminal, or printed by the command.
+fn mask(output: &mut Vec<u8>, start: usize, response: &str) {
+    let response = response.as_bytes();
+    if response.is_empty() {
+        return;
+    }
+    let mut i = start;
+    while let Some(pos) = output[i..]
+        .windows(response.len())
+        .position(|w| w == response)
+    {
+        let pos = i + pos;
+        output.splice(pos..pos + response.len(), MASK.bytes());
+        i = pos + MASK.len();
+    }
+}
+
+/// Reads the pipe in a thread, so the process doesn't block when the pipe buffer is full
+fn read_in_thread(
+    pipe: Option<impl Read + Send + 'static>,
+) -> thread::JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut bytes)?;
+        }
+        Ok(bytes)
+    })
+}
+
+fn join(handle: thread::JoinHandle<io::Result<Vec<u8>>>) -> io::Result<Vec<u8>> {
+    handle
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("reading the output failed")))
+}
+
+/// The error when the shell or the `script` command is missing, with a message explaining how to
+/// fix it
+#[derive(Debug)]
+pub struct MissingProgram(String);
+
+impl fmt::Display for MissingProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for MissingProgram {}
+
+/// Turns the error into a message explaining how to fix it
+fn explain(e: fake_tty::Error) -> StdError {
+    let message = match e {
+        fake_tty::Error::ShellNotFound(shell) if shell == "bash" => {
+            "bash was not found. Install it, or choose a different shell, e.g. `--shell sh`".into()
+        }
+        fake_tty::Error::ShellNotFound(shell) => format!("shell `{shell}` was not found"),
+        fake_tty::Error::ScriptNotFound => "the `script` command was not found. It is needed to \
+            run commands in a pseudo-terminal, and is part of util-linux (bsdutils on Debian and \
+            Ubuntu)"
+            .into(),
+        e => return e.into(),
+    };
+    MissingProgram(message).into()
 }
 
 fn cmp_paths(p1: impl AsRef<Path>, p2: impl AsRef<Path>) -> bool {
@@ -44,11 +455,171 @@ pub fn input(mut child: Child, input: impl AsRef<str>) -> io::Result<Child> {
 
 #[test]
 fn test_run() {
-    let (stdout, stderr, status) = run("ls -l", None).unwrap();
+    let Output {
+        stdout,
+        stderr,
+        exit_code,
+        ..
+    } = run(
+        "ls -l",
+        None,
+        None,
+        &Expect::default(),
+        &[],
+        Streams::Both,
+        true,
+    )
+    .unwrap();
     assert!(
-        status.success(),
+        exit_code == Some(0),
         "Running `ls -l` was unsuccessful (stdout: {:?}, stderr: {:?})",
         stdout,
         stderr
     );
 }
+
+#[test]
+fn test_run_timeout() {
+    let start = Instant::now();
+    let output = run(
+        "echo start; sleep 10; echo end",
+        None,
+        Some(Duration::from_millis(500)),
+        &Expect::default(),
+        &[],
+        Streams::Both,
+        true,
+    )
+    .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(output.timed_out);
+    assert_eq!(output.stdout.trim(), "start");
+    assert_eq!(output.exit_code, None);
+}
+
+#[test]
+fn test_run_expect() {
+    let script = "read -p 'Name: ' name; read -s -p 'Password: ' pw; echo; \
+        echo \"hello $name, your password has ${#pw} characters\"; echo \"$pw\"";
+    let expect = Expect {
+        prompts: vec![
+            (Regex::new("Name: $").unwrap(), "Alice".into()),
+            (Regex::new("(?i)password:").unwrap(), "hunter2".into()),
+        ],
+        timeout: Duration::from_secs(10),
+    };
+    let output = run(
+        script,
+        Some("bash"),
+        None,
+        &expect,
+        &[],
+        Streams::Both,
+        true,
+    )
+    .unwrap();
+    assert_eq!(
+        output.stdout,
+        "Name: ********\nPassword: \nhello ********, your password has 7 characters\n********\n"
+    );
+    assert!(!String::from_utf8_lossy(&output.raw_stdout).contains("hunter2"));
+    assert_eq!(output.exit_code, Some(0));
+
+    // the command is killed if the prompt doesn't appear
+    let expect = Expect {
+        prompts: vec![(Regex::new("Passphrase").unwrap(), "x".into())],
+        timeout: Duration::from_millis(500),
+    };
+    let start = Instant::now();
+    let output = run(
+        "echo start; read -p 'Password: '",
+        None,
+        None,
+        &expect,
+        &[],
+        Streams::Both,
+        true,
+    )
+    .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(output.unanswered.as_deref(), Some("Passphrase"));
+    assert!(!output.timed_out);
+    assert_eq!(output.stdout, "start\nPassword:\n");
+    assert_eq!(output.exit_code, None);
+}
+
+#[test]
+fn test_run_without_cwd_tracking() {
+    let cwd = std::env::current_dir().unwrap();
+    let output = run(
+        "false\necho $?\ncd /",
+        None,
+        None,
+        &Expect::default(),
+        &[],
+        Streams::Both,
+        false,
+    )
+    .unwrap();
+    assert_eq!(output.stdout, "1\n");
+    assert_eq!(output.exit_code, Some(0));
+    assert_eq!(std::env::current_dir().unwrap(), cwd);
+
+    let output = run(
+        "echo failing; exit 3",
+        None,
+        None,
+        &Expect::default(),
+        &[],
+        Streams::Both,
+        false,
+    )
+    .unwrap();
+    assert_eq!(output.stdout, "failing\n");
+    assert_eq!(output.exit_code, Some(3));
+}
+
+#[test]
+fn test_run_in_thread_dir() {
+    let cwd = std::env::current_dir().unwrap();
+    let dir = std::env::temp_dir().canonicalize().unwrap();
+    thread::spawn(move || {
+        set_thread_dir(Some(dir.clone()));
+        let env = [("GREETING".to_string(), "hi".to_string())];
+        let output = run(
+            "pwd; echo $GREETING\ncd /",
+            None,
+            None,
+            &Expect::default(),
+            &env,
+            Streams::Both,
+            true,
+        );
+        assert_eq!(output.unwrap().stdout, format!("{}\nhi\n", dir.display()));
+        assert_eq!(current_dir().unwrap(), Path::new("/"));
+    })
+    .join()
+    .unwrap();
+    assert_eq!(std::env::current_dir().unwrap(), cwd);
+}
+
+#[test]
+fn test_mask() {
+    let mut output = b"secret: secret secre".to_vec();
+    mask(&mut output, 1, "secret");
+    assert_eq!(output, b"secret: ******** secre");
+}
+
+#[test]
+fn test_shell_name() {
+    assert_eq!(shell_name("/usr/bin/fish"), "fish");
+    assert_eq!(
+        shell_name(r"C:\Program Files\PowerShell\7\pwsh.EXE"),
+        "pwsh"
+    );
+    assert_eq!(shell_name("bash"), "bash");
+    assert!(is_powershell(Some("powershell.exe")));
+    assert!(is_powershell(Some("/opt/microsoft/powershell/7/pwsh")));
+    assert!(!is_powershell(Some("bash")));
+    assert!(!is_powershell(None));
+}
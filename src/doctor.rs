@@ -0,0 +1,180 @@
+//! `to-html doctor`: Checks whether commands can be run in this environment.
+//!
+//! Every check prints `✓` or `✗` with a short explanation. If a required check fails, the exit
+//! code is 1. With `--json`, the results are printed as a JSON object instead.
+
+use std::fmt::Write;
+
+use crate::{
+    detect_shell,
+    log::JsonEsc,
+    opts::{self, ConfigStatus, Opts},
+    StdError,
+};
+
+/// The command that is run through the whole pipeline
+const TEST_COMMAND: &str = r"printf '\033[1;31mred\033[0m ok\n'";
+
+/// The expected HTML of [`TEST_COMMAND`]
+const EXPECTED_HTML: &str = "<pre class=\"terminal\">\n<b><span style='color:var(--red,#a00)'>red\
+                             </span></b> ok\n</pre>";
+
+struct Check {
+    name: &'static str,
+    /// Whether the exit code is 1 if this check fails
+    required: bool,
+    ok: bool,
+    message: String,
+}
+
+/// Runs all checks and prints the results. Returns `false` if a required check failed.
+pub(crate) fn run(json: bool) -> Result<bool, StdError> {
+    let checks = checks();
+    let ok = checks.iter().all(|c| c.ok || !c.required);
+
+    if json {
+        println!("{}", to_json(&checks, ok));
+    } else {
+        for check in &checks {
+            let mark = if check.ok { '✓' } else { '✗' };
+            let optional = if check.required { "" } else { " (optional)" };
+            println!("{mark} {}{optional}: {}", check.name, check.message);
+        }
+    }
+    Ok(ok)
+}
+
+fn checks() -> Vec<Check> {
+    let mut checks = vec![check_config()];
+
+    let (shell, source) = match (opts::config_shell(), detect_shell()) {
+        (Some(shell), _) => (shell, "configured"),
+        (None, Some(shell)) => (shell, "detected from the parent process"),
+        (None, None) => ("bash".to_string(), "default"),
+    };
+    let shell_path = fake_tty::which(&shell);
+    checks.push(Check {
+        name: "shell",
+        required: true,
+        ok: shell_path.is_some(),
+        message: match &shell_path {
+            Some(path) => format!("`{shell}` ({source}) is {}", path.display()),
+            None => format!("`{shell}` ({source}) was not found"),
+        },
+    });
+
+    let script_path = fake_tty::which("script");
+    checks.push(Check {
+        name: "script",
+        required: true,
+        ok: script_path.is_some(),
+        message: match &script_path {
+            Some(path) => format!("{}", path.display()),
+            None => "not found. It is part of util-linux (bsdutils on Debian and Ubuntu)".into(),
+        },
+    });
+
+    checks.push(if shell_path.is_some() && script_path.is_some() {
+        check_pipeline(shell)
+    } else {
+        Check {
+            name: "pipeline",
+            required: true,
+            ok: false,
+            message: "skipped, since the shell or `script` is missing".into(),
+        }
+    });
+
+    checks.push(check_terminal_size());
+    checks
+}
+
+fn check_config() -> Check {
+    let (ok, message) = match opts::check_config() {
+        Ok((path, ConfigStatus::NotFound)) => (
+            true,
+            format!("{} not found, using the defaults", path.display()),
+        ),
+        Ok((path, ConfigStatus::Ok)) => (true, format!("{} is valid", path.display())),
+        Ok((path, ConfigStatus::Problems(problems))) => {
+            (false, format!("{}:{}", path.display(), problems.join("; ")))
+        }
+        Err(e) => (false, e.to_string()),
+    };
+    Check {
+        name: "config",
+        required: true,
+        ok,
+        message,
+    }
+}
+
+/// Runs a command printing colored text and compares the HTML with the expected output
+fn check_pipeline(shell: String) -> Check {
+    let opts = Opts {
+        commands: vec![TEST_COMMAND.to_string()],
+        shell: Some(shell),
+        no_prompt: true,
+        ..Opts::default()
+    };
+    let (ok, message) = match crate::render(&opts, None) {
+        Ok(html) if html == EXPECTED_HTML => (true, "colored output is converted correctly".into()),
+        Ok(html) => (
+            false,
+            format!("`{TEST_COMMAND}` produced unexpected HTML: {html:?}"),
+        ),
+        Err(e) => (false, format!("can't run `{TEST_COMMAND}`: {e}")),
+    };
+    Check {
+        name: "pipeline",
+        required: true,
+        ok,
+        message,
+    }
+}
+
+/// Reports the size of the terminal. Commands are run in a pseudo-terminal of this size, so
+/// programs may wrap their output differently if it's not detected.
+fn check_terminal_size() -> Check {
+    let (ok, message) = match terminal_size() {
+        Some((cols, rows)) => (true, format!("{cols}x{rows}")),
+        None => (false, "not detected, not running in a terminal".into()),
+    };
+    Check {
+        name: "terminal size",
+        required: false,
+        ok,
+        message,
+    }
+}
+
+/// Returns the number of columns and rows of the terminal connected to stdin, stdout or stderr
+fn terminal_size() -> Option<(u16, u16)> {
+    [libc::STDOUT_FILENO, libc::STDERR_FILENO, libc::STDIN_FILENO]
+        .into_iter()
+        .find_map(|fd| {
+            // SAFETY: `winsize` is a plain struct that the ioctl writes to
+            let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+            let result = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) };
+            (result == 0 && size.ws_col > 0).then_some((size.ws_col, size.ws_row))
+        })
+}
+
+fn to_json(checks: &[Check], ok: bool) -> String {
+    let mut json = format!("{{\"ok\":{ok},\"checks\":[");
+    for (i, check) in checks.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let _ = write!(
+            json,
+            "{{\"name\":\"{}\",\"required\":{},\"ok\":{},\"message\":\"{}\"}}",
+            check.name,
+            check.required,
+            check.ok,
+            JsonEsc(&check.message),
+        );
+    }
+    json.push_str("]}");
+    json
+}
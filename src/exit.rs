@@ -0,0 +1,67 @@
+//! The errors that end `to-html`, and the exit codes they are mapped to:
+//!
+//! | Code | Meaning                                                                               |
+//! |------|---------------------------------------------------------------------------------------|
+//! | 0    | Success                                                                               |
+//! | 1    | An error in `to-html`, e.g. the output can't be written, or `--check` or a job failed |
+//! | 2    | Invalid arguments, config file or job file (clap uses 2 as well)                      |
+//! | 3    | The environment lacks something, e.g. the shell or the `script` command               |
+//! | 4+   | With `--passthrough-status`, the exit status of the first failed command              |
+
+use std::fmt;
+
+use crate::{cmd::MissingProgram, StdError};
+
+const INTERNAL: i32 = 1;
+pub(crate) const USAGE: i32 = 2;
+pub(crate) const ENVIRONMENT: i32 = 3;
+/// The lowest exit code that is passed through, so it can't be confused with the codes above
+const MIN_COMMAND_STATUS: i32 = 4;
+
+#[derive(Debug)]
+pub(crate) enum AppError {
+    Internal(StdError),
+    /// Invalid arguments or config file
+    Usage(StdError),
+    /// A program that is needed to run the commands is missing
+    Environment(StdError),
+    /// The problem was already reported, so `to-html` just exits with the code
+    Exit(i32),
+}
+
+impl AppError {
+    /// The error for `--passthrough-status` when a command failed with the given status. Statuses
+    /// below 4 become 4.
+    pub(crate) fn command_failed(status: i32) -> Self {
+        AppError::Exit(status.max(MIN_COMMAND_STATUS))
+    }
+
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            AppError::Internal(_) => INTERNAL,
+            AppError::Usage(_) => USAGE,
+            AppError::Environment(_) => ENVIRONMENT,
+            AppError::Exit(code) => *code,
+        }
+    }
+
+    /// Returns the message to print, unless it was already reported
+    pub(crate) fn message(&self) -> Option<&dyn fmt::Display> {
+        match self {
+            AppError::Internal(e) | AppError::Usage(e) | AppError::Environment(e) => Some(e),
+            AppError::Exit(_) => None,
+        }
+    }
+}
+
+/// Errors are internal, unless a program is missing
+impl<E: Into<StdError>> From<E> for AppError {
+    fn from(e: E) -> Self {
+        let e = e.into();
+        if e.is::<MissingProgram>() {
+            AppError::Environment(e)
+        } else {
+            AppError::Internal(e)
+        }
+    }
+}
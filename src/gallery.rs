@@ -0,0 +1,186 @@
+//! A gallery showing the output of `to-html` with various options, for the documentation.
+//!
+//! Nothing is executed: The outputs of the commands are bundled in `src/gallery/`. This also
+//! serves as an end-to-end test of the HTML rendering.
+
+use std::{fmt::Write, fs, path::Path};
+
+use ansi_to_html::Esc;
+
+use crate::{
+    capture::CannedOutput,
+    opts::{Format, LineRange, Opts},
+    StdError,
+};
+
+/// The commands shown in every section, with their canned outputs
+const COMMANDS: &[(&str, &str)] = &[
+    ("ls -l", include_str!("gallery/ls.ansi")),
+    ("git status", include_str!("gallery/git-status.ansi")),
+    ("cargo build", include_str!("gallery/cargo-build.ansi")),
+    ("./run.sh --demo", include_str!("gallery/styles.ansi")),
+];
+
+/// The CSS prefix used in the "CSS prefix" section
+const PREFIX: &str = "th-";
+
+/// A section of the gallery
+struct Entry {
+    id: &'static str,
+    title: &'static str,
+    flags: &'static str,
+    opts: Opts,
+}
+
+/// The options shown in the gallery, one section each
+fn matrix() -> Vec<Entry> {
+    let commands = COMMANDS.iter().map(|&(c, _)| c.to_string()).collect();
+    let opts = Opts {
+        commands,
+        ..Opts::default()
+    };
+
+    vec![
+        Entry {
+            id: "default",
+            title: "Default",
+            flags: "",
+            opts: opts.clone(),
+        },
+        Entry {
+            id: "no-prompt",
+            title: "Without prompt",
+            flags: "--no-prompt",
+            opts: Opts {
+                no_prompt: true,
+                ..opts.clone()
+            },
+        },
+        Entry {
+            id: "prefix",
+            title: "CSS prefix",
+            flags: "--prefix th",
+            opts: Opts {
+                prefix: PREFIX.into(),
+                ..opts.clone()
+            },
+        },
+        Entry {
+            id: "highlight",
+            title: "Highlighted subcommands",
+            flags: "--highlight git,cargo",
+            opts: Opts {
+                highlight: vec!["git".into(), "cargo".into()],
+                ..opts.clone()
+            },
+        },
+        Entry {
+            id: "hl-lines",
+            title: "Highlighted lines",
+            flags: "--hl-lines 2-3",
+            opts: Opts {
+                hl_lines: vec![LineRange { start: 2, end: 3 }],
+                ..opts.clone()
+            },
+        },
+        Entry {
+            id: "toc",
+            title: "Table of contents",
+            flags: "--toc",
+            opts: Opts {
+                toc: true,
+                ..opts.clone()
+            },
+        },
+        Entry {
+            id: "semantic-output",
+            title: "Semantic markup",
+            flags: "--semantic-output",
+            opts: Opts {
+                semantic_output: true,
+                ..opts.clone()
+            },
+        },
+        Entry {
+            id: "github",
+            title: "GitHub format",
+            flags: "--format github",
+            opts: Opts {
+                format: Format::Github,
+                ..opts
+            },
+        },
+    ]
+}
+
+/// Renders the gallery as a complete HTML document
+fn render_gallery() -> Result<String, StdError> {
+    let outputs: Vec<CannedOutput> = COMMANDS
+        .iter()
+        .map(|&(_, output)| CannedOutput {
+            output: output.to_string(),
+            blocks: Vec::new(),
+        })
+        .collect();
+
+    let mut buf = String::new();
+    writeln!(
+        buf,
+        "<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>to-html gallery</title>
+<style>{}{}</style>
+</head>
+<body>
+<h1>to-html gallery</h1>",
+        crate::make_style(""),
+        crate::make_style(PREFIX),
+    )?;
+
+    for entry in matrix() {
+        let mut invocation = String::from("to-html");
+        if !entry.flags.is_empty() {
+            write!(invocation, " {}", entry.flags)?;
+        }
+        for &(command, _) in COMMANDS {
+            write!(invocation, " '{command}'")?;
+        }
+
+        writeln!(
+            buf,
+            "<section id='{}'>\n<h2>{}</h2>\n<p><code>{}</code></p>",
+            entry.id,
+            entry.title,
+            Esc(invocation),
+        )?;
+        writeln!(buf, "{}", crate::render(&entry.opts, Some(&outputs))?)?;
+        writeln!(buf, "</section>")?;
+    }
+
+    writeln!(buf, "</body>\n</html>")?;
+    Ok(buf)
+}
+
+/// Writes the gallery to `index.html` in the given directory
+pub(crate) fn write_gallery(dir: &Path) -> Result<(), StdError> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("index.html"), render_gallery()?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matrix, render_gallery};
+
+    #[test]
+    fn one_section_per_entry() {
+        let html = render_gallery().unwrap();
+        let entries = matrix();
+        assert_eq!(html.matches("<section ").count(), entries.len());
+        for entry in entries {
+            assert!(html.contains(&format!("<section id='{}'>", entry.id)));
+        }
+        assert!(!html.contains('\x1b'));
+    }
+}
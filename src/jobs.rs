@@ -0,0 +1,193 @@
+//! `--jobs`: Renders many snippets in one run, as described by a TOML or YAML file.
+//!
+//! The file contains an array of jobs. Each job has its own commands and output file, and can
+//! override a few options. All other options come from the command line and the config file,
+//! which are parsed only once. A job that fails doesn't stop the others, unless `--fail-fast` is
+//! given. A summary is printed at the end.
+//!
+//! ```toml
+//! [[jobs]]
+//! commands = ["cargo build"]
+//! output = "snippets/build.html"
+//! cwd = "examples/hello"
+//! env = { CARGO_TERM_COLOR = "always" }
+//! ```
+//!
+//! Relative paths are relative to the directory of the job file, which is also where the
+//! commands of a job run if it has no `cwd`.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        OnceLock,
+    },
+    thread,
+};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{
+    cmd, log,
+    opts::{self, Format, Opts},
+    StdError,
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobFile {
+    jobs: Vec<JobEntry>,
+}
+
+/// A job as written in the job file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobEntry {
+    commands: Vec<String>,
+    output: PathBuf,
+    cwd: Option<PathBuf>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    shell: Option<String>,
+    prefix: Option<String>,
+    format: Option<String>,
+}
+
+/// A job that is ready to run
+pub(crate) struct Job {
+    /// The output path as written in the job file, to refer to the job in messages
+    name: String,
+    output: PathBuf,
+    cwd: PathBuf,
+    opts: Opts,
+}
+
+/// Reads the job file. The options of each job are `opts` with the values from the file.
+pub(crate) fn load(path: &Path, opts: &Opts) -> Result<Vec<Job>, StdError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("can't read job file {}: {e}", path.display()))?;
+    let is_yaml = path
+        .extension()
+        .is_some_and(|ext| ext == "yaml" || ext == "yml");
+    let file: JobFile = if is_yaml {
+        serde_yaml_ng::from_str(&contents)
+            .map_err(|e| format!("job file {} is invalid: {e}", path.display()))?
+    } else {
+        toml::from_str(&contents)
+            .map_err(|e| format!("job file {} is invalid: {e}", path.display()))?
+    };
+
+    let dir = std::env::current_dir()?.join(path.parent().unwrap_or(Path::new("")));
+    file.jobs
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let name = entry.output.display().to_string();
+            job(name.clone(), entry, &dir, opts)
+                .map_err(|e| format!("job {} ({name}): {e}", i + 1).into())
+        })
+        .collect()
+}
+
+/// Applies the values of the entry to the options. Relative paths are resolved against `dir`.
+fn job(name: String, entry: JobEntry, dir: &Path, opts: &Opts) -> Result<Job, String> {
+    if entry.commands.is_empty() {
+        return Err("no commands given".into());
+    }
+    let mut opts = opts.with_commands(entry.commands);
+    opts.env = entry.env.into_iter().collect();
+    if let Some(shell) = entry.shell {
+        opts.shell = Some(shell);
+        opts.shells = Vec::new();
+    }
+    if let Some(prefix) = entry.prefix {
+        let prefix = opts::parse_prefix(&prefix)?;
+        opts.prefix = if prefix.is_empty() {
+            prefix
+        } else {
+            format!("{prefix}-")
+        };
+    }
+    if let Some(format) = entry.format {
+        opts.format =
+            Format::from_str(&format, false).map_err(|_| format!("unknown format `{format}`"))?;
+        // like on the command line, these only work with HTML
+        opts.doc &= opts.format == Format::Html;
+        opts.toc &= opts.format == Format::Html;
+    }
+    Ok(Job {
+        name,
+        output: dir.join(entry.output),
+        cwd: dir.join(entry.cwd.unwrap_or_default()),
+        opts,
+    })
+}
+
+/// Runs the jobs, `parallel` at a time, and prints a summary. Returns whether all of them
+/// succeeded.
+pub(crate) fn run(jobs: &[Job], parallel: usize, fail_fast: bool) -> bool {
+    let results: Vec<OnceLock<Result<(), String>>> = jobs.iter().map(|_| OnceLock::new()).collect();
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        for _ in 0..parallel.clamp(1, jobs.len().max(1)) {
+            s.spawn(|| loop {
+                if fail_fast && failed.load(Ordering::Relaxed) {
+                    break;
+                }
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(i) else {
+                    break;
+                };
+                // errors can't be sent to other threads
+                let result = run_job(job).map_err(|e| e.to_string());
+                if let Err(e) = &result {
+                    failed.store(true, Ordering::Relaxed);
+                    log::error(format_args!("job `{}` failed: {e}", job.name));
+                }
+                let _ = results[i].set(result);
+            });
+        }
+    });
+
+    let succeeded = results
+        .iter()
+        .filter(|r| matches!(r.get(), Some(Ok(()))))
+        .count();
+    let failed = results
+        .iter()
+        .filter(|r| matches!(r.get(), Some(Err(_))))
+        .count();
+    let skipped = jobs.len() - succeeded - failed;
+    let mut summary = format!("{succeeded} of {} jobs succeeded", jobs.len());
+    if failed > 0 {
+        summary += &format!(", {failed} failed");
+    }
+    if skipped > 0 {
+        summary += &format!(", {skipped} skipped");
+    }
+    println!("{summary}");
+    for (job, result) in jobs.iter().zip(&results) {
+        match result.get() {
+            Some(Ok(())) => {}
+            Some(Err(_)) => println!("  failed:  {}", job.name),
+            None => println!("  skipped: {}", job.name),
+        }
+    }
+    failed == 0 && skipped == 0
+}
+
+/// Renders the commands of the job in its directory and writes the HTML to its output file
+fn run_job(job: &Job) -> Result<(), StdError> {
+    log::info(None, format_args!("running job `{}`", job.name));
+    cmd::set_thread_dir(Some(job.cwd.clone()));
+    let html = crate::render(&job.opts, None);
+    cmd::set_thread_dir(None);
+    fs::write(&job.output, format!("{}\n", html?))
+        .map_err(|e| format!("can't write {}: {e}", job.output.display()))?;
+    Ok(())
+}
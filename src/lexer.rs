@@ -8,7 +8,7 @@ use std::{borrow::Cow, fmt::Write};
 use ansi_to_html::Esc;
 use logos::{Lexer, Logos};
 
-use crate::opts::Opts;
+use crate::opts::{HighlightRule, Opts};
 
 type StdError = Box<dyn std::error::Error>;
 
@@ -124,6 +124,26 @@ pub(crate) enum Token<'a> {
 
     /// `"$@"`, `"$HELLO_WORLD"`
     Variable(&'a str),
+
+    /// ```bash
+    /// FOO="Hello $world"
+    /// ```
+    /// is represented as (simplified):
+    /// ```ignore
+    /// Assignment {
+    ///     name: "FOO",
+    ///     value: [DString(["Hello ", Variable("$world")])],
+    /// }
+    /// ```
+    Assignment(Assignment<'a>),
+}
+
+/// Variable assignment, which may precede a command
+#[derive(Debug)]
+pub(crate) struct Assignment<'a> {
+    name: &'a str,
+    /// The tokens directly following the `=`, e.g. words, strings and variables
+    value: Tokens<'a>,
 }
 
 /// Double quoted string
@@ -157,7 +177,6 @@ pub(crate) enum TokenKind {
     #[regex(r#"\\[^\d]"#)]
     EscapeSequence,
 
-    #[token("|")]
     #[token("<")]
     #[token(";")]
     #[token("&&")]
@@ -166,6 +185,10 @@ pub(crate) enum TokenKind {
     #[regex("[012]>>?&[012]")]
     Pipe,
 
+    /// `|`, which is separate from [`TokenKind::Pipe`] so pipelines can be split
+    #[token("|")]
+    Bar,
+
     #[token("\n")]
     LineBreak,
 
@@ -304,7 +327,7 @@ pub(crate) fn parse_tokens(
             TokenKind::EscapeSequence => {
                 tokens.push(Token::EscapeSequence(lex.slice()));
             }
-            TokenKind::Pipe => {
+            TokenKind::Pipe | TokenKind::Bar => {
                 tokens.push(Token::Pipe(lex.slice()));
             }
             TokenKind::Whitespace => {
@@ -314,15 +337,30 @@ pub(crate) fn parse_tokens(
                 tokens.push(Token::Whitespace(lex.slice()));
             }
             TokenKind::Word => {
-                tokens.push(Token::Word(lex.slice()));
+                let word = lex.slice();
+                match assignment_name(word) {
+                    Some(name) => {
+                        let value = &word[name.len() + 1..];
+                        let value = if value.is_empty() {
+                            vec![]
+                        } else {
+                            vec![Token::Word(value)]
+                        };
+                        tokens.push(Token::Assignment(Assignment {
+                            name,
+                            value: Tokens(value),
+                        }));
+                    }
+                    None => push_word_part(&mut tokens, Token::Word(word)),
+                }
             }
             TokenKind::DoubleQuote => {
                 let (d_string, lex2) = parse_d_string(lex.morph())?;
                 lex = lex2.morph();
-                tokens.push(Token::DString(d_string));
+                push_word_part(&mut tokens, Token::DString(d_string));
             }
             TokenKind::SingleQuoteString => {
-                tokens.push(Token::SString(lex.slice()));
+                push_word_part(&mut tokens, Token::SString(lex.slice()));
             }
             TokenKind::HeredocStart => {
                 let (first_line, lex2) = parse_tokens(lex, |&t| t == TokenKind::LineBreak)?;
@@ -354,7 +392,7 @@ pub(crate) fn parse_tokens(
             TokenKind::Backtick => {
                 let (backticks, lex2) = parse_tokens(lex, |&t| t == TokenKind::Backtick)?;
                 lex = lex2;
-                tokens.push(Token::Backticks(backticks));
+                push_word_part(&mut tokens, Token::Backticks(backticks));
             }
             TokenKind::OpenBracket => {
                 let (token, lex2) = parse_tokens(lex, |&t| t == TokenKind::CloseBracket)?;
@@ -380,13 +418,13 @@ pub(crate) fn parse_tokens(
             TokenKind::OpenDollarParen => {
                 let (token, lex2) = parse_tokens(lex, |&t| t == TokenKind::CloseParen)?;
                 lex = lex2;
-                tokens.push(Token::DollarParens(token));
+                push_word_part(&mut tokens, Token::DollarParens(token));
             }
             TokenKind::CloseParen => {
                 return Err(Error::UnexpectedToken(")"));
             }
             TokenKind::Variable => {
-                tokens.push(Token::Variable(lex.slice()));
+                push_word_part(&mut tokens, Token::Variable(lex.slice()));
             }
         }
     }
@@ -394,6 +432,25 @@ pub(crate) fn parse_tokens(
     Ok((Tokens(tokens), lex))
 }
 
+/// Returns the variable name if the word starts with an assignment, e.g. `FOO` for `FOO=bar`
+fn assignment_name(word: &str) -> Option<&str> {
+    let (name, _) = word.split_once('=')?;
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    let is_name = (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    is_name.then_some(name)
+}
+
+/// Pushes a token that is part of a word, or adds it to the value of the assignment directly
+/// before it, e.g. the string in `FOO="a b"`
+fn push_word_part<'a>(tokens: &mut Vec<Token<'a>>, token: Token<'a>) {
+    match tokens.last_mut() {
+        Some(Token::Assignment(assignment)) => assignment.value.0.push(token),
+        _ => tokens.push(token),
+    }
+}
+
 fn parse_d_string(
     mut lex: Lexer<DStringTokenKind>,
 ) -> Result<(DString, Lexer<DStringTokenKind>), Error> {
@@ -441,14 +498,50 @@ pub(crate) fn colorize(buf: &mut String, command: &str, opts: &Opts) -> Result<(
     Ok(())
 }
 
+/// Splits a pipeline at the top-level `|` tokens and returns the cumulative prefixes, e.g.
+/// `["a", "a | b", "a | b | c"]` for `a | b | c`. Pipes in strings, substitutions etc. as well as
+/// `||` are ignored.
+pub(crate) fn pipeline_prefixes(command: &str) -> Result<Vec<&str>, Error> {
+    let mut prefixes = Vec::new();
+    let mut lex = TokenKind::lexer(command);
+    loop {
+        let (_, lex2) = parse_tokens(lex, |&t| t == TokenKind::Bar)?;
+        lex = lex2;
+        if lex.slice() != "|" {
+            break;
+        }
+
+        let span = lex.span();
+        let is_pipe =
+            !command[..span.start].ends_with('|') && !command[span.end..].starts_with('|');
+        let prefix = command[..span.start].trim();
+        if is_pipe && !prefix.is_empty() {
+            prefixes.push(prefix);
+        }
+        if lex.remainder().is_empty() {
+            break;
+        }
+    }
+    prefixes.push(command.trim());
+    Ok(prefixes)
+}
+
 impl Tokens<'_> {
     fn colorize(&self, buf: &mut String, opts: &Opts, as_command: bool) -> Result<(), StdError> {
+        Self::colorize_slice(&self.0, buf, opts, as_command)
+    }
+
+    fn colorize_slice(
+        tokens: &[Token<'_>],
+        buf: &mut String,
+        opts: &Opts,
+        as_command: bool,
+    ) -> Result<(), StdError> {
         let mut next = if as_command {
             State::Start
         } else {
             State::Default
         };
-        let prefix = opts.prefix.as_str();
 
         #[derive(Debug, Copy, Clone, Eq, PartialEq)]
         enum State {
@@ -457,27 +550,29 @@ impl Tokens<'_> {
             Pipe,
         }
 
-        let mut hl_subcommand = false;
+        // The highlight rule of the current program, and the number of subcommand words that are
+        // still highlighted
+        let mut hl_subcommand: Option<(&HighlightRule, usize)> = None;
 
-        for token in &self.0 {
+        for token in tokens {
             match token {
                 &Token::Comment(c) => {
-                    write!(buf, "<span class='{}com'>{}</span>", prefix, Esc(c))?;
+                    write!(buf, "<span{}>{}</span>", opts.class("com"), Esc(c))?;
                 }
                 &Token::EscapeSequence(e) => {
-                    write!(buf, "<span class='{}esc'>{}</span>", prefix, Esc(e))?;
+                    write!(buf, "<span{}>{}</span>", opts.class("esc"), Esc(e))?;
                     if e == "\\\n" {
                         continue;
                     }
                 }
                 &Token::Pipe(p) => {
                     if let ";" | "&&" = p {
-                        write!(buf, "<span class='{}punct'>{}</span>", prefix, Esc(p))?;
+                        write!(buf, "<span{}>{}</span>", opts.class("punct"), Esc(p))?;
                     } else {
-                        write!(buf, "<span class='{}pipe'>{}</span>", prefix, Esc(p))?;
+                        write!(buf, "<span{}>{}</span>", opts.class("pipe"), Esc(p))?;
                     }
 
-                    hl_subcommand = false;
+                    hl_subcommand = None;
                     if let "|" | ";" | "&&" = p {
                         next = State::Start;
                         continue;
@@ -488,76 +583,95 @@ impl Tokens<'_> {
                 }
                 &Token::Whitespace(w) => {
                     write!(buf, "{}", w)?;
+                    // like `;`, a line break ends the command
+                    if w.contains('\n') {
+                        hl_subcommand = None;
+                        next = State::Start;
+                    }
                     continue;
                 }
                 &Token::Word(w) => {
                     if next == State::Start {
-                        if w.contains('=') {
-                            write!(buf, "<span class='{}env'>{}</span>", prefix, Esc(w))?;
-                        } else {
-                            next = State::Default;
-                            write!(buf, "<span class='{}cmd'>{}</span>", prefix, Esc(w))?;
-                            if opts.highlight.iter().any(|h| h == w) {
-                                hl_subcommand = true;
-                                continue;
-                            }
+                        next = State::Default;
+                        write!(buf, "<span{}>{}</span>", opts.class("cmd"), Esc(w))?;
+                        if let Some(rule) = opts.highlight_rule(w).filter(|r| r.depth > 0) {
+                            hl_subcommand = Some((rule, rule.depth));
+                            continue;
                         }
                     } else if next == State::Pipe {
-                        write!(buf, "<span class='{}pipe'>{}</span>", prefix, Esc(w))?;
-                    } else if w.starts_with('-') {
-                        if let Some((i, _)) = w.char_indices().find(|&(_, c)| c == '=') {
-                            let (p1, p2) = w.split_at(i);
-
-                            write!(buf, "<span class='{}flag'>{}</span>", prefix, Esc(p1))?;
-                            write!(buf, "<span class='{}arg'>{}</span>", prefix, Esc(p2))?;
+                        write!(buf, "<span{}>{}</span>", opts.class("pipe"), Esc(w))?;
+                    } else {
+                        let skipped = hl_subcommand.is_some_and(|(rule, _)| rule.skips(w));
+                        if w.starts_with('-') {
+                            if let Some((i, _)) = w.char_indices().find(|&(_, c)| c == '=') {
+                                let (p1, p2) = w.split_at(i);
+
+                                write!(buf, "<span{}>{}</span>", opts.class("flag"), Esc(p1))?;
+                                write!(buf, "<span{}>{}</span>", opts.class("arg"), Esc(p2))?;
+                            } else {
+                                write!(buf, "<span{}>{}</span>", opts.class("flag"), Esc(w))?;
+                            }
+                        } else if let (Some((_, depth)), false) = (&mut hl_subcommand, skipped) {
+                            write!(buf, "<span{}>{}</span>", opts.class("hl"), Esc(w))?;
+                            *depth -= 1;
+                            if *depth > 0 {
+                                continue;
+                            }
                         } else {
-                            write!(buf, "<span class='{}flag'>{}</span>", prefix, Esc(w))?;
+                            write!(buf, "<span{}>{}</span>", opts.class("arg"), Esc(w))?;
+                        }
+                        if skipped {
+                            continue;
                         }
-                    } else if hl_subcommand {
-                        write!(buf, "<span class='{}hl'>{}</span>", prefix, Esc(w))?;
-                    } else {
-                        write!(buf, "<span class='{}arg'>{}</span>", prefix, Esc(w))?;
                     }
                 }
                 Token::DString(d) => {
                     d.colorize(buf, opts)?;
                 }
                 &Token::SString(s) => {
-                    write!(buf, "<span class='{}str'>{}</span>", prefix, Esc(s))?;
+                    write!(buf, "<span{}>{}</span>", opts.class("str"), Esc(s))?;
                 }
                 Token::Backticks(t) => {
-                    write!(buf, "<span class='{}punct'>`</span>", prefix)?;
+                    write!(buf, "<span{}>`</span>", opts.class("punct"))?;
                     t.colorize(buf, opts, true)?;
-                    write!(buf, "<span class='{}punct'>`</span>", prefix)?;
+                    write!(buf, "<span{}>`</span>", opts.class("punct"))?;
                 }
                 Token::Brackets(t) => {
-                    write!(buf, "<span class='{}punct'>[</span>", prefix)?;
+                    write!(buf, "<span{}>[</span>", opts.class("punct"))?;
                     t.colorize(buf, opts, false)?;
-                    write!(buf, "<span class='{}punct'>]</span>", prefix)?;
+                    write!(buf, "<span{}>]</span>", opts.class("punct"))?;
                 }
                 Token::Parens(t) => {
-                    write!(buf, "<span class='{}punct'>(</span>", prefix)?;
+                    write!(buf, "<span{}>(</span>", opts.class("punct"))?;
                     t.colorize(buf, opts, false)?;
-                    write!(buf, "<span class='{}punct'>)</span>", prefix)?;
+                    write!(buf, "<span{}>)</span>", opts.class("punct"))?;
                 }
                 Token::DollarParens(t) => {
-                    write!(buf, "<span class='{}punct'>$(</span>", prefix)?;
+                    write!(buf, "<span{}>$(</span>", opts.class("punct"))?;
                     t.colorize(buf, opts, true)?;
-                    write!(buf, "<span class='{}punct'>)</span>", prefix)?;
+                    write!(buf, "<span{}>)</span>", opts.class("punct"))?;
                 }
                 Token::Braces(t) => {
-                    write!(buf, "<span class='{}punct'>{{</span>", prefix)?;
+                    write!(buf, "<span{}>{{</span>", opts.class("punct"))?;
                     t.colorize(buf, opts, true)?;
-                    write!(buf, "<span class='{}punct'>}}</span>", prefix)?;
+                    write!(buf, "<span{}>}}</span>", opts.class("punct"))?;
                 }
                 Token::Heredoc(h) => {
                     h.colorize(buf, opts)?;
                 }
                 &Token::Variable(v) => {
-                    write!(buf, "<span class='{}var'>{}</span>", prefix, Esc(v))?;
+                    write!(buf, "<span{}>{}</span>", opts.class("var"), Esc(v))?;
+                }
+                Token::Assignment(a) => {
+                    let leading = next == State::Start;
+                    a.colorize(buf, opts, leading)?;
+                    if leading {
+                        // more assignments or the command may follow
+                        continue;
+                    }
                 }
             }
-            hl_subcommand = false;
+            hl_subcommand = None;
             next = State::Default;
         }
         Ok(())
@@ -566,8 +680,7 @@ impl Tokens<'_> {
 
 impl DString<'_> {
     fn colorize(&self, buf: &mut String, opts: &Opts) -> Result<(), StdError> {
-        let prefix = opts.prefix.as_str();
-        write!(buf, "<span class='{}str'>\"", prefix)?;
+        write!(buf, "<span{}>\"", opts.class("str"))?;
 
         for token in &self.0 {
             match token {
@@ -575,20 +688,20 @@ impl DString<'_> {
                     write!(buf, "{}", Esc(c))?;
                 }
                 &DStringToken::Variable(v) => {
-                    write!(buf, "<span class='{}var'>{}</span>", prefix, Esc(v))?;
+                    write!(buf, "<span{}>{}</span>", opts.class("var"), Esc(v))?;
                 }
                 &DStringToken::Escaped(e) => {
-                    write!(buf, "<span class='{}esc'>{}</span>", prefix, Esc(e))?;
+                    write!(buf, "<span{}>{}</span>", opts.class("esc"), Esc(e))?;
                 }
                 DStringToken::Backticks(t) => {
                     write!(buf, "`</span>")?;
                     t.colorize(buf, opts, true)?;
-                    write!(buf, "<span class='{}str'>`", prefix)?;
+                    write!(buf, "<span{}>`", opts.class("str"))?;
                 }
                 DStringToken::Parens(t) => {
                     write!(buf, "$(</span>")?;
                     t.colorize(buf, opts, true)?;
-                    write!(buf, "<span class='{}str'>)", prefix)?;
+                    write!(buf, "<span{}>)", opts.class("str"))?;
                 }
             }
         }
@@ -598,15 +711,50 @@ impl DString<'_> {
     }
 }
 
+impl Assignment<'_> {
+    /// Colorizes the assignment. Before the command, the name and the value have distinct classes;
+    /// otherwise it is an argument, e.g. `CC=gcc` in `make CC=gcc`.
+    fn colorize(&self, buf: &mut String, opts: &Opts, leading: bool) -> Result<(), StdError> {
+        let mut value = self.value.0.as_slice();
+        if leading {
+            write!(
+                buf,
+                "<span{}>{}</span>",
+                opts.class("env-name"),
+                Esc(self.name)
+            )?;
+            write!(buf, "<span{}>=</span>", opts.class("punct"))?;
+        } else {
+            let word = match value {
+                [Token::Word(w), rest @ ..] => {
+                    value = rest;
+                    w
+                }
+                _ => "",
+            };
+            let arg = format!("{}={word}", self.name);
+            write!(buf, "<span{}>{}</span>", opts.class("arg"), Esc(&arg))?;
+        }
+        for token in value {
+            match token {
+                &Token::Word(w) => {
+                    let class = if leading { "env" } else { "arg" };
+                    write!(buf, "<span{}>{}</span>", opts.class(class), Esc(w))?;
+                }
+                token => Tokens::colorize_slice(std::slice::from_ref(token), buf, opts, false)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Heredoc<'_> {
     fn colorize(&self, buf: &mut String, opts: &Opts) -> Result<(), StdError> {
-        let prefix = opts.prefix.as_str();
-
         write!(buf, "&lt;&lt;")?;
         self.first_line.colorize(buf, opts, false)?;
         writeln!(buf)?;
 
-        write!(buf, "<span class='{}str'>", prefix)?;
+        write!(buf, "<span{}>", opts.class("str"))?;
         for &line in &self.content {
             writeln!(buf, "{}", Esc(line))?;
         }
@@ -615,3 +763,124 @@ impl Heredoc<'_> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{colorize, pipeline_prefixes};
+    use crate::opts::{HighlightRule, Opts};
+
+    /// Returns the highlighted subcommands
+    fn highlighted(command: &str, opts: &Opts) -> Vec<String> {
+        let mut buf = String::new();
+        colorize(&mut buf, command, opts).unwrap();
+        buf.split("<span class='hl'>")
+            .skip(1)
+            .map(|s| s.split_once('<').unwrap().0.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn subcommands() {
+        let opts = Opts {
+            highlight: vec!["git".into(), "gh".into(), "cargo".into()],
+            ..Opts::default()
+        };
+        assert_eq!(highlighted("git checkout main", &opts), ["checkout"]);
+        assert_eq!(highlighted("gh pr create", &opts), ["pr"]);
+        assert_eq!(highlighted("cargo +nightly build", &opts), ["+nightly"]);
+        assert_eq!(
+            highlighted("git add .\n  git commit", &opts),
+            ["add", "commit"]
+        );
+        assert_eq!(highlighted("git \\\n  checkout", &opts), ["checkout"]);
+
+        let mut opts = opts;
+        opts.highlight_rules.insert(
+            "gh".into(),
+            HighlightRule {
+                depth: 2,
+                skip: vec![],
+            },
+        );
+        opts.highlight_rules.insert(
+            "cargo".into(),
+            HighlightRule {
+                depth: 1,
+                skip: vec!["+*".into()],
+            },
+        );
+        assert_eq!(highlighted("gh pr create --fill", &opts), ["pr", "create"]);
+        assert_eq!(highlighted("gh pr", &opts), ["pr"]);
+        assert_eq!(highlighted("cargo +nightly build", &opts), ["build"]);
+        assert_eq!(highlighted("cargo +nightly fmt --check", &opts), ["fmt"]);
+        assert!(highlighted("cargo --version", &opts).is_empty());
+        assert_eq!(
+            highlighted("gh pr list | cargo run", &opts),
+            ["pr", "list", "run"]
+        );
+    }
+
+    #[test]
+    fn pipelines() {
+        let prefixes = |command| pipeline_prefixes(command).unwrap();
+        assert_eq!(prefixes("ls"), ["ls"]);
+        assert_eq!(
+            prefixes("ps aux | grep firefox |wc -l"),
+            [
+                "ps aux",
+                "ps aux | grep firefox",
+                "ps aux | grep firefox |wc -l"
+            ]
+        );
+        assert_eq!(
+            prefixes(r#"echo 'a|b' "c|d" $(e | f) `g|h` | cat"#),
+            [
+                r#"echo 'a|b' "c|d" $(e | f) `g|h`"#,
+                r#"echo 'a|b' "c|d" $(e | f) `g|h` | cat"#
+            ]
+        );
+        assert_eq!(prefixes("true || false"), ["true || false"]);
+    }
+
+    /// Returns the colorized command, with `<span class='x'>` shortened to `<x>`
+    fn colorized(command: &str) -> String {
+        let mut buf = String::new();
+        colorize(&mut buf, command, &Opts::default()).unwrap();
+        buf.replace("<span class='", "<")
+            .replace("'>", ">")
+            .replace("</span>", "</>")
+    }
+
+    #[test]
+    fn assignments() {
+        assert_eq!(
+            colorized("FOO=bar cmd"),
+            "<env-name>FOO</><punct>=</><env>bar</> <cmd>cmd</>"
+        );
+        assert_eq!(
+            colorized(r#"FOO="a b" BAR='c d' cmd"#),
+            "<env-name>FOO</><punct>=</><str>\"a b\"</> \
+            <env-name>BAR</><punct>=</><str>&#39;c d&#39;</> <cmd>cmd</>"
+        );
+        assert_eq!(
+            colorized(r#"PATH=$HOME/bin:"$PATH" cmd"#),
+            "<env-name>PATH</><punct>=</><var>$HOME</><env>/bin:</>\
+            <str>\"<var>$PATH</>\"</> <cmd>cmd</>"
+        );
+        assert_eq!(
+            colorized("A=1 B= C=$(pwd) env"),
+            "<env-name>A</><punct>=</><env>1</> <env-name>B</><punct>=</> \
+            <env-name>C</><punct>=</><punct>$(</><cmd>pwd</><punct>)</> <cmd>env</>"
+        );
+        // assignments after the command are arguments
+        assert_eq!(
+            colorized("make CC=gcc X=\"y\" --a=b"),
+            "<cmd>make</> <arg>CC=gcc</> <arg>X=</><str>\"y\"</> <flag>--a</><arg>=b</>"
+        );
+        assert_eq!(
+            colorized("X=1 a; Y=2 b"),
+            "<env-name>X</><punct>=</><env>1</> <cmd>a</><punct>;</> \
+            <env-name>Y</><punct>=</><env>2</> <cmd>b</>"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! `--lint-output`: Finds characters in the output that are rendered misaligned when the font of
+//! the page lacks them, or when their width is ambiguous.
+//!
+//! Scans [`ansi_to_html::text_fragments`], like [`binary`](crate::binary).
+
+use std::borrow::Cow;
+
+use crate::{log, opts::LintOutput};
+
+/// A kind of character that may render poorly
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// Lines and corners, e.g. in the output of `tree` or `docker ps`
+    BoxDrawing,
+    /// Wide CJK characters, which take two columns in the terminal
+    Wide,
+    Emoji,
+    /// Symbols for control characters, e.g. `␀`
+    ControlPicture,
+}
+
+impl Kind {
+    const ALL: [Kind; 4] = [
+        Kind::BoxDrawing,
+        Kind::Wide,
+        Kind::Emoji,
+        Kind::ControlPicture,
+    ];
+
+    fn of(c: char) -> Option<Self> {
+        Some(match c {
+            '\u{2500}'..='\u{257F}' => Kind::BoxDrawing,
+            '\u{2400}'..='\u{243F}' => Kind::ControlPicture,
+            '\u{2600}'..='\u{27BF}'
+            | '\u{1F1E6}'..='\u{1F1FF}'
+            | '\u{1F300}'..='\u{1F6FF}'
+            | '\u{1F900}'..='\u{1FAFF}' => Kind::Emoji,
+            '\u{1100}'..='\u{115F}'
+            | '\u{2E80}'..='\u{303E}'
+            | '\u{3041}'..='\u{A4CF}'
+            | '\u{AC00}'..='\u{D7A3}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}'
+            | '\u{FF00}'..='\u{FF60}'
+            | '\u{FFE0}'..='\u{FFE6}'
+            | '\u{20000}'..='\u{3FFFD}' => Kind::Wide,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::BoxDrawing => "box-drawing",
+            Kind::Wide => "wide CJK",
+            Kind::Emoji => "emoji",
+            Kind::ControlPicture => "control picture",
+        }
+    }
+}
+
+/// The number of characters of each [`Kind`], in the order of [`Kind::ALL`]
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Counts([usize; 4]);
+
+impl Counts {
+    fn count(output: &str) -> Self {
+        let mut counts = Counts::default();
+        for fragment in ansi_to_html::text_fragments(output) {
+            for kind in fragment.chars().filter_map(Kind::of) {
+                counts.0[kind as usize] += 1;
+            }
+        }
+        counts
+    }
+
+    fn get(&self, kind: Kind) -> usize {
+        self.0[kind as usize]
+    }
+}
+
+/// Checks the stdout and stderr of the command with the given index and prints a warning if they
+/// contain characters that may render poorly. With [`LintOutput::Fix`], box-drawing characters
+/// are replaced with ASCII.
+pub(crate) fn lint<'a>(
+    i: usize,
+    stdout: &'a str,
+    stderr: &'a str,
+    mode: LintOutput,
+) -> (Cow<'a, str>, Cow<'a, str>) {
+    let mut counts = Counts::count(stdout);
+    let stderr_counts = Counts::count(stderr);
+    for (count, n) in counts.0.iter_mut().zip(stderr_counts.0) {
+        *count += n;
+    }
+
+    let fix = mode == LintOutput::Fix && counts.get(Kind::BoxDrawing) > 0;
+    let (stdout, stderr) = if fix {
+        log::info(
+            Some(i),
+            format_args!(
+                "replaced {} box-drawing characters with ASCII",
+                counts.get(Kind::BoxDrawing)
+            ),
+        );
+        counts.0[Kind::BoxDrawing as usize] = 0;
+        (Cow::Owned(to_ascii(stdout)), Cow::Owned(to_ascii(stderr)))
+    } else {
+        (Cow::Borrowed(stdout), Cow::Borrowed(stderr))
+    };
+
+    let found: Vec<String> = (Kind::ALL.into_iter())
+        .filter(|&kind| counts.get(kind) > 0)
+        .map(|kind| format!("{} {}", counts.get(kind), kind.name()))
+        .collect();
+    if !found.is_empty() {
+        let mut message = format!(
+            "the output contains characters that may be misaligned if the page's font lacks them \
+            ({}). Use a monospace font with these glyphs, e.g. \
+            `font-family: 'DejaVu Sans Mono', monospace`",
+            found.join(", ")
+        );
+        if counts.get(Kind::BoxDrawing) > 0 {
+            message.push_str(", or pass --lint-output=fix to replace box-drawing characters");
+        }
+        log::warn(Some(i), message);
+    }
+    (stdout, stderr)
+}
+
+/// Replaces box-drawing characters with ASCII approximations: lines with `-` and `|`, and corners
+/// and junctions with `+`. Escape sequences are kept, since they don't contain any.
+fn to_ascii(output: &str) -> String {
+    output
+        .chars()
+        .map(|c| match c {
+            '─' | '━' | '┄' | '┅' | '┈' | '┉' | '╌' | '╍' | '═' | '╴' | '╶' | '╸' | '╺' | '╼'
+            | '╾' => '-',
+            '│' | '┃' | '┆' | '┇' | '┊' | '┋' | '╎' | '╏' | '║' | '╵' | '╷' | '╹' | '╻' | '╽'
+            | '╿' => '|',
+            '╱' => '/',
+            '╲' => '\\',
+            '╳' => 'X',
+            '\u{2500}'..='\u{257F}' => '+',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_ascii, Counts, Kind};
+
+    #[test]
+    fn counts() {
+        let counts = Counts::count("├── 日本 ✅ ␀\x1b[31m│\x1b[0m 🦀 plain");
+        assert_eq!(counts.get(Kind::BoxDrawing), 4);
+        assert_eq!(counts.get(Kind::Wide), 2);
+        assert_eq!(counts.get(Kind::Emoji), 2);
+        assert_eq!(counts.get(Kind::ControlPicture), 1);
+        assert_eq!(Counts::count("ascii -> |+-"), Counts::default());
+    }
+
+    #[test]
+    fn ascii_approximation() {
+        let table = "╭───┬───╮\n│ a ║ b │\n╰───┴───╯\n├── \x1b[1m└─╱╲\x1b[0m";
+        assert_eq!(
+            to_ascii(table),
+            "+---+---+\n| a | b |\n+---+---+\n+-- \x1b[1m+-/\\\x1b[0m"
+        );
+    }
+}
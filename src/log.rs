@@ -0,0 +1,143 @@
+//! Minimal logging for diagnostics printed to stderr.
+//!
+//! All messages have the form `to-html: <level>: [command N: ]<message>`, or are printed as one
+//! JSON object per line with `--log-format json`. Warnings are shown by default and can be
+//! suppressed with `--quiet`; `--verbose` enables informational messages, and passing it twice
+//! enables debug messages. Errors are always printed.
+
+use std::{
+    fmt::{self, Write},
+    sync::OnceLock,
+};
+
+use crate::opts::LogFormat;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+}
+
+struct Logger {
+    max_level: Level,
+    format: LogFormat,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Configures the logger. Messages logged before this is called use the default configuration.
+pub(crate) fn init(verbose: u8, quiet: bool, format: LogFormat) {
+    let max_level = match (quiet, verbose) {
+        (true, _) => Level::Error,
+        (false, 0) => Level::Warning,
+        (false, 1) => Level::Info,
+        (false, _) => Level::Debug,
+    };
+    let _ = LOGGER.set(Logger { max_level, format });
+}
+
+/// Logs a message. `command` is the 0-based index of the command the message refers to.
+pub(crate) fn log(level: Level, command: Option<usize>, message: impl fmt::Display) {
+    let logger = LOGGER.get_or_init(|| Logger {
+        max_level: Level::Warning,
+        format: LogFormat::Text,
+    });
+    if level > logger.max_level {
+        return;
+    }
+    eprintln!("{}", format_message(logger.format, level, command, message));
+}
+
+pub(crate) fn error(message: impl fmt::Display) {
+    log(Level::Error, None, message);
+}
+
+pub(crate) fn warn(command: Option<usize>, message: impl fmt::Display) {
+    log(Level::Warning, command, message);
+}
+
+pub(crate) fn info(command: Option<usize>, message: impl fmt::Display) {
+    log(Level::Info, command, message);
+}
+
+pub(crate) fn debug(command: Option<usize>, message: impl fmt::Display) {
+    log(Level::Debug, command, message);
+}
+
+fn format_message(
+    format: LogFormat,
+    level: Level,
+    command: Option<usize>,
+    message: impl fmt::Display,
+) -> String {
+    let mut buf = String::new();
+    match format {
+        LogFormat::Text => {
+            let _ = write!(buf, "to-html: {}: ", level.as_str());
+            if let Some(i) = command {
+                let _ = write!(buf, "command {}: ", i + 1);
+            }
+            let _ = write!(buf, "{message}");
+        }
+        LogFormat::Json => {
+            let _ = write!(buf, "{{\"level\":\"{}\"", level.as_str());
+            if let Some(i) = command {
+                let _ = write!(buf, ",\"command\":{}", i + 1);
+            }
+            let _ = write!(buf, ",\"message\":\"{}\"}}", JsonEsc(&message.to_string()));
+        }
+    }
+    buf
+}
+
+/// Escapes a string for use inside a JSON string literal.
+pub(crate) struct JsonEsc<'a>(pub(crate) &'a str);
+
+impl fmt::Display for JsonEsc<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_message, Level, LogFormat};
+
+    #[test]
+    fn text_format() {
+        let msg = format_message(LogFormat::Text, Level::Warning, Some(1), "oops");
+        assert_eq!(msg, "to-html: warning: command 2: oops");
+        let msg = format_message(LogFormat::Text, Level::Error, None, "oops");
+        assert_eq!(msg, "to-html: error: oops");
+    }
+
+    #[test]
+    fn json_format() {
+        let msg = format_message(LogFormat::Json, Level::Info, Some(0), "a \"b\"\n");
+        assert_eq!(msg, r#"{"level":"info","command":1,"message":"a \"b\"\n"}"#);
+    }
+}
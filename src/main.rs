@@ -1,30 +1,133 @@
 use ansi_to_html::Esc;
-use std::{borrow::Cow, error, fmt::Write};
+use std::{borrow::Cow, error, fmt::Write, sync::OnceLock, time::Instant};
 
+mod binary;
+mod capture;
+mod check;
 pub mod cmd;
+mod doctor;
+mod exit;
+mod gallery;
+mod jobs;
 mod lexer;
+mod lint;
+mod log;
+mod mdbook;
 mod opts;
+mod output;
 mod process;
+mod provenance;
+mod shells;
+mod show_file;
+mod size_hints;
+mod summary;
+mod toc;
+mod typescript;
 
-use opts::{Opts, ShellPrompt};
+use capture::CannedOutput;
+use exit::AppError;
+use opts::{Format, Opts, ShellPrompt};
+use size_hints::TerminalSize;
 
 pub type StdError = Box<dyn error::Error>;
 
+/// The exit status of the first command that failed, for `--passthrough-status`
+static FAILED_STATUS: OnceLock<i32> = OnceLock::new();
+
 fn main() {
-    match main_inner() {
-        Ok(_) => {}
-        Err(e) => {
-            eprintln!("{}", e);
-            std::process::exit(1);
+    if let Err(e) = main_inner() {
+        if let Some(message) = e.message() {
+            log::error(message);
         }
+        std::process::exit(e.exit_code());
     }
 }
 
-fn main_inner() -> Result<(), StdError> {
-    let opts = opts::Opts::load()?;
+fn main_inner() -> Result<(), AppError> {
+    if let Some(command) = opts::config_command() {
+        if !opts::run_config_command(command)? {
+            return Err(AppError::Exit(exit::USAGE));
+        }
+        return Ok(());
+    }
+    if let Some(json) = opts::doctor_command() {
+        if !doctor::run(json)? {
+            return Err(AppError::Exit(exit::ENVIRONMENT));
+        }
+        return Ok(());
+    }
+    if let Some(renderer) = opts::mdbook_command() {
+        if !mdbook::run(renderer.as_deref())? {
+            // mdBook only checks whether the status is 0
+            return Err(AppError::Exit(1));
+        }
+        return Ok(());
+    }
+    if let Some(dir) = opts::gallery_command() {
+        return Ok(gallery::write_gallery(&dir)?);
+    }
+
+    let opts = opts::Opts::load().map_err(AppError::Usage)?;
+    log::init(opts.verbose, opts.quiet, opts.log_format);
+
+    if opts.list_presets {
+        for name in opts::list_presets()? {
+            println!("{name}");
+        }
+        return Ok(());
+    }
+    if let Some(name) = &opts.save_preset {
+        let path = opts.save_preset(name)?;
+        log::info(
+            None,
+            format_args!("saved the preset `{name}` to {}", path.display()),
+        );
+        if opts.commands.is_empty() {
+            return Ok(());
+        }
+    }
 
+    if let Some(path) = &opts.jobs {
+        let jobs = jobs::load(path, &opts).map_err(AppError::Usage)?;
+        if !jobs::run(&jobs, opts.jobs_parallel, opts.fail_fast) {
+            return Err(AppError::Exit(1));
+        }
+        return Ok(());
+    }
+
+    let canned_outputs = if opts.typescript.is_some() {
+        Some(vec![typescript::read(&opts)?])
+    } else if !opts.input_file.is_empty() {
+        Some(capture::read_input_files(&opts)?)
+    } else {
+        None
+    };
+    let buf = render(&opts, canned_outputs.as_deref())?;
+    match &opts.output {
+        Some(path) if opts.check => {
+            check::check_output(path, &format!("{buf}\n"), opts.check_diff)?
+        }
+        Some(path) => std::fs::write(path, format!("{buf}\n"))?,
+        None => println!("{}", buf),
+    }
+
+    match FAILED_STATUS.get() {
+        Some(&status) if opts.passthrough_status => Err(AppError::command_failed(status)),
+        _ => Ok(()),
+    }
+}
+
+/// Renders the commands as HTML.
+///
+/// With `canned_outputs`, the commands aren't run. Instead, the output at the same index is shown
+/// as the output of each command.
+fn render(opts: &Opts, canned_outputs: Option<&[CannedOutput]>) -> Result<String, StdError> {
     let mut buf = String::new();
 
+    if opts.provenance {
+        provenance::write_document_comment(&mut buf)?;
+    }
+
     if opts.doc {
         let lang = std::env::var("LANG")
             .ok()
@@ -56,71 +159,281 @@ fn main_inner() -> Result<(), StdError> {
         )?;
     }
 
-    writeln!(buf, "<pre class=\"{}terminal\">", opts.prefix)?;
-
-    for command in &opts.commands {
-        if opts.no_run {
-            fmt_command_prompt(&mut buf, command, &opts)?;
-        } else {
-            fmt_command(&mut buf, command, &opts)?;
-        }
+    if opts.toc {
+        toc::write_toc(&mut buf, opts)?;
     }
+    show_file::write_files(&mut buf, opts)?;
 
-    if !opts.no_run && !opts.no_prompt {
-        shell_prompt(&mut buf, &opts)?;
-        writeln!(buf, "<span class='{p}caret'> </span>", p = opts.prefix)?;
+    let no_run = opts.no_run || opts.frame_only.is_some();
+    if opts.format == Format::GhSummary {
+        summary::write_summary(&mut buf, opts, canned_outputs)?;
+    } else if opts.shells.is_empty() || no_run || canned_outputs.is_some() {
+        write_terminal(&mut buf, opts, canned_outputs)?;
+    } else {
+        shells::write_shells(&mut buf, opts)?;
     }
-    write!(buf, "</pre>")?;
 
     if opts.doc {
         writeln!(buf, "</body>\n</html>")?;
     }
 
-    println!("{}", buf);
+    Ok(buf)
+}
 
+/// Writes the `<pre>` element with the prompts and outputs of the commands
+fn write_terminal(
+    buf: &mut String,
+    opts: &Opts,
+    canned_outputs: Option<&[CannedOutput]>,
+) -> Result<(), StdError> {
+    match opts.size_hints {
+        None => {
+            open_terminal(buf, opts, None)?;
+            write_terminal_contents(buf, opts, canned_outputs)?;
+        }
+        Some(mode) => {
+            // the size is only known after the contents are written
+            let mut contents = String::new();
+            write_terminal_contents(&mut contents, opts, canned_outputs)?;
+            let size = TerminalSize::of_html(&contents, mode);
+            open_terminal(buf, opts, Some(&size))?;
+            buf.push_str(&contents);
+        }
+    }
+    write!(buf, "</{}>", opts.format.block_element())?;
     Ok(())
 }
 
-fn fmt_command(buf: &mut String, command: &str, opts: &Opts) -> Result<(), StdError> {
-    if !opts.no_prompt {
-        fmt_command_prompt(buf, command, opts)?;
+/// Writes the prompts and outputs of the commands in the `<pre>` element of a terminal
+fn write_terminal_contents(
+    buf: &mut String,
+    opts: &Opts,
+    canned_outputs: Option<&[CannedOutput]>,
+) -> Result<(), StdError> {
+    for (i, command) in opts.commands.iter().enumerate() {
+        if opts.toc {
+            let id = toc::anchor_id(&opts.prefix, i, command);
+            write!(buf, "<span id='{}'></span>", Esc(id))?;
+        }
+        let canned_output = canned_outputs.and_then(|o| o.get(i));
+        let cmd_opts = opts.for_command(i);
+        if let Some(id) = &opts.frame_only {
+            if opts.provenance {
+                provenance::write_command_comment(buf, i, None, opts)?;
+            }
+            if !cmd_opts.no_prompt {
+                fmt_command_prompt(buf, i, command, &cmd_opts)?;
+            }
+            write_mount(buf, i, id, opts)?;
+        } else if opts.no_run && canned_output.is_none() {
+            if opts.provenance {
+                provenance::write_command_comment(buf, i, None, opts)?;
+            }
+            if !cmd_opts.no_prompt {
+                fmt_command_prompt(buf, i, command, &cmd_opts)?;
+            }
+        } else {
+            fmt_command(buf, i, command, canned_output, &cmd_opts)?;
+        }
     }
 
-    let var_prefix = if opts.prefix.is_empty() {
-        None
-    } else {
-        Some(opts.prefix.to_owned())
+    if (!opts.no_run || canned_outputs.is_some()) && !opts.no_prompt {
+        shell_prompt(buf, opts)?;
+        if opts.format == Format::Html {
+            writeln!(buf, "<span class='{p}caret'> </span>", p = opts.prefix)?;
+        } else {
+            writeln!(buf)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the empty element that replaces the output of a command with `--frame-only`. With
+/// several commands, the index of the command is appended to the id, so the ids are unique.
+fn write_mount(buf: &mut String, i: usize, id: &str, opts: &Opts) -> Result<(), StdError> {
+    let id = match opts.commands.len() {
+        1 => Cow::Borrowed(id),
+        _ => Cow::Owned(format!("{id}-{}", i + 1)),
     };
-    let converter = ansi_to_html::Converter::new().four_bit_var_prefix(var_prefix);
+    write!(buf, "<span{} id='{}'></span>", opts.class("mount"), Esc(id))?;
+    Ok(())
+}
 
-    let mut cmd = String::new();
-    let shell = opts.shell.as_deref().or_else(|| {
-        cmd = process::get_ancestor_process_cmd(1)?;
-        Some(cmd.as_str()).filter(|&n| {
-            matches!(
-                n.rsplit('/').next(),
-                Some("bash" | "sh" | "fish" | "zsh" | "csh" | "ksh" | "elvish")
-            )
-        })
-    });
+/// The style of the `<div>` that replaces the `<pre>` of a terminal or file with `--format email`.
+/// Unlike in a `<pre>`, a line break right after the opening tag would be shown, so there is none.
+pub(crate) const EMAIL_BLOCK_STYLE: &str =
+    "font-family:monospace;white-space:pre;background-color:#141414;color:#fff";
 
-    let (cmd_out, cmd_err, _) = cmd::run(command, shell)?;
-    if !cmd_out.is_empty() {
-        let html = converter.convert(&cmd_out)?;
-        write!(buf, "{}", html)?;
+/// Writes the opening `<pre>` tag of a terminal, with the size for `--size-hints`
+fn open_terminal(
+    buf: &mut String,
+    opts: &Opts,
+    size: Option<&TerminalSize>,
+) -> Result<(), StdError> {
+    let (attrs, css) = match size {
+        Some(size) => (size.to_string(), size.css()),
+        None => (String::new(), None),
+    };
+    // the CSS is appended to the inline style of the other formats
+    let append_css = css
+        .as_deref()
+        .map(|css| format!(";{css}"))
+        .unwrap_or_default();
+    match opts.format {
+        Format::Html => {
+            let style = css
+                .map(|css| format!(" style=\"{css}\""))
+                .unwrap_or_default();
+            writeln!(buf, "<pre class=\"{}terminal\"{attrs}{style}>", opts.prefix)?
+        }
+        Format::Github | Format::GhSummary => writeln!(
+            buf,
+            "<pre style=\"background-color:#141414;color:#fff{append_css}\"{attrs}>"
+        )?,
+        Format::Email => write!(
+            buf,
+            "<div style=\"{EMAIL_BLOCK_STYLE}{append_css}\"{attrs}>"
+        )?,
     }
-    if !cmd_err.is_empty() {
-        let html = converter.convert(&cmd_err)?;
-        write!(buf, "{}", html)?;
+    Ok(())
+}
+
+fn fmt_command(
+    buf: &mut String,
+    i: usize,
+    command: &str,
+    canned_output: Option<&CannedOutput>,
+    opts: &Opts,
+) -> Result<(), StdError> {
+    // the prompt is rendered before running the command, which might change the cwd
+    let mut prompt = String::new();
+    if !opts.no_prompt {
+        fmt_command_prompt(&mut prompt, i, command, opts)?;
+    }
+
+    let (cmd_out, cmd_err, run) = match canned_output {
+        Some(canned) => (canned.output.clone(), String::new(), None),
+        None => {
+            let (cmd_out, cmd_err, run) = run_command(i, command, opts)?;
+            (cmd_out, cmd_err, Some(run))
+        }
+    };
+    let timed_out = run.as_ref().is_some_and(|run| run.timed_out);
+
+    if opts.provenance {
+        provenance::write_command_comment(buf, i, run, opts)?;
+    }
+    buf.push_str(&prompt);
+    let blocks = canned_output.map_or(&[][..], |c| &c.blocks);
+    output::write_output(buf, i, &cmd_out, &cmd_err, blocks, opts)?;
+    if let Some(timeout) = opts.timeout.filter(|_| timed_out) {
+        writeln!(
+            buf,
+            "<span{}>… killed after {}s …</span>",
+            opts.class("timeout"),
+            timeout.as_secs_f64(),
+        )?;
     }
 
     Ok(())
 }
 
-fn fmt_command_prompt(buf: &mut String, command: &str, opts: &Opts) -> Result<(), StdError> {
+/// Runs the command and returns its stdout and stderr
+fn run_command(
+    i: usize,
+    command: &str,
+    opts: &Opts,
+) -> Result<(String, String, provenance::Run), StdError> {
+    let detected = opts.shell.is_none().then(detect_shell).flatten();
+    let shell = opts.shell.as_deref().or(detected.as_deref());
+
+    log::info(
+        Some(i),
+        format_args!("running `{command}` with {}", shell.unwrap_or("bash")),
+    );
+    let start = Instant::now();
+    let output = cmd::run(
+        command,
+        shell,
+        opts.timeout,
+        &opts.expect,
+        &opts.env,
+        opts.streams,
+        !opts.no_cwd_tracking,
+    )?;
+    let duration = start.elapsed();
+    if let Some(prompt) = &output.unanswered {
+        log::warn(
+            Some(i),
+            format_args!(
+                "the prompt `{prompt}` didn't appear within {}s and the command was killed",
+                opts.expect.timeout.as_secs_f64()
+            ),
+        );
+    } else if output.timed_out {
+        log::warn(
+            Some(i),
+            "the command timed out and was killed, only the output until then is shown",
+        );
+    }
+    log::debug(
+        Some(i),
+        format_args!(
+            "exit code {}, captured {} bytes of stdout and {} bytes of stderr",
+            output.exit_code.map_or("unknown".into(), |c| c.to_string()),
+            output.stdout.len(),
+            output.stderr.len()
+        ),
+    );
+    capture::write_raw(i, &output, opts);
+    if let Some(status) = output.exit_code.filter(|&code| code != 0) {
+        let _ = FAILED_STATUS.set(status);
+    }
+
+    let run = provenance::Run {
+        shell: shell.unwrap_or("bash").to_string(),
+        exit_code: output.exit_code,
+        timed_out: output.timed_out,
+        duration,
+    };
+    Ok((output.stdout, output.stderr, run))
+}
+
+/// Returns the shell `to-html` was invoked from, if it is a known shell
+fn detect_shell() -> Option<String> {
+    process::get_ancestor_process_cmd(1).filter(|cmd| {
+        matches!(
+            cmd.rsplit('/').next(),
+            Some("bash" | "sh" | "fish" | "zsh" | "csh" | "ksh" | "elvish")
+        )
+    })
+}
+
+fn fmt_command_prompt(
+    buf: &mut String,
+    i: usize,
+    command: &str,
+    opts: &Opts,
+) -> Result<(), StdError> {
     shell_prompt(buf, opts)?;
-    lexer::colorize(buf, command, opts)?;
-    writeln!(buf)?;
+
+    let mut highlighted = String::new();
+    if let Err(e) = lexer::colorize(&mut highlighted, command, opts) {
+        log::warn(
+            Some(i),
+            format_args!("syntax highlighting failed ({e}), showing the command as plain text"),
+        );
+        highlighted.clear();
+        write!(highlighted, "{}", Esc(command))?;
+    }
+    // continuation lines of multi-line commands get a secondary prompt, like `PS2` in bash
+    let continuation = format!("\n<span{}>&gt; </span>", opts.class("shell"));
+    let highlighted = highlighted.replace('\n', &continuation);
+    if opts.semantic_output {
+        writeln!(buf, "<kbd{}>{highlighted}</kbd>", opts.class("cmd-line"))?;
+    } else {
+        writeln!(buf, "{highlighted}")?;
+    }
 
     Ok(())
 }
@@ -128,30 +441,63 @@ fn fmt_command_prompt(buf: &mut String, command: &str, opts: &Opts) -> Result<()
 fn shell_prompt(buf: &mut String, opts: &Opts) -> Result<(), StdError> {
     match &opts.prompt {
         ShellPrompt::Arrow => {
-            write!(buf, "<span class='{}shell'>&gt; </span>", opts.prefix)?;
+            write!(buf, "<span{}>&gt; </span>", opts.class("shell"))?;
         }
         ShellPrompt::Cwd { home } => {
-            let cwd = std::env::current_dir()?;
+            let cwd = cmd::current_dir()?;
             let cwd = cwd.to_str().ok_or("invalid UTF-8 in cwd")?;
             let cwd = match home {
                 Some(home) => {
                     let home = home.to_str().ok_or("invalid UTF-8 in home dir")?;
-                    Cow::Owned(cwd.replace(home, "~"))
+                    abbreviate_home(cwd, home, cfg!(windows))
                 }
                 None => Cow::Borrowed(cwd),
             };
 
-            write!(
-                buf,
-                "<span class='{p}cwd'>{} </span><span class='{p}shell'>$ </span>",
-                Esc(&cwd),
-                p = opts.prefix
-            )?;
+            if cmd::is_powershell(opts.shell.as_deref()) {
+                write!(
+                    buf,
+                    "<span{}>PS </span><span{}>{}</span><span{}>&gt; </span>",
+                    opts.class("shell"),
+                    opts.class("cwd"),
+                    Esc(&cwd),
+                    opts.class("shell"),
+                )?;
+            } else {
+                write!(
+                    buf,
+                    "<span{}>{} </span><span{}>$ </span>",
+                    opts.class("cwd"),
+                    Esc(&cwd),
+                    opts.class("shell"),
+                )?;
+            }
         }
     }
     Ok(())
 }
 
+/// Replaces the home directory at the start of the path with `~`. On Windows, paths are compared
+/// case-insensitively, and both `\\` and `/` are separators.
+fn abbreviate_home<'a>(cwd: &'a str, home: &str, windows: bool) -> Cow<'a, str> {
+    let is_separator = |c: char| c == '/' || (windows && c == '\\');
+    let home = home.trim_end_matches(is_separator);
+    let Some(prefix) = cwd.get(..home.len()).filter(|_| !home.is_empty()) else {
+        return Cow::Borrowed(cwd);
+    };
+    let rest = &cwd[home.len()..];
+    let matches = if windows {
+        prefix.eq_ignore_ascii_case(home)
+    } else {
+        prefix == home
+    };
+    if matches && (rest.is_empty() || rest.starts_with(is_separator)) {
+        Cow::Owned(format!("~{rest}"))
+    } else {
+        Cow::Borrowed(cwd)
+    }
+}
+
 fn make_style(prefix: &str) -> String {
     format!(
         "
@@ -159,7 +505,15 @@ body {{
   background-color: #141414;
   color: white;
 }}
-.{p}terminal {{
+{}",
+        terminal_style(prefix)
+    )
+}
+
+/// Returns the styles of the elements emitted by `to-html`, without styles for the page
+fn terminal_style(prefix: &str) -> String {
+    format!(
+        ".{p}terminal {{
   overflow: auto;
   line-height: 120%;
 }}
@@ -192,11 +546,115 @@ body {{
   color: #d558f5;
   font-weight: bold;
 }}
+.{p}terminal kbd, .{p}terminal samp {{
+  font: inherit;
+  color: inherit;
+  background: none;
+  border: none;
+  padding: 0;
+}}
+.{p}terminal .{p}binary {{
+  color: #888;
+  font-style: italic;
+}}
+.{p}terminal .{p}timeout {{
+  color: #888;
+  font-style: italic;
+}}
+.{p}terminal .{p}timestamp {{
+  opacity: 0.5;
+  user-select: none;
+}}
 .{p}terminal .{p}caret {{
   background-color: white;
   user-select: none;
 }}
+.{p}terminal .{p}hl-line {{
+  display: inline-block;
+  min-width: 100%;
+  background-color: rgba(255, 255, 255, 0.12);
+}}
+
+.{p}file {{
+  overflow: auto;
+  line-height: 120%;
+}}
+.{p}file .{p}file-name {{
+  color: #888;
+  font-weight: bold;
+  user-select: none;
+}}
+.{p}file .{p}file-truncated {{
+  color: #888;
+  font-style: italic;
+}}
+
+.{p}shells {{
+  display: flex;
+  gap: 1em;
+  align-items: flex-start;
+}}
+.{p}shells > div {{
+  flex: 1;
+  min-width: 0;
+}}
+.{p}shells .{p}shell-name {{
+  color: #888;
+  font-family: monospace;
+  font-weight: bold;
+}}
+.{p}shells .{p}shell-missing {{
+  color: #888;
+  font-style: italic;
+}}
+
+.{p}toc ol {{
+  margin: 0 0 1em 0;
+  padding-left: 1.5em;
+}}
+.{p}toc a {{
+  color: #419df3;
+  text-decoration: none;
+}}
+.{p}toc a:hover {{
+  text-decoration: underline;
+}}
 ",
         p = prefix,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::abbreviate_home;
+
+    #[test]
+    fn home_abbreviation() {
+        assert_eq!(
+            abbreviate_home("/home/me/project", "/home/me", false),
+            "~/project"
+        );
+        assert_eq!(abbreviate_home("/home/me", "/home/me/", false), "~");
+        assert_eq!(abbreviate_home("/home/me2", "/home/me", false), "/home/me2");
+        assert_eq!(
+            abbreviate_home("/tmp/home/me", "/home/me", false),
+            "/tmp/home/me"
+        );
+        assert_eq!(abbreviate_home("/HOME/ME", "/home/me", false), "/HOME/ME");
+        assert_eq!(abbreviate_home("/etc", "/", false), "/etc");
+
+        assert_eq!(
+            abbreviate_home(r"c:\users\Me\project", r"C:\Users\me", true),
+            r"~\project"
+        );
+        assert_eq!(abbreviate_home(r"C:\Users\me", r"C:\Users\me\", true), "~");
+        assert_eq!(
+            abbreviate_home(r"C:\Users\meme", r"C:\Users\me", true),
+            r"C:\Users\meme"
+        );
+        assert_eq!(
+            abbreviate_home(r"D:\Users\me", r"C:\Users\me", true),
+            r"D:\Users\me"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! `to-html mdbook`: An [mdBook preprocessor](https://rust-lang.github.io/mdBook/for_developers/preprocessors.html)
+//! that replaces code blocks tagged `to-html` with the HTML of their commands.
+//!
+//! mdBook passes the context and the book as JSON array on stdin, and expects the modified book on
+//! stdout. The book is handled as untyped JSON, so only the `content` of the chapters is
+//! interpreted, and fields added by newer mdBook versions are preserved.
+
+use std::{env, io, ops::Range};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    opts::{self, Opts, ShellPrompt},
+    StdError,
+};
+
+/// The options in the `[preprocessor.to-html]` table of `book.toml`
+#[derive(Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct Config {
+    /// Prefix for CSS classes and variables, like `--prefix`
+    prefix: Option<String>,
+    shell: Option<String>,
+    /// Print the current working directory in the command prompt, like `--cwd`
+    cwd: bool,
+    /// Programs whose subcommands are highlighted, like `--highlight`
+    highlight: Vec<String>,
+    /// Whether a `<style>` element is added to chapters containing terminals
+    stylesheet: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prefix: None,
+            shell: None,
+            cwd: false,
+            highlight: vec!["cargo".into()],
+            stylesheet: true,
+        }
+    }
+}
+
+impl Config {
+    fn to_opts(&self) -> Result<Opts, StdError> {
+        let prefix = match &self.prefix {
+            Some(prefix) => opts::parse_prefix(prefix)?,
+            None => String::new(),
+        };
+        let prompt = if self.cwd {
+            ShellPrompt::Cwd {
+                home: dirs_next::home_dir(),
+            }
+        } else {
+            ShellPrompt::Arrow
+        };
+        Ok(Opts {
+            shell: self.shell.clone(),
+            highlight: self.highlight.clone(),
+            prefix: if prefix.is_empty() {
+                prefix
+            } else {
+                format!("{prefix}-")
+            },
+            prompt,
+            ..Opts::default()
+        })
+    }
+}
+
+/// Runs the preprocessor. With a renderer, only checks whether it is supported. Returns `false`
+/// if it isn't.
+pub(crate) fn run(supports: Option<&str>) -> Result<bool, StdError> {
+    if let Some(renderer) = supports {
+        // the HTML is only useful in HTML output
+        return Ok(renderer == "html");
+    }
+
+    let (context, mut book): (Value, Value) = serde_json::from_reader(io::stdin().lock())
+        .map_err(|e| format!("invalid input from mdBook: {e}"))?;
+    let config = match context.pointer("/config/preprocessor/to-html") {
+        Some(table) => Config::deserialize(table)
+            .map_err(|e| format!("invalid `[preprocessor.to-html]` table: {e}"))?,
+        None => Config::default(),
+    };
+    let opts = config.to_opts()?;
+    // commands are run in the directory containing `book.toml`
+    if let Some(root) = context.get("root").and_then(Value::as_str) {
+        env::set_current_dir(root)?;
+    }
+    let stylesheet = config
+        .stylesheet
+        .then(|| format!("<style>\n{}</style>\n", book_style(&opts.prefix)));
+
+    let mut result = Ok(());
+    for_each_chapter(&mut book, &mut |name, content| {
+        if result.is_ok() {
+            result = preprocess(content, &opts, stylesheet.as_deref())
+                .map(|new| *content = new)
+                .map_err(|e| format!("chapter `{name}`: {e}"));
+        }
+    });
+    result?;
+
+    serde_json::to_writer(io::stdout().lock(), &book)?;
+    Ok(true)
+}
+
+/// Calls `f` with the name and content of every chapter, including nested chapters
+fn for_each_chapter(value: &mut Value, f: &mut impl FnMut(&str, &mut String)) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Object(chapter)) = object.get_mut("Chapter") {
+                let name = chapter.get("name").and_then(Value::as_str).unwrap_or("");
+                let name = name.to_string();
+                if let Some(Value::String(content)) = chapter.get_mut("content") {
+                    f(&name, content);
+                }
+            }
+            object.values_mut().for_each(|v| for_each_chapter(v, f));
+        }
+        Value::Array(array) => array.iter_mut().for_each(|v| for_each_chapter(v, f)),
+        _ => {}
+    }
+}
+
+/// Replaces the `to-html` code blocks in the Markdown with the HTML of their commands. The
+/// stylesheet is added before the first one.
+fn preprocess(content: &str, opts: &Opts, stylesheet: Option<&str>) -> Result<String, StdError> {
+    let mut out = String::with_capacity(content.len());
+    let mut stylesheet = stylesheet;
+    let mut end = 0;
+    for block in code_blocks(content) {
+        let mut words = block.info.split([',', ' ', '\t']).filter(|w| !w.is_empty());
+        if words.next() != Some("to-html") {
+            continue;
+        }
+        let mut opts = Opts {
+            commands: (content[block.body].lines())
+                .map(str::trim_end)
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect(),
+            ..opts.clone()
+        };
+        for word in words {
+            match word {
+                "no-run" => opts.no_run = true,
+                _ => return Err(format!("unknown attribute `{word}` of a `to-html` block").into()),
+            }
+        }
+
+        out.push_str(&content[end..block.range.start]);
+        end = block.range.end;
+        if let Some(stylesheet) = stylesheet.take() {
+            out.push_str(stylesheet);
+        }
+        out.push_str(&crate::render(&opts, None)?);
+        out.push('\n');
+    }
+    out.push_str(&content[end..]);
+    Ok(out)
+}
+
+/// A fenced code block in Markdown
+struct CodeBlock<'a> {
+    /// The range of the block, including the fences and the line break after the closing fence
+    range: Range<usize>,
+    /// The info string after the opening fence, e.g. `rust,ignore`
+    info: &'a str,
+    /// The range of the lines between the fences
+    body: Range<usize>,
+}
+
+/// Returns the fenced code blocks of the Markdown. Code blocks in other code blocks are ignored.
+fn code_blocks(content: &str) -> Vec<CodeBlock<'_>> {
+    let mut blocks = Vec::new();
+    // the opening fence of the current block, its start, its info string and the body start
+    let mut open: Option<(&str, usize, &str, usize)> = None;
+    let mut pos = 0;
+    for line in content.split_inclusive('\n') {
+        let start = pos;
+        pos += line.len();
+        match (open, fence(line)) {
+            // backticks in the info string of a backtick fence make it inline code
+            (None, Some((fence, info))) if !(fence.starts_with('`') && info.contains('`')) => {
+                open = Some((fence, start, info, pos));
+            }
+            (Some((opening, block_start, info, body_start)), Some((fence, "")))
+                if fence.starts_with(&opening[..1]) && fence.len() >= opening.len() =>
+            {
+                blocks.push(CodeBlock {
+                    range: block_start..pos,
+                    info,
+                    body: body_start..start,
+                });
+                open = None;
+            }
+            _ => {}
+        }
+    }
+    // a block that isn't closed ends at the end of the document
+    if let Some((_, block_start, info, body_start)) = open {
+        blocks.push(CodeBlock {
+            range: block_start..content.len(),
+            info,
+            body: body_start..content.len(),
+        });
+    }
+    blocks
+}
+
+/// Returns the fence and the info string, if the line is a code fence: at least three backticks or
+/// tildes, indented by at most three spaces.
+fn fence(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let c = trimmed.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = trimmed.len() - trimmed.trim_start_matches(c).len();
+    (len >= 3).then(|| (&trimmed[..len], trimmed[len..].trim()))
+}
+
+/// Returns the stylesheet added to chapters. mdBook's themes have light backgrounds as well, so
+/// the terminal gets its own background.
+fn book_style(prefix: &str) -> String {
+    format!(
+        ".{prefix}terminal {{
+  background-color: #141414;
+  color: white;
+}}
+{}",
+        crate::terminal_style(prefix)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::code_blocks;
+
+    #[test]
+    fn fenced_code_blocks() {
+        let content = "text\n```to-html\nls\n```\n  ~~~~ rust\n```to-html\n~~~\n~~~~~\n````md\n\
+                       ```to-html,no-run\nnested\n```\n````\n```to-html\nunclosed";
+        let blocks: Vec<_> = code_blocks(content)
+            .into_iter()
+            .map(|b| (&content[b.range], b.info, &content[b.body]))
+            .collect();
+        assert_eq!(
+            blocks,
+            [
+                ("```to-html\nls\n```\n", "to-html", "ls\n"),
+                (
+                    "  ~~~~ rust\n```to-html\n~~~\n~~~~~\n",
+                    "rust",
+                    "```to-html\n~~~\n"
+                ),
+                (
+                    "````md\n```to-html,no-run\nnested\n```\n````\n",
+                    "md",
+                    "```to-html,no-run\nnested\n```\n"
+                ),
+                ("```to-html\nunclosed", "to-html", "unclosed"),
+            ]
+        );
+    }
+}
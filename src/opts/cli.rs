@@ -1,7 +1,92 @@
-use clap::Parser;
+use std::{ffi::OsString, num::NonZeroUsize, time::Duration};
 
-pub fn parse() -> Cli {
-    Cli::parse()
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+
+/// Parses the arguments. The arguments after each `--then` are a command group, which is parsed
+/// separately with [`CommandCli`].
+pub fn parse() -> (Cli, Vec<CommandCli>) {
+    let mut groups = split_groups(std::env::args_os());
+    let cli = Cli::parse_from(groups.remove(0));
+    let command_groups = groups
+        .into_iter()
+        .map(|args| CommandCli::parse_from(std::iter::once("to-html --then".into()).chain(args)))
+        .collect();
+    (cli, command_groups)
+}
+
+/// Splits the arguments at every `--then`. The first group contains the binary name.
+fn split_groups(args: impl IntoIterator<Item = OsString>) -> Vec<Vec<OsString>> {
+    let mut groups = vec![Vec::new()];
+    for arg in args {
+        if arg == "--then" {
+            groups.push(Vec::new());
+        } else if let Some(group) = groups.last_mut() {
+            group.push(arg);
+        }
+    }
+    groups
+}
+
+/// Returns the `config` subcommand, if the arguments are exactly `config <COMMAND>`.
+///
+/// The subcommand is only recognized when there are no other arguments, so `to-html config` or
+/// `to-html config path -d` still run the given commands.
+pub fn parse_config_command() -> Option<ConfigCommand> {
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+    match args.as_slice() {
+        [config, command]
+            if config == "config"
+                && command.to_str().is_some_and(ConfigCommand::has_subcommand) =>
+        {
+            Some(ConfigCli::parse_from(args).command)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the output directory, if the arguments are exactly `gallery --output <DIR>`.
+///
+/// This hidden subcommand is used to generate the gallery in the documentation. Like the `config`
+/// subcommand, it is only recognized when there are no other arguments.
+pub fn parse_gallery_command() -> Option<std::path::PathBuf> {
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+    match args.as_slice() {
+        [gallery, output, _] if gallery == "gallery" && output == "--output" => {
+            Some(GalleryCli::parse_from(args).output)
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether `--json` was passed, if the arguments are exactly `doctor` or `doctor --json`.
+///
+/// Like the `config` subcommand, this is only recognized when there are no other arguments.
+pub fn parse_doctor_command() -> Option<bool> {
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+    match args.as_slice() {
+        [doctor, rest @ ..] if doctor == "doctor" && matches!(rest, [] | [_]) => rest
+            .iter()
+            .all(|arg| arg == "--json")
+            .then(|| DoctorCli::parse_from(&args).json),
+        _ => None,
+    }
+}
+
+/// Returns the renderer whose support is checked, if the arguments are exactly `mdbook` or
+/// `mdbook supports <RENDERER>`.
+///
+/// Like the `config` subcommand, this is only recognized when there are no other arguments.
+pub fn parse_mdbook_command() -> Option<Option<String>> {
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+    match args.as_slice() {
+        [mdbook] | [mdbook, _, _] if mdbook == "mdbook" => {
+            match MdbookCli::parse_from(&args).command {
+                Some(MdbookCommand::Supports { renderer }) => Some(Some(renderer)),
+                None => Some(None),
+            }
+        }
+        _ => None,
+    }
 }
 
 #[derive(Parser)]
@@ -15,23 +100,68 @@ pub fn parse() -> Cli {
 {author-with-newline}{about-with-newline}
 {usage-heading} {usage}
 
-{all-args}{after-help}"
+{all-args}{after-help}",
+    after_help = "Options for individual commands can be given after `--then`, e.g. \
+        `to-html \"cargo build\" --then --timeout 5 \"cargo test\"`. They override the \
+        options above for the commands that follow, up to the next `--then`. Supported are \
+        --timeout, --no-prompt, --cwd, --hl-lines and --allow-binary.
+
+Run `to-html config <path|show|check>` to inspect the config file, or `to-html doctor` to check \
+        that commands can be run."
 )]
 pub struct Cli {
-    /// The command(s) to execute. Must be wrapped in quotes.
-    #[arg(required = true)]
+    /// The command(s) to execute. Must be wrapped in quotes. If none are given, the `commands`
+    /// from the config file are used
     pub commands: Vec<String>,
+    /// Kill commands that run longer than this many seconds. The output printed until then is
+    /// shown, followed by a note
+    #[arg(long, value_name = "SECS", value_parser = parse_timeout, conflicts_with = "no_run")]
+    pub timeout: Option<Duration>,
+    /// Answer a prompt: When the output matches the regex, the response is typed, followed by a
+    /// line break. The response is replaced with `********` in the output. Can be repeated to
+    /// answer several prompts in order. A `:` in the regex is escaped as `\:`, e.g.
+    /// `--expect 'Password\::hunter2'`
+    #[arg(
+        long,
+        value_name = "REGEX:RESPONSE",
+        value_parser = parse_expect,
+        conflicts_with_all = ["no_run", "input_file", "typescript"]
+    )]
+    pub expect: Vec<(String, String)>,
+    /// Kill commands that print nothing for this many seconds while waiting for a prompt of
+    /// `--expect`. Defaults to 10
+    #[arg(long, value_name = "SECS", value_parser = parse_timeout, requires = "expect")]
+    pub expect_timeout: Option<Duration>,
+    /// Exit with the status of the first command that failed, after writing the HTML. Statuses
+    /// below 4 become 4, so they can't be confused with the exit codes of to-html itself
+    #[arg(long, conflicts_with = "no_run")]
+    pub passthrough_status: bool,
+    /// Which output of the commands to show. The other stream is discarded, while the one that
+    /// is shown is still written to the terminal, so commands print colors as usual. Only
+    /// supported in POSIX shells and fish
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        conflicts_with_all = ["no_run", "input_file", "typescript"]
+    )]
+    pub streams: Streams,
     /// The shell to run the command in. On macOS and FreeBSD, the shell has to support
     /// `-c <command>`
     #[arg(short, long)]
     pub shell: Option<String>,
+    /// Run each command once per shell, and show the results side by side. Multiple shells are
+    /// separated with a comma, e.g. `--shells bash,zsh,fish`
+    #[arg(long, value_delimiter = ',', conflicts_with = "shell")]
+    pub shells: Vec<String>,
     /// Programs that have subcommands (which should be highlighted). Multiple arguments are
-    /// separated with a comma, e.g. `to-html -l git,cargo,npm "git checkout main"`
+    /// separated with a comma, e.g. `to-html -l git,cargo,npm "git checkout main"`. Defaults to
+    /// `cargo`
     #[arg(short = 'l', long, value_delimiter = ',')]
     pub highlight: Option<Vec<String>>,
-    /// Prefix for CSS classes. For example, with the `to-html` prefix, the `arg` class becomes
-    /// `to-html-arg`
-    #[arg(short, long)]
+    /// Prefix for CSS classes and variables. For example, with the `to-html` prefix, the `arg`
+    /// class becomes `to-html-arg`. It may contain letters, digits, `-` and `_`
+    #[arg(short, long, value_parser = parse_prefix)]
     pub prefix: Option<String>,
     /// Do not run the commands, just emit the HTML for the command prompt
     #[arg(short = 'n', long, group = "no_prompt_or_run")]
@@ -39,10 +169,429 @@ pub struct Cli {
     /// Do not show the command prompt
     #[arg(short = 'N', long, group = "no_prompt_or_run")]
     pub no_prompt: bool,
+    /// Don't run the commands, emit the terminal with the prompts and an empty
+    /// `<span class='mount' id='ID'>` element where the output of each command would be, e.g. for
+    /// a live demo that writes into it. With several commands, the ids are `ID-1`, `ID-2` etc.
+    #[arg(
+        long,
+        value_name = "ID",
+        value_parser = parse_mount_id,
+        conflicts_with_all = [
+            "no_run",
+            "input_file",
+            "typescript",
+            "shells",
+            "timeout",
+            "expect",
+            "capture_raw",
+            "passthrough_status",
+            "no_cwd_tracking",
+            "streams",
+        ]
+    )]
+    pub frame_only: Option<String>,
     /// Print the (abbreviated) current working directory in the command prompt
     #[arg(short, long)]
     pub cwd: bool,
+    /// Run the commands exactly as given. By default, the exit code and working directory are
+    /// printed after each command, so a `cd` applies to the commands that follow. With this flag,
+    /// every command runs in the directory to-html was started in
+    #[arg(long, conflicts_with = "no_run")]
+    pub no_cwd_tracking: bool,
     /// Output a complete HTML document, not just a `<pre>`
     #[arg(short, long)]
     pub doc: bool,
+    /// Emit a table of contents with a link to each command before the `<pre>`
+    #[arg(long)]
+    pub toc: bool,
+    /// Wrap the commands in `<kbd>` elements and their output in `<samp>` elements, which mark
+    /// up user input and program output
+    #[arg(long)]
+    pub semantic_output: bool,
+    /// The output format. `github` emits inline styles that survive GitHub's HTML sanitizer,
+    /// `email` additionally avoids `<pre>` elements, which some email clients mangle, and
+    /// `gh-summary` emits a GitHub Actions job summary
+    #[arg(long, value_enum, default_value_t, conflicts_with_all = ["doc", "toc"])]
+    pub format: Format,
+    /// Don't run the commands, show the contents of the file as their output instead. Must be
+    /// passed once per command. CRLF line endings are converted to LF
+    #[arg(long, value_name = "PATH", conflicts_with = "no_run")]
+    pub input_file: Vec<std::path::PathBuf>,
+    /// Don't run the command, show the session recorded with `script` in the file as its output
+    /// instead. The `Script started` and `Script done` lines are removed, and CRLF line endings
+    /// are converted to LF
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["no_run", "input_file"])]
+    pub typescript: Option<std::path::PathBuf>,
+    /// The timing file of the `--typescript` session, recorded with `script --timing=<PATH>`.
+    /// The session is split into blocks at pauses of 2 seconds or more
+    #[arg(long, value_name = "PATH", requires = "typescript")]
+    pub timing: Option<std::path::PathBuf>,
+    /// Show the time since the start of the session before each block split by `--timing`
+    #[arg(long, requires = "timing", conflicts_with = "hl_lines")]
+    pub timestamps: bool,
+    /// Show the contents of a file before the commands, e.g. a file the commands read. Can be
+    /// repeated. Files larger than 32 KiB are truncated
+    #[arg(long, value_name = "PATH")]
+    pub show_file: Vec<std::path::PathBuf>,
+    /// Highlight the files shown with `--show-file` as the given language, e.g. `json`. Requires
+    /// the `syntect` feature
+    #[arg(long, value_name = "LANG", requires = "show_file")]
+    pub show_file_lang: Option<String>,
+    /// Render output that looks like binary data, instead of replacing it with a placeholder
+    #[arg(long)]
+    pub allow_binary: bool,
+    /// Warn about characters in the output that many monospace fonts render misaligned, e.g.
+    /// box drawing, wide CJK characters and emoji. With `--lint-output=fix`, box-drawing
+    /// characters are replaced with ASCII
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "warn"
+    )]
+    pub lint_output: Option<LintOutput>,
+    /// Add the number of columns and rows of the terminal as `data-cols` and `data-rows`
+    /// attributes, e.g. to size containers for screenshot comparisons. Wide characters take 2
+    /// columns. With `--size-hints=css`, the terminal gets a `min-width` as well
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "data"
+    )]
+    pub size_hints: Option<SizeHints>,
+    /// Save the raw bytes each command writes to the terminal, followed by its stderr. With
+    /// several commands, this is a directory containing `command-<N>.ansi` files
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["no_run", "input_file", "typescript"]
+    )]
+    pub capture_raw: Option<std::path::PathBuf>,
+    /// Write the HTML to a file instead of printing it
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<std::path::PathBuf>,
+    /// Don't write the `--output` file, but check that it is up to date. If it differs, the path
+    /// is listed and the exit code is 1. Implies `--stable`
+    #[arg(long, requires = "output")]
+    pub check: bool,
+    /// Print a unified diff of the changes when `--check` fails
+    #[arg(long, requires = "check")]
+    pub check_diff: bool,
+    /// Run the jobs in the TOML or YAML file instead of the commands. Each job has its own
+    /// commands and output file, the other options apply to all jobs. A summary is printed at the
+    /// end
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "commands",
+            "output",
+            "input_file",
+            "typescript",
+            "capture_raw",
+            "passthrough_status",
+            "save_preset",
+        ]
+    )]
+    pub jobs: Option<std::path::PathBuf>,
+    /// Run up to this many jobs of `--jobs` at the same time. Defaults to 1
+    #[arg(long, value_name = "N", requires = "jobs")]
+    pub jobs_parallel: Option<NonZeroUsize>,
+    /// Don't start any more jobs of `--jobs` after one failed
+    #[arg(long, requires = "jobs")]
+    pub fail_fast: bool,
+    /// Emit HTML comments with the to-html invocation, and the time, shell, exit code and
+    /// duration of each command
+    #[arg(long)]
+    pub provenance: bool,
+    /// Make the output deterministic by replacing timestamps and durations with placeholders
+    #[arg(long)]
+    pub stable: bool,
+    /// Show the intermediate stages of pipelines: `a | b | c` is shown as three commands, `a`,
+    /// `a | b` and `a | b | c`
+    #[arg(long)]
+    pub explode_pipeline: bool,
+    /// Highlight lines of the output, e.g. `3,7-9`. Line numbers start at 1 for each command
+    #[arg(long, value_delimiter = ',', value_parser = parse_line_range)]
+    pub hl_lines: Vec<LineRange>,
+    /// Print more diagnostics to stderr. Can be repeated for even more detail
+    #[arg(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Don't print warnings to stderr, only errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Format of the diagnostics printed to stderr
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+    /// Load the options saved with `--save-preset`. They override the config file, and are
+    /// overridden by the command line
+    #[arg(long, value_name = "NAME", value_parser = parse_preset_name)]
+    pub preset: Option<String>,
+    /// Save the options, except for the commands, as a preset that can be loaded with `--preset`.
+    /// If no commands are given, none are run
+    #[arg(long, value_name = "NAME", value_parser = parse_preset_name)]
+    pub save_preset: Option<String>,
+    /// List the saved presets
+    #[arg(long, exclusive = true)]
+    pub list_presets: bool,
+}
+
+/// Options for the commands after a `--then`. They override the global options
+#[derive(Parser)]
+#[command(name = "to-html --then")]
+pub struct CommandCli {
+    /// The command(s) to execute. Must be wrapped in quotes
+    #[arg(required = true)]
+    pub commands: Vec<String>,
+    /// Kill the commands if they run longer than this many seconds
+    #[arg(long, value_name = "SECS", value_parser = parse_timeout)]
+    pub timeout: Option<Duration>,
+    /// Do not show the command prompt
+    #[arg(short = 'N', long)]
+    pub no_prompt: bool,
+    /// Print the (abbreviated) current working directory in the command prompt
+    #[arg(short, long)]
+    pub cwd: bool,
+    /// Highlight lines of the output, e.g. `3,7-9`
+    #[arg(long, value_delimiter = ',', value_parser = parse_line_range)]
+    pub hl_lines: Vec<LineRange>,
+    /// Render output that looks like binary data, instead of replacing it with a placeholder
+    #[arg(long)]
+    pub allow_binary: bool,
+}
+
+/// Render a gallery of example outputs with various options
+#[derive(Parser)]
+#[command(name = "to-html gallery")]
+pub struct GalleryCli {
+    /// The directory where `index.html` is written
+    #[arg(long)]
+    pub output: std::path::PathBuf,
+}
+
+/// Check that the shell, the `script` command and the config file work
+#[derive(Parser)]
+#[command(name = "to-html doctor")]
+pub struct DoctorCli {
+    /// Print the results as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Run as mdBook preprocessor, which reads the book as JSON from stdin and replaces `to-html` code
+/// blocks with the HTML
+#[derive(Parser)]
+#[command(name = "to-html mdbook")]
+pub struct MdbookCli {
+    #[command(subcommand)]
+    pub command: Option<MdbookCommand>,
+}
+
+#[derive(Subcommand)]
+pub enum MdbookCommand {
+    /// Exit with code 0 if the renderer is supported
+    Supports { renderer: String },
+}
+
+/// Inspect the configuration file
+#[derive(Parser)]
+#[command(name = "to-html config")]
+pub struct ConfigCli {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the path of the config file
+    Path,
+    /// Print the effective configuration as TOML
+    Show,
+    /// Check the config file for invalid values and unknown keys
+    Check,
+}
+
+/// An inclusive range of 1-based line numbers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LineRange {
+    pub fn contains(&self, line: usize) -> bool {
+        self.start <= line && line <= self.end
+    }
+}
+
+impl std::fmt::Display for LineRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+fn parse_line_range(s: &str) -> Result<LineRange, String> {
+    let parse = |n: &str| match n.trim().parse::<usize>() {
+        Ok(0) => Err("line numbers start at 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(format!("invalid line number {n:?}: {e}")),
+    };
+    let (start, end) = match s.split_once('-') {
+        Some((start, end)) => (parse(start)?, parse(end)?),
+        None => (parse(s)?, parse(s)?),
+    };
+    if start > end {
+        return Err(format!("invalid range {s:?}: start is greater than end"));
+    }
+    Ok(LineRange { start, end })
+}
+
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs > 0.0 => {
+            Duration::try_from_secs_f64(secs).map_err(|e| format!("invalid timeout {s:?}: {e}"))
+        }
+        Ok(_) => Err("the timeout must be positive".to_string()),
+        Err(e) => Err(format!("invalid number of seconds {s:?}: {e}")),
+    }
+}
+
+/// Splits an `--expect` argument at the first `:` that isn't escaped with a backslash
+pub fn parse_expect(s: &str) -> Result<(String, String), String> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            ':' if !escaped => {
+                if i == 0 {
+                    return Err("the prompt regex must not be empty".to_string());
+                }
+                return Ok((s[..i].to_string(), s[i + 1..].to_string()));
+            }
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    Err(format!("expected `REGEX:RESPONSE`, got {s:?}"))
+}
+
+/// Checks that the preset name can be used as a file name
+fn parse_preset_name(s: &str) -> Result<String, String> {
+    if s.is_empty() {
+        return Err("the preset name must not be empty".to_string());
+    }
+    if let Some(c) = s
+        .chars()
+        .find(|&c| !c.is_alphanumeric() && !matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!(
+            "{c:?} is not allowed in a preset name, only letters, digits, `-`, `_` and `.`"
+        ));
+    }
+    if s.starts_with('.') {
+        return Err("a preset name must not start with `.`".to_string());
+    }
+    Ok(s.to_string())
+}
+
+/// Validates a CSS prefix, which must be a valid CSS identifier. Surrounding whitespace and trailing
+/// hyphens are removed, since a hyphen is added between the prefix and the class name. An empty
+/// prefix means that no prefix is used.
+/// Parses the id of the elements emitted by `--frame-only`. Like every HTML id, it must not be
+/// empty or contain whitespace.
+fn parse_mount_id(s: &str) -> Result<String, String> {
+    if s.is_empty() || s.contains(char::is_whitespace) {
+        return Err("the id must not be empty or contain whitespace".to_string());
+    }
+    Ok(s.to_string())
+}
+
+pub fn parse_prefix(s: &str) -> Result<String, String> {
+    let prefix = s.trim().trim_end_matches('-');
+    if let Some(c) = prefix
+        .chars()
+        .find(|&c| !c.is_alphanumeric() && c != '-' && c != '_')
+    {
+        return Err(format!(
+            "{c:?} is not allowed in a CSS prefix, only letters, digits, `-` and `_`"
+        ));
+    }
+    let rest = prefix.strip_prefix('-').unwrap_or(prefix);
+    if rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err("a CSS prefix must not start with a digit".to_string());
+    }
+    if rest.starts_with('-') {
+        return Err("a CSS prefix must not start with `--`".to_string());
+    }
+    Ok(prefix.to_string())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// HTML with CSS classes, styled by the stylesheet
+    #[default]
+    Html,
+    /// HTML with inline styles and without classes, for GitHub READMEs and issues
+    Github,
+    /// Like `github`, but in a `<div>` instead of a `<pre>`, for pasting into emails
+    Email,
+    /// Like `github`, but with a collapsible `<details>` block per command, for appending to the
+    /// job summary of a GitHub Actions workflow. The output is truncated to the 1 MiB limit of
+    /// job summaries
+    GhSummary,
+}
+
+impl Format {
+    /// Returns the element that contains a terminal or a file. Some email clients change the
+    /// whitespace and font of a `<pre>`, so it's a `<div>` with `--format email`.
+    pub fn block_element(self) -> &'static str {
+        match self {
+            Format::Html | Format::Github | Format::GhSummary => "pre",
+            Format::Email => "div",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LintOutput {
+    /// Only print a warning
+    Warn,
+    /// Replace box-drawing characters with ASCII, and warn about the others
+    Fix,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Streams {
+    /// Show stdout and stderr, in the order in which they were written
+    #[default]
+    Both,
+    /// Show only stdout, and discard stderr
+    Stdout,
+    /// Show only stderr, and discard stdout
+    Stderr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SizeHints {
+    /// Only add the `data-cols` and `data-rows` attributes
+    Data,
+    /// Add the attributes and a `min-width` in `ch` units
+    Css,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Messages like `to-html: warning: ...`
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
 }
@@ -1,6 +1,11 @@
-use std::{fs, io, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use toml::Spanned;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -10,46 +15,338 @@ pub enum Error {
     Io(#[from] io::Error),
     #[error("Config file {0} has invalid format: {1}")]
     Parsing(PathBuf, toml::de::Error),
+    #[error("Preset `{0}` not found at {1}")]
+    PresetNotFound(String, PathBuf),
 }
 
-pub fn load() -> Result<Config, Error> {
-    let to_html_config = dirs_next::config_dir()
+/// Returns the path of the config file, which may not exist
+pub fn path() -> Result<PathBuf, Error> {
+    Ok(dirs_next::config_dir()
         .ok_or(Error::ConfigDetection)?
         .join("to-html")
-        .join("config.toml");
+        .join("config.toml"))
+}
+
+pub fn load() -> Result<Config, Error> {
+    load_with_preset(None)
+}
+
+/// Loads the config file. The values in the preset with the given name, if any, take priority over
+/// the ones in the config file.
+pub fn load_with_preset(preset: Option<&str>) -> Result<Config, Error> {
+    let config_path = path()?;
+    let mut table = read_table(&config_path)?.unwrap_or_default();
+    if let Some(name) = preset {
+        let path = preset_path(name)?;
+        let Some(preset) = read_table(&path)? else {
+            return Err(Error::PresetNotFound(name.to_string(), path));
+        };
+        merge(&mut table, preset);
+    }
+    table.try_into().map_err(|e| Error::Parsing(config_path, e))
+}
+
+/// Returns the directory containing the presets saved with `--save-preset`
+pub fn presets_dir() -> Result<PathBuf, Error> {
+    Ok(path()?.with_file_name("presets"))
+}
+
+/// Returns the path of the preset with the given name, which may not exist
+pub fn preset_path(name: &str) -> Result<PathBuf, Error> {
+    Ok(presets_dir()?.join(format!("{name}.toml")))
+}
+
+/// Returns the names of the saved presets, sorted
+pub fn list_presets() -> Result<Vec<String>, Error> {
+    let entries = match fs::read_dir(presets_dir()?) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Reads a config file or preset as a table, after checking that it is valid. Returns `None` if it
+/// doesn't exist.
+fn read_table(path: &Path) -> Result<Option<toml::Table>, Error> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let parsing_error = |e| Error::Parsing(path.to_owned(), e);
+    toml::from_str::<Config>(&contents).map_err(parsing_error)?;
+    toml::from_str(&contents).map(Some).map_err(parsing_error)
+}
+
+/// Merges `overrides` into `table`. Sections are merged key by key, other values are replaced.
+fn merge(table: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(toml::Value::Table(section)), toml::Value::Table(value)) => merge(section, value),
+            (_, value) => _ = table.insert(key, value),
+        }
+    }
+}
+
+/// A problem found by [`check`]
+#[derive(Debug)]
+pub struct Problem {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// The keys that are allowed outside of sections
+const TOP_LEVEL_KEYS: &[&str] = &["commands"];
+
+/// The keys that are allowed in each section of the config file
+const KNOWN_KEYS: &[(&str, &[&str])] = &[
+    ("shell", &["program", "programs"]),
+    (
+        "output",
+        &["cwd", "full_document", "highlight", "css_prefix"],
+    ),
+    // the keys of this section are program names, see `RULE_KEYS`
+    ("highlight", &[]),
+];
+
+/// The keys that are allowed in a `[highlight.<program>]` section
+const RULE_KEYS: &[&str] = &["depth", "skip"];
+
+/// Validates the contents of a config file. Unknown keys are ignored by [`load`], but reported
+/// here, as they're usually typos.
+pub fn check(contents: &str) -> Vec<Problem> {
+    let problem = |span: std::ops::Range<usize>, message: String| {
+        let before = &contents[..span.start];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Problem {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message,
+        }
+    };
+
+    if let Err(e) = toml::from_str::<Config>(contents) {
+        let span = e.span().unwrap_or(0..0);
+        return vec![problem(span, e.message().to_owned())];
+    }
+
+    type Section = BTreeMap<Spanned<String>, toml::Value>;
+    let Ok(top_level) = toml::from_str::<BTreeMap<Spanned<String>, toml::Value>>(contents) else {
+        return Vec::new();
+    };
+
+    let mut problems = Vec::new();
+    for (name, value) in &top_level {
+        if TOP_LEVEL_KEYS.contains(&name.get_ref().as_str()) {
+            continue;
+        }
+        if !value.is_table() {
+            let message = format!("unknown key `{}`", name.get_ref());
+            problems.push(problem(name.span(), message));
+        } else if !KNOWN_KEYS.iter().any(|(s, _)| s == name.get_ref()) {
+            let message = format!("unknown section `{}`", name.get_ref());
+            problems.push(problem(name.span(), message));
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Sections {
+        #[serde(default)]
+        shell: Section,
+        #[serde(default)]
+        output: Section,
+    }
+    if let Ok(Sections { shell, output }) = toml::from_str(contents) {
+        for (section, keys) in [("shell", shell), ("output", output)] {
+            let (_, known) = KNOWN_KEYS.iter().find(|(s, _)| *s == section).unwrap();
+            for key in keys.keys() {
+                if !known.contains(&key.get_ref().as_str()) {
+                    let message = format!("unknown key `{section}.{}`", key.get_ref());
+                    problems.push(problem(key.span(), message));
+                }
+            }
+        }
+    }
 
-    match fs::read_to_string(&to_html_config) {
-        Ok(contents) => match toml::from_str(&contents) {
-            Ok(config) => Ok(config),
-            Err(e) => Err(Error::Parsing(to_html_config, e)),
-        },
-        Err(e) => match e.kind() {
-            io::ErrorKind::NotFound => Ok(Config::default()),
-            _ => Err(e.into()),
-        },
+    type Rules = BTreeMap<String, Section>;
+    #[derive(Deserialize)]
+    struct HighlightSection {
+        #[serde(default)]
+        highlight: Rules,
+    }
+    if let Ok(HighlightSection { highlight }) = toml::from_str(contents) {
+        for (program, keys) in &highlight {
+            for key in keys.keys() {
+                if !RULE_KEYS.contains(&key.get_ref().as_str()) {
+                    let message = format!("unknown key `highlight.{program}.{}`", key.get_ref());
+                    problems.push(problem(key.span(), message));
+                }
+            }
+        }
     }
+
+    problems.sort_by_key(|p| (p.line, p.column));
+    problems
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Serialize, Default)]
 pub struct Config {
+    /// The commands that are run when none are given on the command line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
     #[serde(default)]
     pub shell: Shell,
     #[serde(default)]
     pub output: Output,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub highlight: BTreeMap<String, HighlightRule>,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Serialize, Default)]
 pub struct Shell {
     pub program: Option<String>,
+    /// The shells that each command is run in, like `--shells`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub programs: Vec<String>,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Serialize)]
 pub struct Output {
     #[serde(default)]
     pub cwd: bool,
     #[serde(default)]
     pub full_document: bool,
-    #[serde(default)]
+    #[serde(default = "default_highlight")]
     pub highlight: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_prefix")]
     pub css_prefix: Option<String>,
 }
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            cwd: false,
+            full_document: false,
+            highlight: default_highlight(),
+            css_prefix: None,
+        }
+    }
+}
+
+/// Validates and normalizes the CSS prefix like `--prefix`
+fn deserialize_prefix<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    let prefix = String::deserialize(d)?;
+    super::cli::parse_prefix(&prefix)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// How the subcommands of a program are highlighted, configured in a `[highlight.<program>]`
+/// section
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct HighlightRule {
+    /// The number of subcommand words that are highlighted, e.g. 2 for `gh pr create`
+    #[serde(default = "default_depth")]
+    pub depth: usize,
+    /// Patterns of words before or between the subcommands that are skipped, e.g. `+*` for
+    /// `cargo +nightly build`. `*` matches any text
+    #[serde(default)]
+    pub skip: Vec<String>,
+}
+
+impl HighlightRule {
+    /// The rule for programs passed to `--highlight`
+    pub const SIMPLE: Self = Self {
+        depth: 1,
+        skip: Vec::new(),
+    };
+
+    /// Returns whether the word is skipped when looking for subcommands
+    pub fn skips(&self, word: &str) -> bool {
+        self.skip
+            .iter()
+            .any(|pattern| matches_pattern(pattern, word))
+    }
+}
+
+fn default_depth() -> usize {
+    1
+}
+
+/// Matches a word against a pattern, where `*` matches any text
+fn matches_pattern(pattern: &str, word: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == word,
+        Some((prefix, rest)) => word.strip_prefix(prefix).is_some_and(|word| {
+            (0..=word.len())
+                .filter(|&i| word.is_char_boundary(i))
+                .any(|i| matches_pattern(rest, &word[i..]))
+        }),
+    }
+}
+
+/// Programs whose subcommands are highlighted if the config file doesn't say otherwise
+fn default_highlight() -> Vec<String> {
+    vec!["cargo".into()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, matches_pattern};
+
+    #[test]
+    fn unknown_keys() {
+        let problems = check(
+            "commands = ['ls']\ncwd = true\n[shell]\nprogram = 'zsh'\n\n[output]\n  cwdd = true\n[foo]\n",
+        );
+        let problems: Vec<_> = problems
+            .iter()
+            .map(|p| format!("{}:{}: {}", p.line, p.column, p.message))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "2:1: unknown key `cwd`",
+                "7:3: unknown key `output.cwdd`",
+                "8:2: unknown section `foo`"
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_rule_keys() {
+        let problems = check("[highlight.gh]\ndepth = 2\nskp = ['-*']\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].message, "unknown key `highlight.gh.skp`");
+        assert_eq!((problems[0].line, problems[0].column), (3, 1));
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(matches_pattern("+*", "+nightly"));
+        assert!(!matches_pattern("+*", "build"));
+        assert!(matches_pattern("--*=*", "--color=always"));
+        assert!(!matches_pattern("--*=*", "--release"));
+        assert!(matches_pattern("run", "run"));
+        assert!(matches_pattern("*", ""));
+    }
+
+    #[test]
+    fn bad_values() {
+        let problems = check("[output]\ncwd = 'yes'\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].line, problems[0].column), (2, 7));
+    }
+}
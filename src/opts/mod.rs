@@ -6,31 +6,228 @@
 //! The flow is represented by `cli::Args` and `config::Config` being consolidated into the final
 //! `Opts` that is used through the rest of the application
 
-use std::path::PathBuf;
+use std::{borrow::Cow, collections::BTreeMap, fmt, fs, io, path::PathBuf, time::Duration};
 
-use ansi_to_html::Esc;
+use regex::Regex;
+
+use crate::cmd;
 
 mod cli;
 mod config;
 
+pub use cli::{
+    parse_expect, parse_prefix, ConfigCommand, Format, LineRange, LintOutput, LogFormat, SizeHints,
+    Streams,
+};
+pub use config::HighlightRule;
+
+/// Returns the `config` subcommand, if `to-html` was invoked as `to-html config <COMMAND>`
+pub fn config_command() -> Option<ConfigCommand> {
+    cli::parse_config_command()
+}
+
+/// Returns the output directory, if `to-html` was invoked as `to-html gallery --output <DIR>`
+pub fn gallery_command() -> Option<PathBuf> {
+    cli::parse_gallery_command()
+}
+
+/// Returns whether `--json` was passed, if `to-html` was invoked as `to-html doctor [--json]`
+pub fn doctor_command() -> Option<bool> {
+    cli::parse_doctor_command()
+}
+
+/// Returns `Some` if `to-html` was invoked as mdBook preprocessor, with the renderer if it was
+/// invoked as `to-html mdbook supports <RENDERER>`
+pub fn mdbook_command() -> Option<Option<String>> {
+    cli::parse_mdbook_command()
+}
+
+/// Runs the `config` subcommand. Returns `false` if `to-html config check` found problems.
+pub fn run_config_command(command: ConfigCommand) -> Result<bool, crate::StdError> {
+    match command {
+        ConfigCommand::Path => println!("{}", config::path()?.display()),
+        ConfigCommand::Show => print!("{}", toml::to_string(&config::load()?)?),
+        ConfigCommand::Check => {
+            let (path, status) = check_config()?;
+            match &status {
+                ConfigStatus::NotFound => {
+                    println!("{}: not found, using the defaults", path.display());
+                }
+                ConfigStatus::Ok => println!("{}: ok", path.display()),
+                ConfigStatus::Problems(problems) => {
+                    for p in problems {
+                        println!("{}:{p}", path.display());
+                    }
+                }
+            }
+            return Ok(!matches!(status, ConfigStatus::Problems(_)));
+        }
+    }
+    Ok(true)
+}
+
+/// The result of checking the config file
 #[derive(Debug)]
+pub enum ConfigStatus {
+    NotFound,
+    Ok,
+    /// Problems in the form `line:column: message`
+    Problems(Vec<String>),
+}
+
+/// Checks the config file for invalid values and unknown keys. Returns its path and the result.
+pub fn check_config() -> Result<(PathBuf, ConfigStatus), crate::StdError> {
+    let path = config::path()?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((path, ConfigStatus::NotFound)),
+        Err(e) => return Err(config::Error::Io(e).into()),
+    };
+
+    let problems: Vec<String> = config::check(&contents)
+        .into_iter()
+        .map(|p| format!("{}:{}: {}", p.line, p.column, p.message))
+        .collect();
+    let status = if problems.is_empty() {
+        ConfigStatus::Ok
+    } else {
+        ConfigStatus::Problems(problems)
+    };
+    Ok((path, status))
+}
+
+/// Returns the shell configured in the config file, if it can be loaded
+pub fn config_shell() -> Option<String> {
+    config::load().ok()?.shell.program
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct Opts {
     pub commands: Vec<String>,
+    /// The options given after `--then` for each command, see [`Opts::for_command`]
+    pub command_opts: Vec<CommandOpts>,
     pub shell: Option<String>,
+    /// The shells from `--shells`. If there are any, `shell` is ignored when running commands
+    pub shells: Vec<String>,
     pub highlight: Vec<String>,
+    /// Rules for highlighting subcommands from the config file
+    pub highlight_rules: BTreeMap<String, HighlightRule>,
     pub prefix: String,
     pub no_run: bool,
+    /// The id of the elements emitted instead of the outputs with `--frame-only`
+    pub frame_only: Option<String>,
     pub prompt: ShellPrompt,
+    pub no_cwd_tracking: bool,
+    /// Environment variables set for the commands, from the `env` of a job of `--jobs`
+    pub env: Vec<(String, String)>,
     pub doc: bool,
     pub no_prompt: bool,
+    pub toc: bool,
+    pub semantic_output: bool,
+    pub format: Format,
+    pub input_file: Vec<PathBuf>,
+    pub typescript: Option<PathBuf>,
+    pub timing: Option<PathBuf>,
+    pub timestamps: bool,
+    pub show_file: Vec<PathBuf>,
+    pub show_file_lang: Option<String>,
+    pub allow_binary: bool,
+    pub lint_output: Option<LintOutput>,
+    pub size_hints: Option<SizeHints>,
+    pub capture_raw: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub check: bool,
+    pub check_diff: bool,
+    /// The job file of `--jobs`, see [`crate::jobs`]
+    pub jobs: Option<PathBuf>,
+    pub jobs_parallel: usize,
+    pub fail_fast: bool,
+    pub provenance: bool,
+    pub stable: bool,
+    pub explode_pipeline: bool,
+    pub hl_lines: Vec<LineRange>,
+    pub timeout: Option<Duration>,
+    pub expect: cmd::Expect,
+    pub passthrough_status: bool,
+    pub streams: Streams,
+    pub verbose: u8,
+    pub quiet: bool,
+    pub log_format: LogFormat,
+    /// The name of the preset to save, see [`Opts::save_preset`]
+    pub save_preset: Option<String>,
+    pub list_presets: bool,
+}
+
+/// Options of a command given after `--then`. They override the global options.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommandOpts {
+    pub timeout: Option<Duration>,
+    pub no_prompt: bool,
+    pub cwd: bool,
+    pub hl_lines: Vec<LineRange>,
+    pub allow_binary: bool,
 }
 
 impl Opts {
     pub fn load() -> Result<Self, crate::StdError> {
-        let config::Config {
-            shell: config::Shell {
-                program: config_shell,
+        let (
+            cli::Cli {
+                commands: cli_commands,
+                shell: cli_shell,
+                shells: cli_shells,
+                highlight: cli_highlight,
+                prefix: cli_prefix,
+                no_run: cli_no_run,
+                frame_only: cli_frame_only,
+                cwd: cli_cwd,
+                no_cwd_tracking: cli_no_cwd_tracking,
+                doc: cli_doc,
+                no_prompt: cli_no_prompt,
+                toc: cli_toc,
+                semantic_output: cli_semantic_output,
+                format: cli_format,
+                input_file: cli_input_file,
+                typescript: cli_typescript,
+                timing: cli_timing,
+                timestamps: cli_timestamps,
+                show_file: cli_show_file,
+                show_file_lang: cli_show_file_lang,
+                allow_binary: cli_allow_binary,
+                lint_output: cli_lint_output,
+                size_hints: cli_size_hints,
+                capture_raw: cli_capture_raw,
+                output: cli_output,
+                check: cli_check,
+                check_diff: cli_check_diff,
+                jobs: cli_jobs,
+                jobs_parallel: cli_jobs_parallel,
+                fail_fast: cli_fail_fast,
+                provenance: cli_provenance,
+                stable: cli_stable,
+                explode_pipeline: cli_explode_pipeline,
+                hl_lines: cli_hl_lines,
+                timeout: cli_timeout,
+                expect: cli_expect,
+                expect_timeout: cli_expect_timeout,
+                passthrough_status: cli_passthrough_status,
+                streams: cli_streams,
+                verbose: cli_verbose,
+                quiet: cli_quiet,
+                log_format: cli_log_format,
+                preset: cli_preset,
+                save_preset: cli_save_preset,
+                list_presets: cli_list_presets,
             },
+            command_groups,
+        ) = cli::parse();
+
+        let config::Config {
+            commands: config_commands,
+            shell:
+                config::Shell {
+                    program: config_shell,
+                    programs: config_shells,
+                },
             output:
                 config::Output {
                     cwd: config_cwd,
@@ -38,18 +235,61 @@ impl Opts {
                     highlight: config_highlight,
                     css_prefix: config_prefix,
                 },
-        } = config::load()?;
+            highlight: mut config_highlight_rules,
+        } = config::load_with_preset(cli_preset.as_deref())?;
 
-        let cli::Cli {
-            commands: cli_commands,
-            shell: cli_shell,
-            highlight: cli_highlight,
-            prefix: cli_prefix,
-            no_run: cli_no_run,
-            cwd: cli_cwd,
-            doc: cli_doc,
-            no_prompt: cli_no_prompt,
-        } = cli::parse();
+        // Commands given on the command line replace the ones from the config file. Multi-line
+        // strings in TOML usually end with a line break, which isn't part of the command
+        let mut commands: Vec<(String, CommandOpts)> =
+            if cli_commands.is_empty() && command_groups.is_empty() {
+                config_commands
+                    .into_iter()
+                    .map(|c| {
+                        (
+                            c.trim_end_matches(['\n', '\r']).to_string(),
+                            Default::default(),
+                        )
+                    })
+                    .collect()
+            } else {
+                cli_commands
+                    .into_iter()
+                    .map(|c| (c, Default::default()))
+                    .collect()
+            };
+        if cli_jobs.is_some() && !command_groups.is_empty() {
+            return Err("`--then` can't be combined with `--jobs`".into());
+        }
+        for group in command_groups {
+            let command_opts = CommandOpts {
+                timeout: group.timeout,
+                no_prompt: group.no_prompt,
+                cwd: group.cwd,
+                hl_lines: group.hl_lines,
+                allow_binary: group.allow_binary,
+            };
+            commands.extend(
+                group
+                    .commands
+                    .into_iter()
+                    .map(|c| (c, command_opts.clone())),
+            );
+        }
+        if commands.is_empty()
+            && cli_save_preset.is_none()
+            && !cli_list_presets
+            && cli_jobs.is_none()
+        {
+            return Err(
+                "no commands given. Pass them as arguments, or add a `commands` array \
+                to the config file"
+                    .into(),
+            );
+        }
+        if cli_explode_pipeline {
+            commands = explode_pipelines(commands);
+        }
+        let (commands, command_opts) = commands.into_iter().unzip();
 
         let prompt = if cli_cwd || config_cwd {
             ShellPrompt::Cwd {
@@ -58,26 +298,256 @@ impl Opts {
         } else {
             ShellPrompt::Arrow
         };
-        let prefix = cli_prefix
-            .or(config_prefix)
-            .map(|s| format!("{}-", Esc(s)))
-            .unwrap_or_default();
+        // `--shell` replaces the shells from the config file as well
+        let shells = if !cli_shells.is_empty() || cli_shell.is_some() {
+            cli_shells
+        } else {
+            config_shells
+        };
+        // With `--highlight`, only the rules for the given programs are used
+        if let Some(cli_highlight) = &cli_highlight {
+            config_highlight_rules.retain(|program, _| cli_highlight.contains(program));
+        }
+        let prefix = match cli_prefix.or(config_prefix) {
+            Some(prefix) if !prefix.is_empty() => format!("{prefix}-"),
+            _ => String::new(),
+        };
+
+        let prompts = cli_expect
+            .into_iter()
+            .map(|(regex, response)| match Regex::new(&regex) {
+                Ok(regex) => Ok((regex, response)),
+                Err(e) => Err(format!("invalid regex in --expect: {e}")),
+            })
+            .collect::<Result<_, _>>()?;
+        let expect = cmd::Expect {
+            prompts,
+            timeout: cli_expect_timeout.unwrap_or(Duration::from_secs(10)),
+        };
 
         Ok(Self {
-            commands: cli_commands,
+            commands,
+            command_opts,
             shell: cli_shell.or(config_shell),
+            shells,
             highlight: cli_highlight.unwrap_or(config_highlight),
+            highlight_rules: config_highlight_rules,
             prefix,
             no_run: cli_no_run,
+            frame_only: cli_frame_only,
             prompt,
-            doc: cli_doc || config_doc,
+            no_cwd_tracking: cli_no_cwd_tracking,
+            env: Vec::new(),
+            doc: (cli_doc || config_doc) && cli_format == Format::Html,
             no_prompt: cli_no_prompt,
+            toc: cli_toc,
+            semantic_output: cli_semantic_output,
+            format: cli_format,
+            input_file: cli_input_file,
+            typescript: cli_typescript,
+            timing: cli_timing,
+            timestamps: cli_timestamps,
+            show_file: cli_show_file,
+            show_file_lang: cli_show_file_lang,
+            allow_binary: cli_allow_binary,
+            lint_output: cli_lint_output,
+            size_hints: cli_size_hints,
+            capture_raw: cli_capture_raw,
+            output: cli_output,
+            check: cli_check,
+            check_diff: cli_check_diff,
+            jobs: cli_jobs,
+            jobs_parallel: cli_jobs_parallel.map_or(1, |n| n.get()),
+            fail_fast: cli_fail_fast,
+            provenance: cli_provenance,
+            // the output must be deterministic to be compared
+            stable: cli_stable || cli_check,
+            explode_pipeline: cli_explode_pipeline,
+            hl_lines: cli_hl_lines,
+            timeout: cli_timeout,
+            expect,
+            passthrough_status: cli_passthrough_status,
+            streams: cli_streams,
+            verbose: cli_verbose,
+            quiet: cli_quiet,
+            log_format: cli_log_format,
+            save_preset: cli_save_preset,
+            list_presets: cli_list_presets,
         })
     }
 }
 
-#[derive(Debug)]
+impl Opts {
+    /// Saves the options that can be set in the config file, except for the commands, as a preset
+    /// with the given name. Returns the path of the preset.
+    pub fn save_preset(&self, name: &str) -> Result<PathBuf, crate::StdError> {
+        let preset = config::Config {
+            commands: Vec::new(),
+            shell: config::Shell {
+                program: self.shell.clone(),
+                programs: self.shells.clone(),
+            },
+            output: config::Output {
+                cwd: matches!(self.prompt, ShellPrompt::Cwd { .. }),
+                full_document: self.doc,
+                highlight: self.highlight.clone(),
+                // an empty prefix is saved as well, so it overrides the config file
+                css_prefix: Some(self.prefix.trim_end_matches('-').to_string()),
+            },
+            highlight: self.highlight_rules.clone(),
+        };
+        let path = config::preset_path(name)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string(&preset)?)?;
+        Ok(path)
+    }
+}
+
+/// Returns the names of the presets saved with `--save-preset`, sorted
+pub fn list_presets() -> Result<Vec<String>, crate::StdError> {
+    Ok(config::list_presets()?)
+}
+
+/// Replaces every command with the stages of its pipeline for `--explode-pipeline`
+fn explode_pipelines(commands: Vec<(String, CommandOpts)>) -> Vec<(String, CommandOpts)> {
+    commands
+        .into_iter()
+        .flat_map(|(c, opts)| {
+            let stages = pipeline_stages(&c);
+            stages.into_iter().map(move |stage| (stage, opts.clone()))
+        })
+        .collect()
+}
+
+/// Returns the stages of a pipeline for `--explode-pipeline`, or the command itself if it can't
+/// be parsed
+fn pipeline_stages(command: &str) -> Vec<String> {
+    match crate::lexer::pipeline_prefixes(command) {
+        Ok(prefixes) => prefixes.into_iter().map(String::from).collect(),
+        Err(e) => {
+            crate::log::warn(None, format_args!("can't split pipeline `{command}`: {e}"));
+            vec![command.to_string()]
+        }
+    }
+}
+
+impl Opts {
+    /// Returns the options with other commands, e.g. the ones of a job of `--jobs`. Like the
+    /// commands from the config file, they may end with a line break.
+    pub fn with_commands(&self, commands: Vec<String>) -> Opts {
+        let mut commands: Vec<(String, CommandOpts)> = commands
+            .into_iter()
+            .map(|c| {
+                (
+                    c.trim_end_matches(['\n', '\r']).to_string(),
+                    Default::default(),
+                )
+            })
+            .collect();
+        if self.explode_pipeline {
+            commands = explode_pipelines(commands);
+        }
+        let (commands, command_opts) = commands.into_iter().unzip();
+        Opts {
+            commands,
+            command_opts,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the options for the command with the given index. Options given after `--then`
+    /// override the global options, which override the config file.
+    pub fn for_command(&self, i: usize) -> Cow<'_, Opts> {
+        let Some(command) = self
+            .command_opts
+            .get(i)
+            .filter(|c| **c != CommandOpts::default())
+        else {
+            return Cow::Borrowed(self);
+        };
+        let mut opts = self.clone();
+        opts.timeout = command.timeout.or(self.timeout);
+        opts.no_prompt |= command.no_prompt;
+        if command.cwd {
+            opts.prompt = ShellPrompt::Cwd {
+                home: dirs_next::home_dir(),
+            };
+        }
+        if !command.hl_lines.is_empty() {
+            opts.hl_lines = command.hl_lines.clone();
+        }
+        opts.allow_binary |= command.allow_binary;
+        Cow::Owned(opts)
+    }
+
+    /// Returns the rule for highlighting the subcommands of the program, if it should be
+    /// highlighted
+    pub fn highlight_rule(&self, program: &str) -> Option<&HighlightRule> {
+        static SIMPLE: HighlightRule = HighlightRule::SIMPLE;
+        match self.highlight_rules.get(program) {
+            Some(rule) => Some(rule),
+            None => self
+                .highlight
+                .iter()
+                .any(|h| h == program)
+                .then_some(&SIMPLE),
+        }
+    }
+
+    /// Returns the attribute for an element with the given CSS class, e.g. ` class='prefix-cmd'`.
+    ///
+    /// With `--format github` and `--format email`, the class is replaced by the equivalent inline
+    /// style, since GitHub and email clients remove `class` attributes.
+    pub fn class<'a>(&'a self, name: &'a str) -> Class<'a> {
+        Class { opts: self, name }
+    }
+}
+
+pub struct Class<'a> {
+    opts: &'a Opts,
+    name: &'a str,
+}
+
+impl fmt::Display for Class<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.opts.format {
+            Format::Html => write!(f, " class='{}{}'", self.opts.prefix, self.name),
+            Format::Github | Format::Email | Format::GhSummary => match inline_style(self.name) {
+                Some(style) => write!(f, " style='{style}'"),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+/// The inline styles used with `--format github` and `--format email`. They must match the
+/// stylesheet in `main.rs`, except for properties that GitHub strips anyway. Colors are hex
+/// colors, which all email clients support.
+fn inline_style(class: &str) -> Option<&'static str> {
+    Some(match class {
+        "shell" => "color:#32d132",
+        "cmd" => "color:#419df3",
+        "hl" => "color:#00ffff;font-weight:bold",
+        "arg" => "color:#fff",
+        "str" => "color:#ffba24",
+        "pipe" | "punct" => "color:#a2be00",
+        "flag" => "color:#ff7167",
+        "esc" => "color:#d558f5;font-weight:bold",
+        "hl-line" => "background-color:#3b3b3b",
+        "file-name" | "shell-name" => "color:#888;font-weight:bold",
+        "file-truncated" | "binary" | "shell-missing" | "timeout" => "color:#888;font-style:italic",
+        "timestamp" => "color:#888",
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Debug, Default)]
 pub enum ShellPrompt {
+    #[default]
     Arrow,
-    Cwd { home: Option<PathBuf> },
+    Cwd {
+        home: Option<PathBuf>,
+    },
 }
@@ -0,0 +1,158 @@
+//! Converts the output of a command to HTML
+
+use std::{fmt::Write, time::Duration};
+
+use ansi_to_html::{Converter, FaintBlend, PreparedConverter, StyleState};
+
+use crate::{
+    binary, lint, log,
+    opts::{Format, Opts},
+    typescript::Elapsed,
+    StdError,
+};
+
+/// Converts the captured stdout and stderr of the command with the given index and appends the
+/// HTML to `buf`. `blocks` are the blocks of a `--typescript` session, see
+/// [`CannedOutput`](crate::capture::CannedOutput).
+pub(crate) fn write_output(
+    buf: &mut String,
+    i: usize,
+    stdout: &str,
+    stderr: &str,
+    blocks: &[(usize, Duration)],
+    opts: &Opts,
+) -> Result<(), StdError> {
+    let converter = match opts.format {
+        Format::Html if opts.prefix.is_empty() => Converter::new(),
+        Format::Html => Converter::new().four_bit_var_prefix(Some(opts.prefix.to_owned())),
+        // GitHub and email clients strip `var()` and `opacity`
+        Format::Github | Format::Email | Format::GhSummary => Converter::new()
+            .skip_css_vars(true)
+            .faint_blend(Some(FaintBlend::new([255, 255, 255], [0x14, 0x14, 0x14]))),
+    };
+    // a job summary may be truncated after any line
+    let converter = converter.line_safe(opts.format == Format::GhSummary);
+    // e.g. the links of rustc's error codes to the error index
+    let converter = converter.render_links(true);
+    let converter = converter.build()?;
+
+    let semantic = opts.semantic_output && !(stdout.is_empty() && stderr.is_empty());
+    if semantic {
+        buf.push_str("<samp>");
+    }
+    let binary = !opts.allow_binary && (binary::is_binary(stdout) || binary::is_binary(stderr));
+    let linted;
+    let (stdout, stderr) = match opts.lint_output {
+        Some(mode) if !binary => {
+            linted = lint::lint(i, stdout, stderr, mode);
+            (&*linted.0, &*linted.1)
+        }
+        _ => (stdout, stderr),
+    };
+    if binary {
+        let size = binary::original_size(stdout) + binary::original_size(stderr);
+        log::warn(
+            Some(i),
+            "the output looks like binary data and is not shown. Pass --allow-binary to show it",
+        );
+        writeln!(
+            buf,
+            "<span{}>… binary output ({}) suppressed …</span>",
+            opts.class("binary"),
+            binary::format_size(size),
+        )?;
+    } else if opts.timestamps && !blocks.is_empty() {
+        write_timestamped_blocks(buf, &converter, stdout, blocks, opts)?;
+    } else if opts.hl_lines.is_empty() {
+        for output in [stdout, stderr] {
+            if !output.is_empty() {
+                let html = converter.convert(output)?;
+                write!(buf, "{}", html)?;
+            }
+        }
+    } else {
+        write_highlighted_lines(buf, i, &converter, stdout, stderr, opts)?;
+    }
+    if semantic {
+        buf.push_str("</samp>");
+    }
+
+    Ok(())
+}
+
+/// Converts the blocks of a `--typescript` session and puts the time since the start of the
+/// session before each block after the first. Styles continue across blocks.
+fn write_timestamped_blocks(
+    buf: &mut String,
+    converter: &PreparedConverter,
+    output: &str,
+    blocks: &[(usize, Duration)],
+    opts: &Opts,
+) -> Result<(), StdError> {
+    let mut state = StyleState::new();
+    let mut start = 0;
+    for &(end, time) in blocks {
+        let block = &output[start..end];
+        converter.convert_append(&mut state, block, buf)?;
+        if !block.ends_with('\n') {
+            buf.push('\n');
+        }
+        writeln!(
+            buf,
+            "<span{}>[{}]</span>",
+            opts.class("timestamp"),
+            Elapsed(time)
+        )?;
+        start = end;
+    }
+    converter.convert_append(&mut state, &output[start..], buf)?;
+    Ok(())
+}
+
+/// Converts the output line by line, so the lines selected with `--hl-lines` can be wrapped in an
+/// element. Styles spanning multiple lines are closed at the end of each line and re-opened in the
+/// next line, so the tags nest correctly. Line numbers start at 1 and continue from stdout to
+/// stderr.
+fn write_highlighted_lines(
+    buf: &mut String,
+    i: usize,
+    converter: &PreparedConverter,
+    stdout: &str,
+    stderr: &str,
+    opts: &Opts,
+) -> Result<(), StdError> {
+    let mut line_count = 0;
+
+    for output in [stdout, stderr] {
+        let mut state = StyleState::new();
+        for line in output.split_inclusive('\n') {
+            line_count += 1;
+            let (line, newline) = match line.strip_suffix('\n') {
+                Some(line) => (line, "\n"),
+                None => (line, ""),
+            };
+
+            let highlighted = opts.hl_lines.iter().any(|r| r.contains(line_count));
+            if highlighted {
+                write!(buf, "<span{}>", opts.class("hl-line"))?;
+            }
+            converter.convert_append(&mut state, line, buf)?;
+            if highlighted {
+                buf.push_str("</span>");
+            }
+            buf.push_str(newline);
+        }
+    }
+
+    for range in &opts.hl_lines {
+        if range.end > line_count {
+            log::warn(
+                Some(i),
+                format_args!(
+                    "--hl-lines {range} is out of range, the output has only {line_count} lines"
+                ),
+            );
+        }
+    }
+    Ok(())
+}
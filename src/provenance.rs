@@ -0,0 +1,210 @@
+//! Provenance comments emitted with `--provenance`.
+//!
+//! The document gets a comment with the `to-html` invocation, and each command block gets a
+//! comment with the time, shell, exit code and duration of the command. With `--stable`, the time
+//! and duration are replaced with placeholders, so the output is deterministic.
+
+use std::{
+    fmt::{self, Write},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    opts::{self, Opts},
+    StdError,
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Metadata about a command that was run
+pub(crate) struct Run {
+    pub(crate) shell: String,
+    pub(crate) exit_code: Option<i32>,
+    /// Whether the command was killed by `--timeout`
+    pub(crate) timed_out: bool,
+    pub(crate) duration: Duration,
+}
+
+/// Flags omitted from the invocation, so a file generated with `--stable` can be checked with
+/// `--check`
+const OMITTED_FLAGS: &[&str] = &["--stable", "--check", "--check-diff"];
+
+/// Writes the comment with the `to-html` invocation
+pub(crate) fn write_document_comment(buf: &mut String) -> Result<(), StdError> {
+    let mut invocation = String::from("to-html");
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if OMITTED_FLAGS.contains(&arg.as_str()) {
+            continue;
+        }
+        // the responses to prompts may be passwords
+        let arg = match arg.strip_prefix("--expect=") {
+            Some(value) => format!("--expect={}", mask_response(value)),
+            None if arg == "--expect" => {
+                write!(invocation, " --expect")?;
+                let Some(value) = args.next() else { break };
+                mask_response(&value)
+            }
+            None => arg,
+        };
+        write!(invocation, " {}", ShellQuote(&arg))?;
+    }
+    writeln!(
+        buf,
+        "<!-- generated by to-html {VERSION}: {} -->",
+        EscComment(&invocation)
+    )?;
+    Ok(())
+}
+
+/// Replaces the response of an `--expect` argument with `********`
+fn mask_response(value: &str) -> String {
+    match opts::parse_expect(value) {
+        Ok((prompt, _)) => format!("{prompt}:********"),
+        Err(_) => value.to_string(),
+    }
+}
+
+/// Writes the comment before a command block. `run` is `None` if the command wasn't run.
+pub(crate) fn write_command_comment(
+    buf: &mut String,
+    i: usize,
+    run: Option<Run>,
+    opts: &Opts,
+) -> Result<(), StdError> {
+    write!(buf, "<!-- to-html {VERSION}; command {}", i + 1)?;
+    if opts.stable {
+        write!(buf, "; time: [TIME]")?;
+    } else {
+        write!(buf, "; time: {}", Timestamp(SystemTime::now()))?;
+    }
+
+    match run {
+        Some(run) => {
+            write!(buf, "; shell: {}", EscComment(&run.shell))?;
+            match run.exit_code {
+                Some(code) => write!(buf, "; exit code: {code}")?,
+                None => write!(buf, "; exit code: unknown")?,
+            }
+            if run.timed_out {
+                write!(buf, "; timed out")?;
+            }
+            if opts.stable {
+                write!(buf, "; duration: [DURATION]")?;
+            } else {
+                write!(buf, "; duration: {:.3}s", run.duration.as_secs_f64())?;
+            }
+        }
+        None => write!(buf, "; not run")?,
+    }
+    writeln!(buf, " -->")?;
+    Ok(())
+}
+
+/// Escapes text in an HTML comment, so it can't end the comment early. Every `-` that follows
+/// another `-` is replaced with `&#45;`, and `<` and `>` are escaped.
+struct EscComment<'a>(&'a str);
+
+impl fmt::Display for EscComment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut prev = None;
+        for c in self.0.chars() {
+            match c {
+                '-' if prev == Some('-') => f.write_str("&#45;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                c => f.write_char(c)?,
+            }
+            prev = Some(c);
+        }
+        Ok(())
+    }
+}
+
+/// Quotes an argument, if necessary, so it can be pasted into a shell
+struct ShellQuote<'a>(&'a str);
+
+impl fmt::Display for ShellQuote<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let is_plain = !self.0.is_empty()
+            && self
+                .0
+                .chars()
+                .all(|c| c.is_alphanumeric() || "-_./,=:@%+".contains(c));
+        if is_plain {
+            f.write_str(self.0)
+        } else {
+            write!(f, "'{}'", self.0.replace('\'', r"'\''"))
+        }
+    }
+}
+
+/// Formats a point in time as `YYYY-MM-DDThh:mm:ssZ` (UTC)
+struct Timestamp(SystemTime);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self
+            .0
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (days, secs) = (secs / 86400, secs % 86400);
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+        )
+    }
+}
+
+/// Converts days since 1970-01-01 to a date in the proleptic Gregorian calendar.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{EscComment, ShellQuote, Timestamp};
+
+    #[test]
+    fn timestamps() {
+        let t = |secs| Timestamp(UNIX_EPOCH + Duration::from_secs(secs)).to_string();
+        assert_eq!(t(0), "1970-01-01T00:00:00Z");
+        assert_eq!(t(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(t(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn comments_are_escaped() {
+        assert_eq!(
+            EscComment("a -- b --> c").to_string(),
+            "a -&#45; b -&#45;&gt; c"
+        );
+        assert_eq!(EscComment("---").to_string(), "-&#45;&#45;");
+    }
+
+    #[test]
+    fn shell_quoting() {
+        assert_eq!(ShellQuote("--doc").to_string(), "--doc");
+        assert_eq!(ShellQuote("ls -l").to_string(), "'ls -l'");
+        assert_eq!(ShellQuote("it's").to_string(), r"'it'\''s'");
+        assert_eq!(ShellQuote("").to_string(), "''");
+    }
+}
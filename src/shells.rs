@@ -0,0 +1,60 @@
+//! `--shells`: Runs the commands once per shell and shows the results side by side.
+//!
+//! Every shell gets its own terminal, labeled with the name of the shell. The terminals are
+//! wrapped in a `<div>` that places them next to each other. Shells that aren't installed are
+//! shown as a block with a warning, so the other shells are still rendered.
+
+use std::fmt::Write;
+
+use ansi_to_html::Esc;
+
+use crate::{cmd, log, opts::Opts, StdError};
+
+/// Writes a labeled terminal for every shell passed to `--shells`.
+pub(crate) fn write_shells(buf: &mut String, opts: &Opts) -> Result<(), StdError> {
+    // every shell starts in the same directory, even if the commands change it
+    let cwd = cmd::current_dir()?;
+    // the anchors of the table of contents link to the first terminal
+    let mut toc = opts.toc;
+
+    writeln!(buf, "<div{}>", opts.class("shells"))?;
+    for shell in &opts.shells {
+        let name = cmd::shell_name(shell);
+        writeln!(buf, "<div>")?;
+        writeln!(
+            buf,
+            "<span{}>{}</span>",
+            opts.class("shell-name"),
+            Esc(name)
+        )?;
+
+        cmd::set_current_dir(&cwd)?;
+        if fake_tty::which(shell).is_some() {
+            let opts = Opts {
+                shell: Some(shell.clone()),
+                toc,
+                ..opts.clone()
+            };
+            crate::write_terminal(buf, &opts, None)?;
+            toc = false;
+        } else {
+            log::warn(
+                None,
+                format_args!("shell `{shell}` was not found, its output is not shown"),
+            );
+            crate::open_terminal(buf, opts, None)?;
+            write!(
+                buf,
+                "<span{}>`{}` is not installed</span>\n</{}>",
+                opts.class("shell-missing"),
+                Esc(shell),
+                opts.format.block_element(),
+            )?;
+        }
+        writeln!(buf, "\n</div>")?;
+    }
+    write!(buf, "</div>")?;
+
+    cmd::set_current_dir(cwd)?;
+    Ok(())
+}
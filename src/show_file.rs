@@ -0,0 +1,135 @@
+//! Blocks showing the contents of files for `--show-file`, e.g. the input of the commands.
+//!
+//! Each file is rendered in its own `<pre>` before the commands, with its path as header. With
+//! `--show-file-lang`, the contents are highlighted with syntect, if the `syntect` feature is
+//! enabled.
+
+use std::{fmt::Write, fs, path::Path};
+
+use ansi_to_html::Esc;
+
+use crate::{
+    log,
+    opts::{Format, Opts},
+    StdError, EMAIL_BLOCK_STYLE,
+};
+
+/// Maximum number of bytes shown of each file.
+const MAX_FILE_LEN: usize = 32 * 1024;
+
+/// Writes a `<pre>` block for every file passed to `--show-file`.
+pub(crate) fn write_files(buf: &mut String, opts: &Opts) -> Result<(), StdError> {
+    for path in &opts.show_file {
+        let bytes = fs::read(path).map_err(|e| format!("can't read {}: {e}", path.display()))?;
+        let contents = String::from_utf8_lossy(&bytes);
+        write_file(buf, path, &contents, opts)?;
+    }
+    Ok(())
+}
+
+fn write_file(buf: &mut String, path: &Path, contents: &str, opts: &Opts) -> Result<(), StdError> {
+    match opts.format {
+        Format::Html => writeln!(buf, "<pre class=\"{}file\">", opts.prefix)?,
+        Format::Github | Format::GhSummary => {
+            writeln!(buf, "<pre style=\"background-color:#141414;color:#fff\">")?
+        }
+        Format::Email => write!(buf, "<div style=\"{EMAIL_BLOCK_STYLE}\">")?,
+    }
+    writeln!(
+        buf,
+        "<span{}>{}</span>",
+        opts.class("file-name"),
+        Esc(path.display().to_string()),
+    )?;
+
+    let shown = truncate(contents, MAX_FILE_LEN);
+    let highlighted = opts.show_file_lang.as_deref().and_then(|lang| {
+        highlight(shown, lang)
+            .map_err(|e| {
+                log::warn(
+                    None,
+                    format_args!(
+                        "can't highlight {} ({e}), showing it as plain text",
+                        path.display()
+                    ),
+                );
+            })
+            .ok()
+    });
+    match highlighted {
+        Some(html) => buf.push_str(&html),
+        None => write!(buf, "{}", Esc(shown))?,
+    }
+
+    if shown.len() < contents.len() {
+        if !shown.ends_with('\n') {
+            writeln!(buf)?;
+        }
+        writeln!(
+            buf,
+            "<span{}>[{} more bytes]</span>",
+            opts.class("file-truncated"),
+            contents.len() - shown.len(),
+        )?;
+    }
+    writeln!(buf, "</{}>", opts.format.block_element())?;
+    Ok(())
+}
+
+/// Returns the longest prefix of `s` with at most `max_len` bytes, ending at a char boundary.
+fn truncate(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let end = (0..=max_len)
+        .rev()
+        .find(|&i| s.is_char_boundary(i))
+        .unwrap_or(0);
+    &s[..end]
+}
+
+/// Highlights the contents with syntect. The language is a name or file extension, e.g. `json`.
+#[cfg(feature = "syntect")]
+fn highlight(contents: &str, lang: &str) -> Result<String, StdError> {
+    use syntect::{
+        easy::HighlightLines,
+        highlighting::ThemeSet,
+        html::{styled_line_to_highlighted_html, IncludeBackground},
+        parsing::SyntaxSet,
+        util::LinesWithEndings,
+    };
+
+    let syntaxes = SyntaxSet::load_defaults_newlines();
+    let syntax = syntaxes
+        .find_syntax_by_token(lang)
+        .ok_or_else(|| format!("unknown language `{lang}`"))?;
+    let themes = ThemeSet::load_defaults();
+    let mut highlighter = HighlightLines::new(syntax, &themes.themes["base16-ocean.dark"]);
+
+    let mut html = String::new();
+    for line in LinesWithEndings::from(contents) {
+        let regions = highlighter.highlight_line(line, &syntaxes)?;
+        html.push_str(&styled_line_to_highlighted_html(
+            &regions,
+            IncludeBackground::No,
+        )?);
+    }
+    Ok(html)
+}
+
+#[cfg(not(feature = "syntect"))]
+fn highlight(_contents: &str, _lang: &str) -> Result<String, StdError> {
+    Err("to-html was built without the `syntect` feature".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::truncate;
+
+    #[test]
+    fn truncate_at_char_boundary() {
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(truncate("abc", 2), "ab");
+        assert_eq!(truncate("aä", 2), "a");
+    }
+}
@@ -0,0 +1,86 @@
+//! `--size-hints`: The number of columns and rows of a terminal, as attributes of its `<pre>`.
+//!
+//! The size is measured in the HTML of the terminal, so prompts, highlighted lines etc. are
+//! included. Tags and comments are removed, and every entity counts as one character, since the
+//! entities written by `to-html` are all ASCII.
+
+use std::fmt;
+
+use crate::opts::SizeHints;
+
+/// The size of the text in a terminal
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct TerminalSize {
+    cols: usize,
+    rows: usize,
+    mode: SizeHints,
+}
+
+impl TerminalSize {
+    /// Measures the HTML of the contents of a terminal
+    pub(crate) fn of_html(html: &str, mode: SizeHints) -> Self {
+        let text = text_of_html(html);
+        TerminalSize {
+            cols: ansi_to_html::visible_width(&text),
+            rows: text.lines().count(),
+            mode,
+        }
+    }
+
+    /// Returns the CSS that sizes the terminal, with `--size-hints=css`
+    pub(crate) fn css(&self) -> Option<String> {
+        (self.mode == SizeHints::Css).then(|| format!("min-width:{}ch", self.cols))
+    }
+}
+
+/// Writes the `data-cols` and `data-rows` attributes, with a space before each
+impl fmt::Display for TerminalSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            " data-cols=\"{}\" data-rows=\"{}\"",
+            self.cols, self.rows
+        )
+    }
+}
+
+/// Returns the text of the HTML, without tags and comments. Entities are replaced with `&`.
+fn text_of_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(i) = rest.find(['<', '&']) {
+        text.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let end = if rest.starts_with("<!--") {
+            rest.find("-->").map(|end| end + 3)
+        } else if rest.starts_with('<') {
+            rest.find('>').map(|end| end + 1)
+        } else {
+            text.push('&');
+            rest.find(';').map(|end| end + 1)
+        };
+        rest = &rest[end.unwrap_or(rest.len())..];
+    }
+    text.push_str(rest);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{text_of_html, TerminalSize};
+    use crate::opts::SizeHints;
+
+    #[test]
+    fn text() {
+        let html = "<span class='a'>&gt; </span><!-- <b> -->日本 &amp;&#39;x\n<b>two</b>\n";
+        assert_eq!(text_of_html(html), "& 日本 &&x\ntwo\n");
+    }
+
+    #[test]
+    fn size() {
+        let size = TerminalSize::of_html("<b>日本語</b> &lt;\n\nlast\n", SizeHints::Css);
+        assert_eq!(size.to_string(), " data-cols=\"8\" data-rows=\"3\"");
+        assert_eq!(size.css().as_deref(), Some("min-width:8ch"));
+        assert_eq!(TerminalSize::of_html("a", SizeHints::Data).css(), None);
+    }
+}
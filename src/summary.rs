@@ -0,0 +1,91 @@
+//! The job summary of a GitHub Actions workflow, emitted with `--format gh-summary`.
+//!
+//! Each command is a collapsible `<details>` element with the command as its summary and the
+//! output in a `<pre>` with inline styles, like with `--format github`. The summary is Markdown,
+//! so the `<pre>` starts on its own line after a blank line: that way, blank lines in the output
+//! don't end the HTML block.
+
+use ansi_to_html::Esc;
+
+use crate::{
+    capture::CannedOutput, fmt_command, log, opts::Opts, provenance, write_mount, StdError,
+};
+
+/// The maximum size of a job summary. GitHub rejects larger summaries.
+const MAX_SUMMARY_LEN: usize = 1024 * 1024;
+
+/// The closing tags of a command
+const CLOSE: &str = "</pre>\n\n</details>\n";
+
+/// The notice after the last command when the summary is truncated
+const TRUNCATED: &str =
+    "\n<p><b>… output truncated to the 1 MiB limit of job summaries …</b></p>\n";
+
+/// Writes a `<details>` element for every command. If the summary would exceed 1 MiB, the output
+/// is cut off after the last line that fits, and the remaining commands are omitted.
+pub(crate) fn write_summary(
+    buf: &mut String,
+    opts: &Opts,
+    canned_outputs: Option<&[CannedOutput]>,
+) -> Result<(), StdError> {
+    for (i, command) in opts.commands.iter().enumerate() {
+        let mut block = format!(
+            "<details><summary><code>$ {}</code></summary>\n\n\
+            <pre style=\"background-color:#141414;color:#fff\">\n",
+            Esc(command),
+        );
+        let output_start = block.len();
+        write_output(&mut block, i, command, canned_outputs, opts)?;
+
+        // one byte is left for the line break after the HTML
+        let reserved = buf.len() + CLOSE.len() + TRUNCATED.len() + 1;
+        let available = MAX_SUMMARY_LEN.saturating_sub(reserved);
+        if block.len() <= available {
+            buf.push_str(&block);
+            buf.push_str(CLOSE);
+            continue;
+        }
+        // the output is converted line by line, so no element spans a line break
+        let cut = block.as_bytes()[..available.min(block.len())]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .filter(|&cut| cut + 1 >= output_start);
+        if let Some(cut) = cut {
+            buf.push_str(&block[..cut + 1]);
+            buf.push_str(CLOSE);
+        }
+        buf.push_str(TRUNCATED);
+        log::warn(
+            Some(i),
+            "the job summary exceeds 1 MiB, so the output is truncated",
+        );
+        break;
+    }
+    Ok(())
+}
+
+/// Writes the output of the command with the given index, without a prompt
+fn write_output(
+    buf: &mut String,
+    i: usize,
+    command: &str,
+    canned_outputs: Option<&[CannedOutput]>,
+    opts: &Opts,
+) -> Result<(), StdError> {
+    let canned_output = canned_outputs.and_then(|o| o.get(i));
+    let mut cmd_opts = opts.for_command(i).into_owned();
+    cmd_opts.no_prompt = true;
+    if let Some(id) = &opts.frame_only {
+        if opts.provenance {
+            provenance::write_command_comment(buf, i, None, opts)?;
+        }
+        write_mount(buf, i, id, opts)?;
+    } else if opts.no_run && canned_output.is_none() {
+        if opts.provenance {
+            provenance::write_command_comment(buf, i, None, opts)?;
+        }
+    } else {
+        fmt_command(buf, i, command, canned_output, &cmd_opts)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Table of contents for documents containing several commands.
+//!
+//! Every command block gets an anchor whose `id` is derived from the command. The ids are made
+//! unique by including the index of the command, so two identical commands still get different
+//! anchors.
+
+use std::fmt::Write;
+
+use ansi_to_html::Esc;
+
+use crate::{opts::Opts, StdError};
+
+/// Maximum number of characters taken from the command for the slug.
+const MAX_SLUG_LEN: usize = 40;
+
+/// Returns the `id` of the anchor of the command block with the given (0-based) index.
+pub(crate) fn anchor_id(prefix: &str, index: usize, command: &str) -> String {
+    let slug = slugify(command);
+    if slug.is_empty() {
+        format!("{prefix}cmd-{}", index + 1)
+    } else {
+        format!("{prefix}cmd-{}-{slug}", index + 1)
+    }
+}
+
+/// Writes the `<nav>` element containing a link to each command block.
+pub(crate) fn write_toc(buf: &mut String, opts: &Opts) -> Result<(), StdError> {
+    writeln!(buf, "<nav class='{}toc'>\n<ol>", opts.prefix)?;
+    for (i, command) in opts.commands.iter().enumerate() {
+        writeln!(
+            buf,
+            "<li><a href='#{}'><code>{}</code></a></li>",
+            Esc(anchor_id(&opts.prefix, i, command)),
+            Esc(command),
+        )?;
+    }
+    writeln!(buf, "</ol>\n</nav>")?;
+    Ok(())
+}
+
+/// Converts a command to a string that can be used in an `id` attribute and in a URL fragment.
+///
+/// Alphanumeric characters (including non-ASCII letters) are lowercased and kept, everything else
+/// is replaced with a single `-`. Leading and trailing dashes are removed.
+fn slugify(command: &str) -> String {
+    let mut slug = String::new();
+    let mut pending_dash = false;
+
+    for c in command.chars().filter(|&c| c != '\'' && c != '"') {
+        if c.is_alphanumeric() {
+            if slug.chars().count() >= MAX_SLUG_LEN {
+                break;
+            }
+            if pending_dash && !slug.is_empty() {
+                slug.push('-');
+            }
+            pending_dash = false;
+            slug.extend(c.to_lowercase());
+        } else {
+            pending_dash = true;
+        }
+    }
+    slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{anchor_id, slugify};
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("ls -l"), "ls-l");
+        assert_eq!(slugify("echo \"it's <b>&</b>\""), "echo-its-b-b");
+        assert_eq!(slugify("  cat ./Grüße.txt | wc  "), "cat-grüße-txt-wc");
+        assert_eq!(slugify("#?%/"), "");
+    }
+
+    #[test]
+    fn anchor_ids_are_unique() {
+        assert_eq!(anchor_id("", 0, "ls"), "cmd-1-ls");
+        assert_eq!(anchor_id("", 1, "ls"), "cmd-2-ls");
+        assert_eq!(anchor_id("x-", 2, "&&"), "x-cmd-3");
+    }
+}
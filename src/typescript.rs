@@ -0,0 +1,195 @@
+//! `--typescript`: Shows a session recorded with `script` as the output of the command.
+//!
+//! The `Script started on …` line at the start of the file and the `Script done on …` line at the
+//! end are removed. With `--timing`, the timing file written by `script --timing=<PATH>` is used
+//! to split the session into blocks at long pauses. Every line of this file is a chunk of output:
+//! `<delay> <bytes>`, where the delay is the time in seconds since the previous chunk. In the
+//! advanced format (`script --logging-format advanced`), lines start with the type of the entry;
+//! only output (`O`) entries contain bytes of the session.
+
+use std::{fmt, fs, time::Duration};
+
+use crate::{capture::CannedOutput, opts::Opts, StdError};
+
+/// The minimum pause after which a new block starts
+const MIN_PAUSE: Duration = Duration::from_secs(2);
+
+const BANNER_START: &[u8] = b"Script started on ";
+const BANNER_END: &[u8] = b"\nScript done on ";
+
+/// Reads the `--typescript` file and splits it into blocks if `--timing` is given
+pub(crate) fn read(opts: &Opts) -> Result<CannedOutput, StdError> {
+    let Some(path) = &opts.typescript else {
+        return Err("no --typescript file given".into());
+    };
+    if opts.commands.len() != 1 {
+        return Err(format!(
+            "--typescript shows the output of a single command, got {} commands",
+            opts.commands.len()
+        )
+        .into());
+    }
+
+    let bytes = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let mut session = bytes.as_slice();
+    if session.starts_with(BANNER_START) {
+        let end = session
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(session.len(), |i| i + 1);
+        session = &session[end..];
+    }
+    if let Some(i) = session
+        .windows(BANNER_END.len())
+        .rposition(|w| w == BANNER_END)
+    {
+        session = &session[..i];
+    }
+
+    let splits = match &opts.timing {
+        Some(timing) => {
+            let content = fs::read_to_string(timing)
+                .map_err(|e| format!("failed to read {}: {e}", timing.display()))?;
+            let splits = pauses(&content)
+                .map_err(|(line, e)| format!("{}:{line}: {e}", timing.display()))?;
+            splits
+                .into_iter()
+                .map(|(offset, time)| (split_point(session, offset), time))
+                .filter(|&(offset, _)| offset > 0 && offset < session.len())
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    // the blocks are decoded separately, so the offsets can be adjusted to the normalized output
+    let mut output = String::with_capacity(session.len());
+    let mut blocks = Vec::with_capacity(splits.len());
+    let mut start = 0;
+    for &(end, time) in &splits {
+        output.push_str(&normalize(&session[start..end]));
+        blocks.push((output.len(), time));
+        start = end;
+    }
+    output.push_str(&normalize(&session[start..]));
+
+    Ok(CannedOutput { output, blocks })
+}
+
+/// Returns the byte offsets in the session where a chunk starts after a long pause, and the time
+/// since the start of the session. An error contains the line number.
+fn pauses(timing: &str) -> Result<Vec<(usize, Duration)>, (usize, String)> {
+    let mut pauses = Vec::new();
+    let mut offset = 0;
+    let mut time = Duration::ZERO;
+    let mut last_output = Duration::ZERO;
+    for (i, line) in timing.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (delay, bytes) = parse_entry(line).map_err(|e| (i + 1, e))?;
+        time += delay;
+        if bytes > 0 {
+            if time - last_output >= MIN_PAUSE {
+                pauses.push((offset, time));
+            }
+            last_output = time;
+            offset += bytes;
+        }
+    }
+    Ok(pauses)
+}
+
+/// Parses a line of the timing file into the delay and the number of output bytes
+fn parse_entry(line: &str) -> Result<(Duration, usize), String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (delay, bytes) = match fields[..] {
+        [delay, bytes] => (delay, Some(bytes)),
+        ["O", delay, bytes] => (delay, Some(bytes)),
+        [_, delay, ..] => (delay, None),
+        _ => return Err(format!("invalid entry `{line}`")),
+    };
+    let delay = delay
+        .parse()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| format!("invalid delay `{delay}`"))?;
+    let bytes = match bytes {
+        Some(bytes) => (bytes.parse()).map_err(|_| format!("invalid byte count `{bytes}`"))?,
+        None => 0,
+    };
+    Ok((delay, bytes))
+}
+
+/// Moves the offset forward so it doesn't split a `\r\n` or a UTF-8 character
+fn split_point(session: &[u8], mut offset: usize) -> usize {
+    if offset > 0 && session.get(offset - 1) == Some(&b'\r') && session.get(offset) == Some(&b'\n')
+    {
+        offset += 1;
+    }
+    while session
+        .get(offset)
+        .is_some_and(|&b| b & 0b1100_0000 == 0b1000_0000)
+    {
+        offset += 1;
+    }
+    offset
+}
+
+fn normalize(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).replace("\r\n", "\n")
+}
+
+/// The time since the start of the session, e.g. `01:05` or `1:02:03`
+pub(crate) struct Elapsed(pub Duration);
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if hours > 0 {
+            write!(f, "{hours}:{minutes:02}:{secs:02}")
+        } else {
+            write!(f, "{minutes:02}:{secs:02}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_entry, pauses, split_point, Elapsed};
+
+    #[test]
+    fn timing_entries() {
+        let secs = Duration::from_secs_f64;
+        assert_eq!(parse_entry("0.010127 24"), Ok((secs(0.010127), 24)));
+        assert_eq!(parse_entry("O 2.5 3"), Ok((secs(2.5), 3)));
+        assert_eq!(parse_entry("I 1.5 3"), Ok((secs(1.5), 0)));
+        assert_eq!(parse_entry("H 0.0 TERM xterm"), Ok((Duration::ZERO, 0)));
+        assert!(parse_entry("-1 3").is_err());
+        assert!(parse_entry("1.0").is_err());
+
+        let timing = "0.1 10\n2.5 5\nI 3.0 2\n0.1 1\nO 2.0 4\n";
+        assert_eq!(
+            pauses(timing),
+            Ok(vec![(10, secs(2.6)), (15, secs(5.7)), (16, secs(7.7))])
+        );
+        assert_eq!(pauses("0.1 10\nx\n"), Err((2, "invalid entry `x`".into())));
+    }
+
+    #[test]
+    fn split_points() {
+        assert_eq!(split_point(b"a\r\nb", 2), 3);
+        assert_eq!(split_point(b"a\r\nb", 3), 3);
+        assert_eq!(split_point("aü".as_bytes(), 2), 3);
+    }
+
+    #[test]
+    fn elapsed() {
+        let elapsed = |secs| Elapsed(Duration::from_secs(secs)).to_string();
+        assert_eq!(elapsed(2), "00:02");
+        assert_eq!(elapsed(65), "01:05");
+        assert_eq!(elapsed(3723), "1:02:03");
+    }
+}
@@ -0,0 +1,1329 @@
+//! Integration tests running the `to-html` binary
+
+use std::{
+    fs,
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+use tempfile::TempDir;
+
+/// Runs `to-html` with the given arguments in an environment without a config file
+fn to_html(args: &[&str]) -> Output {
+    to_html_with_config(None, args)
+}
+
+/// Runs `to-html` with the given arguments and the given fixture in `tests/fixtures` as config
+/// file. In the output, the path of the config directory is replaced with `[CONFIG]`.
+fn to_html_with_config(config: Option<&str>, args: &[&str]) -> Output {
+    to_html_with_env(config, args, &[])
+}
+
+/// Like [`to_html_with_config`], but sets additional environment variables.
+fn to_html_with_env(config: Option<&str>, args: &[&str], env: &[(&str, &str)]) -> Output {
+    let home = config_home(config);
+    to_html_in(&home, args, env)
+}
+
+/// Creates a temporary config directory, with the given fixture in `tests/fixtures` as config file
+fn config_home(config: Option<&str>) -> TempDir {
+    let home = TempDir::new().unwrap();
+    if let Some(config) = config {
+        let dir = home.path().join("to-html");
+        fs::create_dir(&dir).unwrap();
+        let fixture = format!("{}/tests/fixtures/{config}", env!("CARGO_MANIFEST_DIR"));
+        fs::copy(fixture, dir.join("config.toml")).unwrap();
+    }
+    home
+}
+
+/// Runs `to-html` with the given config directory, which is kept between runs
+fn to_html_in(home: &TempDir, args: &[&str], env: &[(&str, &str)]) -> Output {
+    let mut output = Command::new(env!("CARGO_BIN_EXE_to-html"))
+        .args(args)
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("LANG", "en_US.UTF-8")
+        .envs(env.iter().copied())
+        .output()
+        .unwrap();
+
+    let home = home.path().to_str().unwrap();
+    for out in [&mut output.stdout, &mut output.stderr] {
+        *out = String::from_utf8_lossy(out)
+            .replace(home, "[CONFIG]")
+            .into_bytes();
+    }
+    output
+}
+
+fn stdout(args: &[&str]) -> String {
+    let output = to_html(args);
+    assert!(
+        output.status.success(),
+        "to-html failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn stderr(args: &[&str]) -> String {
+    let output = to_html(args);
+    assert!(output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn table_of_contents() {
+    let html = stdout(&[
+        "--no-run",
+        "--toc",
+        "ls -l",
+        r#"echo "it's <b>quoted</b>""#,
+        "cat Grüße.txt",
+    ]);
+    insta::assert_snapshot!(html, @r#"
+    <nav class='toc'>
+    <ol>
+    <li><a href='#cmd-1-ls-l'><code>ls -l</code></a></li>
+    <li><a href='#cmd-2-echo-its-b-quoted-b'><code>echo &quot;it&#39;s &lt;b&gt;quoted&lt;/b&gt;&quot;</code></a></li>
+    <li><a href='#cmd-3-cat-grüße-txt'><code>cat Grüße.txt</code></a></li>
+    </ol>
+    </nav>
+    <pre class="terminal">
+    <span id='cmd-1-ls-l'></span><span class='shell'>&gt; </span><span class='cmd'>ls</span> <span class='flag'>-l</span>
+    <span id='cmd-2-echo-its-b-quoted-b'></span><span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='str'>"it&#39;s &lt;b&gt;quoted&lt;/b&gt;"</span>
+    <span id='cmd-3-cat-grüße-txt'></span><span class='shell'>&gt; </span><span class='cmd'>cat</span> <span class='arg'>Grüße.txt</span>
+    </pre>
+    "#);
+}
+
+#[test]
+fn lexer_fallback_warning() {
+    let args = ["--no-run", "ls", "echo )"];
+    insta::assert_snapshot!(stderr(&args), @r#"to-html: warning: command 2: syntax highlighting failed (Unexpected token ")" found), showing the command as plain text"#);
+    insta::assert_snapshot!(stdout(&args), @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>ls</span>
+    <span class='shell'>&gt; </span>echo )
+    </pre>
+    "#);
+
+    let json = stderr(&["--log-format", "json", "--no-run", "echo )"]);
+    insta::assert_snapshot!(json, @r#"{"level":"warning","command":1,"message":"syntax highlighting failed (Unexpected token \")\" found), showing the command as plain text"}"#);
+
+    assert_eq!(stderr(&["--quiet", "--no-run", "echo )"]), "");
+}
+
+#[test]
+fn highlight_lines() {
+    let args = ["--hl-lines", "3,7-9", "--hl-lines", "12", "seq 10"];
+    insta::assert_snapshot!(stdout(&args), @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>seq</span> <span class='arg'>10</span>
+    1
+    2
+    <span class='hl-line'>3</span>
+    4
+    5
+    6
+    <span class='hl-line'>7</span>
+    <span class='hl-line'>8</span>
+    <span class='hl-line'>9</span>
+    10
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+    insta::assert_snapshot!(stderr(&args), @"to-html: warning: command 1: --hl-lines 12 is out of range, the output has only 10 lines");
+
+    // Styles spanning multiple lines are closed and re-opened around the highlighted line
+    let args = ["-N", "--hl-lines", "2", r"printf '\e[1ma\nb\nc\e[0m\n'"];
+    insta::assert_snapshot!(stdout(&args), @r#"
+    <pre class="terminal">
+    <b>a</b>
+    <span class='hl-line'><b>b</b></span>
+    <b>c</b>
+    </pre>
+    "#);
+}
+
+#[test]
+fn github_format() {
+    let html = stdout(&[
+        "--format",
+        "github",
+        "--hl-lines",
+        "2",
+        r#"printf '\e[2mfaint\e[0m \e[31mred \e[2mfaint red\e[0m\n"str" | \e[1;96mbold\e[0m\n'"#,
+    ]);
+    assert!(!html.contains("class="));
+    assert!(!html.contains("var("));
+    assert!(!html.contains("opacity:"));
+    insta::assert_snapshot!(html, @r#"
+    <pre style="background-color:#141414;color:#fff">
+    <span style='color:#32d132'>&gt; </span><span style='color:#419df3'>printf</span> <span style='color:#ffba24'>&#39;\e[2mfaint\e[0m \e[31mred \e[2mfaint red\e[0m\n&quot;str&quot; | \e[1;96mbold\e[0m\n&#39;</span>
+    <span style='color:#b1b1b1'>faint</span> <span style='color:#a00'>red </span><span style='color:#b1b1b1'><span style='color:#790707'>faint red</span></span>
+    <span style='background-color:#3b3b3b'>&quot;str&quot; | <b><span style='color:#5ff'>bold</span></b></span>
+    <span style='color:#32d132'>&gt; </span>
+    </pre>
+    "#);
+}
+
+#[test]
+fn email_format() {
+    let html = stdout(&[
+        "--format",
+        "email",
+        "--hl-lines",
+        "1",
+        "--show-file",
+        "tests/fixtures/package.json",
+        r#"printf '\e[2mfaint \e[31mred\e[0m \e[4;38;5;200mpink\e[0m\n'"#,
+    ]);
+    for absent in [
+        "class=",
+        "var(",
+        "<pre",
+        "opacity",
+        "text-decoration-style",
+        "<table",
+    ] {
+        assert!(!html.contains(absent), "{absent}");
+    }
+    insta::assert_snapshot!(html, @r#"
+    <div style="font-family:monospace;white-space:pre;background-color:#141414;color:#fff"><span style='color:#888;font-weight:bold'>tests/fixtures/package.json</span>
+    {
+      &quot;name&quot;: &quot;&lt;demo&gt;&quot;,
+      &quot;version&quot;: &quot;1.0.0&quot;
+    }
+    </div>
+    <div style="font-family:monospace;white-space:pre;background-color:#141414;color:#fff"><span style='color:#32d132'>&gt; </span><span style='color:#419df3'>printf</span> <span style='color:#ffba24'>&#39;\e[2mfaint \e[31mred\e[0m \e[4;38;5;200mpink\e[0m\n&#39;</span>
+    <span style='background-color:#3b3b3b'><span style='color:#b1b1b1'>faint <span style='color:#790707'>red</span></span> <u><span style='color:#ff00d7'>pink</span></u></span>
+    <span style='color:#32d132'>&gt; </span>
+    </div>
+    "#);
+}
+
+#[test]
+fn gh_summary_format() {
+    let html = stdout(&[
+        "--format",
+        "gh-summary",
+        r"printf '\e[31mred\e[0m\n\nplain\n'",
+        "echo '<b>'",
+    ]);
+    assert!(!html.contains("class="));
+    insta::assert_snapshot!(html, @r#"
+    <details><summary><code>$ printf &#39;\e[31mred\e[0m\n\nplain\n&#39;</code></summary>
+
+    <pre style="background-color:#141414;color:#fff">
+    <span style='color:#a00'>red</span>
+
+    plain
+    </pre>
+
+    </details>
+    <details><summary><code>$ echo &#39;&lt;b&gt;&#39;</code></summary>
+
+    <pre style="background-color:#141414;color:#fff">
+    &lt;b&gt;
+    </pre>
+
+    </details>
+    "#);
+
+    // The output is cut off after the last line that fits into 1 MiB
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("big.txt");
+    let line = "\x1b[32mok\x1b[0m a line of output\n";
+    fs::write(&path, line.repeat(40_000)).unwrap();
+    let output = to_html(&[
+        "--format",
+        "gh-summary",
+        "--input-file",
+        path.to_str().unwrap(),
+        "--input-file",
+        path.to_str().unwrap(),
+        "cat big.txt",
+        "cat big.txt",
+    ]);
+    assert!(output.status.success());
+    let html = String::from_utf8(output.stdout).unwrap();
+    assert!(html.len() <= 1024 * 1024);
+    assert_eq!(html.matches("<details>").count(), 1);
+    assert!(html.contains("<span style='color:#0a0'>ok</span> a line of output\n</pre>"));
+    assert!(html.ends_with("output truncated to the 1 MiB limit of job summaries …</b></p>\n\n"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("truncated"), "{stderr}");
+}
+
+#[test]
+fn size_hints() {
+    // the widest line has 12 ASCII characters and 8 wide characters
+    let html = stdout(&["--no-prompt", "--size-hints", "cat tests/fixtures/wide.txt"]);
+    assert!(html.starts_with("<pre class=\"terminal\" data-cols=\"28\" data-rows=\"3\">"));
+
+    let html = stdout(&[
+        "--no-prompt",
+        "--size-hints=css",
+        "cat tests/fixtures/wide.txt",
+    ]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="terminal" data-cols="28" data-rows="3" style="min-width:28ch">
+    サイズ  名前
+    12 KiB  日本語のファイル.txt
+     3 KiB  README.md
+    </pre>
+    "#);
+
+    // the prompts are measured as well
+    let html = stdout(&[
+        "--format",
+        "github",
+        "--size-hints=css",
+        "cat tests/fixtures/wide.txt",
+    ]);
+    insta::assert_snapshot!(html.lines().next().unwrap(), @r#"<pre style="background-color:#141414;color:#fff;min-width:29ch" data-cols="29" data-rows="5">"#);
+
+    // without the flag, there are no attributes
+    let html = stdout(&["--no-prompt", "cat tests/fixtures/wide.txt"]);
+    assert!(html.starts_with("<pre class=\"terminal\">"));
+}
+
+#[test]
+fn frame_only() {
+    // the commands aren't run
+    let html = stdout(&["--frame-only", "demo", "-p", "t", "--cwd", "touch not-run"]);
+    insta::assert_snapshot!(html.replace(env!("CARGO_MANIFEST_DIR"), "[DIR]"), @r#"
+    <pre class="t-terminal">
+    <span class='t-cwd'>[DIR] </span><span class='t-shell'>$ </span><span class='t-cmd'>touch</span> <span class='t-arg'>not-run</span>
+    <span class='t-mount' id='demo'></span><span class='t-cwd'>[DIR] </span><span class='t-shell'>$ </span><span class='t-caret'> </span>
+    </pre>
+    "#);
+    assert!(!std::path::Path::new("not-run").exists());
+
+    let html = stdout(&["--frame-only", "demo", "ls", "--then", "-N", "ls"]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>ls</span>
+    <span class='mount' id='demo-1'></span><span class='mount' id='demo-2'></span><span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+
+    let html = stdout(&["--frame-only", "demo", "--doc", "ls"]);
+    assert!(html.contains("<style>"));
+    assert!(html.contains("<span class='mount' id='demo'></span>"));
+
+    let output = to_html(&["--frame-only", "a b", "ls"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn config_path() {
+    let output = to_html(&["config", "path"]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @"[CONFIG]/to-html/config.toml");
+
+    // `config` is only a subcommand if there are no other arguments
+    let html = stdout(&["--no-run", "config", "path"]);
+    assert!(html.contains("<span class='cmd'>config</span>"));
+}
+
+#[test]
+fn config_show() {
+    let output = to_html_with_config(Some("valid_config.toml"), &["config", "show"]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r#"
+    [shell]
+    program = "bash"
+
+    [output]
+    cwd = true
+    full_document = false
+    highlight = ["git", "cargo"]
+    css_prefix = "th"
+    "#);
+
+    let output = to_html(&["config", "show"]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r#"
+    [shell]
+
+    [output]
+    cwd = false
+    full_document = false
+    highlight = ["cargo"]
+    "#);
+}
+
+#[test]
+fn config_check() {
+    let check = |config| {
+        let output = to_html_with_config(config, &["config", "check"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        (output.status.success(), stdout)
+    };
+
+    let (success, stdout) = check(Some("valid_config.toml"));
+    assert!(success);
+    insta::assert_snapshot!(stdout, @"[CONFIG]/to-html/config.toml: ok");
+
+    let (success, stdout) = check(None);
+    assert!(success);
+    insta::assert_snapshot!(stdout, @"[CONFIG]/to-html/config.toml: not found, using the defaults");
+
+    let (success, stdout) = check(Some("unknown_keys_config.toml"));
+    assert!(!success);
+    insta::assert_snapshot!(stdout, @"
+    [CONFIG]/to-html/config.toml:5:1: unknown key `output.full_documnet`
+    [CONFIG]/to-html/config.toml:8:2: unknown section `colors`
+    ");
+
+    let (success, stdout) = check(Some("invalid_config.toml"));
+    assert!(!success);
+    insta::assert_snapshot!(stdout, @r#"[CONFIG]/to-html/config.toml:2:7: invalid type: string "yes", expected a boolean"#);
+}
+
+#[test]
+fn provenance() {
+    let args = ["--provenance", "--stable", "-N", "echo 'a -- b'", "false"];
+    let html = stdout(&args).replace(env!("CARGO_PKG_VERSION"), "[VERSION]");
+    insta::assert_snapshot!(html, @r#"
+    <!-- generated by to-html [VERSION]: to-html -&#45;provenance -N 'echo '\''a -&#45; b'\''' false -->
+    <pre class="terminal">
+    <!-- to-html [VERSION]; command 1; time: [TIME]; shell: bash; exit code: 0; duration: [DURATION] -->
+    a -- b
+    <!-- to-html [VERSION]; command 2; time: [TIME]; shell: bash; exit code: 1; duration: [DURATION] -->
+    </pre>
+    "#);
+
+    let args = ["--provenance", "--stable", "--no-run", "echo '-->'"];
+    let html = stdout(&args).replace(env!("CARGO_PKG_VERSION"), "[VERSION]");
+    insta::assert_snapshot!(html, @r#"
+    <!-- generated by to-html [VERSION]: to-html -&#45;provenance -&#45;no-run 'echo '\''-&#45;&gt;'\''' -->
+    <pre class="terminal">
+    <!-- to-html [VERSION]; command 1; time: [TIME]; not run -->
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='str'>&#39;--&gt;&#39;</span>
+    </pre>
+    "#);
+}
+
+#[test]
+fn capture_raw_and_replay() {
+    let dir = TempDir::new().unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+
+    let command = r"printf '\e[1;31mred\e[0m\r\nplain\n'; echo error >&2";
+    let html = stdout(&["--capture-raw", &path("raw.ansi"), command]);
+    let raw = fs::read(path("raw.ansi")).unwrap();
+    assert_eq!(raw, b"\x1b[1;31mred\x1b[0m\r\r\nplain\r\nerror\r\n");
+    assert_eq!(html, stdout(&["--input-file", &path("raw.ansi"), command]));
+
+    // With several commands, the files are written to a directory
+    let html = stdout(&["--capture-raw", &path("raw"), "echo a", "echo b"]);
+    let replayed = stdout(&[
+        "--input-file",
+        &path("raw/command-1.ansi"),
+        "--input-file",
+        &path("raw/command-2.ansi"),
+        "echo a",
+        "echo b",
+    ]);
+    assert_eq!(html, replayed);
+
+    let output = to_html(&["--input-file", &path("raw.ansi"), "echo a", "echo b"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn typescript() {
+    let html = stdout(&[
+        "--typescript",
+        "tests/fixtures/session.log",
+        "--timing",
+        "tests/fixtures/session.tm",
+        "--timestamps",
+        "build",
+    ]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>build</span>
+    <b>Compiling</b> demo
+    <span class='timestamp'>[00:02]</span>
+    <span style='color:var(--green,#0a0)'>Finished</span> in 2.2s
+    done
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+
+    // Without --timing, the session is shown as a whole
+    let html = stdout(&["--typescript", "tests/fixtures/session.log", "build"]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>build</span>
+    <b>Compiling</b> demo
+    <span style='color:var(--green,#0a0)'>Finished</span> in 2.2s
+    done
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+
+    let output = to_html(&["--typescript", "tests/fixtures/session.log", "a", "b"]);
+    assert!(!output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"to-html: error: --typescript shows the output of a single command, got 2 commands");
+}
+
+#[test]
+fn cargo_build_error() {
+    let fixture = format!(
+        "{}/tests/fixtures/cargo-build-error.ansi",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let html = stdout(&["--input-file", &fixture, "cargo build"]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>cargo</span> <span class='hl'>build</span>
+    <b><span style='color:var(--bright-green,#5f5)'>   Compiling</span></b> demo-crate v0.1.0 (/tmp/demo-crate)
+    <b><span style='color:var(--bright-red,#f55)'>error[</span></b><a href='https://doc.rust-lang.org/error_codes/E0425.html'><b><span style='color:var(--bright-red,#f55)'>E0425</span></b></a><b><span style='color:var(--bright-red,#f55)'>]</span>: cannot find value `y` in this scope</b>
+     <b><span style='color:var(--bright-blue,#55f)'>--&gt; </span></b>src/main.rs:4:20
+      <b><span style='color:var(--bright-blue,#55f)'>|</span></b>
+    <b><span style='color:var(--bright-blue,#55f)'>4</span></b> <b><span style='color:var(--bright-blue,#55f)'>|</span></b>     println!(&quot;{}&quot;, y);
+      <b><span style='color:var(--bright-blue,#55f)'>|</span></b>                    <b><span style='color:var(--bright-red,#f55)'>^</span></b>
+      <b><span style='color:var(--bright-blue,#55f)'>|</span></b>
+    <b><span style='color:var(--bright-cyan,#5ff)'>help</span></b>: a local variable with a similar name exists
+      <b><span style='color:var(--bright-blue,#55f)'>|</span></b>
+    <b><span style='color:var(--bright-blue,#55f)'>4</span></b> <span style='color:var(--bright-red,#f55)'>- </span>    println!(&quot;{}&quot;, <span style='color:var(--bright-red,#f55)'>y</span>);
+    <b><span style='color:var(--bright-blue,#55f)'>4</span></b> <span style='color:var(--bright-green,#5f5)'>+ </span>    println!(&quot;{}&quot;, <span style='color:var(--bright-green,#5f5)'>x</span>);
+      <b><span style='color:var(--bright-blue,#55f)'>|</span></b>
+
+    <b><span style='color:var(--bright-red,#f55)'>error[</span></b><a href='https://doc.rust-lang.org/error_codes/E0308.html'><b><span style='color:var(--bright-red,#f55)'>E0308</span></b></a><b><span style='color:var(--bright-red,#f55)'>]</span>: mismatched types</b>
+     <b><span style='color:var(--bright-blue,#55f)'>--&gt; </span></b>src/main.rs:2:18
+      <b><span style='color:var(--bright-blue,#55f)'>|</span></b>
+    <b><span style='color:var(--bright-blue,#55f)'>2</span></b> <b><span style='color:var(--bright-blue,#55f)'>|</span></b>     let x: i32 = &quot;hello&quot;;
+      <b><span style='color:var(--bright-blue,#55f)'>|</span></b>            <b><span style='color:var(--bright-blue,#55f)'>---</span></b>   <b><span style='color:var(--bright-red,#f55)'>^^^^^^^</span></b> <b><span style='color:var(--bright-red,#f55)'>expected `i32`, found `&amp;str`</span></b>
+      <b><span style='color:var(--bright-blue,#55f)'>|</span></b>            <b><span style='color:var(--bright-blue,#55f)'>|</span></b>
+      <b><span style='color:var(--bright-blue,#55f)'>|</span></b>            <b><span style='color:var(--bright-blue,#55f)'>expected due to this</span></b>
+
+    <b>Some errors have detailed explanations: E0308, E0425.</b>
+    <b>For more information about an error, try `rustc --explain E0308`.</b>
+    <b><span style='color:var(--bright-red,#f55)'>error</span></b>: could not compile `demo-crate` (bin &quot;demo-crate&quot;) due to 2 previous errors
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+
+    // The error codes link to the error index
+    assert!(html.contains("<a href='https://doc.rust-lang.org/error_codes/E0425.html'>"));
+    // Bold bright colors are nested the same way as in the terminal
+    assert!(html.contains(
+        "<b><span style='color:var(--bright-cyan,#5ff)'>help</span></b>: a local variable"
+    ));
+}
+
+#[test]
+fn explode_pipeline() {
+    let html = stdout(&[
+        "--explode-pipeline",
+        r"printf 'a|b\nc\nab\n' | grep a | wc -l",
+    ]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>printf</span> <span class='str'>&#39;a|b\nc\nab\n&#39;</span>
+    a|b
+    c
+    ab
+    <span class='shell'>&gt; </span><span class='cmd'>printf</span> <span class='str'>&#39;a|b\nc\nab\n&#39;</span> <span class='pipe'>|</span> <span class='cmd'>grep</span> <span class='arg'>a</span>
+    a|b
+    ab
+    <span class='shell'>&gt; </span><span class='cmd'>printf</span> <span class='str'>&#39;a|b\nc\nab\n&#39;</span> <span class='pipe'>|</span> <span class='cmd'>grep</span> <span class='arg'>a</span> <span class='pipe'>|</span> <span class='cmd'>wc</span> <span class='flag'>-l</span>
+    2
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+}
+
+#[test]
+fn streams() {
+    let command = "echo out; echo err >&2; cd /; echo out; printf '\\033[31merr\\033[0m\\n' >&2";
+    let html = |streams: &str| stdout(&["--streams", streams, "-s", "bash", command, "pwd"]);
+    insta::assert_snapshot!(html("both"), @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>out</span><span class='punct'>;</span> <span class='cmd'>echo</span> <span class='arg'>err</span> <span class='pipe'>&gt;</span><span class='pipe'>&amp;2</span><span class='punct'>;</span> <span class='cmd'>cd</span> <span class='arg'>/</span><span class='punct'>;</span> <span class='cmd'>echo</span> <span class='arg'>out</span><span class='punct'>;</span> <span class='cmd'>printf</span> <span class='str'>&#39;\033[31merr\033[0m\n&#39;</span> <span class='pipe'>&gt;</span><span class='pipe'>&amp;2</span>
+    out
+    err
+    out
+    <span style='color:var(--red,#a00)'>err</span>
+    <span class='shell'>&gt; </span><span class='cmd'>pwd</span>
+    /
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+    insta::assert_snapshot!(html("stdout"), @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>out</span><span class='punct'>;</span> <span class='cmd'>echo</span> <span class='arg'>err</span> <span class='pipe'>&gt;</span><span class='pipe'>&amp;2</span><span class='punct'>;</span> <span class='cmd'>cd</span> <span class='arg'>/</span><span class='punct'>;</span> <span class='cmd'>echo</span> <span class='arg'>out</span><span class='punct'>;</span> <span class='cmd'>printf</span> <span class='str'>&#39;\033[31merr\033[0m\n&#39;</span> <span class='pipe'>&gt;</span><span class='pipe'>&amp;2</span>
+    out
+    out
+    <span class='shell'>&gt; </span><span class='cmd'>pwd</span>
+    /
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+    // the prompt is shown even if the command printed nothing
+    insta::assert_snapshot!(html("stderr"), @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>out</span><span class='punct'>;</span> <span class='cmd'>echo</span> <span class='arg'>err</span> <span class='pipe'>&gt;</span><span class='pipe'>&amp;2</span><span class='punct'>;</span> <span class='cmd'>cd</span> <span class='arg'>/</span><span class='punct'>;</span> <span class='cmd'>echo</span> <span class='arg'>out</span><span class='punct'>;</span> <span class='cmd'>printf</span> <span class='str'>&#39;\033[31merr\033[0m\n&#39;</span> <span class='pipe'>&gt;</span><span class='pipe'>&amp;2</span>
+    err
+    <span style='color:var(--red,#a00)'>err</span>
+    <span class='shell'>&gt; </span><span class='cmd'>pwd</span>
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+
+    let output = to_html(&["--streams", "stdout", "-s", "csh", "echo out"]);
+    assert!(!output.status.success());
+    insta::assert_snapshot!(String::from_utf8_lossy(&output.stderr), @"to-html: error: --streams is not supported in csh");
+}
+
+#[test]
+fn per_command_options() {
+    let html = stdout(&[
+        "cd /",
+        "--then",
+        "--cwd",
+        "echo two",
+        "--then",
+        "--no-prompt",
+        "echo three",
+    ]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>cd</span> <span class='arg'>/</span>
+    <span class='cwd'>/ </span><span class='shell'>$ </span><span class='cmd'>echo</span> <span class='arg'>two</span>
+    two
+    three
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+
+    // the global timeout kills the first command, the second one has a longer timeout
+    let output = to_html(&[
+        "--timeout",
+        "0.5",
+        "echo start; sleep 3; echo never",
+        "--then",
+        "--timeout",
+        "10",
+        "sleep 1; echo done",
+    ]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>start</span><span class='punct'>;</span> <span class='cmd'>sleep</span> <span class='arg'>3</span><span class='punct'>;</span> <span class='cmd'>echo</span> <span class='arg'>never</span>
+    start
+    <span class='timeout'>… killed after 0.5s …</span>
+    <span class='shell'>&gt; </span><span class='cmd'>sleep</span> <span class='arg'>1</span><span class='punct'>;</span> <span class='cmd'>echo</span> <span class='arg'>done</span>
+    done
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"to-html: warning: command 1: the command timed out and was killed, only the output until then is shown");
+
+    // a group without commands is an error
+    let output = to_html(&["echo one", "--then", "--no-prompt"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn missing_executables() {
+    let output = to_html(&["--shell", "/nonexistent/shell", "ls"]);
+    assert!(!output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"to-html: error: shell `/nonexistent/shell` was not found");
+
+    let home = TempDir::new().unwrap();
+    let with_path = |path: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_to-html"))
+            .arg("ls")
+            .env("PATH", path)
+            .env("HOME", home.path())
+            .env("XDG_CONFIG_HOME", home.path())
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    // Without a `PATH`, neither bash nor `script` can be found
+    insta::assert_snapshot!(with_path(""), @"to-html: error: bash was not found. Install it, or choose a different shell, e.g. `--shell sh`");
+
+    let bin = TempDir::new().unwrap();
+    let bash = String::from_utf8(Command::new("which").arg("bash").output().unwrap().stdout);
+    std::os::unix::fs::symlink(bash.unwrap().trim(), bin.path().join("bash")).unwrap();
+    insta::assert_snapshot!(with_path(bin.path().to_str().unwrap()), @"to-html: error: the `script` command was not found. It is needed to run commands in a pseudo-terminal, and is part of util-linux (bsdutils on Debian and Ubuntu)");
+}
+
+#[test]
+fn commands_from_config() {
+    let output = to_html_with_config(Some("commands_config.toml"), &[]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>one</span>
+    one
+    <span class='shell'>&gt; </span><span class='cmd'>for</span> <span class='arg'>word</span> <span class='arg'>in</span> <span class='arg'>two</span> <span class='arg'>three</span><span class='punct'>;</span> <span class='cmd'>do</span>
+    <span class='shell'>&gt; </span>  <span class='cmd'>echo</span> <span class='str'>"<span class='var'>$word</span>"</span>
+    <span class='shell'>&gt; </span><span class='cmd'>done</span>
+    two
+    three
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+
+    // commands on the command line take precedence
+    let output = to_html_with_config(Some("commands_config.toml"), &["echo cli"]);
+    let html = String::from_utf8(output.stdout).unwrap();
+    assert!(html.contains("\ncli\n"));
+    assert!(!html.contains("three"));
+
+    let output = to_html_with_config(None, &[]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn multi_line_command() {
+    let html = stdout(&["echo a # comment\necho b"]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>a</span> <span class='com'># comment</span>
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>b</span>
+    a
+    b
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+}
+
+#[test]
+fn show_file() {
+    let html = stdout(&[
+        "--show-file",
+        "tests/fixtures/package.json",
+        "grep -c name tests/fixtures/package.json",
+    ]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="file">
+    <span class='file-name'>tests/fixtures/package.json</span>
+    {
+      &quot;name&quot;: &quot;&lt;demo&gt;&quot;,
+      &quot;version&quot;: &quot;1.0.0&quot;
+    }
+    </pre>
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>grep</span> <span class='flag'>-c</span> <span class='arg'>name</span> <span class='arg'>tests/fixtures/package.json</span>
+    1
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("big.txt");
+    fs::write(&path, "ab\n".repeat(20_000)).unwrap();
+    let html = stdout(&["--no-run", "--show-file", path.to_str().unwrap(), "true"]);
+    // 32 KiB are shown, ending in the middle of a line
+    assert_eq!(html.matches("ab\n").count(), 32 * 1024 / 3 + 1);
+    assert!(html.contains("ab\n<span class='file-truncated'>[27232 more bytes]</span>\n</pre>"));
+}
+
+#[test]
+fn check_output_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("out.html");
+    let path = path.to_str().unwrap();
+    let args = |extra: &[&'static str]| {
+        let mut args = vec!["--provenance", "--output", path];
+        args.extend(extra);
+        args.push("echo hello");
+        args
+    };
+
+    assert!(to_html(&args(&["--stable"])).status.success());
+    assert!(fs::read_to_string(path).unwrap().contains("\nhello\n"));
+
+    let output = to_html(&args(&["--check"]));
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stdout.is_empty());
+
+    let modified = fs::read_to_string(path)
+        .unwrap()
+        .replace("\nhello\n", "\nworld\n");
+    fs::write(path, modified).unwrap();
+    let output = to_html(&args(&["--check", "--check-diff"]));
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr.trim(),
+        format!("to-html: error: {path} is not up to date")
+    );
+    let diff = String::from_utf8(output.stdout).unwrap();
+    assert!(diff.contains("\n-world\n+hello\n"), "{diff}");
+    assert!(fs::read_to_string(path).unwrap().contains("world"));
+}
+
+#[test]
+fn jobs() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    let jobs = dir.path().join("jobs.toml");
+    fs::write(
+        &jobs,
+        r#"
+[[jobs]]
+commands = ["echo $GREETING", "cd sub"]
+output = "first.html"
+env = { GREETING = "hello" }
+
+[[jobs]]
+commands = ["pwd"]
+output = "sub/second.html"
+cwd = "sub"
+prefix = "th"
+"#,
+    )
+    .unwrap();
+    let jobs = jobs.to_str().unwrap();
+
+    for parallel in ["1", "2"] {
+        let output = to_html(&["-N", "--jobs", jobs, "--jobs-parallel", parallel]);
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(output.stdout, b"2 of 2 jobs succeeded\n");
+
+        let first = fs::read_to_string(dir.path().join("first.html")).unwrap();
+        assert_eq!(first, "<pre class=\"terminal\">\nhello\n</pre>\n");
+        // the `cd` of the first job doesn't affect the second one
+        let second = fs::read_to_string(dir.path().join("sub/second.html")).unwrap();
+        let sub = dir.path().join("sub").canonicalize().unwrap();
+        assert_eq!(
+            second,
+            format!("<pre class=\"th-terminal\">\n{}\n</pre>\n", sub.display())
+        );
+    }
+
+    // a failed job doesn't stop the others, unless --fail-fast is given
+    let yaml = dir.path().join("jobs.yaml");
+    fs::write(
+        &yaml,
+        "jobs:\n  - commands: [true]\n    output: missing/out.html\n  \
+        - commands: [echo yaml]\n    output: yaml.html\n",
+    )
+    .unwrap();
+    let yaml = yaml.to_str().unwrap();
+    let output = to_html(&["-N", "--jobs", yaml]);
+    assert_eq!(output.status.code(), Some(1));
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r"
+    1 of 2 jobs succeeded, 1 failed
+      failed:  missing/out.html
+    ");
+    assert!(fs::read_to_string(dir.path().join("yaml.html"))
+        .unwrap()
+        .contains("\nyaml\n"));
+    fs::remove_file(dir.path().join("yaml.html")).unwrap();
+
+    let output = to_html(&["-N", "--jobs", yaml, "--fail-fast"]);
+    assert_eq!(output.status.code(), Some(1));
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r"
+    0 of 2 jobs succeeded, 1 failed, 1 skipped
+      failed:  missing/out.html
+      skipped: yaml.html
+    ");
+    assert!(!dir.path().join("yaml.html").exists());
+
+    // unknown keys are rejected
+    fs::write(
+        dir.path().join("bad.toml"),
+        "[[jobs]]\ncommands = []\ntheme = 'dark'\n",
+    )
+    .unwrap();
+    let bad = dir.path().join("bad.toml");
+    let output = to_html(&["--jobs", bad.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("unknown field `theme`"));
+}
+
+#[test]
+fn powershell_prompt() {
+    let html = stdout(&["--no-run", "--cwd", "--shell", "pwsh", "Get-ChildItem"]);
+    let cwd = std::env::current_dir().unwrap();
+    let prompt = format!(
+        "<span class='shell'>PS </span><span class='cwd'>{}</span><span class='shell'>&gt; </span>\
+         <span class='cmd'>Get-ChildItem</span>",
+        cwd.display(),
+    );
+    assert!(html.contains(&prompt), "{html}");
+}
+
+#[test]
+fn doctor() {
+    let doctor = |config, args: &[&str], env: &[(&str, &str)]| {
+        let output = to_html_with_env(config, args, env);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        (output.status.success(), stdout)
+    };
+
+    let (success, stdout) = doctor(None, &["doctor"], &[]);
+    assert!(success, "{stdout}");
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines[0],
+        "✓ config: [CONFIG]/to-html/config.toml not found, using the defaults"
+    );
+    assert!(
+        lines[1].starts_with("✓ shell: `bash` (default) is /"),
+        "{stdout}"
+    );
+    assert!(lines[2].starts_with("✓ script: /"), "{stdout}");
+    assert_eq!(
+        lines[3],
+        "✓ pipeline: colored output is converted correctly"
+    );
+    // stdin is not inherited, and stdout and stderr are piped
+    assert_eq!(
+        lines[4],
+        "✗ terminal size (optional): not detected, not running in a terminal"
+    );
+
+    let (success, stdout) = doctor(Some("missing_shell_config.toml"), &["doctor"], &[]);
+    assert!(!success);
+    insta::assert_snapshot!(stdout.lines().nth(1).unwrap(), @"✗ shell: `/nonexistent/sh` (configured) was not found");
+    insta::assert_snapshot!(stdout.lines().nth(3).unwrap(), @"✗ pipeline: skipped, since the shell or `script` is missing");
+
+    let (success, stdout) = doctor(None, &["doctor", "--json"], &[("PATH", "/nonexistent")]);
+    assert!(!success);
+    insta::assert_snapshot!(stdout.replace("},{", "},\n{"), @r#"
+    {"ok":false,"checks":[{"name":"config","required":true,"ok":true,"message":"[CONFIG]/to-html/config.toml not found, using the defaults"},
+    {"name":"shell","required":true,"ok":false,"message":"`bash` (default) was not found"},
+    {"name":"script","required":true,"ok":false,"message":"not found. It is part of util-linux (bsdutils on Debian and Ubuntu)"},
+    {"name":"pipeline","required":true,"ok":false,"message":"skipped, since the shell or `script` is missing"},
+    {"name":"terminal size","required":false,"ok":false,"message":"not detected, not running in a terminal"}]}
+    "#);
+}
+
+#[test]
+fn semantic_output() {
+    let html = stdout(&[
+        "--semantic-output",
+        "--prefix",
+        "x",
+        r"printf '\033[1mbold\033[0m\n'",
+        "true",
+        "echo a\necho b",
+    ]);
+    insta::assert_snapshot!(html, @r#"
+    <pre class="x-terminal">
+    <span class='x-shell'>&gt; </span><kbd class='x-cmd-line'><span class='x-cmd'>printf</span> <span class='x-str'>&#39;\033[1mbold\033[0m\n&#39;</span></kbd>
+    <samp><b>bold</b>
+    </samp><span class='x-shell'>&gt; </span><kbd class='x-cmd-line'><span class='x-cmd'>true</span></kbd>
+    <span class='x-shell'>&gt; </span><kbd class='x-cmd-line'><span class='x-cmd'>echo</span> <span class='x-arg'>a</span>
+    <span class='x-shell'>&gt; </span><span class='x-cmd'>echo</span> <span class='x-arg'>b</span></kbd>
+    <samp>a
+    b
+    </samp><span class='x-shell'>&gt; </span><span class='x-caret'> </span>
+    </pre>
+    "#);
+}
+
+#[test]
+fn css_prefix() {
+    let terminal = |prefix: &str| {
+        let html = stdout(&["--no-run", &format!("--prefix={prefix}"), "ls"]);
+        html.lines().next().unwrap().to_string()
+    };
+    // trailing hyphens are removed, since a hyphen is added after the prefix
+    assert_eq!(terminal("foo"), "<pre class=\"foo-terminal\">");
+    assert_eq!(terminal(" foo- "), "<pre class=\"foo-terminal\">");
+    assert_eq!(
+        terminal("-my_theme2"),
+        "<pre class=\"-my_theme2-terminal\">"
+    );
+    assert_eq!(terminal(""), "<pre class=\"terminal\">");
+
+    let rejected = |prefix: &str| {
+        let output = to_html(&["--no-run", &format!("--prefix={prefix}"), "ls"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        stderr.lines().next().unwrap().to_string()
+    };
+    insta::assert_snapshot!(rejected("1foo"), @"error: invalid value '1foo' for '--prefix <PREFIX>': a CSS prefix must not start with a digit");
+    insta::assert_snapshot!(rejected("-2foo"), @"error: invalid value '-2foo' for '--prefix <PREFIX>': a CSS prefix must not start with a digit");
+    insta::assert_snapshot!(rejected("--foo"), @"error: invalid value '--foo' for '--prefix <PREFIX>': a CSS prefix must not start with `--`");
+    insta::assert_snapshot!(rejected("my.theme"), @"error: invalid value 'my.theme' for '--prefix <PREFIX>': '.' is not allowed in a CSS prefix, only letters, digits, `-` and `_`");
+    insta::assert_snapshot!(rejected("my theme"), @"error: invalid value 'my theme' for '--prefix <PREFIX>': ' ' is not allowed in a CSS prefix, only letters, digits, `-` and `_`");
+    insta::assert_snapshot!(rejected("x'><script>"), @r"error: invalid value 'x'><script>' for '--prefix <PREFIX>': '\'' is not allowed in a CSS prefix, only letters, digits, `-` and `_`");
+
+    let output = to_html_with_config(Some("invalid_prefix_config.toml"), &["config", "check"]);
+    assert!(!output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @"[CONFIG]/to-html/config.toml:2:14: '.' is not allowed in a CSS prefix, only letters, digits, `-` and `_`");
+
+    let output = to_html_with_config(Some("invalid_prefix_config.toml"), &["--no-run", "ls"]);
+    assert!(!output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @r#"
+    to-html: error: Config file [CONFIG]/to-html/config.toml has invalid format: TOML parse error at line 2, column 14
+      |
+    2 | css_prefix = "my.theme"
+      |              ^^^^^^^^^^
+    '.' is not allowed in a CSS prefix, only letters, digits, `-` and `_`
+    "#);
+}
+
+#[test]
+fn presets() {
+    let home = config_home(Some("valid_config.toml"));
+    let run = |args: &[&str]| {
+        let output = to_html_in(&home, args, &[]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(output.status.success(), "{stderr}");
+        (stdout, stderr)
+    };
+    let first_line = |args: &[&str]| run(args).0.lines().next().unwrap().to_string();
+
+    assert_eq!(run(&["--list-presets"]).0, "");
+
+    // without commands, the preset is saved and nothing is run
+    let (stdout, stderr) = run(&[
+        "--save-preset",
+        "docs",
+        "--prefix",
+        "docs",
+        "-l",
+        "npm",
+        "-v",
+    ]);
+    assert_eq!(stdout, "");
+    insta::assert_snapshot!(stderr, @"to-html: info: saved the preset `docs` to [CONFIG]/to-html/presets/docs.toml");
+    // the options from the config file are saved as well
+    let preset = fs::read_to_string(home.path().join("to-html/presets/docs.toml")).unwrap();
+    insta::assert_snapshot!(preset, @r#"
+    [shell]
+    program = "bash"
+
+    [output]
+    cwd = true
+    full_document = false
+    highlight = ["npm"]
+    css_prefix = "docs"
+    "#);
+
+    // the preset overrides the config file, and the command line overrides the preset
+    assert_eq!(
+        first_line(&["--no-run", "ls"]),
+        "<pre class=\"th-terminal\">"
+    );
+    assert_eq!(
+        first_line(&["--preset", "docs", "--no-run", "ls"]),
+        "<pre class=\"docs-terminal\">"
+    );
+    assert_eq!(
+        first_line(&["--preset", "docs", "--prefix", "cli", "--no-run", "ls"]),
+        "<pre class=\"cli-terminal\">"
+    );
+
+    // with commands, they are run after saving the preset
+    let (stdout, _) = run(&["--save-preset", "plain", "--prefix", "", "--no-run", "ls"]);
+    assert!(stdout.starts_with("<pre class=\"terminal\">"));
+    assert_eq!(run(&["--list-presets"]).0, "docs\nplain\n");
+    assert_eq!(
+        first_line(&["--preset", "plain", "--no-run", "ls"]),
+        "<pre class=\"terminal\">"
+    );
+
+    let output = to_html_in(&home, &["--preset", "missing", "--no-run", "ls"], &[]);
+    assert!(!output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"to-html: error: Preset `missing` not found at [CONFIG]/to-html/presets/missing.toml");
+
+    let output = to_html(&["--save-preset", "../evil"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    insta::assert_snapshot!(stderr.lines().next().unwrap(), @"error: invalid value '../evil' for '--save-preset <NAME>': '/' is not allowed in a preset name, only letters, digits, `-`, `_` and `.`");
+}
+
+#[test]
+fn binary_output() {
+    let args = ["--input-file", "tests/fixtures/image.png", "cat image.png"];
+    let output = to_html(&args);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r#"
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>cat</span> <span class='arg'>image.png</span>
+    <span class='binary'>… binary output (1.8 KiB) suppressed …</span>
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    "#);
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"to-html: warning: command 1: the output looks like binary data and is not shown. Pass --allow-binary to show it");
+
+    let html = stdout(&["--allow-binary", args[0], args[1], args[2]]);
+    assert!(!html.contains("binary output"));
+    assert!(html.contains("PNG"));
+
+    // output with many escape sequences isn't mistaken for binary data
+    let session = "gzip -dc crates/ansi-to-html/assets/terminal_session.gz";
+    let output = to_html(&[session]);
+    let html = String::from_utf8(output.stdout).unwrap();
+    assert!(!html.contains("binary output"));
+    assert!(html.len() > 100_000);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn lint_output() {
+    let args = ["--input-file", "tests/fixtures/tree.txt", "-N", "tree"];
+    let output = to_html(&["--lint-output", args[0], args[1], args[2], args[3]]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"to-html: warning: command 1: the output contains characters that may be misaligned if the page's font lacks them (17 box-drawing, 3 wide CJK, 1 emoji). Use a monospace font with these glyphs, e.g. `font-family: 'DejaVu Sans Mono', monospace`, or pass --lint-output=fix to replace box-drawing characters");
+
+    let output = to_html(&["--lint-output=fix", args[0], args[1], args[2], args[3]]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r#"
+    <pre class="terminal">
+    .
+    +-- Cargo.toml
+    +-- src
+    |   +-- main.rs
+    |   +-- 日本語.rs
+    +-- 📄 README.md
+
+    2 directories, 4 files
+    </pre>
+    "#);
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"to-html: warning: command 1: the output contains characters that may be misaligned if the page's font lacks them (3 wide CJK, 1 emoji). Use a monospace font with these glyphs, e.g. `font-family: 'DejaVu Sans Mono', monospace`");
+
+    assert!(stderr(&args).is_empty());
+}
+
+#[test]
+fn no_cwd_tracking() {
+    let html = stdout(&["-N", "cd /", "pwd"]);
+    assert!(html.contains("\n/\n"), "{html}");
+
+    // the commands run unmodified, so the exit code is the one of the last statement
+    let args = ["--provenance", "--stable", "-N", "--no-cwd-tracking"];
+    let html = stdout(&[args[0], args[1], args[2], args[3], "cd /", "pwd; exit 3"])
+        .replace(env!("CARGO_PKG_VERSION"), "[VERSION]")
+        .replace(env!("CARGO_MANIFEST_DIR"), "[DIR]");
+    insta::assert_snapshot!(html, @r#"
+    <!-- generated by to-html [VERSION]: to-html -&#45;provenance -N -&#45;no-cwd-tracking 'cd /' 'pwd; exit 3' -->
+    <pre class="terminal">
+    <!-- to-html [VERSION]; command 1; time: [TIME]; shell: bash; exit code: 0; duration: [DURATION] -->
+    <!-- to-html [VERSION]; command 2; time: [TIME]; shell: bash; exit code: 3; duration: [DURATION] -->
+    [DIR]
+    </pre>
+    "#);
+}
+
+#[test]
+fn exit_codes() {
+    let code = |args: &[&str]| to_html(args).status.code();
+    assert_eq!(code(&["--no-such-flag", "true"]), Some(2));
+    assert_eq!(code(&["--timeout", "soon", "true"]), Some(2));
+    assert_eq!(code(&["--shell", "no-such-shell", "true"]), Some(3));
+
+    // failing commands are rendered, and only change the exit code with --passthrough-status
+    assert_eq!(code(&["false"]), Some(0));
+    assert_eq!(code(&["--passthrough-status", "true"]), Some(0));
+    assert_eq!(
+        code(&["--passthrough-status", "false", "(exit 7)"]),
+        Some(4)
+    );
+    assert_eq!(code(&["--passthrough-status", "true", "(exit 7)"]), Some(7));
+    let output = to_html(&["--passthrough-status", "-N", "echo shown; (exit 9)"]);
+    assert_eq!(output.status.code(), Some(9));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("shown"));
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn expect() {
+    let script = "read -p 'User: ' user; read -s -p 'Password:' pw; echo; \
+        echo \"logged in as $user with ${#pw} characters: $pw\"";
+    let args = [
+        "--provenance",
+        "--stable",
+        "-N",
+        "--expect",
+        "User\\: ?$:admin",
+        "--expect=(?i)password:hunter2",
+        script,
+    ];
+    let html = stdout(&args).replace(env!("CARGO_PKG_VERSION"), "[VERSION]");
+    assert!(!html.contains("hunter2"));
+    insta::assert_snapshot!(html, @r#"
+    <!-- generated by to-html [VERSION]: to-html -&#45;provenance -N -&#45;expect 'User\: ?$:********' '-&#45;expect=(?i)password:********' 'read -p '\''User: '\'' user; read -s -p '\''Password:'\'' pw; echo; echo "logged in as $user with ${#pw} characters: $pw"' -->
+    <pre class="terminal">
+    <!-- to-html [VERSION]; command 1; time: [TIME]; shell: bash; exit code: 0; duration: [DURATION] -->
+    User: ********
+    Password:
+    logged in as ******** with 7 characters: ********
+    </pre>
+    "#);
+
+    let args = ["--expect", "Passphrase:x", "--expect-timeout", "0.5"];
+    let output = to_html(&[args[0], args[1], args[2], args[3], "read -p 'Password: '"]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"to-html: warning: command 1: the prompt `Passphrase` didn't appear within 0.5s and the command was killed");
+
+    let output = to_html(&["--expect", "no-colon", "true"]);
+    assert!(!output.status.success());
+    let output = to_html(&["--expect", "(:x", "true"]);
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"
+    to-html: error: invalid regex in --expect: regex parse error:
+        (
+        ^
+    error: unclosed group
+    ");
+}
+
+#[test]
+fn shells() {
+    let output = to_html(&["--shells", "bash,tests/fixtures/fake-shell", "echo hi"]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r#"
+    <div class='shells'>
+    <div>
+    <span class='shell-name'>bash</span>
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>hi</span>
+    hi
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    </div>
+    <div>
+    <span class='shell-name'>fake-shell</span>
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>hi</span>
+    fake shell
+    hi
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    </div>
+    </div>
+    "#);
+
+    // shells from the config file; missing shells are shown as a warning
+    let output = to_html_with_config(Some("shells_config.toml"), &[]);
+    assert!(output.status.success());
+    insta::assert_snapshot!(String::from_utf8(output.stdout).unwrap(), @r#"
+    <div class='shells'>
+    <div>
+    <span class='shell-name'>fake-shell</span>
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>hi</span>
+    fake shell
+    hi
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+    </div>
+    <div>
+    <span class='shell-name'>no-such-shell</span>
+    <pre class="terminal">
+    <span class='shell-missing'>`no-such-shell` is not installed</span>
+    </pre>
+    </div>
+    </div>
+    "#);
+    insta::assert_snapshot!(String::from_utf8(output.stderr).unwrap(), @"to-html: warning: shell `no-such-shell` was not found, its output is not shown");
+
+    // `--shell` overrides the config file
+    let output = to_html_with_config(Some("shells_config.toml"), &["--shell", "sh"]);
+    let html = String::from_utf8(output.stdout).unwrap();
+    assert!(!html.contains("shells"));
+    assert!(!html.contains("fake shell"));
+}
+
+/// Runs `to-html mdbook` with a book containing the given chapters, and returns their contents
+fn mdbook(config: &str, chapters: &str) -> Vec<String> {
+    let home = config_home(None);
+    let root = env!("CARGO_MANIFEST_DIR");
+    let input = format!(
+        r#"[{{"root":"{root}","renderer":"html","config":{{"preprocessor":{{"to-html":{config}}}}}}},
+            {{"sections":[{chapters},"Separator"],"__non_exhaustive":null}}]"#
+    );
+    let mut child = Command::new(env!("CARGO_BIN_EXE_to-html"))
+        .arg("mdbook")
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let book: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mut contents = Vec::new();
+    let mut sections = book["sections"].as_array().unwrap().clone();
+    while !sections.is_empty() {
+        let section = sections.remove(0);
+        if let Some(chapter) = section.get("Chapter") {
+            contents.push(chapter["content"].as_str().unwrap().to_string());
+            sections.extend(chapter["sub_items"].as_array().unwrap().iter().cloned());
+        }
+    }
+    contents
+}
+
+#[test]
+fn mdbook_preprocessor() {
+    let chapters = r##"
+        {"Chapter":{"name":"Intro","content":"# Intro\n\n```to-html\necho hello\n```\n\n````md\n```to-html\nnested\n```\n````\n","sub_items":[
+            {"Chapter":{"name":"Nested","content":"```rust\nfn main() {}\n```\n~~~ to-html, no-run\nls\nrm -rf target\n~~~\n","sub_items":[]}}
+        ]}}"##;
+    let contents = mdbook(r#"{"shell":"bash","stylesheet":false}"#, chapters);
+    insta::assert_snapshot!(contents.join("---\n"), @r#"
+    # Intro
+
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>echo</span> <span class='arg'>hello</span>
+    hello
+    <span class='shell'>&gt; </span><span class='caret'> </span>
+    </pre>
+
+    ````md
+    ```to-html
+    nested
+    ```
+    ````
+    ---
+    ```rust
+    fn main() {}
+    ```
+    <pre class="terminal">
+    <span class='shell'>&gt; </span><span class='cmd'>ls</span>
+    <span class='shell'>&gt; </span><span class='cmd'>rm</span> <span class='flag'>-rf</span> <span class='arg'>target</span>
+    </pre>
+    "#);
+
+    // the stylesheet is added once per chapter, with the prefix
+    let chapter = r#"{"Chapter":{"name":"A","content":"```to-html,no-run\nls\n```\n```to-html,no-run\nls\n```\n","sub_items":[]}}"#;
+    let contents = mdbook(r#"{"prefix":"th"}"#, &format!("{chapter},{chapter}"));
+    for content in contents {
+        assert_eq!(content.matches("<style>").count(), 1);
+        assert!(content.starts_with("<style>\n.th-terminal {\n  background-color: #141414;"));
+        assert_eq!(content.matches("<pre class=\"th-terminal\">").count(), 2);
+    }
+}
+
+#[test]
+fn mdbook_supports() {
+    assert!(to_html(&["mdbook", "supports", "html"]).status.success());
+    assert!(!to_html(&["mdbook", "supports", "latex"]).status.success());
+}
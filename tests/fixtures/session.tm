@@ -0,0 +1,3 @@
+0.010127 24
+2.192427 27
+0.301333 6